@@ -1,5 +1,5 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use regexer::do_matching;
+use regexer::{do_matching, MatchMode};
 use std::time::Duration;
 
 /// (計測のid, a?^n a^nという正規表現､文字列)というタプル
@@ -17,7 +17,7 @@ fn depth_first(c: &mut Criterion) {
 
     for i in INPUTS {
         g.bench_with_input(i.0, &(i.1, i.2), |b, args| {
-            b.iter(|| do_matching(args.0, args.1, true))
+            b.iter(|| do_matching(args.0, args.1, MatchMode::Dfs))
         });
     }
 }
@@ -28,7 +28,7 @@ fn width_first(c: &mut Criterion) {
 
     for i in INPUTS {
         g.bench_with_input(i.0, &(i.1, i.2), |b, args| {
-            b.iter(|| do_matching(args.0, args.1, false))
+            b.iter(|| do_matching(args.0, args.1, MatchMode::Bfs))
         });
     }
 }
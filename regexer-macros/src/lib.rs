@@ -0,0 +1,240 @@
+//! regexerの正規表現をコンパイル時に検査する手続き型マクロ
+//!
+//! ## 利用例
+//!
+//! ```
+//! use regexer_macros::regexer;
+//! let re = regexer!("(?<key>\\w+)=(\\d+)");
+//! let caps = re.captures("retry=3").unwrap().unwrap();
+//! assert_eq!(&caps["key"], "retry");
+//! ```
+//!
+//! 誤ったパターンはビルドエラーとなる
+//!
+//! ```compile_fail
+//! use regexer_macros::regexer;
+//! let re = regexer!("a(b");
+//! ```
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use regexer_syntax::{
+    codegen::gen_code,
+    parser::{parse_with_flags, Flags, LineTerminator},
+    Instruction,
+};
+use std::fmt::Write;
+
+/// パターンをコンパイル時にパースとコード生成し､その命令列から生成した&'static regexer::Regexに展開する
+///
+/// 引数には文字列リテラルを1つ与え､Regex::newと同じ既定のオプションでコンパイルする
+/// パターンにエラーがある場合は､誤った位置を示すメッセージとともにビルドエラーとなる
+///
+/// 展開したコードは実行時にパースとコード生成を行わない
+/// Regexは呼び出し箇所ごとのstaticに最初の評価時に1度だけ生成し､以降の評価では同じRegexを返す
+/// 利用するクレートは､regexerにも依存している必要がある
+/// \p{..}による文字クラスを使う場合は､このクレートのunicode-gencatやunicode-scriptフィーチャを有効にするか､
+/// regexerのmacrosフィーチャで再公開したregexer::regexerを利用する
+#[proc_macro]
+pub fn regexer(input: TokenStream) -> TokenStream {
+    let (expr, span) = match pattern_literal(input) {
+        Ok(pair) => pair,
+        Err((msg, span)) => return compile_error(&msg, span),
+    };
+
+    // Regex::newと同じく､既定のオプションでパースとコード生成を行う
+    let ast = match parse_with_flags(&expr, Flags::default()) {
+        Ok(ast) => ast,
+        Err(e) => return compile_error(&e.render(&expr), span),
+    };
+    let insts = match gen_code(&ast) {
+        Ok(insts) => insts,
+        Err(e) => return compile_error(&e.to_string(), span),
+    };
+
+    let mut code = String::new();
+    for inst in &insts {
+        write_inst(&mut code, inst);
+        code.push_str(", ");
+    }
+    let names = ast
+        .capture_names()
+        .iter()
+        .map(|n| match n {
+            Some(n) => format!("::core::option::Option::Some({n:?})"),
+            None => "::core::option::Option::None".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let info = format!(
+        "::regexer::__private::Info {{ min_len: {}, max_len: {}, prefix: {:?}.into(), suffix: {:?}.into(), anchored_start: {}, anchored_end: {} }}",
+        ast.min_len(),
+        match ast.max_len() {
+            Some(len) => format!("::core::option::Option::Some({len})"),
+            None => "::core::option::Option::None".to_string(),
+        },
+        ast.literal_prefix(),
+        ast.literal_suffix(),
+        ast.is_anchored_start(),
+        ast.is_anchored_end(),
+    );
+
+    // 呼び出し箇所ごとのstaticに1度だけ生成し､DFAなどの構築を評価のたびに繰り返さない
+    format!(
+        "{{ static RE: ::regexer::__private::OnceLock<::regexer::Regex> = ::regexer::__private::OnceLock::new(); \
+         RE.get_or_init(|| ::regexer::Regex::__from_compiled({expr:?}, ::regexer::__private::vec![{code}], &[{names}], {info})) }}"
+    )
+    .parse()
+    .expect("generated code is valid")
+}
+
+/// 命令を､その命令を生成するRustの式として書き出す
+fn write_inst(dst: &mut String, inst: &Instruction) {
+    let path = "::regexer::Instruction";
+    let _ = match inst {
+        Instruction::Char(c) => write!(dst, "{path}::Char({c:?})"),
+        Instruction::Class(ranges, negated) => {
+            let ranges = ranges
+                .iter()
+                .map(|(lo, hi)| format!("({lo:?}, {hi:?})"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(
+                dst,
+                "{path}::Class(::regexer::__private::vec![{ranges}], {negated})"
+            )
+        }
+        Instruction::AnyChar => write!(dst, "{path}::AnyChar"),
+        Instruction::AnyCharExceptNewline => write!(dst, "{path}::AnyCharExceptNewline"),
+        Instruction::Grapheme => write!(dst, "{path}::Grapheme"),
+        Instruction::Match => write!(dst, "{path}::Match"),
+        Instruction::Jump(addr) => write!(dst, "{path}::Jump({addr})"),
+        Instruction::Split(addr1, addr2) => write!(dst, "{path}::Split({addr1}, {addr2})"),
+        Instruction::Save(slot) => write!(dst, "{path}::Save({slot})"),
+        Instruction::Backref(n) => write!(dst, "{path}::Backref({n})"),
+        Instruction::BackrefIgnoreCase(n) => write!(dst, "{path}::BackrefIgnoreCase({n})"),
+        Instruction::LookBehind(len, addr) => write!(dst, "{path}::LookBehind({len}, {addr})"),
+        Instruction::NegativeLookBehind(len, addr) => {
+            write!(dst, "{path}::NegativeLookBehind({len}, {addr})")
+        }
+        Instruction::Cond(n, addr) => write!(dst, "{path}::Cond({n}, {addr})"),
+        Instruction::Recurse(addr) => write!(dst, "{path}::Recurse({addr})"),
+        Instruction::Return => write!(dst, "{path}::Return"),
+        Instruction::EmptyCheckStart(n) => write!(dst, "{path}::EmptyCheckStart({n})"),
+        Instruction::EmptyCheckEnd(n, addr) => write!(dst, "{path}::EmptyCheckEnd({n}, {addr})"),
+        Instruction::MatchBegin => write!(dst, "{path}::MatchBegin"),
+        Instruction::MatchStart => write!(dst, "{path}::MatchStart"),
+        Instruction::MatchEnd(term) => write!(dst, "{path}::MatchEnd({})", terminator(*term)),
+        Instruction::MatchLineBegin(term) => {
+            write!(dst, "{path}::MatchLineBegin({})", terminator(*term))
+        }
+        Instruction::MatchLineEnd(term) => {
+            write!(dst, "{path}::MatchLineEnd({})", terminator(*term))
+        }
+        Instruction::MatchTextEnd => write!(dst, "{path}::MatchTextEnd"),
+    };
+}
+
+/// 改行の種類を､その値を生成するRustの式に変換
+fn terminator(term: LineTerminator) -> String {
+    match term {
+        LineTerminator::Lf => "::regexer::LineTerminator::Lf".to_string(),
+        LineTerminator::Crlf => "::regexer::LineTerminator::Crlf".to_string(),
+        LineTerminator::Char(c) => format!("::regexer::LineTerminator::Char({c:?})"),
+    }
+}
+
+/// マクロの引数から､文字列リテラルの内容とその位置を取り出す
+///
+/// 引数が文字列リテラル1つでない場合は､エラーのメッセージと位置を返す
+fn pattern_literal(input: TokenStream) -> Result<(String, Span), (String, Span)> {
+    let mut tokens = input.into_iter();
+    let lit = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(lit)), None) => lit,
+        // macro_rules!から渡された場合は､引数が区切りのないGroupで囲まれる
+        (Some(TokenTree::Group(g)), None) if g.delimiter() == Delimiter::None => {
+            return pattern_literal(g.stream());
+        }
+        (Some(t), _) => return Err(("expected a string literal".to_string(), t.span())),
+        (None, _) => {
+            return Err(("expected a string literal".to_string(), Span::call_site()));
+        }
+    };
+
+    let span = lit.span();
+    unquote(&lit.to_string())
+        .map(|s| (s, span))
+        .ok_or_else(|| ("expected a string literal".to_string(), span))
+}
+
+/// "..."やr#"..."#のような文字列リテラルを､その内容の文字列に変換
+///
+/// 文字列リテラルでない場合はNoneを返す
+fn unquote(lit: &str) -> Option<String> {
+    if let Some(raw) = lit.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let body = raw[hashes..].strip_suffix(&"#".repeat(hashes))?;
+        return Some(body.strip_prefix('"')?.strip_suffix('"')?.to_string());
+    }
+
+    let body = lit.strip_prefix('"')?.strip_suffix('"')?;
+    let mut s = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            s.push(c);
+            continue;
+        }
+
+        match chars.next()? {
+            'n' => s.push('\n'),
+            't' => s.push('\t'),
+            'r' => s.push('\r'),
+            '0' => s.push('\0'),
+            '\\' => s.push('\\'),
+            '\'' => s.push('\''),
+            '"' => s.push('"'),
+            'x' => {
+                let hex = chars.next()?.to_string() + &chars.next()?.to_string();
+                s.push(char::from(u8::from_str_radix(&hex, 16).ok()?));
+            }
+            'u' => {
+                chars.next().filter(|c| *c == '{')?;
+                let hex = chars
+                    .by_ref()
+                    .take_while(|c| *c != '}')
+                    .filter(|c| *c != '_')
+                    .collect::<String>();
+                s.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            // 行末の\は､改行と次の行の先頭の空白を取り除く
+            '\n' => while chars.next_if(|c| c.is_whitespace()).is_some() {},
+            _ => return None,
+        }
+    }
+
+    Some(s)
+}
+
+/// spanの位置でmsgを報告するcompile_error!の呼び出しを生成
+fn compile_error(msg: &str, span: Span) -> TokenStream {
+    let mut lit = Literal::string(msg);
+    lit.set_span(span);
+    let mut args = Group::new(Delimiter::Parenthesis, TokenTree::from(lit).into());
+    args.set_span(span);
+
+    [
+        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+        TokenTree::Ident(Ident::new("core", span)),
+        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(Punct::new('!', Spacing::Alone)),
+        TokenTree::Group(args),
+    ]
+    .into_iter()
+    .map(|mut t| {
+        t.set_span(span);
+        t
+    })
+    .collect()
+}
@@ -0,0 +1,56 @@
+use regexer::Regex;
+use regexer_macros::regexer;
+
+/// マクロで生成したRegexと､Regex::newで生成したRegexのマッチ結果が一致するかを判定
+fn assert_same(re: &Regex, haystacks: &[&str]) {
+    let expected = Regex::new(re.as_str()).unwrap();
+    assert_eq!(expected.captures_len(), re.captures_len());
+    assert!(expected.capture_names().eq(re.capture_names()));
+    assert_eq!(expected.min_len(), re.min_len());
+    assert_eq!(expected.max_len(), re.max_len());
+    assert_eq!(expected.literal_prefix(), re.literal_prefix());
+    assert_eq!(expected.literal_suffix(), re.literal_suffix());
+    assert_eq!(expected.is_anchored_start(), re.is_anchored_start());
+    assert_eq!(expected.is_anchored_end(), re.is_anchored_end());
+    for h in haystacks {
+        let spans = |re: &Regex| {
+            re.captures_iter(h)
+                .map(|caps| {
+                    let caps = caps.unwrap();
+                    caps.iter()
+                        .map(|m| m.map(|m| m.range()))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(spans(&expected), spans(re), "{} on {h:?}", re.as_str());
+    }
+}
+
+#[test]
+fn test_regexer() {
+    assert_same(regexer!("a+b|c"), &["aab", "xc", "b"]);
+    assert_same(regexer!("(?<y>\\d{4})-(\\d{2})"), &["on 2024-05", "x"]);
+    assert_same(regexer!("[^a-c]\\w*$"), &["abcdef", "ab\n"]);
+    assert_same(regexer!(r"(?m)^\s*#.*$"), &["a\n  # c\nb"]);
+    assert_same(regexer!(r#"(?<=")\w+(?<!")"#), &["\"key\" \"val"]);
+    assert_same(regexer!("(a)?(?(1)b|c)\\1"), &["abab", "c", "ab"]);
+    assert_same(regexer!("\\((a|(?R))*\\)"), &["((a)(a))", "(()"]);
+    assert_same(regexer!("\u{3042}\x41\t\\X"), &["あA\te\u{301}"]);
+    assert_same(regexer!("(?i)straße\\Z"), &["STRASSE", "Straße\n"]);
+
+    let re = regexer!("(?<key>\\w+)=(\\d+)");
+    let caps = re.captures("retry=3").unwrap().unwrap();
+    assert_eq!(&caps["key"], "retry");
+    assert_eq!(re.to_string(), "(?<key>\\w+)=(\\d+)");
+
+    // 同じ呼び出し箇所では､最初に生成したRegexを使い回す
+    let regexes = (0..3).map(|_| regexer!("a+")).collect::<Vec<_>>();
+    assert!(regexes.iter().all(|re| core::ptr::eq(*re, regexes[0])));
+}
+
+#[cfg(feature = "unicode-gencat")]
+#[test]
+fn test_regexer_unicode() {
+    assert_same(regexer!("\\p{L}+"), &["abc あいう 123"]);
+}
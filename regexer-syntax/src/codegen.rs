@@ -0,0 +1,670 @@
+//! ASTからコード生成を行う
+use super::{
+    parser::{case_fold_ranges, Ast, Flags},
+    Instruction, Limits,
+};
+use crate::helper::safe_add;
+use alloc::vec::Vec;
+use core::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+/// コード生成エラーを表す型
+#[derive(Debug)]
+pub enum CodeGenError {
+    PCOverFlow,
+    FailStar,
+    FailOr,
+    FailQuestion,
+    FailRepeat,
+    FailLookBehind,
+    FailCond,
+    ProgramTooLarge(usize), // 命令数がlimits.instsを超えた｡値は上限
+    RepeatTooLarge(usize),  // 繰り返し回数がlimits.repeatを超えた｡値は指定された回数
+}
+
+impl Display for CodeGenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeGenError::ProgramTooLarge(limit) => {
+                write!(
+                    f,
+                    "CodeGenError: program too large: limit = {limit} instructions"
+                )
+            }
+            CodeGenError::RepeatTooLarge(n) => {
+                write!(f, "CodeGenError: repetition count too large: count = {n}")
+            }
+            _ => write!(f, "CodeGenError: {:?}", self),
+        }
+    }
+}
+
+impl Error for CodeGenError {}
+
+#[derive(Debug, Default)]
+struct Generator {
+    pc: usize,
+    insts: Vec<Instruction>,
+    flags: Flags,      // コード生成中の式に適用されているフラグ
+    has_recurse: bool, // (?R)による再帰呼び出しを含むか
+    loops: usize,      // 空文字列にマッチしうる繰り返しの数
+    limits: Limits,    // 命令数と繰り返し回数の上限
+}
+
+impl Generator {
+    /// プログラムカウントをインクリメント
+    ///
+    /// 命令数がlimits.instsを超えた場合はエラーとする
+    fn inc_pc(&mut self) -> Result<(), CodeGenError> {
+        safe_add(&mut self.pc, &1, || CodeGenError::PCOverFlow)?;
+        if self.pc > self.limits.insts {
+            return Err(CodeGenError::ProgramTooLarge(self.limits.insts));
+        }
+
+        Ok(())
+    }
+
+    /// ASTをパターン分けし､コード生成を行う関数
+    fn gen_expr(&mut self, ast: &Ast) -> Result<(), CodeGenError> {
+        match ast {
+            Ast::Char(c) => self.gen_char(*c)?,
+            Ast::Dot => self.gen_dot()?,
+            Ast::Grapheme => self.gen_grapheme()?,
+            Ast::Class(ranges, negated) => self.gen_class(ranges, *negated)?,
+            Ast::Or(e1, e2) => self.gen_or(e1, e2)?,
+            Ast::Plus(e) => self.gen_plus(e)?,
+            Ast::Star(e) => self.gen_star(e)?,
+            Ast::Question(e) => self.gen_question(e)?,
+            Ast::Repeat(e, min, max) => self.gen_repeat(e, *min, *max)?,
+            Ast::Seq(v) => self.gen_seq(v)?,
+            Ast::Capture(e, n, _) => self.gen_capture(e, *n)?,
+            Ast::Backref(n) => self.gen_backref(*n)?,
+            Ast::LookBehind(e, negated) => self.gen_look_behind(e, *negated)?,
+            Ast::Cond(n, yes, no) => self.gen_cond(*n, yes, no.as_deref())?,
+            Ast::Recurse => self.gen_recurse()?,
+            Ast::ResetStart => self.gen_reset_start()?,
+            Ast::SearchStart => self.gen_anchor(Instruction::MatchStart)?,
+            Ast::Flags(flags, e) => self.gen_flags(*flags, e)?,
+            Ast::Doller => self.gen_doller()?,
+            Ast::Hat => self.gen_hat()?,
+            Ast::TextBegin => self.gen_anchor(Instruction::MatchBegin)?,
+            Ast::TextEnd => self.gen_anchor(Instruction::MatchTextEnd)?,
+            Ast::TextEndNewline => {
+                self.gen_anchor(Instruction::MatchEnd(self.flags.line_terminator))?
+            }
+        }
+
+        Ok(())
+    }
+
+    /// char命令生成器
+    ///
+    /// 大文字と小文字を区別しない場合は､対応する文字をまとめたclass命令を生成
+    fn gen_char(&mut self, c: char) -> Result<(), CodeGenError> {
+        if self.flags.case_insensitive {
+            let ranges = case_fold_ranges(&[(c, c)]);
+            if ranges.len() > 1 {
+                return self.gen_class(&ranges, false);
+            }
+        }
+
+        let inst = Instruction::Char(c);
+        self.insts.push(inst);
+        self.inc_pc()?;
+
+        Ok(())
+    }
+
+    /// .の命令生成器
+    ///
+    /// dotallモードの場合は任意の文字に､それ以外は改行以外の文字にマッチする命令を生成
+    fn gen_dot(&mut self) -> Result<(), CodeGenError> {
+        if self.flags.dot_all {
+            self.insts.push(Instruction::AnyChar);
+        } else {
+            self.insts.push(Instruction::AnyCharExceptNewline);
+        }
+        self.inc_pc()?;
+
+        Ok(())
+    }
+
+    /// \Xの命令生成器
+    ///
+    /// 1つの拡張書記素クラスタにマッチするgrapheme命令を生成
+    fn gen_grapheme(&mut self) -> Result<(), CodeGenError> {
+        self.insts.push(Instruction::Grapheme);
+        self.inc_pc()?;
+
+        Ok(())
+    }
+
+    /// class命令生成器
+    ///
+    /// 大文字と小文字を区別しない場合は､対応する文字を範囲に加える
+    fn gen_class(&mut self, ranges: &[(char, char)], negated: bool) -> Result<(), CodeGenError> {
+        let ranges = if self.flags.case_insensitive {
+            case_fold_ranges(ranges)
+        } else {
+            ranges.to_vec()
+        };
+        let inst = Instruction::Class(ranges, negated);
+        self.insts.push(inst);
+        self.inc_pc()?;
+
+        Ok(())
+    }
+
+    /// Or演算子のコード生成器
+    ///
+    /// 以下のようなコードを生成
+    ///
+    /// ```text
+    ///     split L1, L2
+    /// L1: e1のコード
+    ///     jmp L3
+    /// L2: e2のコード
+    /// L3:
+    /// ```
+    fn gen_or(&mut self, e1: &Ast, e2: &Ast) -> Result<(), CodeGenError> {
+        // split L1, L2
+        let split_addr = self.pc;
+        self.inc_pc()?;
+        let split = Instruction::Split(self.pc, 0); // L1 = self.pc L2は仮に0と設定
+        self.insts.push(split);
+
+        // L1: e1のコード
+        self.gen_expr(e1)?;
+
+        // jmp L3
+        let jmp_addr = self.pc;
+        self.insts.push(Instruction::Jump(0)); // L3を仮に0と設定
+
+        // L2の値を設定
+        self.inc_pc()?;
+        if let Some(Instruction::Split(_, l2)) = self.insts.get_mut(split_addr) {
+            *l2 = self.pc;
+        } else {
+            return Err(CodeGenError::FailOr);
+        }
+
+        // L2: e2のコード
+        self.gen_expr(e2)?;
+
+        // L3の値を設定
+        if let Some(Instruction::Jump(l3)) = self.insts.get_mut(jmp_addr) {
+            *l3 = self.pc;
+        } else {
+            return Err(CodeGenError::FailOr);
+        }
+
+        Ok(())
+    }
+
+    /// plus命令器
+    ///
+    /// 以下のようなコードを生成
+    /// ```text
+    /// L1: e1のコード
+    ///     split L1, L2
+    /// L2:
+    /// ```
+    ///
+    /// ()+のように､eが空文字列にしかマッチしない場合は､無限ループを避けるためにeのコードのみを生成
+    /// (a|)+のように､eが空文字列にもマッチしうる場合は､文字を読まなかった繰り返しでループを抜ける
+    /// ```text
+    /// L1: empty check start n
+    ///     e1のコード
+    ///     empty check end n, L2
+    ///     split L1, L2
+    /// L2:
+    /// ```
+    fn gen_plus(&mut self, e: &Ast) -> Result<(), CodeGenError> {
+        if e.fixed_len() == Some(0) {
+            return self.gen_expr(e);
+        }
+
+        // L1: eのコード生成
+        let addr = self.pc;
+        let check = self.gen_empty_check_start(e)?;
+        self.gen_expr(e)?;
+        let check_addr = self.gen_empty_check_end(check)?;
+
+        // split L1, L2
+        self.inc_pc()?;
+        self.insts.push(Instruction::Split(addr, self.pc));
+
+        // L2は次の命令になる
+        self.patch_empty_check_end(check_addr)
+    }
+
+    /// star命令生成器
+    ///
+    /// 以下のようなコードを生成
+    /// ```text
+    /// L1: split L2, L3
+    /// L2: e1のコード
+    ///     jmp L1
+    /// L3:
+    /// ```
+    ///
+    /// ()*のように､eが空文字列にしかマッチしない場合は､無限ループを避けるためにe?のコードを生成
+    /// (a|)*のように､eが空文字列にもマッチしうる場合は､文字を読まなかった繰り返しでループを抜ける
+    /// ```text
+    /// L1: split L2, L3
+    /// L2: empty check start n
+    ///     e1のコード
+    ///     empty check end n, L3
+    ///     jmp L1
+    /// L3:
+    /// ```
+    fn gen_star(&mut self, e: &Ast) -> Result<(), CodeGenError> {
+        if e.fixed_len() == Some(0) {
+            return self.gen_question(e);
+        }
+
+        // L1: split L2, L3
+        let addr = self.pc;
+        self.inc_pc()?;
+        self.insts.push(Instruction::Split(self.pc, 0)); // L2はL1直下の行になり､L3は不明のため0と仮定
+
+        // L2: e1のコード
+        let check = self.gen_empty_check_start(e)?;
+        self.gen_expr(e)?;
+        let check_addr = self.gen_empty_check_end(check)?;
+
+        // jmp L1
+        self.insts.push(Instruction::Jump(addr));
+        self.inc_pc()?;
+
+        // L3:
+        if let Some(Instruction::Split(_, l3)) = self.insts.get_mut(addr) {
+            *l3 = self.pc;
+        } else {
+            return Err(CodeGenError::FailStar);
+        }
+
+        // L3は次の命令になる
+        self.patch_empty_check_end(check_addr)
+    }
+
+    /// eが空文字列にもマッチしうる場合に､繰り返しの開始位置を記録するempty check start命令を生成
+    ///
+    /// 生成した場合は繰り返しの番号を返す
+    fn gen_empty_check_start(&mut self, e: &Ast) -> Result<Option<usize>, CodeGenError> {
+        if e.min_len() > 0 {
+            return Ok(None);
+        }
+
+        let n = self.loops;
+        self.loops += 1;
+        self.insts.push(Instruction::EmptyCheckStart(n));
+        self.inc_pc()?;
+
+        Ok(Some(n))
+    }
+
+    /// gen_empty_check_startで生成した繰り返しの末尾に､empty check end命令を生成
+    ///
+    /// ジャンプ先はループを抜けた位置で､不明のため0と仮定する
+    /// 生成した場合は､ジャンプ先を設定するために命令の位置を返す
+    fn gen_empty_check_end(&mut self, check: Option<usize>) -> Result<Option<usize>, CodeGenError> {
+        let Some(n) = check else {
+            return Ok(None);
+        };
+
+        let addr = self.pc;
+        self.insts.push(Instruction::EmptyCheckEnd(n, 0));
+        self.inc_pc()?;
+
+        Ok(Some(addr))
+    }
+
+    /// addrのempty check end命令のジャンプ先を､次の命令の位置に設定
+    fn patch_empty_check_end(&mut self, addr: Option<usize>) -> Result<(), CodeGenError> {
+        let Some(addr) = addr else {
+            return Ok(());
+        };
+
+        if let Some(Instruction::EmptyCheckEnd(_, exit)) = self.insts.get_mut(addr) {
+            *exit = self.pc;
+            Ok(())
+        } else {
+            Err(CodeGenError::FailStar)
+        }
+    }
+
+    /// question命令器
+    ///
+    /// 以下のようなコードを生成
+    /// ```text
+    ///     split L1, L2
+    /// L1: e1のコード
+    /// L2:
+    /// ```
+    fn gen_question(&mut self, e: &Ast) -> Result<(), CodeGenError> {
+        // split L1, L2
+        let split_addr = self.pc; // L1はsplit直下の行になる
+        self.inc_pc()?;
+        let addr = self.pc; // L1はsplit直下の行になる
+        self.insts.push(Instruction::Split(addr, 0)); // L2は不明のため0と仮定
+
+        // L1: e1のコード
+        self.gen_expr(e)?;
+
+        // L2:
+        if let Some(Instruction::Split(_, l2)) = self.insts.get_mut(split_addr) {
+            *l2 = self.pc;
+        } else {
+            return Err(CodeGenError::FailQuestion);
+        }
+
+        // L2は次の命令になる
+        Ok(())
+    }
+
+    /// repeat命令器
+    ///
+    /// e{n,m}は､eのコードをn回展開した後に､
+    /// 省略可能なeのコードをm - n回展開した以下のようなコードを生成
+    /// ```text
+    ///     e1のコード (n回)
+    ///     ...
+    ///     split L1, L3
+    /// L1: e1のコード
+    ///     split L2, L3
+    /// L2: e1のコード
+    ///     ...
+    /// L3:
+    /// ```
+    ///
+    /// e{n,}は､eのコードをn回展開した後に､e*のコードを生成
+    /// nやmがlimits.repeatを超える場合は､展開する前にエラーとする
+    fn gen_repeat(&mut self, e: &Ast, min: usize, max: Option<usize>) -> Result<(), CodeGenError> {
+        let count = max.unwrap_or(min);
+        if count > self.limits.repeat {
+            return Err(CodeGenError::RepeatTooLarge(count));
+        }
+
+        for _ in 0..min {
+            self.gen_expr(e)?;
+        }
+
+        let max = if let Some(max) = max {
+            max
+        } else {
+            return self.gen_star(e);
+        };
+
+        // split L1, L3
+        // L1: e1のコード
+        let mut split_addrs = Vec::new();
+        for _ in min..max {
+            split_addrs.push(self.pc);
+            self.inc_pc()?;
+            self.insts.push(Instruction::Split(self.pc, 0)); // L3は不明のため0と仮定
+            self.gen_expr(e)?;
+        }
+
+        // L3:
+        for addr in split_addrs {
+            if let Some(Instruction::Split(_, l3)) = self.insts.get_mut(addr) {
+                *l3 = self.pc;
+            } else {
+                return Err(CodeGenError::FailRepeat);
+            }
+        }
+
+        // L3は次の命令になる
+        Ok(())
+    }
+
+    /// doller命令器
+    /// これは文字列の終端をチェックする
+    /// 文字列の終端か､終端の改行の直前ならマッチする
+    /// マルチラインモードの場合は､次の文字が改行か終端ならマッチする
+    /// 改行の種類はflags.line_terminatorに従う
+    fn gen_doller(&mut self) -> Result<(), CodeGenError> {
+        let term = self.flags.line_terminator;
+        if self.flags.multi_line {
+            self.insts.push(Instruction::MatchLineEnd(term));
+        } else {
+            self.insts.push(Instruction::MatchEnd(term));
+        }
+        self.inc_pc()?;
+
+        Ok(())
+    }
+
+    /// hat命令器
+    /// これは文字列の先頭をチェックする
+    /// 文字列の先頭ならマッチする
+    /// マルチラインモードの場合は､改行の直後でもマッチする
+    fn gen_hat(&mut self) -> Result<(), CodeGenError> {
+        if self.flags.multi_line {
+            self.insts
+                .push(Instruction::MatchLineBegin(self.flags.line_terminator));
+        } else {
+            self.insts.push(Instruction::MatchBegin);
+        }
+        self.inc_pc()?;
+
+        Ok(())
+    }
+
+    /// \A, \z, \Z, \Gのコード生成器
+    /// これらはマルチラインモードに関わらず､文字列全体の先頭と末尾､探索の開始位置をチェックする
+    fn gen_anchor(&mut self, inst: Instruction) -> Result<(), CodeGenError> {
+        self.insts.push(inst);
+        self.inc_pc()?;
+
+        Ok(())
+    }
+
+    /// \Kのコード生成器
+    ///
+    /// マッチ全体の開始位置を保存するslot 0に､現在の位置を上書きする
+    fn gen_reset_start(&mut self) -> Result<(), CodeGenError> {
+        self.insts.push(Instruction::Save(0));
+        self.inc_pc()?;
+
+        Ok(())
+    }
+
+    /// キャプチャグループのコード生成器
+    ///
+    /// n番目のグループは以下のようなコードを生成
+    /// ```text
+    /// save 2n
+    /// e1のコード
+    /// save 2n + 1
+    /// ```
+    fn gen_capture(&mut self, e: &Ast, n: usize) -> Result<(), CodeGenError> {
+        self.gen_save(n * 2)?;
+        self.gen_expr(e)?;
+        self.gen_save(n * 2 + 1)?;
+
+        Ok(())
+    }
+
+    /// save命令生成器
+    fn gen_save(&mut self, slot: usize) -> Result<(), CodeGenError> {
+        self.insts.push(Instruction::Save(slot));
+        self.inc_pc()?;
+
+        Ok(())
+    }
+
+    /// backref命令生成器
+    ///
+    /// 大文字と小文字を区別しない場合は､backref ignore case命令を生成
+    fn gen_backref(&mut self, n: usize) -> Result<(), CodeGenError> {
+        if self.flags.case_insensitive {
+            self.insts.push(Instruction::BackrefIgnoreCase(n));
+        } else {
+            self.insts.push(Instruction::Backref(n));
+        }
+        self.inc_pc()?;
+
+        Ok(())
+    }
+
+    /// 後読みのコード生成器
+    ///
+    /// eの長さをnとして､以下のようなコードを生成
+    /// ```text
+    ///     lookbehind n, L1
+    ///     e1のコード
+    ///     match
+    /// L1:
+    /// ```
+    ///
+    /// lookbehind命令は､n文字前からe1のコードを評価し､
+    /// matchに到達した場合にL1へジャンプする
+    /// 否定の場合はnegative lookbehind命令となり､matchに到達しなかった場合にL1へジャンプする
+    fn gen_look_behind(&mut self, e: &Ast, negated: bool) -> Result<(), CodeGenError> {
+        let len = e.fixed_len().ok_or(CodeGenError::FailLookBehind)?;
+
+        // lookbehind n, L1
+        let addr = self.pc;
+        self.inc_pc()?;
+        if negated {
+            self.insts.push(Instruction::NegativeLookBehind(len, 0)); // L1は不明のため0と仮定
+        } else {
+            self.insts.push(Instruction::LookBehind(len, 0)); // L1は不明のため0と仮定
+        }
+
+        // e1のコード
+        // match
+        self.gen_expr(e)?;
+        self.inc_pc()?;
+        self.insts.push(Instruction::Match);
+
+        // L1:
+        match self.insts.get_mut(addr) {
+            Some(Instruction::LookBehind(_, l1)) | Some(Instruction::NegativeLookBehind(_, l1)) => {
+                *l1 = self.pc;
+            }
+            _ => return Err(CodeGenError::FailLookBehind),
+        }
+
+        Ok(())
+    }
+
+    /// 条件分岐のコード生成器
+    ///
+    /// n番目のグループを条件として､以下のようなコードを生成
+    /// ```text
+    ///     cond n, L1
+    ///     yesのコード
+    ///     jmp L2
+    /// L1: noのコード
+    /// L2:
+    /// ```
+    ///
+    /// cond命令は､n番目のグループがマッチしていない場合にL1へジャンプする
+    fn gen_cond(&mut self, n: usize, yes: &Ast, no: Option<&Ast>) -> Result<(), CodeGenError> {
+        // cond n, L1
+        let cond_addr = self.pc;
+        self.inc_pc()?;
+        self.insts.push(Instruction::Cond(n, 0)); // L1を仮に0と設定
+
+        // yesのコード
+        self.gen_expr(yes)?;
+
+        // jmp L2
+        let jmp_addr = self.pc;
+        self.inc_pc()?;
+        self.insts.push(Instruction::Jump(0)); // L2を仮に0と設定
+
+        // L1の値を設定
+        if let Some(Instruction::Cond(_, l1)) = self.insts.get_mut(cond_addr) {
+            *l1 = self.pc;
+        } else {
+            return Err(CodeGenError::FailCond);
+        }
+
+        // L1: noのコード
+        if let Some(no) = no {
+            self.gen_expr(no)?;
+        }
+
+        // L2の値を設定
+        if let Some(Instruction::Jump(l2)) = self.insts.get_mut(jmp_addr) {
+            *l2 = self.pc;
+        } else {
+            return Err(CodeGenError::FailCond);
+        }
+
+        Ok(())
+    }
+
+    /// 再帰呼び出しのコード生成器
+    ///
+    /// パターン全体の先頭であるsave 0の次の命令へ､recurse命令で再帰呼び出しを行う
+    /// 呼び出し先からは､パターン全体の末尾のreturn命令で戻る
+    fn gen_recurse(&mut self) -> Result<(), CodeGenError> {
+        self.insts.push(Instruction::Recurse(1));
+        self.inc_pc()?;
+        self.has_recurse = true;
+
+        Ok(())
+    }
+
+    /// フラグ付きの式のコード生成器
+    ///
+    /// eのコード生成中のみ､フラグを適用する
+    fn gen_flags(&mut self, flags: Flags, e: &Ast) -> Result<(), CodeGenError> {
+        let prev = self.flags;
+        self.flags = flags;
+        self.gen_expr(e)?;
+        self.flags = prev;
+
+        Ok(())
+    }
+
+    /// 連続するASTのコードを生成
+    fn gen_seq(&mut self, exprs: &[Ast]) -> Result<(), CodeGenError> {
+        for e in exprs {
+            self.gen_expr(e)?;
+        }
+
+        Ok(())
+    }
+
+    /// コード生成を行う関数の入り口
+    ///
+    /// マッチ全体を0番目のグループとして保存する
+    /// 再帰呼び出しを含む場合は､式の末尾に呼び出し元へ戻るreturn命令を生成
+    fn gen_code(&mut self, ast: &Ast) -> Result<(), CodeGenError> {
+        self.gen_save(0)?;
+        self.gen_expr(ast)?;
+        if self.has_recurse {
+            self.insts.push(Instruction::Return);
+            self.inc_pc()?;
+        }
+        self.gen_save(1)?;
+        self.inc_pc()?;
+        self.insts.push(Instruction::Match);
+
+        Ok(())
+    }
+}
+
+pub fn gen_code(ast: &Ast) -> Result<Vec<Instruction>, CodeGenError> {
+    gen_code_with_limits(ast, &Limits::default())
+}
+
+/// 資源の上限を指定してコード生成を行う
+///
+/// 命令数がlimits.instsを超える場合や､{n,m}のnかmがlimits.repeatを超える場合はErrを返す
+pub fn gen_code_with_limits(ast: &Ast, limits: &Limits) -> Result<Vec<Instruction>, CodeGenError> {
+    let mut generator = Generator {
+        limits: *limits,
+        ..Default::default()
+    };
+    generator.gen_code(ast)?;
+    Ok(generator.insts)
+}
@@ -0,0 +1,106 @@
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use core::{
+    marker::PhantomData,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+#[cfg(feature = "std")]
+pub use std::sync::OnceLock;
+
+pub trait SafeAdd: Sized {
+    fn safe_add(&self, n: &Self) -> Option<Self>;
+}
+
+impl SafeAdd for usize {
+    fn safe_add(&self, n: &Self) -> Option<Self> {
+        self.checked_add(*n)
+    }
+}
+
+pub fn safe_add<T, F, E>(dst: &mut T, src: &T, f: F) -> Result<(), E>
+where
+    T: SafeAdd,
+    F: Fn() -> E,
+{
+    if let Some(n) = dst.safe_add(src) {
+        *dst = n;
+        Ok(())
+    } else {
+        Err(f())
+    }
+}
+
+/// 一度だけ初期化される値
+///
+/// stdフィーチャが無効な場合の､std::sync::OnceLockの代わり
+/// 複数のスレッドが同時に初期化した場合は､最初に格納した値を使い､他の値は捨てる
+#[cfg(not(feature = "std"))]
+pub struct OnceLock<T> {
+    ptr: AtomicPtr<T>,
+    _marker: PhantomData<T>, // 格納した値を所有することを表す
+}
+
+// SAFETY: 共有したOnceLockからは､どのスレッドも&Tを得て､初期化したスレッドとは別のスレッドでTを捨てうる
+// そのため､std::sync::OnceLockと同じくTがSyncかつSendの場合のみ共有できる
+#[cfg(not(feature = "std"))]
+unsafe impl<T: Send + Sync> Sync for OnceLock<T> {}
+
+// SAFETY: 別のスレッドへ移すと､格納した値もそのスレッドで使われて捨てられる
+#[cfg(not(feature = "std"))]
+unsafe impl<T: Send> Send for OnceLock<T> {}
+
+#[cfg(not(feature = "std"))]
+impl<T> OnceLock<T> {
+    pub const fn new() -> Self {
+        OnceLock {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        let p = self.ptr.load(Ordering::Acquire);
+        if !p.is_null() {
+            // SAFETY: 格納したポインタはselfを捨てるまで解放しないため､selfを借用している間は有効
+            // Acquireで読んだため､格納したスレッドでの値の初期化も見える
+            return unsafe { &*p };
+        }
+
+        let new = Box::into_raw(Box::new(f()));
+        match self
+            .ptr
+            .compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire)
+        {
+            // SAFETY: 格納に成功したnewは､以降selfを捨てるまで解放しない
+            Ok(_) => unsafe { &*new },
+            Err(p) => {
+                // SAFETY: 他のスレッドが先に格納したため､newは格納されず他のスレッドからは見えない
+                // そのため､このスレッドが唯一の所有者として解放できる
+                // pは他のスレッドが格納に成功したポインタで､AcqRelの失敗時のAcquireで初期化も見える
+                drop(unsafe { Box::from_raw(new) });
+                unsafe { &*p }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<T> Default for OnceLock<T> {
+    fn default() -> Self {
+        OnceLock::new()
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<T> Drop for OnceLock<T> {
+    fn drop(&mut self) {
+        let p = *self.ptr.get_mut();
+        if !p.is_null() {
+            // SAFETY: 格納したポインタはBox::into_rawで生成し､まだ解放していない
+            // &mut selfのため､他に参照している箇所はない
+            drop(unsafe { Box::from_raw(p) });
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! regexerのパターンのパーサとコード生成器
+//!
+//! regexerと､パターンをコンパイル時に検査するregexer-macrosの双方から利用する
+//! 通常はregexerが再公開する項目を利用し､このクレートを直接利用することは想定しない
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod codegen;
+#[doc(hidden)]
+pub mod helper;
+pub mod parser;
+pub mod visit;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Display;
+use parser::LineTerminator;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Instruction {
+    Char(char),
+    Class(Vec<(char, char)>, bool),
+    AnyChar,
+    AnyCharExceptNewline,
+    Grapheme,
+    Match,
+    Jump(usize),
+    Split(usize, usize),
+    Save(usize),
+    Backref(usize),
+    BackrefIgnoreCase(usize),
+    LookBehind(usize, usize),
+    NegativeLookBehind(usize, usize),
+    Cond(usize, usize),
+    Recurse(usize),
+    Return,
+    EmptyCheckStart(usize),      // n番目の繰り返しの開始位置を記録する
+    EmptyCheckEnd(usize, usize), // n番目の繰り返しが文字を読まなかった場合は､ループを抜けてジャンプする
+    MatchBegin,
+    MatchStart,
+    MatchEnd(LineTerminator),
+    MatchLineBegin(LineTerminator),
+    MatchLineEnd(LineTerminator),
+    MatchTextEnd,
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Instruction::Char(c) => write!(f, "char {}", c),
+            Instruction::AnyChar => write!(f, "any char"),
+            Instruction::AnyCharExceptNewline => write!(f, "any char except newline"),
+            Instruction::Grapheme => write!(f, "grapheme"),
+            Instruction::Class(ranges, negated) => {
+                let set = ranges
+                    .iter()
+                    .map(|(lo, hi)| {
+                        if lo == hi {
+                            lo.to_string()
+                        } else {
+                            format!("{lo}-{hi}")
+                        }
+                    })
+                    .collect::<String>();
+                if *negated {
+                    write!(f, "class [^{}]", set)
+                } else {
+                    write!(f, "class [{}]", set)
+                }
+            }
+            Instruction::Match => write!(f, "match"),
+            Instruction::Jump(addr) => write!(f, "jump {:>04}", addr),
+            Instruction::Split(addr1, addr2) => write!(f, "split {:>04}, {:>04}", addr1, addr2),
+            Instruction::Save(slot) => write!(f, "save {}", slot),
+            Instruction::Backref(n) => write!(f, "backref {}", n),
+            Instruction::BackrefIgnoreCase(n) => write!(f, "backref {} (ignore case)", n),
+            Instruction::LookBehind(len, addr) => write!(f, "lookbehind {}, {:>04}", len, addr),
+            Instruction::NegativeLookBehind(len, addr) => {
+                write!(f, "negative lookbehind {}, {:>04}", len, addr)
+            }
+            Instruction::Cond(n, addr) => write!(f, "cond {}, {:>04}", n, addr),
+            Instruction::Recurse(addr) => write!(f, "recurse {:>04}", addr),
+            Instruction::Return => write!(f, "return"),
+            Instruction::EmptyCheckStart(n) => write!(f, "empty check start {}", n),
+            Instruction::EmptyCheckEnd(n, addr) => {
+                write!(f, "empty check end {}, {:>04}", n, addr)
+            }
+            Instruction::MatchBegin => write!(f, "match begin"),
+            Instruction::MatchStart => write!(f, "match start"),
+            Instruction::MatchEnd(term) => write!(f, "match end{}", TerminatorSuffix(term)),
+            Instruction::MatchLineBegin(term) => {
+                write!(f, "match line begin{}", TerminatorSuffix(term))
+            }
+            Instruction::MatchLineEnd(term) => {
+                write!(f, "match line end{}", TerminatorSuffix(term))
+            }
+            Instruction::MatchTextEnd => write!(f, "match text end"),
+        }
+    }
+}
+
+/// 命令の表示で､\n以外の改行を指定した場合に付ける接尾辞
+struct TerminatorSuffix<'a>(&'a LineTerminator);
+
+impl Display for TerminatorSuffix<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            LineTerminator::Lf => Ok(()),
+            LineTerminator::Crlf => write!(f, " (crlf)"),
+            LineTerminator::Char(c) => write!(f, " ({:?})", c),
+        }
+    }
+}
+
+/// コード生成時と評価時の資源の上限
+///
+/// 一部の上限のみを変更する場合は､..Default::default()で残りを既定値とする
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// (?R)による再帰呼び出しの深さの上限
+    pub recursion: usize,
+    /// コード生成する命令数の上限
+    pub insts: usize,
+    /// {n,m}による繰り返しのnとmの上限
+    pub repeat: usize,
+    /// 深さ優先探索で1回のマッチの試行中に実行する命令数の上限
+    ///
+    /// バックトラックが指数的に増えるパターンで､評価を打ち切るために用いる
+    pub steps: usize,
+    /// 深さ優先探索で､訪れた命令と位置の組を記録する数の上限
+    ///
+    /// 一度失敗した組を再び試さないため､(a|a)*bのようなパターンも命令数と文字数の積に比例する時間で終わる
+    /// 命令数と文字数の積が上限を超える場合や0の場合は記録しない
+    /// 後方参照､条件分岐､再帰を含むパターンや近似マッチングでは､
+    /// 同じ組でもキャプチャなどによって結果が変わるため記録しない
+    pub visited: usize,
+    /// マッチの有無の判定に用いるDFAの大きさの上限
+    ///
+    /// 遷移表の要素数(状態数と文字の種類の数の積)と､各状態が持つ命令の位置の数の合計で数える
+    /// 上限を超える場合は､必要になった状態のみを上限の範囲で構築する遅延DFAとなる
+    /// 0の場合は､DFAを用いずに探索で判定する
+    pub dfa: usize,
+    /// 分岐が入力の1文字で一意に決まるパターンで､1度の走査でマッチングする評価器の大きさの上限
+    ///
+    /// 文字を読む命令の次の位置ごとに､文字を読まずに到達できる命令の数と経路上のsave命令やアンカーの数を合計して数える
+    /// 上限を超える場合や0の場合は､探索でマッチングする
+    pub onepass: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            recursion: 32,
+            insts: 1 << 20,
+            repeat: 10_000,
+            steps: 10_000_000,
+            visited: 1 << 24,
+            dfa: 1 << 16,
+            onepass: 1 << 16,
+        }
+    }
+}
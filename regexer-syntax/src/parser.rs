@@ -0,0 +1,2441 @@
+//! 正規表現の式をパースし､中小構文木へ変換
+use crate::helper::OnceLock;
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    error::Error,
+    fmt::{self, Display},
+    iter::{Enumerate, Peekable},
+    mem::take,
+    ops::Range,
+    str::Chars,
+};
+
+/// 正規表現の抽象構文木
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ast {
+    Char(char),
+    Dot,
+    Grapheme,                       // \X｡拡張書記素クラスタ
+    Class(Vec<(char, char)>, bool), // 文字クラス｡trueの場合は否定
+    Plus(Box<Ast>),
+    Star(Box<Ast>),
+    Question(Box<Ast>),
+    Repeat(Box<Ast>, usize, Option<usize>), // {n,m}による繰り返し｡Noneの場合は上限なし
+    Or(Box<Ast>, Box<Ast>),
+    Seq(Vec<Ast>),
+    Capture(Box<Ast>, usize, Option<String>), // キャプチャグループ｡番号は1から始まり､名前は省略可能
+    Backref(usize),                           // \1のような後方参照
+    LookBehind(Box<Ast>, bool),               // 後読み｡trueの場合は否定
+    Flags(Flags, Box<Ast>),                   // フラグを適用する式
+    Doller,
+    Hat,
+    TextBegin,                               // \A｡マルチラインモードに関わらず文字列の先頭
+    TextEnd,                                 // \z｡マルチラインモードに関わらず文字列の末尾
+    TextEndNewline,                          // \Z｡文字列の末尾か､末尾の改行の直前
+    Cond(usize, Box<Ast>, Option<Box<Ast>>), // (?(n)yes|no)による条件分岐
+    Recurse,                                 // (?R)によるパターン全体の再帰呼び出し
+    ResetStart,                              // \K｡マッチの開始位置を現在の位置とする
+    SearchStart,                             // \G｡探索を開始した位置
+}
+
+/// パースエラーの種類
+///
+/// 最初の要素は､パターン中でエラーとなった範囲を､パターンの先頭からの文字単位の位置で表す
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    InvalidEscape(Range<usize>, char), // 誤ったエスケープシーケンス
+    InvalidRightParen(Range<usize>),   // 開き括弧なし
+    NoPrev(Range<usize>),              // +,*,?の前に式がない
+    NoRightParen(Range<usize>),        // 閉じ括弧なし｡範囲は閉じられていない開き括弧
+    NoRightBracket(Range<usize>),      // 文字クラスの閉じ括弧なし｡範囲は開き括弧
+    InvalidRange(Range<usize>, char, char), // 文字クラスの範囲の順序が逆
+    InvalidRepeat(Range<usize>),       // 誤った繰り返し回数の指定
+    NoRepeatMin(Range<usize>),         // 繰り返しの最小回数がない
+    InvalidRepeatRange(Range<usize>, usize, usize), // 繰り返しの範囲の順序が逆
+    InvalidBackref(Range<usize>, usize), // 存在しないグループへの後方参照
+    InvalidGroup(Range<usize>),        // 誤った(?...)の構文
+    VariableLookBehind(Range<usize>),  // 後読みの長さが固定でない
+    UnknownFlag(Range<usize>, char),   // 未知のフラグ
+    UnknownPosixClass(Range<usize>, String), // 未知のPOSIX文字クラス
+    UnknownProperty(Range<usize>, String), // 未知のUnicodeプロパティ
+    InvalidHexEscape(Range<usize>),    // 誤った16進数のエスケープ
+    InvalidOctalEscape(Range<usize>),  // 誤った8進数のエスケープ
+    InvalidCodePoint(Range<usize>, u32), // 文字として不正なコードポイント
+    InvalidCondition(Range<usize>),    // 条件分岐の選択肢が3つ以上
+    InvalidGroupName(Range<usize>),    // 誤ったグループの名前
+    DuplicateGroupName(Range<usize>, String), // 同じ名前のグループが複数ある
+    Empty(Range<usize>),               // 空のパターン
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidEscape(Range { start: pos, .. }, c) => {
+                write!(f, "ParseError: invalid escape: pos = {pos}, char = '{c}'")
+            }
+            ParseError::InvalidRightParen(Range { start: pos, .. }) => {
+                write!(f, "ParseError: invalid right parenthesis: pos = {pos}")
+            }
+            ParseError::NoPrev(Range { start: pos, .. }) => {
+                write!(f, "ParseError: no previous expression: pos = {pos}")
+            }
+            ParseError::NoRightParen(_) => {
+                write!(f, "ParseError: no right parenthesis")
+            }
+            ParseError::NoRightBracket(_) => {
+                write!(f, "ParseError: no right bracket")
+            }
+            ParseError::InvalidRange(_, lo, hi) => {
+                write!(f, "ParseError: invalid range: '{lo}-{hi}'")
+            }
+            ParseError::InvalidRepeat(Range { start: pos, .. }) => {
+                write!(f, "ParseError: invalid repetition: pos = {pos}")
+            }
+            ParseError::NoRepeatMin(Range { start: pos, .. }) => {
+                write!(f, "ParseError: no minimum repetition count: pos = {pos}")
+            }
+            ParseError::InvalidRepeatRange(_, min, max) => {
+                write!(f, "ParseError: invalid repetition range: {{{min},{max}}}")
+            }
+            ParseError::InvalidBackref(_, n) => {
+                write!(f, "ParseError: invalid back reference: group = {n}")
+            }
+            ParseError::InvalidGroup(Range { start: pos, .. }) => {
+                write!(f, "ParseError: invalid group: pos = {pos}")
+            }
+            ParseError::VariableLookBehind(Range { start: pos, .. }) => {
+                write!(
+                    f,
+                    "ParseError: look-behind requires fixed length: pos = {pos}"
+                )
+            }
+            ParseError::UnknownFlag(Range { start: pos, .. }, c) => {
+                write!(f, "ParseError: unknown flag: pos = {pos}, char = '{c}'")
+            }
+            ParseError::UnknownPosixClass(Range { start: pos, .. }, name) => {
+                write!(
+                    f,
+                    "ParseError: unknown POSIX class: pos = {pos}, name = '{name}'"
+                )
+            }
+            ParseError::UnknownProperty(Range { start: pos, .. }, name) => {
+                write!(
+                    f,
+                    "ParseError: unknown Unicode property: pos = {pos}, name = '{name}'"
+                )
+            }
+            ParseError::InvalidHexEscape(Range { start: pos, .. }) => {
+                write!(f, "ParseError: invalid hex escape: pos = {pos}")
+            }
+            ParseError::InvalidOctalEscape(Range { start: pos, .. }) => {
+                write!(f, "ParseError: invalid octal escape: pos = {pos}")
+            }
+            ParseError::InvalidCodePoint(Range { start: pos, .. }, code) => {
+                write!(
+                    f,
+                    "ParseError: invalid code point: pos = {pos}, code = U+{code:X}"
+                )
+            }
+            ParseError::InvalidCondition(Range { start: pos, .. }) => {
+                write!(
+                    f,
+                    "ParseError: conditional group has more than two branches: pos = {pos}"
+                )
+            }
+            ParseError::InvalidGroupName(Range { start: pos, .. }) => {
+                write!(f, "ParseError: invalid group name: pos = {pos}")
+            }
+            ParseError::DuplicateGroupName(_, name) => {
+                write!(f, "ParseError: duplicate group name: name = {name}")
+            }
+            ParseError::Empty(_) => {
+                write!(f, "ParseError: empty expression")
+            }
+        }
+    }
+}
+
+impl Error for ParseError {} // エラー用に､Errorトレイトを実装
+
+impl ParseError {
+    /// パターン中でエラーとなった範囲
+    ///
+    /// 範囲はパターンの先頭からの文字単位の位置となる
+    /// 閉じ括弧がない場合は閉じられていない開き括弧を､空のパターンの場合はパターン全体を返す
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer;
+    /// let e = regexer::parse("ab{2,x}").unwrap_err();
+    /// assert_eq!(e.span(), 2..7);
+    /// ```
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            ParseError::InvalidEscape(span, _)
+            | ParseError::InvalidRightParen(span)
+            | ParseError::NoPrev(span)
+            | ParseError::NoRightParen(span)
+            | ParseError::NoRightBracket(span)
+            | ParseError::InvalidRange(span, ..)
+            | ParseError::InvalidRepeat(span)
+            | ParseError::NoRepeatMin(span)
+            | ParseError::InvalidRepeatRange(span, ..)
+            | ParseError::InvalidBackref(span, _)
+            | ParseError::InvalidGroup(span)
+            | ParseError::VariableLookBehind(span)
+            | ParseError::UnknownFlag(span, _)
+            | ParseError::UnknownPosixClass(span, _)
+            | ParseError::UnknownProperty(span, _)
+            | ParseError::InvalidHexEscape(span)
+            | ParseError::InvalidOctalEscape(span)
+            | ParseError::InvalidCodePoint(span, _)
+            | ParseError::InvalidCondition(span)
+            | ParseError::InvalidGroupName(span)
+            | ParseError::DuplicateGroupName(span, _)
+            | ParseError::Empty(span) => span.clone(),
+        }
+    }
+
+    /// エラーの修正方法の手がかり
+    pub fn hint(&self) -> &'static str {
+        match self {
+            ParseError::InvalidEscape(..) => {
+                "unknown escape sequence; write \\\\ for a literal backslash"
+            }
+            ParseError::InvalidRightParen(_) => "no matching '('; write \\) for a literal ')'",
+            ParseError::NoPrev(_) => "nothing to repeat; escape it to match the character itself",
+            ParseError::NoRightParen(_) => "this group is not closed; add ')'",
+            ParseError::NoRightBracket(_) => "this character class is not closed; add ']'",
+            ParseError::InvalidRange(..) => "the start of a range must not be greater than the end",
+            ParseError::InvalidRepeat(_) => "write a repetition as {n}, {n,} or {n,m}",
+            ParseError::NoRepeatMin(_) => "the minimum count is required, such as {0,m}",
+            ParseError::InvalidRepeatRange(..) => {
+                "the minimum count must not be greater than the maximum"
+            }
+            ParseError::InvalidBackref(..) => "there is no group with this number",
+            ParseError::InvalidGroup(_) => "unknown or unclosed group syntax",
+            ParseError::VariableLookBehind(_) => {
+                "a look-behind must match a fixed number of characters"
+            }
+            ParseError::UnknownFlag(..) => "supported flags are i, m, s and x",
+            ParseError::UnknownPosixClass(..) => "such as [:alpha:], [:digit:] or [:space:]",
+            ParseError::UnknownProperty(..) => {
+                "some properties require the unicode-gencat or unicode-script feature"
+            }
+            ParseError::InvalidHexEscape(_) => "write a hex escape as \\xHH or \\x{H..}",
+            ParseError::InvalidOctalEscape(_) => "write an octal escape as \\o{..} with digits 0-7",
+            ParseError::InvalidCodePoint(..) => {
+                "a code point must be at most U+10FFFF and not a surrogate"
+            }
+            ParseError::InvalidCondition(_) => {
+                "a conditional group takes at most two branches: (?(n)yes|no)"
+            }
+            ParseError::InvalidGroupName(_) => {
+                "a group name must start with a letter or '_' and contain only word characters"
+            }
+            ParseError::DuplicateGroupName(..) => "each group name must be unique",
+            ParseError::Empty(_) => "the pattern is empty",
+        }
+    }
+
+    /// パターンのエラーとなった範囲に^^^で下線を引き､手がかりを付けた診断メッセージを生成
+    ///
+    /// 1行目にエラーの内容を､2行目にパターンを､3行目に下線と手がかりを表示する
+    /// パターンが複数行の場合は､範囲の開始位置を含む行のみを表示する
+    /// 下線の位置は1文字を幅1として求めるため､全角文字を含む場合はずれることがある
+    /// exprには､このエラーを返したパースに与えたパターンを指定する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer;
+    /// let e = regexer::parse("a)").unwrap_err();
+    /// assert_eq!(
+    ///     e.render("a)"),
+    ///     "ParseError: invalid right parenthesis: pos = 1\n  a)\n   ^ no matching '('; write \\) for a literal ')'\n"
+    /// );
+    /// ```
+    pub fn render(&self, expr: &str) -> String {
+        let chars = expr.chars().collect::<Vec<char>>();
+        let span = self.span();
+        let start = span.start.min(chars.len());
+        let span = start..span.end.clamp(start, chars.len());
+
+        // 範囲の開始位置を含む行
+        let line_start = chars[..span.start]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1);
+        let line_end = chars[span.start..]
+            .iter()
+            .position(|c| *c == '\n')
+            .map_or(chars.len(), |i| span.start + i);
+        let line = chars[line_start..line_end].iter().collect::<String>();
+        let width = span.end.min(line_end).saturating_sub(span.start).max(1);
+
+        format!(
+            "{self}\n  {line}\n  {}{} {}\n",
+            " ".repeat(span.start - line_start),
+            "^".repeat(width),
+            self.hint()
+        )
+    }
+}
+
+/// ASTを､同じ文字列にマッチするパターンに戻す
+///
+/// エスケープは必要な文字のみに行い､(?i)などのフラグは(?i-ms:..)のように適用範囲を括弧で囲む
+/// パース時に展開される\wや[[:alpha:]]のような略記は､展開後の文字クラスとなる
+/// また､line_terminatorのようなパターン中で指定できないフラグは表されない
+///
+/// # 利用例
+///
+/// ```
+/// use regexer;
+/// let ast = regexer::parse("(?<y>\\d{4})-(a|b)+\\.").unwrap();
+/// let pattern = ast.to_string();
+/// assert_eq!(pattern, "(?<y>[0-9]{4})-(a|b)+\\.");
+/// assert_eq!(regexer::parse(&pattern).unwrap(), ast);
+/// ```
+impl Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ast::Char(c) => write_char(f, *c),
+            Ast::Dot => write!(f, "."),
+            Ast::Grapheme => write!(f, "\\X"),
+            Ast::Class(ranges, negated) => write_class(f, ranges, *negated),
+            Ast::Plus(e) => {
+                write_atom(f, e)?;
+                write!(f, "+")
+            }
+            Ast::Star(e) => {
+                write_atom(f, e)?;
+                write!(f, "*")
+            }
+            Ast::Question(e) => {
+                write_atom(f, e)?;
+                write!(f, "?")
+            }
+            Ast::Repeat(e, min, max) => {
+                write_atom(f, e)?;
+                match max {
+                    Some(max) if max == min => write!(f, "{{{min}}}"),
+                    Some(max) => write!(f, "{{{min},{max}}}"),
+                    None => write!(f, "{{{min},}}"),
+                }
+            }
+            Ast::Or(e1, e2) => write!(f, "{e1}|{e2}"),
+            Ast::Seq(v) => write_seq(f, &v.iter().collect::<Vec<_>>()),
+            Ast::Capture(e, _, None) => write!(f, "({e})"),
+            Ast::Capture(e, _, Some(name)) => write!(f, "(?<{name}>{e})"),
+            Ast::Backref(n) => write!(f, "\\{n}"),
+            Ast::LookBehind(e, false) => write!(f, "(?<={e})"),
+            Ast::LookBehind(e, true) => write!(f, "(?<!{e})"),
+            Ast::Flags(flags, e) => {
+                write_flags(f, flags)?;
+                write!(f, "{e})")
+            }
+            Ast::Doller => write!(f, "$"),
+            Ast::Hat => write!(f, "^"),
+            Ast::TextBegin => write!(f, "\\A"),
+            Ast::TextEnd => write!(f, "\\z"),
+            Ast::TextEndNewline => write!(f, "\\Z"),
+            Ast::Cond(n, yes, no) => {
+                write!(f, "(?({n})")?;
+                write_branch(f, yes)?;
+                if let Some(no) = no {
+                    write!(f, "|")?;
+                    write_branch(f, no)?;
+                }
+                write!(f, ")")
+            }
+            Ast::Recurse => write!(f, "(?R)"),
+            Ast::ResetStart => write!(f, "\\K"),
+            Ast::SearchStart => write!(f, "\\G"),
+        }
+    }
+}
+
+/// 連接する式を書き出す
+///
+/// 同じフラグを適用する式が続く場合は､1つの(?i-ms:..)にまとめる
+/// 空の(?:)は､パターン全体が空とならないようそのまま書き出す
+fn write_seq(f: &mut fmt::Formatter<'_>, exprs: &[&Ast]) -> fmt::Result {
+    let mut i = 0;
+    while let Some(e) = exprs.get(i) {
+        match e {
+            Ast::Flags(flags, _) => {
+                let run = exprs[i..]
+                    .iter()
+                    .map_while(|e| match e {
+                        Ast::Flags(fl, inner) if fl == flags => Some(inner.as_ref()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                // 括弧の中は空でもよいため､空の(?:)は書き出さない
+                let inner = run
+                    .iter()
+                    .copied()
+                    .filter(|e| !is_empty_seq(e))
+                    .collect::<Vec<_>>();
+                write_flags(f, flags)?;
+                write_seq(f, &inner)?;
+                write!(f, ")")?;
+                i += run.len();
+                continue;
+            }
+            e if is_empty_seq(e) => write!(f, "(?:)")?,
+            // 後方参照に続く数字は､後方参照の番号の一部とならないようにする
+            Ast::Char(c @ '0'..='9') if i > 0 && matches!(exprs[i - 1], Ast::Backref(_)) => {
+                write!(f, "\\x{{{:X}}}", *c as u32)?
+            }
+            _ => write_branch(f, e)?,
+        }
+        i += 1;
+    }
+
+    Ok(())
+}
+
+/// 何にも展開されない､空の連接であるか
+fn is_empty_seq(e: &Ast) -> bool {
+    matches!(e, Ast::Seq(v) if v.is_empty())
+}
+
+/// フラグを適用する括弧の始まりを､(?i-ms:のように書き出す
+///
+/// 外側のフラグによらないよう､有効なフラグと無効なフラグを全て書く
+fn write_flags(f: &mut fmt::Formatter<'_>, flags: &Flags) -> fmt::Result {
+    let on_off = [
+        ('i', flags.case_insensitive),
+        ('m', flags.multi_line),
+        ('s', flags.dot_all),
+    ];
+    write!(f, "(?")?;
+    for (c, _) in on_off.iter().filter(|(_, on)| *on) {
+        write!(f, "{c}")?;
+    }
+    if on_off.iter().any(|(_, on)| !on) {
+        write!(f, "-")?;
+        for (c, _) in on_off.iter().filter(|(_, on)| !on) {
+            write!(f, "{c}")?;
+        }
+    }
+    write!(f, ":")
+}
+
+/// 連接や条件分岐の選択肢の一部として式を書き出す
+///
+/// |を含む式は､選択肢が分かれないよう(?:..)で囲む
+fn write_branch(f: &mut fmt::Formatter<'_>, e: &Ast) -> fmt::Result {
+    match e {
+        Ast::Or(..) => write!(f, "(?:{e})"),
+        _ => write!(f, "{e}"),
+    }
+}
+
+/// 繰り返しの対象として式を書き出す
+///
+/// 1つの文字や括弧で囲まれた式でない場合は､(?:..)で囲む
+fn write_atom(f: &mut fmt::Formatter<'_>, e: &Ast) -> fmt::Result {
+    fn is_atom(e: &Ast) -> bool {
+        match e {
+            Ast::Seq(v) if v.len() == 1 => is_atom(&v[0]),
+            Ast::Char(_)
+            | Ast::Dot
+            | Ast::Grapheme
+            | Ast::Class(..)
+            | Ast::Capture(..)
+            | Ast::Backref(_)
+            | Ast::LookBehind(..)
+            | Ast::Flags(..)
+            | Ast::Cond(..)
+            | Ast::Recurse => true,
+            _ => false,
+        }
+    }
+
+    if is_atom(e) {
+        write!(f, "{e}")
+    } else {
+        write!(f, "(?:{e})")
+    }
+}
+
+/// 文字クラスの外の文字を書き出す
+///
+/// メタ文字はエスケープし､制御文字は\nや\x{..}とする
+fn write_char(f: &mut fmt::Formatter<'_>, c: char) -> fmt::Result {
+    match c {
+        '\\' | '(' | ')' | '|' | '+' | '*' | '?' | '[' | ']' | '{' | '}' | '.' | '^' | '$' => {
+            write!(f, "\\{c}")
+        }
+        _ => write_control(f, c),
+    }
+}
+
+/// 制御文字と空白文字を\nや\x{..}として､それ以外の文字はそのまま書き出す
+fn write_control(f: &mut fmt::Formatter<'_>, c: char) -> fmt::Result {
+    match c {
+        '\n' => write!(f, "\\n"),
+        '\t' => write!(f, "\\t"),
+        '\r' => write!(f, "\\r"),
+        '\x0c' => write!(f, "\\f"),
+        _ if c.is_control() || (c.is_whitespace() && c != ' ') => {
+            write!(f, "\\x{{{:X}}}", c as u32)
+        }
+        _ => write!(f, "{c}"),
+    }
+}
+
+/// 文字クラスを書き出す
+///
+/// 空の文字クラスは書けないため､全ての文字の範囲を否定したものとする
+fn write_class(f: &mut fmt::Formatter<'_>, ranges: &[(char, char)], negated: bool) -> fmt::Result {
+    let class_char = |f: &mut fmt::Formatter<'_>, c: char| match c {
+        '\\' | ']' | '[' | '^' | '-' | '&' => write!(f, "\\{c}"),
+        _ => write_control(f, c),
+    };
+
+    if ranges.is_empty() {
+        let not = if negated { "" } else { "^" };
+        return write!(f, "[{not}\\x{{0}}-\\x{{10FFFF}}]");
+    }
+
+    write!(f, "[")?;
+    if negated {
+        write!(f, "^")?;
+    }
+    for (lo, hi) in ranges {
+        class_char(f, *lo)?;
+        if lo != hi {
+            write!(f, "-")?;
+            class_char(f, *hi)?;
+        }
+    }
+    write!(f, "]")
+}
+
+impl Ast {
+    /// 式がマッチする文字列の長さが固定の場合は､その長さを返す
+    ///
+    /// 例えば､ab[cd]は3となり､a+やab|cのように長さが定まらない場合はNoneとなる
+    pub fn fixed_len(&self) -> Option<usize> {
+        match self {
+            Ast::Char(_) | Ast::Dot | Ast::Class(_, _) => Some(1),
+            Ast::Doller
+            | Ast::Hat
+            | Ast::TextBegin
+            | Ast::TextEnd
+            | Ast::TextEndNewline
+            | Ast::ResetStart
+            | Ast::SearchStart
+            | Ast::LookBehind(_, _) => Some(0),
+            Ast::Capture(e, _, _) | Ast::Flags(_, e) => e.fixed_len(),
+            Ast::Seq(v) => v
+                .iter()
+                .try_fold(0usize, |len, e| len.checked_add(e.fixed_len()?)),
+            Ast::Or(e1, e2) => {
+                let len = e1.fixed_len()?;
+                (Some(len) == e2.fixed_len()).then_some(len)
+            }
+            Ast::Plus(e) | Ast::Star(e) | Ast::Question(e) | Ast::Repeat(e, _, None) => {
+                // 空文字列にしかマッチしない式の繰り返しは長さ0
+                (e.fixed_len()? == 0).then_some(0)
+            }
+            Ast::Repeat(e, min, Some(max)) => {
+                let len = e.fixed_len()?;
+                if len == 0 || min == max {
+                    len.checked_mul(*min)
+                } else {
+                    None
+                }
+            }
+            Ast::Cond(_, yes, no) => {
+                let len = yes.fixed_len()?;
+                let no_len = match no {
+                    Some(no) => no.fixed_len()?,
+                    None => 0,
+                };
+                (len == no_len).then_some(len)
+            }
+            Ast::Grapheme | Ast::Backref(_) | Ast::Recurse => None,
+        }
+    }
+
+    /// 式がマッチする文字列の､最小の長さを文字単位で返す
+    ///
+    /// 後方参照と(?R)は空文字列にもマッチしうるものとして0とする
+    /// 例えば､ab+|cde?は2となる
+    pub fn min_len(&self) -> usize {
+        match self {
+            Ast::Char(_) | Ast::Dot | Ast::Grapheme | Ast::Class(_, _) => 1,
+            Ast::Plus(e) | Ast::Capture(e, _, _) | Ast::Flags(_, e) => e.min_len(),
+            Ast::Star(_)
+            | Ast::Question(_)
+            | Ast::Backref(_)
+            | Ast::Recurse
+            | Ast::Doller
+            | Ast::Hat
+            | Ast::TextBegin
+            | Ast::TextEnd
+            | Ast::TextEndNewline
+            | Ast::ResetStart
+            | Ast::SearchStart
+            | Ast::LookBehind(_, _) => 0,
+            Ast::Repeat(e, min, _) => e.min_len().saturating_mul(*min),
+            Ast::Or(e1, e2) => e1.min_len().min(e2.min_len()),
+            Ast::Seq(v) => v.iter().fold(0, |len, e| len.saturating_add(e.min_len())),
+            Ast::Cond(_, yes, no) => yes.min_len().min(no.as_ref().map_or(0, |no| no.min_len())),
+        }
+    }
+
+    /// 式がマッチする文字列の､最大の長さを文字単位で返す
+    ///
+    /// a*や\X､後方参照､(?R)のように上限がない場合はNoneを返す
+    /// 例えば､ab?|c{2,4}は4となる
+    pub fn max_len(&self) -> Option<usize> {
+        match self {
+            Ast::Char(_) | Ast::Dot | Ast::Class(_, _) => Some(1),
+            Ast::Grapheme | Ast::Backref(_) | Ast::Recurse => None,
+            Ast::Capture(e, _, _) | Ast::Flags(_, e) | Ast::Question(e) => e.max_len(),
+            Ast::Plus(e) | Ast::Star(e) | Ast::Repeat(e, _, None) => {
+                // 空文字列にしかマッチしない式の繰り返しは長さ0
+                (e.max_len()? == 0).then_some(0)
+            }
+            Ast::Repeat(_, _, Some(0)) => Some(0),
+            Ast::Repeat(e, _, Some(max)) => e.max_len()?.checked_mul(*max),
+            Ast::Or(e1, e2) => Some(e1.max_len()?.max(e2.max_len()?)),
+            Ast::Seq(v) => v
+                .iter()
+                .try_fold(0usize, |len, e| len.checked_add(e.max_len()?)),
+            Ast::Cond(_, yes, no) => {
+                let no_len = match no {
+                    Some(no) => no.max_len()?,
+                    None => 0,
+                };
+                Some(yes.max_len()?.max(no_len))
+            }
+            Ast::Doller
+            | Ast::Hat
+            | Ast::TextBegin
+            | Ast::TextEnd
+            | Ast::TextEndNewline
+            | Ast::ResetStart
+            | Ast::SearchStart
+            | Ast::LookBehind(_, _) => Some(0),
+        }
+    }
+
+    /// 全てのマッチが文字列の先頭から始まるかを判定
+    ///
+    /// 例えば､^abや\A(a|b)はtrueとなり､(?m)^abや^a|bはfalseとなる
+    pub fn is_anchored_start(&self) -> bool {
+        self.is_anchored(Flags::default(), false)
+    }
+
+    /// 全てのマッチが文字列の末尾か､末尾の改行の直前で終わるかを判定
+    ///
+    /// 例えば､ab$やa\zはtrueとなり､(?m)ab$やa|b$はfalseとなる
+    pub fn is_anchored_end(&self) -> bool {
+        self.is_anchored(Flags::default(), true)
+    }
+
+    /// 式が先頭に(revの場合は末尾に)アンカーを持つかを判定
+    fn is_anchored(&self, flags: Flags, rev: bool) -> bool {
+        match self {
+            Ast::TextBegin => !rev,
+            Ast::Hat => !rev && !flags.multi_line,
+            Ast::TextEnd | Ast::TextEndNewline => rev,
+            Ast::Doller => rev && !flags.multi_line,
+            Ast::Plus(e) | Ast::Repeat(e, 1.., _) | Ast::Capture(e, _, _) => {
+                e.is_anchored(flags, rev)
+            }
+            Ast::Flags(flags, e) => e.is_anchored(*flags, rev),
+            Ast::Or(e1, e2) => e1.is_anchored(flags, rev) && e2.is_anchored(flags, rev),
+            Ast::Seq(v) => {
+                let e = if rev { v.last() } else { v.first() };
+                e.is_some_and(|e| e.is_anchored(flags, rev))
+            }
+            Ast::Cond(_, yes, no) => {
+                yes.is_anchored(flags, rev)
+                    && no.as_ref().is_some_and(|no| no.is_anchored(flags, rev))
+            }
+            _ => false,
+        }
+    }
+
+    /// 全てのマッチが共通して持つ､リテラルの接頭辞を返す
+    ///
+    /// 例えば､abc+はab､(?:ab|ac)dはaとなる
+    /// \Kを含む場合は､マッチの開始位置が変わるため空文字列とする
+    pub fn literal_prefix(&self) -> String {
+        if self.has_reset_start() {
+            return String::new();
+        }
+
+        self.literal_affix(Flags::default(), false)
+            .0
+            .into_iter()
+            .collect()
+    }
+
+    /// 全てのマッチが共通して持つ､リテラルの接尾辞を返す
+    ///
+    /// 例えば､a+bcはbc､d(?:ba|ca)はaとなる
+    pub fn literal_suffix(&self) -> String {
+        let (mut suffix, _) = self.literal_affix(Flags::default(), true);
+        suffix.reverse();
+        suffix.into_iter().collect()
+    }
+
+    /// 式の先頭の(revの場合は末尾から逆順に並べた)リテラルの文字列と､式全体がリテラルかを返す
+    ///
+    /// アンカーや後読みのような幅のない式は､空文字列のリテラルとみなす
+    fn literal_affix(&self, flags: Flags, rev: bool) -> (Vec<char>, bool) {
+        match self {
+            Ast::Char(c) => {
+                // 大文字と小文字を区別しない場合は､対応する文字を持たない文字のみをリテラルとする
+                if flags.case_insensitive && case_fold_ranges(&[(*c, *c)]) != [(*c, *c)] {
+                    (Vec::new(), false)
+                } else {
+                    (vec![*c], true)
+                }
+            }
+            Ast::Capture(e, _, _) => e.literal_affix(flags, rev),
+            Ast::Flags(flags, e) => e.literal_affix(*flags, rev),
+            Ast::Plus(e) => (e.literal_affix(flags, rev).0, false),
+            Ast::Repeat(e, min @ 1.., max) => {
+                let (lit, complete) = e.literal_affix(flags, rev);
+                if complete && Some(*min) == *max {
+                    (lit.repeat(*min), true)
+                } else {
+                    (lit, false)
+                }
+            }
+            Ast::Seq(v) => {
+                let mut lit = Vec::new();
+                let exprs: Vec<&Ast> = if rev {
+                    v.iter().rev().collect()
+                } else {
+                    v.iter().collect()
+                };
+                for e in exprs {
+                    let (l, complete) = e.literal_affix(flags, rev);
+                    lit.extend(l);
+                    if !complete {
+                        return (lit, false);
+                    }
+                }
+                (lit, true)
+            }
+            Ast::Or(e1, e2) => {
+                common_affix(e1.literal_affix(flags, rev), e2.literal_affix(flags, rev))
+            }
+            Ast::Cond(_, yes, no) => {
+                let no = match no {
+                    Some(no) => no.literal_affix(flags, rev),
+                    None => (Vec::new(), true),
+                };
+                common_affix(yes.literal_affix(flags, rev), no)
+            }
+            Ast::Doller
+            | Ast::Hat
+            | Ast::TextBegin
+            | Ast::TextEnd
+            | Ast::TextEndNewline
+            | Ast::SearchStart
+            | Ast::LookBehind(_, _) => (Vec::new(), true),
+            // \Kより前はマッチに含まれないため､接尾辞はそこで終わる
+            Ast::ResetStart => (Vec::new(), !rev),
+            _ => (Vec::new(), false),
+        }
+    }
+
+    /// 逆順にした文字列にマッチする式を返す
+    ///
+    /// マッチの有無のみを保つため､キャプチャグループは外し､\Kは幅のない空の式とする
+    /// 例えば､\Aab+は(?:b+a)\zとなる
+    /// 後方参照､後読み､条件分岐､再帰､\G､\X､$､\Z､マルチラインモードの^を含む場合はNoneを返す
+    #[doc(hidden)]
+    pub fn reversed(&self) -> Option<Ast> {
+        self.reverse(Flags::default())
+    }
+
+    /// flagsのもとで､逆順にした文字列にマッチする式を返す
+    fn reverse(&self, flags: Flags) -> Option<Ast> {
+        let ast = match self {
+            Ast::Char(_) | Ast::Dot | Ast::Class(..) => self.clone(),
+            Ast::Plus(e) => Ast::Plus(Box::new(e.reverse(flags)?)),
+            Ast::Star(e) => Ast::Star(Box::new(e.reverse(flags)?)),
+            Ast::Question(e) => Ast::Question(Box::new(e.reverse(flags)?)),
+            Ast::Repeat(e, min, max) => Ast::Repeat(Box::new(e.reverse(flags)?), *min, *max),
+            Ast::Or(e1, e2) => Ast::Or(Box::new(e1.reverse(flags)?), Box::new(e2.reverse(flags)?)),
+            Ast::Seq(v) => Ast::Seq(
+                v.iter()
+                    .rev()
+                    .map(|e| e.reverse(flags))
+                    .collect::<Option<_>>()?,
+            ),
+            Ast::Capture(e, _, _) => e.reverse(flags)?,
+            Ast::Flags(flags, e) => Ast::Flags(*flags, Box::new(e.reverse(*flags)?)),
+            Ast::Hat if !flags.multi_line => Ast::TextEnd,
+            Ast::TextBegin => Ast::TextEnd,
+            Ast::TextEnd => Ast::TextBegin,
+            Ast::ResetStart => Ast::Seq(Vec::new()),
+            _ => return None,
+        };
+
+        Some(ast)
+    }
+
+    /// 式が\Kを含むかを判定
+    fn has_reset_start(&self) -> bool {
+        matches!(self, Ast::ResetStart) || self.children().into_iter().any(Ast::has_reset_start)
+    }
+
+    /// キャプチャグループの名前を､グループの番号順に並べて返す
+    ///
+    /// 0番目はマッチ全体を表し､名前のないグループと同じくNoneとなる
+    /// 例えば､(a)(?<x>b)は[None, None, Some("x")]となる
+    pub fn capture_names(&self) -> Vec<Option<String>> {
+        fn collect(ast: &Ast, names: &mut Vec<Option<String>>) {
+            match ast {
+                Ast::Capture(e, n, name) => {
+                    if names.len() <= *n {
+                        names.resize(*n + 1, None);
+                    }
+                    names[*n] = name.clone();
+                    collect(e, names);
+                }
+                Ast::Plus(e)
+                | Ast::Star(e)
+                | Ast::Question(e)
+                | Ast::Repeat(e, _, _)
+                | Ast::LookBehind(e, _)
+                | Ast::Flags(_, e) => collect(e, names),
+                Ast::Or(e1, e2) => {
+                    collect(e1, names);
+                    collect(e2, names);
+                }
+                Ast::Seq(v) => v.iter().for_each(|e| collect(e, names)),
+                Ast::Cond(_, yes, no) => {
+                    collect(yes, names);
+                    if let Some(no) = no {
+                        collect(no, names);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut names = vec![None];
+        collect(self, &mut names);
+        names
+    }
+}
+
+/// パース時のオプション
+///
+/// パターン中の(?i)などで変更できるフラグは､Ast::Flagsとしてコード生成に引き継がれる
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flags {
+    /// \wなどの略記をUnicodeの定義で解釈する
+    pub unicode: bool,
+    /// 大文字と小文字を区別しない(i)
+    pub case_insensitive: bool,
+    /// ^と$を行の先頭と末尾にもマッチさせる(m)
+    pub multi_line: bool,
+    /// .を改行にもマッチさせる(s)
+    pub dot_all: bool,
+    /// パターン中の空白を無視し､#から行末までをコメントとする(x)
+    pub extended: bool,
+    /// ^､$､\Zで行末とみなす改行
+    pub line_terminator: LineTerminator,
+    /// パターンの構文
+    pub dialect: Dialect,
+    /// NやRのようなIUPACの塩基の曖昧さを表す記号を､対応する塩基の文字クラスとする
+    pub iupac: bool,
+    /// マッチング前に､パターンとマッチ対象の文字列に行うUnicodeの正規化
+    #[cfg(feature = "unicode-normalization")]
+    pub normalization: Normalization,
+}
+
+/// マッチング前に行うUnicodeの正規化の形式
+#[cfg(feature = "unicode-normalization")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Normalization {
+    /// 正規化しない
+    #[default]
+    None,
+    /// 正準等価な文字を合成する(NFC)
+    Nfc,
+    /// 互換等価な文字も含めて合成する(NFKC)
+    Nfkc,
+}
+
+/// ^､$､\Zで行末とみなす改行の種類
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineTerminator {
+    /// \nのみ
+    #[default]
+    Lf,
+    /// \r\n､\r､\nのいずれか｡\r\nは1つの改行として扱い､\rと\nの間にはマッチしない
+    Crlf,
+    /// 指定した1文字
+    Char(char),
+}
+
+/// パターンの構文の種類
+///
+/// grepやegrep向けに書かれたパターンを､そのままパースするために利用する
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// 通常の構文｡(?...)の拡張構文や\Qなどを含む
+    #[default]
+    Perl,
+    /// POSIXの拡張正規表現(ERE)｡egrepやgrep -Eの構文
+    ///
+    /// (?...)の拡張構文はなく､文字クラス内の\は通常の文字とし､\1から\9は常に後方参照とする
+    Ere,
+    /// POSIXの基本正規表現(BRE)｡grepの構文
+    ///
+    /// EREの規則に加えて､\(\)､\{n,m\}､\|､\+､\?をメタ文字とし､( ) { } | + ?は通常の文字とする
+    /// *は式の先頭と^の直後､^は式の先頭､$は式の末尾でのみ特殊な意味を持つ
+    Bre,
+}
+
+/// IUPACの塩基の曖昧さを表す記号を､対応する塩基の範囲に変換
+///
+/// 例えば､Rは[AG]､Nは[ACGT]となり､小文字の記号は小文字の塩基となる
+/// A, C, G, T, Uや記号以外の文字の場合はNoneを返す
+fn iupac_bases(c: char) -> Option<Vec<(char, char)>> {
+    let bases = match c.to_ascii_uppercase() {
+        'R' => "AG",
+        'Y' => "CT",
+        'S' => "CG",
+        'W' => "AT",
+        'K' => "GT",
+        'M' => "AC",
+        'B' => "CGT",
+        'D' => "AGT",
+        'H' => "ACT",
+        'V' => "ACG",
+        'N' => "ACGT",
+        _ => return None,
+    };
+    let bases = bases.chars().map(|b| {
+        let b = if c.is_ascii_lowercase() {
+            b.to_ascii_lowercase()
+        } else {
+            b
+        };
+        (b, b)
+    });
+    Some(bases.collect())
+}
+
+/// パターン中の通常の文字cをASTに変換
+///
+/// flags.iupacがtrueの場合は､IUPACの記号を塩基の文字クラスとする
+fn literal(c: char, flags: Flags) -> Ast {
+    match iupac_bases(c).filter(|_| flags.iupac) {
+        Some(bases) => Ast::Class(bases, false),
+        None => Ast::Char(c),
+    }
+}
+
+/// BREで\を前に付けた場合にメタ文字となる文字かを判定
+fn is_bre_special(c: char) -> bool {
+    matches!(c, '(' | ')' | '{' | '}' | '|' | '+' | '?')
+}
+
+/// BREで$がアンカーとなる位置かを判定
+///
+/// 式の末尾か､\)または\|の直前の場合のみアンカーとする
+fn is_bre_end(chars: &PatternChars) -> bool {
+    let mut ahead = chars.clone();
+    match ahead.next() {
+        None => true,
+        Some((_, '\\')) => matches!(ahead.next(), Some((_, ')' | '|'))),
+        Some(_) => false,
+    }
+}
+
+/// parse_plus_star_question関数で利用するための列挙型
+enum Psq {
+    Plus,
+    Star,
+    Question,
+    Repeat(usize, Option<usize>),
+}
+
+/// 括弧で囲まれた式の種類
+#[derive(Clone)]
+enum Group {
+    Capture(usize, Option<String>), // (...), (?<name>...)｡キャプチャグループの番号と名前を持つ
+    LookBehind(bool),               // (?<=...), (?<!...)｡trueの場合は否定
+    Cond(usize),                    // (?(n)...)｡条件とするグループの番号を持つ
+    Recurse,                        // (?R), (?0)｡括弧としては扱わない
+    Flags(Flags),                   // (?:...), (?i:...)｡括弧内に適用するフラグを持つ
+    SetFlags(Flags),                // (?i)｡以降に適用するフラグを持ち､括弧としては扱わない
+}
+
+/// パターン文字列を､位置付きで先読み可能な形で走査するための型
+type PatternChars<'a> = Peekable<Enumerate<Chars<'a>>>;
+
+/// 文字クラスの(範囲, 否定)の組
+type ClassSet = (Vec<(char, char)>, bool);
+
+/// 特殊文字のエスケープ
+///
+/// \の直後の文字cを受け取り､\p{L}や\x41のように複数の文字からなる場合は､続きをcharsから読み込む
+/// groupsはパターン中のキャプチャグループの総数で､後方参照と8進数のエスケープの判別に利用する
+fn parse_escape(
+    chars: &mut PatternChars,
+    pos: usize,
+    c: char,
+    flags: Flags,
+    groups: usize,
+) -> Result<Ast, ParseError> {
+    if let Some((ranges, negated)) = parse_perl_class(c, flags) {
+        return Ok(Ast::Class(ranges, negated));
+    }
+
+    match c {
+        'p' | 'P' => {
+            let (ranges, negated) = parse_unicode_property(chars, pos, c == 'P')?;
+            Ok(Ast::Class(ranges, negated))
+        }
+        // POSIXの構文では\1から\9のみを後方参照とする
+        '1'..='9' if flags.dialect != Dialect::Perl => Ok(Ast::Backref(c as usize - '0' as usize)),
+        'x' | 'u' => Ok(Ast::Char(parse_hex_escape(chars, pos, c)?)),
+        'n' | 't' | 'r' | 'f' => Ok(Ast::Char(control_escape(c))),
+        '0' | 'o' => Ok(Ast::Char(parse_octal_escape(chars, pos, c)?)),
+        '\\' | '(' | ')' | '|' | '+' | '*' | '?' | '[' | ']' | '{' | '}' | '#' | ' ' | '.'
+        | '^' | '$' | '-' | '&' | '~' => Ok(Ast::Char(c)),
+        '1'..='9' => parse_backref_or_octal(chars, pos, c, groups),
+        'X' => Ok(Ast::Grapheme),
+        'R' => Ok(linebreak()),
+        'A' => Ok(Ast::TextBegin),
+        'z' => Ok(Ast::TextEnd),
+        'Z' => Ok(Ast::TextEndNewline),
+        'K' => Ok(Ast::ResetStart),
+        'G' => Ok(Ast::SearchStart),
+        _ => Err(ParseError::InvalidEscape(pos - 1..pos + 1, c)),
+    }
+}
+
+/// \d, \Dのような文字クラスの略記を､(範囲, 否定)の組に変換
+///
+/// 略記でない場合はNoneを返す
+/// flags.unicodeがtrueの場合､\wはUnicodeのアルファベットと数字および_となり､
+/// \sはUnicodeの空白文字となる
+/// \hと\vはflagsに関わらず､Unicodeの水平方向と垂直方向の空白文字となる
+fn parse_perl_class(c: char, flags: Flags) -> Option<ClassSet> {
+    let word = || {
+        if flags.unicode {
+            unicode_word().clone()
+        } else {
+            vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')]
+        }
+    };
+
+    let space = || {
+        if flags.unicode {
+            unicode_space().clone()
+        } else {
+            vec![('\t', '\n'), ('\x0c', '\r'), (' ', ' ')]
+        }
+    };
+
+    let horizontal = || {
+        vec![
+            ('\t', '\t'),
+            (' ', ' '),
+            ('\u{A0}', '\u{A0}'),
+            ('\u{1680}', '\u{1680}'),
+            ('\u{180E}', '\u{180E}'),
+            ('\u{2000}', '\u{200A}'),
+            ('\u{202F}', '\u{202F}'),
+            ('\u{205F}', '\u{205F}'),
+            ('\u{3000}', '\u{3000}'),
+        ]
+    };
+
+    let vertical = || vec![('\n', '\r'), ('\u{85}', '\u{85}'), ('\u{2028}', '\u{2029}')];
+
+    match c {
+        'd' => Some((vec![('0', '9')], false)),
+        'D' => Some((vec![('0', '9')], true)),
+        'w' => Some((word(), false)),
+        'W' => Some((word(), true)),
+        's' => Some((space(), false)),
+        'S' => Some((space(), true)),
+        'h' => Some((horizontal(), false)),
+        'H' => Some((horizontal(), true)),
+        'v' => Some((vertical(), false)),
+        'V' => Some((vertical(), true)),
+        _ => None,
+    }
+}
+
+/// Unicodeの\wに相当する文字の範囲
+fn unicode_word() -> &'static Vec<(char, char)> {
+    static WORD: OnceLock<Vec<(char, char)>> = OnceLock::new();
+    WORD.get_or_init(|| ranges_by(|c| c.is_alphanumeric() || c == '_'))
+}
+
+/// Unicodeの\sに相当する文字の範囲
+fn unicode_space() -> &'static Vec<(char, char)> {
+    static SPACE: OnceLock<Vec<(char, char)>> = OnceLock::new();
+    SPACE.get_or_init(|| ranges_by(char::is_whitespace))
+}
+
+/// \Q...\Eで囲まれた文字列を読み込む
+///
+/// Qの直後から\Eまでを読み込み､\Eがない場合はパターンの末尾までとする
+fn parse_quote(chars: &mut PatternChars) -> Vec<char> {
+    let mut quoted = Vec::new();
+    while let Some((_, c)) = chars.next() {
+        if c == '\\' && chars.next_if(|(_, c)| *c == 'E').is_some() {
+            break;
+        }
+        quoted.push(c);
+    }
+
+    quoted
+}
+
+/// \Rに相当する､任意の改行にマッチするAST
+///
+/// \r\n|[\n\x0b\x0c\r\u{85}\u{2028}\u{2029}]とし､\r\nは1つの改行として扱う
+fn linebreak() -> Ast {
+    let crlf = Ast::Seq(vec![Ast::Char('\r'), Ast::Char('\n')]);
+    let single = Ast::Class(
+        vec![('\n', '\r'), ('\u{85}', '\u{85}'), ('\u{2028}', '\u{2029}')],
+        false,
+    );
+    Ast::Or(Box::new(crlf), Box::new(single))
+}
+
+/// \n, \t, \r, \fの制御文字のエスケープを､対応する文字に変換
+fn control_escape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        _ => '\x0c',
+    }
+}
+
+/// \1から始まるエスケープを､後方参照か8進数のエスケープとしてパース
+///
+/// PCREと同様に､続く10進数の列をnとして以下のように判別する
+///
+/// - nが10未満か､キャプチャグループの総数groups以下の場合は､n番目のグループへの後方参照
+/// - それ以外で先頭が0から7の場合は､最大3桁の8進数のエスケープ
+/// - それ以外の\8や\9から始まる場合は､存在しないグループへの後方参照としてエラー
+///
+/// 例えばグループが2つの場合､\2は後方参照､\12は改行､\81はエラーとなる
+fn parse_backref_or_octal(
+    chars: &mut PatternChars,
+    pos: usize,
+    c: char,
+    groups: usize,
+) -> Result<Ast, ParseError> {
+    let mut ahead = chars.clone();
+    let mut digits = c.to_string();
+    while let Some((_, d)) = ahead.next_if(|(_, d)| d.is_ascii_digit()) {
+        digits.push(d);
+    }
+    let n = digits.parse().unwrap_or(usize::MAX);
+
+    if n < 10 || n <= groups {
+        *chars = ahead;
+        Ok(Ast::Backref(n))
+    } else if c < '8' {
+        Ok(Ast::Char(parse_octal_escape(chars, pos, c)?))
+    } else {
+        Err(ParseError::InvalidBackref(pos - 1..pos + digits.len(), n))
+    }
+}
+
+/// \0, \012, \101, \o{101}のような8進数のエスケープをパースし､文字に変換
+///
+/// 先頭の数字cの後に最大2桁の8進数を読み込み､cがoの場合は{}で囲まれた8進数を読み込む
+/// posは\の直後の位置で､エラーの報告に利用する
+fn parse_octal_escape(chars: &mut PatternChars, pos: usize, c: char) -> Result<char, ParseError> {
+    let mut digits = String::new();
+    let mut end = pos + 1; // 読み込んだエスケープの直後の位置
+    if c == 'o' {
+        if chars.next_if(|(_, c)| *c == '{').is_none() {
+            return Err(ParseError::InvalidOctalEscape(pos - 1..end));
+        }
+        end += 1;
+        loop {
+            match chars.next() {
+                Some((i, c)) => {
+                    end = i + 1;
+                    if c == '}' {
+                        break;
+                    }
+                    digits.push(c);
+                }
+                None => return Err(ParseError::InvalidOctalEscape(pos - 1..end)),
+            }
+        }
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(8)) {
+            return Err(ParseError::InvalidOctalEscape(pos - 1..end));
+        }
+    } else {
+        digits.push(c);
+        while digits.len() < 3 {
+            match chars.next_if(|(_, c)| c.is_digit(8)) {
+                Some((i, c)) => {
+                    end = i + 1;
+                    digits.push(c);
+                }
+                None => break,
+            }
+        }
+    }
+
+    let span = pos - 1..end;
+    let code = u32::from_str_radix(&digits, 8)
+        .map_err(|_| ParseError::InvalidOctalEscape(span.clone()))?;
+    char::from_u32(code).ok_or(ParseError::InvalidCodePoint(span, code))
+}
+
+/// パターン中のキャプチャグループの総数を数える
+///
+/// エスケープされた括弧や文字クラス内の括弧､(?...)の拡張構文は数えない
+fn count_groups(expr: &str) -> usize {
+    let mut count = 0;
+    let mut depth = 0; // 文字クラスの入れ子の深さ
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // エスケープされた文字は読み飛ばし､\Q...\Eの間は全て通常の文字とする
+            '\\' if chars.next() == Some('Q') => {
+                while let Some(c) = chars.next() {
+                    if c == '\\' && chars.next_if_eq(&'E').is_some() {
+                        break;
+                    }
+                }
+            }
+            '[' => {
+                depth += 1;
+                // 先頭の^と]は通常の文字
+                chars.next_if_eq(&'^');
+                chars.next_if_eq(&']');
+            }
+            ']' if depth > 0 => depth -= 1,
+            '(' if depth == 0 => {
+                if chars.next_if_eq(&'?').is_none() {
+                    count += 1;
+                } else if chars.next_if_eq(&'P').is_some()
+                    || (chars.next_if_eq(&'<').is_some()
+                        && chars.next_if(|c| matches!(c, '=' | '!')).is_none())
+                {
+                    // (?<name>...)と(?P<name>...)は名前付きのキャプチャグループ
+                    count += 1;
+                } else if chars.next_if_eq(&'#').is_some() {
+                    // コメント中の括弧は数えない
+                    for c in chars.by_ref() {
+                        if c == ')' {
+                            break;
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    count
+}
+
+/// \x41, \x{41}, \u3042, \u{3042}のような16進数のエスケープをパースし､文字に変換
+///
+/// xまたはuの直後から読み込み､{}で囲まない場合は\xは2桁､\uは4桁の16進数とする
+/// {}で囲む場合は1から6桁とし､サロゲートなど文字として不正な値はエラー
+/// posは\の直後の位置で､エラーの報告に利用する
+fn parse_hex_escape(chars: &mut PatternChars, pos: usize, kind: char) -> Result<char, ParseError> {
+    let mut digits = String::new();
+    let mut end = pos + 1; // 読み込んだエスケープの直後の位置
+    if let Some((i, _)) = chars.next_if(|(_, c)| *c == '{') {
+        end = i + 1;
+        loop {
+            match chars.next() {
+                Some((i, c)) => {
+                    end = i + 1;
+                    if c == '}' {
+                        break;
+                    }
+                    digits.push(c);
+                }
+                None => return Err(ParseError::InvalidHexEscape(pos - 1..end)),
+            }
+        }
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(ParseError::InvalidHexEscape(pos - 1..end));
+        }
+    } else {
+        let len = if kind == 'x' { 2 } else { 4 };
+        for _ in 0..len {
+            match chars.next() {
+                Some((i, c)) => {
+                    end = i + 1;
+                    digits.push(c);
+                }
+                None => return Err(ParseError::InvalidHexEscape(pos - 1..end)),
+            }
+        }
+    }
+
+    let span = pos - 1..end;
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ParseError::InvalidHexEscape(span));
+    }
+    let code =
+        u32::from_str_radix(&digits, 16).map_err(|_| ParseError::InvalidHexEscape(span.clone()))?;
+    char::from_u32(code).ok_or(ParseError::InvalidCodePoint(span, code))
+}
+
+/// \p{L}, \pL, \P{L}のようなUnicodeプロパティをパースし､(範囲, 否定)の組を返す
+///
+/// pまたはPの直後から読み込み､negatedがtrueの場合は\Pとして否定する
+/// posは\の直後の位置で､エラーの報告に利用する
+fn parse_unicode_property(
+    chars: &mut PatternChars,
+    pos: usize,
+    negated: bool,
+) -> Result<ClassSet, ParseError> {
+    let mut end = pos + 1; // 読み込んだエスケープの直後の位置
+    let name = match chars.next() {
+        Some((i, '{')) => {
+            end = i + 1;
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some((i, c)) => {
+                        end = i + 1;
+                        if c == '}' {
+                            break name;
+                        }
+                        name.push(c);
+                    }
+                    None => return Err(ParseError::InvalidEscape(pos - 1..end, 'p')),
+                }
+            }
+        }
+        Some((i, c)) => {
+            end = i + 1;
+            c.to_string()
+        }
+        None => return Err(ParseError::InvalidEscape(pos - 1..end, 'p')),
+    };
+
+    match unicode_property(&name) {
+        Some(ranges) => Ok((ranges, negated)),
+        None => Err(ParseError::UnknownProperty(pos - 1..end, name)),
+    }
+}
+
+/// Unicodeプロパティの名前を､文字の範囲に変換
+///
+/// - L, Lu : 一般カテゴリ｡General_Category=Lu, gc=Luとも書ける
+/// - Hiragana, Hira : 用字｡Script=Hiragana, sc=Hiraとも書ける
+/// - InHiragana : ブロック｡Block=Hiragana, blk=Hiraganaとも書ける
+///
+/// 用字とブロックの名前は､大文字小文字と空白､_､-の有無を区別しない
+/// 未知の名前の場合はNoneを返す
+fn unicode_property(name: &str) -> Option<Vec<(char, char)>> {
+    match name.split_once('=') {
+        Some((key, value)) => match loose_name(key).as_str() {
+            "generalcategory" | "gc" => general_category(value),
+            "script" | "sc" => script(value),
+            "block" | "blk" => block(value),
+            _ => None,
+        },
+        None => general_category(name)
+            .or_else(|| script(name))
+            .or_else(|| name.strip_prefix("In").and_then(block)),
+    }
+}
+
+/// 大文字小文字と空白､_､-を無視して比較するための名前に変換
+///
+/// 例えば､Old_ItalicとOld Italic､olditalicは全てolditalicとなる
+fn loose_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// 文字の範囲と値の組の一覧から､条件を満たす値の範囲を結合して返す
+///
+/// 条件を満たす範囲がない場合はNoneを返す
+#[cfg(any(feature = "unicode-gencat", feature = "unicode-script"))]
+fn ranges_in_table<T, F>(table: &[(char, char, T)], f: F) -> Option<Vec<(char, char)>>
+where
+    F: Fn(&T) -> bool,
+{
+    let mut ranges: Vec<(char, char)> = Vec::new();
+    for (lo, hi, _) in table.iter().filter(|(_, _, v)| f(v)) {
+        match ranges.last_mut() {
+            Some((_, last)) if char_after(*last) == Some(*lo) => *last = *hi,
+            _ => ranges.push((*lo, *hi)),
+        }
+    }
+
+    (!ranges.is_empty()).then_some(ranges)
+}
+
+/// 全ての文字について､同じ値が連続する範囲の一覧を求める
+#[cfg(any(feature = "unicode-gencat", feature = "unicode-script"))]
+fn table_by<T, F>(f: F) -> Vec<(char, char, T)>
+where
+    T: PartialEq,
+    F: Fn(char) -> T,
+{
+    let mut table: Vec<(char, char, T)> = Vec::new();
+    for c in '\0'..=char::MAX {
+        let v = f(c);
+        match table.last_mut() {
+            Some((_, hi, last)) if *last == v && char_after(*hi) == Some(c) => *hi = c,
+            _ => table.push((c, c, v)),
+        }
+    }
+
+    table
+}
+
+/// Unicodeの用字の名前を､文字の範囲に変換
+///
+/// 名前はHiraganaのような正式名か､Hiraのような4文字の略称とする
+/// 未知の名前の場合はNoneを返す
+#[cfg(feature = "unicode-script")]
+fn script(name: &str) -> Option<Vec<(char, char)>> {
+    use unicode_script::{Script, UnicodeScript};
+
+    // 同じ用字が連続する範囲の一覧を､初回のみ求める
+    static TABLE: OnceLock<Vec<(char, char, Script)>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| table_by(|c| c.script()));
+
+    let name = loose_name(name);
+    ranges_in_table(table, |script| {
+        loose_name(script.full_name()) == name || loose_name(script.short_name()) == name
+    })
+}
+
+/// Unicodeのブロックの名前を､文字の範囲に変換
+///
+/// 名前はHiraganaやCJK Unified Ideographsのようなブロックの名前とする
+/// 未知の名前の場合はNoneを返す
+#[cfg(feature = "unicode-script")]
+fn block(name: &str) -> Option<Vec<(char, char)>> {
+    use unicode_blocks::{find_unicode_block, UnicodeBlock};
+
+    // ブロックの一覧を､初回のみ求める
+    static TABLE: OnceLock<Vec<(char, char, Option<UnicodeBlock>)>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| table_by(find_unicode_block));
+
+    let name = loose_name(name);
+    ranges_in_table(table, |block| {
+        block.is_some_and(|block| loose_name(block.name()) == name)
+    })
+}
+
+/// unicode-scriptフィーチャが無効な場合は､全ての用字の名前を未知とする
+#[cfg(not(feature = "unicode-script"))]
+fn script(_name: &str) -> Option<Vec<(char, char)>> {
+    None
+}
+
+/// unicode-scriptフィーチャが無効な場合は､全てのブロックの名前を未知とする
+#[cfg(not(feature = "unicode-script"))]
+fn block(_name: &str) -> Option<Vec<(char, char)>> {
+    None
+}
+
+/// Unicodeの一般カテゴリの名前を､文字の範囲に変換
+///
+/// 名前はLuのような略称か､Lのような大分類とし､LetterやNumberのような大分類の名前も受け付ける
+/// 未知の名前の場合はNoneを返す
+#[cfg(feature = "unicode-gencat")]
+fn general_category(name: &str) -> Option<Vec<(char, char)>> {
+    use unicode_general_category::{get_general_category, GeneralCategory};
+
+    // 同じカテゴリが連続する範囲の一覧を､初回のみ求める
+    static TABLE: OnceLock<Vec<(char, char, GeneralCategory)>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| table_by(get_general_category));
+
+    let abbr = match name {
+        "Letter" => "L",
+        "Mark" => "M",
+        "Number" => "N",
+        "Punctuation" => "P",
+        "Symbol" => "S",
+        "Separator" => "Z",
+        "Other" => "C",
+        _ => name,
+    };
+    let is_target = |cat: &GeneralCategory| match abbr.len() {
+        1 => cat.abbreviation().starts_with(abbr),
+        _ => cat.abbreviation() == abbr,
+    };
+
+    ranges_in_table(table, is_target)
+}
+
+/// unicode-gencatフィーチャが無効な場合は､全ての名前を未知とする
+#[cfg(not(feature = "unicode-gencat"))]
+fn general_category(_name: &str) -> Option<Vec<(char, char)>> {
+    None
+}
+
+/// 全ての文字の中から､条件を満たす文字の範囲を求める
+fn ranges_by<F>(f: F) -> Vec<(char, char)>
+where
+    F: Fn(char) -> bool,
+{
+    let mut ranges: Vec<(char, char)> = Vec::new();
+    for c in ('\0'..=char::MAX).filter(|c| f(*c)) {
+        match ranges.last_mut() {
+            Some((_, hi)) if char_after(*hi) == Some(c) => *hi = c,
+            _ => ranges.push((c, c)),
+        }
+    }
+
+    ranges
+}
+
+/// 文字の範囲の補集合を求める
+///
+/// 例えば､[('0', '9')]は[('\0', '/'), (':', char::MAX)]となる
+pub fn negate_ranges(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort_unstable();
+
+    let mut result = Vec::new();
+    let mut next = Some('\0'); // 補集合に含まれる可能性のある最小の文字
+    for (lo, hi) in sorted {
+        let Some(n) = next else {
+            break;
+        };
+        if n < lo {
+            // loの直前の文字までが補集合に含まれる
+            if let Some(prev) = char_before(lo) {
+                result.push((n, prev));
+            }
+        }
+        if n <= hi {
+            next = char_after(hi);
+        }
+    }
+    if let Some(n) = next {
+        result.push((n, char::MAX));
+    }
+
+    result
+}
+
+/// 大文字と小文字を区別しない場合に､マッチすべき文字の範囲を求める
+///
+/// 範囲に含まれる各文字の､1文字に対応する大文字と小文字を範囲に加える
+pub fn case_fold_ranges(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut folded = ranges.to_vec();
+    for (lo, hi) in ranges {
+        for c in *lo..=*hi {
+            let lower = single_char(c.to_lowercase());
+            let upper = single_char(c.to_uppercase());
+            for f in [lower, upper].into_iter().flatten() {
+                if f != c {
+                    folded.push((f, f));
+                }
+            }
+        }
+    }
+
+    normalize_ranges(folded)
+}
+
+/// 大文字､小文字への変換結果が1文字の場合は､その文字を返す
+fn single_char<I>(mut mapped: I) -> Option<char>
+where
+    I: Iterator<Item = char>,
+{
+    match (mapped.next(), mapped.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// 文字の範囲を整列し､重複または隣接する範囲を結合する
+pub fn normalize_ranges(mut ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+    ranges.sort_unstable();
+
+    let mut result: Vec<(char, char)> = Vec::new();
+    for (lo, hi) in ranges {
+        match result.last_mut() {
+            Some((_, last_hi)) if char_after(*last_hi).is_none_or(|n| lo <= n) => {
+                *last_hi = (*last_hi).max(hi);
+            }
+            _ => result.push((lo, hi)),
+        }
+    }
+
+    result
+}
+
+/// 直前の文字を返す(サロゲート領域は飛ばす)
+fn char_before(c: char) -> Option<char> {
+    match c as u32 {
+        0 => None,
+        0xE000 => Some('\u{D7FF}'),
+        n => char::from_u32(n - 1),
+    }
+}
+
+/// 直後の文字を返す(サロゲート領域は飛ばす)
+pub fn char_after(c: char) -> Option<char> {
+    match c {
+        char::MAX => None,
+        '\u{D7FF}' => Some('\u{E000}'),
+        _ => char::from_u32(c as u32 + 1),
+    }
+}
+
+/// 文字クラス内のエスケープ
+///
+/// escapeの結果をそのまま埋め込めるよう､文字クラスの外でエスケープできる記号も受け付ける
+/// posは文字クラスの開き括弧の位置で､エラーの報告に利用する
+fn parse_class_escape(chars: &mut PatternChars, pos: usize) -> Result<char, ParseError> {
+    match chars.next() {
+        Some((
+            _,
+            c @ ('\\' | ']' | '[' | '^' | '-' | '.' | '&' | '~' | '(' | ')' | '|' | '+' | '*' | '?'
+            | '{' | '}' | '#' | ' ' | '$'),
+        )) => Ok(c),
+        Some((i, c @ ('x' | 'u'))) => parse_hex_escape(chars, i, c),
+        Some((_, c @ ('n' | 't' | 'r' | 'f'))) => Ok(control_escape(c)),
+        Some((i, c @ ('0'..='7' | 'o'))) => parse_octal_escape(chars, i, c),
+        Some((i, c)) => Err(ParseError::InvalidEscape(i - 1..i + 1, c)),
+        None => Err(ParseError::NoRightBracket(pos..pos + 1)),
+    }
+}
+
+/// [:alpha:]のようなPOSIX文字クラスの名前を､ASCIIの範囲に変換
+///
+/// 未知の名前の場合はNoneを返す
+fn posix_class(name: &str) -> Option<Vec<(char, char)>> {
+    let ranges = match name {
+        "alnum" => vec![('0', '9'), ('A', 'Z'), ('a', 'z')],
+        "alpha" => vec![('A', 'Z'), ('a', 'z')],
+        "blank" => vec![('\t', '\t'), (' ', ' ')],
+        "cntrl" => vec![('\0', '\x1f'), ('\x7f', '\x7f')],
+        "digit" => vec![('0', '9')],
+        "graph" => vec![('!', '~')],
+        "lower" => vec![('a', 'z')],
+        "print" => vec![(' ', '~')],
+        "punct" => vec![('!', '/'), (':', '@'), ('[', '`'), ('{', '~')],
+        "space" => vec![('\t', '\r'), (' ', ' ')],
+        "upper" => vec![('A', 'Z')],
+        "word" => vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')],
+        "xdigit" => vec![('0', '9'), ('A', 'F'), ('a', 'f')],
+        _ => return None,
+    };
+    Some(ranges)
+}
+
+/// 文字クラス内の[:alpha:], [:^alpha:]をパースし､(範囲, 否定)の組を返す
+///
+/// 開き括弧の直後から読み込み､[:名前:]の形でない場合は何も読み込まずにNoneを返す
+/// posは開き括弧の位置で､エラーの報告に利用する
+fn parse_posix_class(chars: &mut PatternChars, pos: usize) -> Result<Option<ClassSet>, ParseError> {
+    let mut ahead = chars.clone();
+    if ahead.next_if(|(_, c)| *c == ':').is_none() {
+        return Ok(None);
+    }
+    let negated = ahead.next_if(|(_, c)| *c == '^').is_some();
+
+    let mut name = String::new();
+    while let Some((_, c)) = ahead.next_if(|(_, c)| c.is_ascii_alphabetic()) {
+        name.push(c);
+    }
+    let close = ahead
+        .next_if(|(_, c)| *c == ':')
+        .and_then(|_| ahead.next_if(|(_, c)| *c == ']'));
+    let Some((end, _)) = close else {
+        return Ok(None);
+    };
+
+    *chars = ahead;
+    match posix_class(&name) {
+        Some(ranges) => Ok(Some((ranges, negated))),
+        None => Err(ParseError::UnknownPosixClass(pos..end + 1, name)),
+    }
+}
+
+/// 次が範囲指定の-であるかを判定
+///
+/// 文字クラスの末尾の-と､差集合の--は範囲指定ではなく､通常の文字として扱う
+fn is_class_range(chars: &PatternChars) -> bool {
+    let mut ahead = chars.clone();
+    matches!(ahead.next(), Some((_, '-'))) && !matches!(ahead.next(), Some((_, ']' | '-')) | None)
+}
+
+/// 文字クラスの集合演算
+#[derive(Clone, Copy)]
+enum ClassOp {
+    Intersection, // &&｡積集合
+    Difference,   // --｡差集合
+}
+
+/// 左辺と演算子の組に､右辺の範囲を適用した結果を返す
+///
+/// 左辺がない場合は､右辺をそのまま返す
+fn apply_class_op(
+    left: Option<(ClassOp, Vec<(char, char)>)>,
+    right: Vec<(char, char)>,
+) -> Vec<(char, char)> {
+    match left {
+        Some((ClassOp::Intersection, left)) => intersect_ranges(&left, &right),
+        Some((ClassOp::Difference, left)) => intersect_ranges(&left, &negate_ranges(&right)),
+        None => right,
+    }
+}
+
+/// 2つの文字の範囲の積集合を求める
+///
+/// 例えば､[('a', 'z')]と[('0', 'b')]の積集合は[('a', 'b')]となる
+pub fn intersect_ranges(a: &[(char, char)], b: &[(char, char)]) -> Vec<(char, char)> {
+    let a = normalize_ranges(a.to_vec());
+    let b = normalize_ranges(b.to_vec());
+
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while let (Some((a_lo, a_hi)), Some((b_lo, b_hi))) = (a.get(i), b.get(j)) {
+        let lo = (*a_lo).max(*b_lo);
+        let hi = (*a_hi).min(*b_hi);
+        if lo <= hi {
+            result.push((lo, hi));
+        }
+
+        // 終端が小さい方の範囲を進める
+        if a_hi < b_hi {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    result
+}
+
+/// (範囲, 否定)の組を､否定を適用した範囲に変換
+fn resolve_class_set((ranges, negated): ClassSet) -> Vec<(char, char)> {
+    if negated {
+        negate_ranges(&ranges)
+    } else {
+        ranges
+    }
+}
+
+/// 文字クラス[...]をASTに変換
+///
+/// 開き括弧の直後から読み込み､閉じ括弧までを1つの文字クラスとする
+/// 先頭が^の場合は否定となり､先頭の]と先頭､末尾の-は通常の文字として扱う
+/// 文字は(開始, 終了)という範囲の組として保持し､単一の文字は(c, c)となる
+/// \dのような略記や[:alpha:]のようなPOSIX文字クラス､[a-c]のような入れ子の文字クラスは､
+/// その範囲をクラスに追加する｡そのため､[自体は\[とエスケープする
+///
+/// &&と--はそれぞれ積集合と差集合を表し､左から順に適用する
+///
+/// 例 : [abc], [^abc], []a], [a-z0-9_], [\d_], [[:alpha:]_], [[a-c][x-z]], [a-z&&[^aeiou]], [\w--\d]
+///
+/// posは開き括弧の位置で､エラーの報告に利用する
+fn parse_class(chars: &mut PatternChars, pos: usize, flags: Flags) -> Result<Ast, ParseError> {
+    let (ranges, negated) = parse_class_set(chars, pos, flags)?;
+    Ok(Ast::Class(ranges, negated))
+}
+
+/// 文字クラス[...]をパースし､(範囲, 否定)の組を返す
+///
+/// posは開き括弧の位置で､エラーの報告に利用する
+fn parse_class_set(
+    chars: &mut PatternChars,
+    pos: usize,
+    flags: Flags,
+) -> Result<ClassSet, ParseError> {
+    let mut ranges = Vec::new();
+    let negated = chars.next_if(|(_, c)| *c == '^').is_some();
+    let mut left = None; // 集合演算の左辺と演算子
+
+    let mut is_first = true;
+    while let Some((i, c)) = chars.next() {
+        let lo = match c {
+            ']' if !is_first => return Ok((apply_class_op(left, ranges), negated)),
+            '&' | '-'
+                if flags.dialect == Dialect::Perl
+                    && chars.next_if(|(_, next)| *next == c).is_some() =>
+            {
+                // それまでの要素を左辺として､集合演算を行う
+                let op = if c == '&' {
+                    ClassOp::Intersection
+                } else {
+                    ClassOp::Difference
+                };
+                left = Some((op, apply_class_op(left, take(&mut ranges))));
+                is_first = false;
+                continue;
+            }
+            '[' => {
+                // [:alpha:]のようなPOSIX文字クラス
+                // それ以外は[^aeiou]のような入れ子の文字クラスとし､和集合をとる
+                // POSIXの構文では入れ子の文字クラスはなく､[は通常の文字とする
+                let set = match parse_posix_class(chars, i)? {
+                    Some(set) => set,
+                    None if flags.dialect != Dialect::Perl => {
+                        ranges.push(('[', '['));
+                        is_first = false;
+                        continue;
+                    }
+                    None => parse_class_set(chars, i, flags)?,
+                };
+                ranges.extend(resolve_class_set(set));
+                is_first = false;
+                continue;
+            }
+            '\\' if flags.dialect == Dialect::Perl => {
+                // \dのような略記や､\p{L}のようなUnicodeプロパティ
+                let set = if let Some((pos, p)) = chars.next_if(|(_, c)| matches!(c, 'p' | 'P')) {
+                    Some(parse_unicode_property(chars, pos, p == 'P')?)
+                } else {
+                    let perl = chars.peek().and_then(|(_, c)| parse_perl_class(*c, flags));
+                    if perl.is_some() {
+                        chars.next();
+                    }
+                    perl
+                };
+                if let Some(set) = set {
+                    ranges.extend(resolve_class_set(set));
+                    is_first = false;
+                    continue;
+                }
+                parse_class_escape(chars, pos)?
+            }
+            // IUPACの記号は､範囲指定でない場合に塩基の範囲を追加する
+            _ => match iupac_bases(c).filter(|_| flags.iupac && !is_class_range(chars)) {
+                Some(bases) => {
+                    ranges.extend(bases);
+                    is_first = false;
+                    continue;
+                }
+                None => c,
+            },
+        };
+        is_first = false;
+
+        // a-zのような範囲指定
+        let hi = if is_class_range(chars) {
+            chars.next();
+            let (j, hi) = match chars.next() {
+                Some((j, '\\')) if flags.dialect == Dialect::Perl => {
+                    (j, parse_class_escape(chars, pos)?)
+                }
+                Some((j, c)) => (j, c),
+                None => break,
+            };
+            if hi < lo {
+                // "[z-a]"のように､範囲の順序が逆の場合はエラー
+                let end = chars.peek().map_or(j + 1, |(k, _)| *k);
+                return Err(ParseError::InvalidRange(i..end, lo, hi));
+            }
+            hi
+        } else {
+            lo
+        };
+        ranges.push((lo, hi));
+    }
+
+    // "[abc"のように､閉じ括弧がない場合はエラー
+    Err(ParseError::NoRightBracket(pos..pos + 1))
+}
+
+/// {n}, {n,m}, {n,}をパースし､(最小回数, 最大回数)の組を返す
+///
+/// 開き括弧の直後から読み込み､閉じ括弧までを繰り返し回数とする
+/// 最大回数がない{n,}の場合は､最大回数をNoneとする
+/// BREの場合は\{n,m\}の形とし､\}を閉じ括弧とする
+/// posは開き括弧の位置で､エラーの報告に利用する
+///
+/// 例 : {3}は(3, Some(3))､{2,5}は(2, Some(5))､{2,}は(2, None)を返し､
+/// {}, {a}, {3, {,}, {,5}, {5,2}などはエラー
+fn parse_repeat(
+    chars: &mut PatternChars,
+    pos: usize,
+    dialect: Dialect,
+) -> Result<(usize, Option<usize>), ParseError> {
+    let mut min = String::new();
+    let mut max = String::new();
+    let mut has_comma = false;
+    let mut end = pos + 1; // 読み込んだ文字の直後の位置
+    while let Some((i, c)) = chars.next() {
+        let close = if dialect == Dialect::Bre {
+            chars
+                .next_if(|(_, next)| c == '\\' && *next == '}')
+                .map(|(j, _)| j)
+        } else {
+            Some(i).filter(|_| c == '}')
+        };
+        if let Some(j) = close {
+            let span = pos..j + 1;
+            if min.is_empty() {
+                return if has_comma {
+                    Err(ParseError::NoRepeatMin(span))
+                } else {
+                    Err(ParseError::InvalidRepeat(span))
+                };
+            }
+
+            let min = min
+                .parse()
+                .map_err(|_| ParseError::InvalidRepeat(span.clone()))?;
+            let max = if !has_comma {
+                Some(min)
+            } else if max.is_empty() {
+                None
+            } else {
+                let max = max
+                    .parse()
+                    .map_err(|_| ParseError::InvalidRepeat(span.clone()))?;
+                if max < min {
+                    // "{5,2}"のように､範囲の順序が逆の場合はエラー
+                    return Err(ParseError::InvalidRepeatRange(span, min, max));
+                }
+                Some(max)
+            };
+
+            return Ok((min, max));
+        }
+
+        end = i + 1;
+        match c {
+            ',' if !has_comma => has_comma = true,
+            '0'..='9' if has_comma => max.push(c),
+            '0'..='9' => min.push(c),
+            // 繰り返し回数として誤った文字がある場合は､閉じ括弧までを範囲とする
+            _ => {
+                end = skip_past(chars, (i, c), '}');
+                break;
+            }
+        }
+    }
+
+    Err(ParseError::InvalidRepeat(pos..end))
+}
+
+/// 読み込んだ文字(i, c)から､閉じ括弧closeが現れるまで読み進め､その直後の位置を返す
+///
+/// closeが現れない場合は､最後に読み込んだ文字の直後の位置を返す
+fn skip_past(chars: &mut PatternChars, (i, c): (usize, char), close: char) -> usize {
+    if c == close {
+        return i + 1;
+    }
+    let mut end = i + 1;
+    for (j, c) in chars.by_ref() {
+        end = j + 1;
+        if c == close {
+            break;
+        }
+    }
+    end
+}
+
+/// 開き括弧の直後を読み込み､括弧の種類を判定
+///
+/// (?で始まる場合は拡張構文として解釈し､それ以外はキャプチャグループとして､
+/// groupをインクリメントした値をグループの番号とする
+/// flagsは現在のフラグで､(?i)などのフラグの変更はこれを基準とする
+/// (?#...)はコメントとして読み飛ばし､フラグを変更しない(?)と同じく扱う
+/// (?(n)...)はn番目のグループの有無による条件分岐とし､(?R)と(?0)はパターン全体の再帰呼び出しとする
+/// (?<name>...)と(?P<name>...)は名前付きのキャプチャグループとする
+/// POSIXの構文では拡張構文はなく､常にキャプチャグループとする
+/// posは開き括弧の位置で､エラーの報告に利用する
+fn parse_group(
+    chars: &mut PatternChars,
+    pos: usize,
+    group: &mut usize,
+    flags: Flags,
+) -> Result<Group, ParseError> {
+    if flags.dialect != Dialect::Perl || chars.next_if(|(_, c)| *c == '?').is_none() {
+        *group += 1;
+        return Ok(Group::Capture(*group, None));
+    }
+
+    if chars.next_if(|(_, c)| *c == '#').is_some() {
+        // コメント中の文字は閉じ括弧まで全て無視する
+        return if chars.any(|(_, c)| c == ')') {
+            Ok(Group::SetFlags(flags))
+        } else {
+            Err(ParseError::NoRightParen(pos..pos + 1))
+        };
+    }
+
+    if let Some((r, _)) = chars.next_if(|(_, c)| matches!(c, 'R' | '0')) {
+        return match chars.next() {
+            Some((_, ')')) => Ok(Group::Recurse),
+            Some(next) => Err(ParseError::InvalidGroup(pos..skip_past(chars, next, ')'))),
+            None => Err(ParseError::InvalidGroup(pos..r + 1)),
+        };
+    }
+
+    if let Some((mut end, _)) = chars.next_if(|(_, c)| *c == '(') {
+        // (?(n)の条件とするグループの番号
+        let mut n = String::new();
+        for (i, c) in chars.by_ref() {
+            end = i;
+            match c {
+                ')' => break,
+                _ => n.push(c),
+            }
+        }
+        return match n.parse() {
+            Ok(n) if n > 0 => Ok(Group::Cond(n)),
+            _ => Err(ParseError::InvalidGroup(pos..end + 1)),
+        };
+    }
+
+    if chars.next_if(|(_, c)| *c == '<').is_some() {
+        return match chars.next_if(|(_, c)| matches!(c, '=' | '!')) {
+            Some((_, c)) => Ok(Group::LookBehind(c == '!')),
+            None => {
+                let name = parse_group_name(chars, pos)?;
+                *group += 1;
+                Ok(Group::Capture(*group, Some(name)))
+            }
+        };
+    }
+
+    if let Some(p) = chars.next_if(|(_, c)| *c == 'P') {
+        if chars.next_if(|(_, c)| *c == '<').is_none() {
+            return Err(ParseError::InvalidGroup(pos..skip_past(chars, p, ')')));
+        }
+        let name = parse_group_name(chars, pos)?;
+        *group += 1;
+        return Ok(Group::Capture(*group, Some(name)));
+    }
+
+    parse_flags(chars, pos, flags)
+}
+
+/// (?<name>のようなグループの名前をパース
+///
+/// <の直後から>までを名前とし､名前は英数字と_からなり､数字で始まらないものとする
+/// posは開き括弧の位置で､エラーの報告に利用する
+fn parse_group_name(chars: &mut PatternChars, pos: usize) -> Result<String, ParseError> {
+    let mut name = String::new();
+    let mut end = pos + 1; // 読み込んだ文字の直後の位置
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '>' if name.chars().next().is_some_and(|c| !c.is_ascii_digit()) => return Ok(name),
+            c if c.is_alphanumeric() || c == '_' => name.push(c),
+            // 誤った名前の場合は､グループの閉じ括弧までを範囲とする
+            _ => {
+                end = skip_past(chars, (i, c), ')');
+                break;
+            }
+        }
+        end = i + 1;
+    }
+
+    Err(ParseError::InvalidGroupName(pos..end))
+}
+
+/// (?im), (?-i), (?i:...)のようなフラグの指定をパース
+///
+/// -より後のフラグは無効にする
+/// :で終わる場合は括弧内に､)で終わる場合は以降に適用するフラグとなる
+fn parse_flags(
+    chars: &mut PatternChars,
+    pos: usize,
+    mut flags: Flags,
+) -> Result<Group, ParseError> {
+    let mut enable = true;
+    let mut end = pos + 2; // 読み込んだ文字の直後の位置
+    for (i, c) in chars.by_ref() {
+        end = i + 1;
+        match c {
+            ':' => return Ok(Group::Flags(flags)),
+            ')' => return Ok(Group::SetFlags(flags)),
+            '-' if enable => enable = false,
+            'i' => flags.case_insensitive = enable,
+            'm' => flags.multi_line = enable,
+            's' => flags.dot_all = enable,
+            'x' => flags.extended = enable,
+            _ => return Err(ParseError::UnknownFlag(i..i + 1, c)),
+        }
+    }
+
+    Err(ParseError::InvalidGroup(pos..end))
+}
+
+/// 現在のフラグがスコープのフラグと異なる場合は､フラグ付きのASTとしてseqに追加
+///
+/// scopeは現在のコンテキストを囲むAst::Flagsのフラグで､
+/// (?i)によって途中でフラグが変更された場合に､それ以降の式にのみフラグを適用するために利用する
+fn push_with_flags(seq: &mut Vec<Ast>, ast: Ast, flags: Flags, scope: Flags) {
+    if flags == scope {
+        seq.push(ast);
+    } else {
+        seq.push(Ast::Flags(flags, Box::new(ast)));
+    }
+}
+
+/// +,*,?,{n,m}をASTに変換
+///
+/// 後置記法で､+,*,?,{n,m}の前にパターンがない場合はエラー
+///
+/// 例 : *ab, abc|+, {2}aなどはエラー
+fn parse_plus_star_question(
+    seq: &mut Vec<Ast>,
+    ast_type: Psq,
+    pos: usize,
+) -> Result<(), ParseError> {
+    if let Some(prev) = seq.pop() {
+        let ast = match ast_type {
+            Psq::Plus => Ast::Plus(Box::new(prev)),
+            Psq::Star => Ast::Star(Box::new(prev)),
+            Psq::Question => Ast::Question(Box::new(prev)),
+            Psq::Repeat(min, max) => Ast::Repeat(Box::new(prev), min, max),
+        };
+        seq.push(ast);
+        Ok(())
+    } else {
+        Err(ParseError::NoPrev(pos..pos + 1))
+    }
+}
+
+/// (?(n)yes|no)の選択肢をASTに変換
+///
+/// 選択肢が1つの場合はnoをNoneとし､3つ以上の場合はエラー
+/// spanは開き括弧から閉じ括弧までの範囲で､エラーの報告に利用する
+fn fold_cond(n: usize, seq_or: Vec<Ast>, span: Range<usize>) -> Result<Ast, ParseError> {
+    if seq_or.len() > 2 {
+        return Err(ParseError::InvalidCondition(span));
+    }
+
+    let mut branches = seq_or.into_iter();
+    let yes = branches.next().unwrap_or(Ast::Seq(Vec::new()));
+    let no = branches.next().map(Box::new);
+    Ok(Ast::Cond(n, Box::new(yes), no))
+}
+
+/// Orで結合された複数の式をASTに変換
+///
+/// 例えば､abc|def|ghiは､AST::Or("abc", Ast::Or("def", "fhi"))というASTとなる｡
+fn fold_or(mut seq_or: Vec<Ast>) -> Option<Ast> {
+    if seq_or.len() > 1 {
+        // seq_orの要素が複数ある場合は､Orで式を結合
+        let mut ast = seq_or.pop().unwrap();
+        seq_or.reverse();
+        for s in seq_or {
+            ast = Ast::Or(Box::new(s), Box::new(ast));
+        }
+        Some(ast)
+    } else {
+        // seq_orの要素が1つのみの場合は､Orではなく､最初の値を返す
+        seq_or.pop()
+    }
+}
+
+/// 正規表現を抽象構文木に変換
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, Ast};
+/// let ast = regexer::parse("a|b").unwrap();
+/// assert!(matches!(ast, Ast::Or(..)));
+/// assert!(regexer::parse("a(").is_err());
+/// ```
+///
+/// # 返り値
+///
+/// パースに成功した場合はASTを返し､正規表現にエラーがある場合はErr(ParseError)を返す
+pub fn parse(expr: &str) -> Result<Ast, ParseError> {
+    parse_with_flags(expr, Flags::default())
+}
+
+/// オプションを指定して､正規表現を抽象構文木に変換
+///
+/// flagsの大文字小文字の区別などのフラグは､パターン全体の初期値となる
+pub fn parse_with_flags(expr: &str, flags: Flags) -> Result<Ast, ParseError> {
+    // 内部状態を表現するための型
+    // Char 状態 : 文字列処理中
+    // Escape 状態 : エスケープシーケンス処理中
+    enum ParseState {
+        Char,
+        Escape,
+    }
+
+    let mut seq = Vec::new(); // 現在のSeqのコンテキスト
+    let mut seq_or = Vec::new(); // 現在のOrコンテキスト
+    let mut stack = Vec::new(); // コンテキストのスタック
+    let mut state = ParseState::Char; // 現在の状態
+    let mut group = 0; // 最後に開いたキャプチャグループの番号
+    let mut max_backref = 0; // 後方参照されたグループの番号の最大値
+    let mut backref_span = 0..0; // 番号が最大の後方参照の範囲
+    let mut cur = flags; // 現在のフラグ
+    let mut scope = flags; // 現在のコンテキストを囲むAst::Flagsのフラグ
+    let groups = count_groups(expr); // 後方参照と8進数のエスケープの判別に利用するグループの総数
+    let mut names: Vec<String> = Vec::new(); // 名前付きのキャプチャグループの名前
+    let len = expr.chars().count(); // パターンの文字数
+
+    let mut chars = expr.chars().enumerate().peekable();
+    while let Some((i, c)) = chars.next() {
+        // BREでは\(などをメタ文字とし､(などを通常の文字とする
+        if cur.dialect == Dialect::Bre && is_bre_special(c) {
+            match state {
+                ParseState::Char => {
+                    push_with_flags(&mut seq, Ast::Char(c), cur, scope);
+                    continue;
+                }
+                ParseState::Escape => state = ParseState::Char,
+            }
+        }
+
+        match &state {
+            // 拡張モードでは､エスケープされていない空白を無視する
+            ParseState::Char if cur.extended && c.is_whitespace() => (),
+            // 拡張モードでは､#から行末までをコメントとして読み飛ばす
+            ParseState::Char if cur.extended && c == '#' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            ParseState::Char => match c {
+                // BREでは式の先頭と^の直後の*､先頭以外の^､末尾以外の$は通常の文字とする
+                '*' | '^' | '$'
+                    if cur.dialect == Dialect::Bre
+                        && match c {
+                            '*' => matches!(seq.as_slice(), [] | [Ast::Hat]),
+                            '^' => !seq.is_empty(),
+                            _ => !is_bre_end(&chars),
+                        } =>
+                {
+                    push_with_flags(&mut seq, Ast::Char(c), cur, scope)
+                }
+                '+' => parse_plus_star_question(&mut seq, Psq::Plus, i)?,
+                '*' => parse_plus_star_question(&mut seq, Psq::Star, i)?,
+                '?' => parse_plus_star_question(&mut seq, Psq::Question, i)?,
+                '{' => {
+                    let (min, max) = parse_repeat(&mut chars, i, cur.dialect)?;
+                    parse_plus_star_question(&mut seq, Psq::Repeat(min, max), i)?
+                }
+                '[' => push_with_flags(&mut seq, parse_class(&mut chars, i, cur)?, cur, scope),
+                '.' => push_with_flags(&mut seq, Ast::Dot, cur, scope),
+                '$' => push_with_flags(&mut seq, Ast::Doller, cur, scope),
+                '^' => push_with_flags(&mut seq, Ast::Hat, cur, scope),
+                '(' => {
+                    let kind = parse_group(&mut chars, i, &mut group, cur)?;
+                    if let Group::Capture(_, Some(name)) = &kind {
+                        // "(?<x>a)(?<x>b)"のように､同じ名前のグループがある場合はエラー
+                        if names.contains(name) {
+                            // 範囲は<name>の部分とする
+                            let end = chars.peek().map_or(len, |(j, _)| *j);
+                            let start = end - name.chars().count() - 2;
+                            return Err(ParseError::DuplicateGroupName(start..end, name.clone()));
+                        }
+                        names.push(name.clone());
+                    }
+                    if let Group::SetFlags(new_flags) = kind {
+                        // (?i)のようなフラグの変更は､現在のコンテキストの残りに適用
+                        cur = new_flags;
+                        continue;
+                    }
+                    if let Group::Recurse = kind {
+                        push_with_flags(&mut seq, Ast::Recurse, cur, scope);
+                        continue;
+                    }
+
+                    // 現在のコンテキストとフラグをスタックに保存し､
+                    // 現在のコンテキストをからの状態にする
+                    // キャプチャグループの番号は開き括弧の順に割り当てる
+                    let prev = take(&mut seq);
+                    let prev_or = take(&mut seq_or);
+                    let (prev_cur, prev_scope) = (cur, scope);
+                    if let Group::Flags(new_flags) = kind {
+                        cur = new_flags;
+                        scope = new_flags;
+                    }
+                    stack.push((prev, prev_or, kind, prev_cur, prev_scope, i));
+                }
+                ')' => {
+                    // 現在のコンテキストをスタックからポップ
+                    if let Some((mut prev, prev_or, kind, prev_cur, prev_scope, start)) =
+                        stack.pop()
+                    {
+                        // "()"や"(a|)"のように､空の式は空文字列にマッチする式とする
+                        seq_or.push(Ast::Seq(seq));
+
+                        // 以前のフラグに戻す
+                        cur = prev_cur;
+                        scope = prev_scope;
+
+                        // Orを生成し､括弧の種類に応じたASTとする
+                        // 条件分岐の場合は､Orの代わりに選択肢ごとに分ける
+                        let ast = match kind {
+                            Group::Cond(n) => {
+                                if n > max_backref {
+                                    max_backref = n;
+                                    backref_span = start..i + 1;
+                                }
+                                Some(fold_cond(n, seq_or, start..i + 1)?)
+                            }
+                            _ => fold_or(seq_or),
+                        };
+                        if let Some(ast) = ast {
+                            let ast = match kind {
+                                Group::Capture(n, name) => Ast::Capture(Box::new(ast), n, name),
+                                Group::LookBehind(negated) => {
+                                    // "(?<=a+)"のように､長さが固定でない場合はエラー
+                                    if ast.fixed_len().is_none() {
+                                        return Err(ParseError::VariableLookBehind(start..i + 1));
+                                    }
+                                    Ast::LookBehind(Box::new(ast), negated)
+                                }
+                                Group::Flags(new_flags) if new_flags != cur => {
+                                    Ast::Flags(new_flags, Box::new(ast))
+                                }
+                                Group::Cond(_)
+                                | Group::Recurse
+                                | Group::Flags(_)
+                                | Group::SetFlags(_) => ast,
+                            };
+                            push_with_flags(&mut prev, ast, cur, scope);
+                        }
+
+                        // 以前のコンテキストを､現在のコンテキストにする
+                        seq = prev;
+                        seq_or = prev_or;
+                    } else {
+                        // "abc)"のように､開き括弧がないのに閉じ括弧がある場合はエラー
+                        return Err(ParseError::InvalidRightParen(i..i + 1));
+                    }
+                }
+                '|' => {
+                    // "||", "(|abc)"などと式が空の場合は､空文字列にマッチする選択肢とする
+                    let prev = take(&mut seq);
+                    seq_or.push(Ast::Seq(prev));
+                }
+                '\\' => state = ParseState::Escape,
+                _ => push_with_flags(&mut seq, literal(c, cur), cur, scope),
+            },
+            ParseState::Escape if c == 'Q' && cur.dialect == Dialect::Perl => {
+                // \Q...\Eの間の文字は､全て通常の文字として扱う
+                for c in parse_quote(&mut chars) {
+                    push_with_flags(&mut seq, Ast::Char(c), cur, scope);
+                }
+                state = ParseState::Char;
+            }
+            ParseState::Escape => {
+                // エスケープシーケンス処理
+                let ast = parse_escape(&mut chars, i, c, cur, groups)?;
+                if let Ast::Backref(n) = ast {
+                    if n > max_backref {
+                        max_backref = n;
+                        backref_span = i - 1..chars.peek().map_or(len, |(j, _)| *j);
+                    }
+                }
+                push_with_flags(&mut seq, ast, cur, scope);
+                state = ParseState::Char;
+            }
+        }
+    }
+
+    // 閉じ括弧が足りない場合はエラー
+    if let Some((.., start)) = stack.last() {
+        return Err(ParseError::NoRightParen(*start..start + 1));
+    }
+
+    // "(a)\\2"や"(a)(?(2)b)"のように､存在しないグループを参照している場合はエラー
+    if max_backref > group {
+        return Err(ParseError::InvalidBackref(backref_span, max_backref));
+    }
+
+    // "()"のように､式が空の場合はpushしない
+    // "a|"のように､空の選択肢は空文字列にマッチする式とする
+    if !seq.is_empty() || !seq_or.is_empty() {
+        seq_or.push(Ast::Seq(seq));
+    }
+
+    // Orを生成し､成功した場合はそれを返す
+    // 初期値のフラグが指定されている場合は､全体をフラグ付きのASTとする
+    match fold_or(seq_or) {
+        Some(ast) if flags != Flags::default() => Ok(Ast::Flags(flags, Box::new(ast))),
+        Some(ast) => Ok(ast),
+        None => Err(ParseError::Empty(0..len)),
+    }
+}
+
+/// 2つの式のliteral_affixの結果から､両方に共通するリテラルを求める
+fn common_affix((l1, c1): (Vec<char>, bool), (l2, c2): (Vec<char>, bool)) -> (Vec<char>, bool) {
+    let n = l1.iter().zip(&l2).take_while(|(a, b)| a == b).count();
+    let complete = c1 && c2 && l1 == l2;
+    (l1[..n].to_vec(), complete)
+}
+
+/// 文字列中のメタ文字をエスケープし､その文字列そのものにマッチするパターンを返す
+///
+/// (?x)を指定した箇所に埋め込んでも同じ意味になるよう､空白と#もエスケープし､
+/// 空白以外の空白文字と制御文字は\nや\x{..}とする
+/// 文字クラスの中に埋め込んでも範囲や集合演算とならないよう､-､&､~もエスケープする
+/// POSIXの構文のパターンに埋め込む場合には用いることができない
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, Regex};
+/// let input = "1+1=2?";
+/// assert_eq!(regexer::escape(input), "1\\+1=2\\?");
+///
+/// let re = Regex::new(&format!("^{}$", regexer::escape(input))).unwrap();
+/// assert!(re.is_match(input).unwrap());
+/// assert!(!re.is_match("11=2").unwrap());
+/// ```
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '(' | ')' | '|' | '+' | '*' | '?' | '[' | ']' | '{' | '}' | '.' | '^' | '$'
+            | '#' | ' ' | '-' | '&' | '~' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\x0c' => escaped.push_str("\\f"),
+            _ if c.is_control() || c.is_whitespace() => {
+                escaped.push_str(&format!("\\x{{{:X}}}", c as u32));
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
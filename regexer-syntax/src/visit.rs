@@ -0,0 +1,123 @@
+//! ASTを辿るVisitorと､ASTを書き換えるfold
+use super::parser::Ast;
+use alloc::{boxed::Box, vec, vec::Vec};
+
+/// ASTを深さ優先で辿りながら､各式で呼ばれる処理
+///
+/// Ast::walkに渡すと､親の式のenter､子の式の処理､親の式のleaveの順に呼ばれる
+/// 必要なメソッドのみを実装すればよい
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, Ast, Visitor};
+///
+/// // キャプチャグループの数を数える
+/// struct CountGroups(usize);
+/// impl Visitor for CountGroups {
+///     fn enter(&mut self, ast: &Ast) -> bool {
+///         if matches!(ast, Ast::Capture(..)) {
+///             self.0 += 1;
+///         }
+///         true
+///     }
+/// }
+///
+/// let mut v = CountGroups(0);
+/// regexer::parse("(a|(b))*(?:c)(d)").unwrap().walk(&mut v);
+/// assert_eq!(v.0, 3);
+/// ```
+pub trait Visitor {
+    /// 式に入る際に呼ばれる
+    ///
+    /// falseを返した場合は､その式の子を辿らない
+    fn enter(&mut self, _ast: &Ast) -> bool {
+        true
+    }
+
+    /// 式の子を全て辿った後に呼ばれる
+    ///
+    /// enterがfalseを返した場合も呼ばれる
+    fn leave(&mut self, _ast: &Ast) {}
+}
+
+impl Ast {
+    /// 直接の子の式を､パターン中の順に返す
+    ///
+    /// 例えば､ab|cのOrは､abのSeqとcのCharを返す
+    pub fn children(&self) -> Vec<&Ast> {
+        match self {
+            Ast::Plus(e)
+            | Ast::Star(e)
+            | Ast::Question(e)
+            | Ast::Repeat(e, _, _)
+            | Ast::Capture(e, _, _)
+            | Ast::LookBehind(e, _)
+            | Ast::Flags(_, e) => vec![e.as_ref()],
+            Ast::Or(e1, e2) => vec![e1.as_ref(), e2.as_ref()],
+            Ast::Seq(v) => v.iter().collect(),
+            Ast::Cond(_, yes, no) => core::iter::once(yes.as_ref())
+                .chain(no.as_deref())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// 式と全ての子孫を､深さ優先でvisitorに渡して辿る
+    pub fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        if visitor.enter(self) {
+            for e in self.children() {
+                e.walk(visitor);
+            }
+        }
+        visitor.leave(self);
+    }
+
+    /// 子から順に全ての式をfで変換した､新たなASTを返す
+    ///
+    /// 子の式を先に変換し､変換後の子を持つ式をfに渡す
+    /// fは変換しない式をそのまま返せばよい
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::{self, Ast};
+    ///
+    /// // リテラルを小文字にし､^と$を取り除く
+    /// let ast = regexer::parse("^A(B|c)$").unwrap().fold(&mut |ast| match ast {
+    ///     Ast::Char(c) => Ast::Char(c.to_ascii_lowercase()),
+    ///     Ast::Seq(v) => Ast::Seq(
+    ///         v.into_iter()
+    ///             .filter(|e| !matches!(e, Ast::Hat | Ast::Doller))
+    ///             .collect(),
+    ///     ),
+    ///     ast => ast,
+    /// });
+    /// assert_eq!(ast, regexer::parse("a(b|c)").unwrap());
+    /// ```
+    pub fn fold<F: FnMut(Ast) -> Ast>(self, f: &mut F) -> Ast {
+        let fold_box = |e: Box<Ast>, f: &mut F| Box::new(e.fold(f));
+
+        let ast = match self {
+            Ast::Plus(e) => Ast::Plus(fold_box(e, f)),
+            Ast::Star(e) => Ast::Star(fold_box(e, f)),
+            Ast::Question(e) => Ast::Question(fold_box(e, f)),
+            Ast::Repeat(e, min, max) => Ast::Repeat(fold_box(e, f), min, max),
+            Ast::Or(e1, e2) => {
+                let e1 = fold_box(e1, f);
+                Ast::Or(e1, fold_box(e2, f))
+            }
+            Ast::Seq(v) => Ast::Seq(v.into_iter().map(|e| e.fold(f)).collect()),
+            Ast::Capture(e, n, name) => Ast::Capture(fold_box(e, f), n, name),
+            Ast::LookBehind(e, negated) => Ast::LookBehind(fold_box(e, f), negated),
+            Ast::Flags(flags, e) => Ast::Flags(flags, fold_box(e, f)),
+            Ast::Cond(n, yes, no) => {
+                let yes = fold_box(yes, f);
+                Ast::Cond(n, yes, no.map(|no| fold_box(no, f)))
+            }
+            ast => ast,
+        };
+
+        f(ast)
+    }
+}
@@ -0,0 +1,504 @@
+//! 2つの正規表現が受理する文字列の集合を比較する静的解析
+//!
+//! 正規表現が受理する文字列とは､その文字列全体にマッチする文字列とする
+//! 命令列をNFAとみなし､2つのNFAの状態の組を部分集合構成で辿る直積のDFAによって判定する
+//!
+//! 後方参照､後読み､条件分岐､再帰､\Xを含むパターンと､
+//! 改行にLineTerminator::Crlfを指定したパターンの^や$は扱えない
+//!
+//! また､深さ優先探索で指数的な時間がかかりうるパターンを､マッチングせずに検出する
+use crate::{parse, Ast, Flags, Instruction, Limits, LineTerminator, MatchMode, ParseError, Regex};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeSet, VecDeque},
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+/// 探索する状態の組の数の上限
+const MAX_STATES: usize = 100_000;
+
+/// 解析時のエラーを表す型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnalysisError {
+    Unsupported(&'static str), // 正規言語として扱えない構文｡値は構文の名前
+    TooManyStates(usize),      // 状態の組の数が上限を超えた｡値は上限
+}
+
+impl Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalysisError::Unsupported(name) => {
+                write!(f, "AnalysisError: unsupported construct: {name}")
+            }
+            AnalysisError::TooManyStates(limit) => {
+                write!(f, "AnalysisError: too many states: limit = {limit}")
+            }
+        }
+    }
+}
+
+impl Error for AnalysisError {}
+
+/// checkが検出する､深さ優先探索で指数的な時間がかかりうる構造
+///
+/// 値は検出した箇所の式を､パターンの文字列として書き出したもの
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    NestedQuantifier(String), // 上限のない繰り返しの本体が､上限のない繰り返しのみにマッチしうる
+    OverlappingAlternation(String), // 上限のない繰り返しの中の選択で､複数の分岐が同じ文字列にマッチする
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::NestedQuantifier(expr) => write!(f, "Warning: nested quantifier: {expr}"),
+            Warning::OverlappingAlternation(expr) => {
+                write!(f, "Warning: overlapping alternation: {expr}")
+            }
+        }
+    }
+}
+
+/// 2つの正規表現が､同じ文字列の集合を受理するかを判定
+///
+/// 例えば､a+とaa*や､[a-c]とa|b|cは等価となる
+/// 大文字と小文字を区別しないなどのフラグは､コンパイル時に指定したものが反映される
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{analysis, Regex};
+///
+/// let a = Regex::new("(?:ab)*a").unwrap();
+/// let b = Regex::new("a(?:ba)*").unwrap();
+/// assert!(analysis::is_equivalent(&a, &b).unwrap());
+///
+/// let c = Regex::new("a(?:ba)+").unwrap();
+/// assert!(!analysis::is_equivalent(&a, &c).unwrap());
+/// ```
+///
+/// # 引数
+///
+/// 比較する2つの正規表現を与える
+///
+/// # 返り値
+///
+/// 等価な場合はOk(true)を､そうでない場合はOk(false)を返す
+/// 扱えない構文を含む場合や､状態の組の数が上限を超えた場合はErrを返す
+pub fn is_equivalent(a: &Regex, b: &Regex) -> Result<bool, AnalysisError> {
+    Ok(!explore(a, b, |a, b| a != b)?)
+}
+
+/// 2つの正規表現の両方が受理する文字列が､存在しないかを判定
+///
+/// パターンの集合から､同じ文字列にマッチしうる規則を見つけるために利用できる
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{analysis, Regex};
+///
+/// let a = Regex::new("\\d+px").unwrap();
+/// let b = Regex::new("[0-9]+(?:em|rem)").unwrap();
+/// assert!(analysis::is_disjoint(&a, &b).unwrap());
+///
+/// let c = Regex::new("1\\w*").unwrap();
+/// assert!(!analysis::is_disjoint(&a, &c).unwrap());
+/// ```
+///
+/// # 引数
+///
+/// 比較する2つの正規表現を与える
+///
+/// # 返り値
+///
+/// 共通に受理する文字列が存在しない場合はOk(true)を､存在する場合はOk(false)を返す
+/// 扱えない構文を含む場合や､状態の組の数が上限を超えた場合はErrを返す
+pub fn is_disjoint(a: &Regex, b: &Regex) -> Result<bool, AnalysisError> {
+    Ok(!explore(a, b, |a, b| a && b)?)
+}
+
+/// 深さ優先探索で､マッチしない入力に対して指数的な時間がかかりうる構造を検出
+///
+/// (a+)+や(\w+\s?)*のような入れ子の繰り返しと､(a|a)*や(\d|\w)+のように
+/// 繰り返しの中で複数の分岐が同じ文字列にマッチする選択を報告する
+/// 利用者が与えたパターンを､マッチングする前に拒否するために利用できる
+///
+/// 検出は構文に基づく近似で､警告のないパターンが常に線形時間でマッチングできるとは限らない
+/// 比較できない構文を含む分岐の組は､重なりを報告しない
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::analysis::{self, Warning};
+///
+/// let warnings = analysis::check("^([a-z]+-?)*$").unwrap();
+/// assert_eq!(warnings, vec![Warning::NestedQuantifier("([a-z]+-?)*".to_string())]);
+///
+/// let warnings = analysis::check("(?:[0-9]|[0-9a-f])+h").unwrap();
+/// assert_eq!(warnings, vec![Warning::OverlappingAlternation("[0-9]|[0-9a-f]".to_string())]);
+///
+/// assert!(analysis::check("(?:a+b|c)*").unwrap().is_empty());
+/// ```
+///
+/// # 引数
+///
+/// 調べるパターンを与える
+///
+/// # 返り値
+///
+/// 検出した構造をパターン中の順に並べてOkで返す
+/// パターンにエラーがある場合はErrを返す
+pub fn check(expr: &str) -> Result<Vec<Warning>, ParseError> {
+    let ast = parse(expr)?;
+    let mut warnings = Vec::new();
+    check_ast(&ast, Flags::default(), false, &mut warnings);
+    Ok(warnings)
+}
+
+/// 式を辿り､検出した構造をwarningsに加える
+///
+/// flagsは式に適用されるフラグで､in_loopは上限のない繰り返しの中かを表す
+fn check_ast(ast: &Ast, flags: Flags, in_loop: bool, warnings: &mut Vec<Warning>) {
+    match ast {
+        Ast::Flags(flags, e) => return check_ast(e, *flags, in_loop, warnings),
+        Ast::Plus(e) | Ast::Star(e) | Ast::Repeat(e, _, None) => {
+            if has_inner_loop(e) {
+                warnings.push(Warning::NestedQuantifier(ast.to_string()));
+            }
+            return check_ast(e, flags, true, warnings);
+        }
+        Ast::Or(..) if in_loop => {
+            let mut branches = Vec::new();
+            flatten_or(ast, &mut branches);
+            if has_overlap(&branches, flags) {
+                warnings.push(Warning::OverlappingAlternation(ast.to_string()));
+            }
+            for e in branches {
+                check_ast(e, flags, in_loop, warnings);
+            }
+            return;
+        }
+        _ => (),
+    }
+
+    for e in ast.children() {
+        check_ast(e, flags, in_loop, warnings);
+    }
+}
+
+/// 繰り返しの本体eが､上限のない繰り返しのみにマッチしうるかを判定
+///
+/// 該当する場合は､同じ文字列を外側と内側のどちらの繰り返しでも分けられるため､
+/// 分け方の数だけバックトラックが生じる
+fn has_inner_loop(e: &Ast) -> bool {
+    match e {
+        Ast::Plus(e) | Ast::Star(e) | Ast::Repeat(e, _, None) => e.max_len() != Some(0),
+        Ast::Question(e)
+        | Ast::Repeat(e, _, Some(_))
+        | Ast::Capture(e, _, _)
+        | Ast::Flags(_, e) => has_inner_loop(e),
+        Ast::Or(e1, e2) => has_inner_loop(e1) || has_inner_loop(e2),
+        // 他の要素が全て空文字列にマッチしうる場合のみ､その要素の繰り返しのみにマッチする
+        Ast::Seq(v) => v.iter().enumerate().any(|(i, e)| {
+            has_inner_loop(e)
+                && v.iter()
+                    .enumerate()
+                    .all(|(j, e)| j == i || e.min_len() == 0)
+        }),
+        _ => false,
+    }
+}
+
+/// a|b|cのように連なる選択の分岐を､パターン中の順にbranchesに加える
+fn flatten_or<'a>(ast: &'a Ast, branches: &mut Vec<&'a Ast>) {
+    match ast {
+        Ast::Or(e1, e2) => {
+            flatten_or(e1, branches);
+            flatten_or(e2, branches);
+        }
+        e => branches.push(e),
+    }
+}
+
+/// 選択の分岐のいずれかの組が､同じ文字列にマッチするかを判定
+fn has_overlap(branches: &[&Ast], flags: Flags) -> bool {
+    let regexes = branches
+        .iter()
+        .map(|e| {
+            let ast = Ast::Flags(flags, Box::new((*e).clone()));
+            Regex::from_ast(ast.to_string(), &ast, Limits::default(), MatchMode::Dfs, 0).ok()
+        })
+        .collect::<Vec<_>>();
+
+    regexes.iter().enumerate().any(|(i, a)| {
+        regexes[i + 1..].iter().any(|b| match (a, b) {
+            (Some(a), Some(b)) => is_disjoint(a, b) == Ok(false),
+            _ => false,
+        })
+    })
+}
+
+/// 2つの正規表現の状態の組を､空文字列から1文字ずつ読み進めながら幅優先で辿る
+///
+/// stopに2つの状態が受理状態かを渡し､trueを返す組に到達した場合はOk(true)を返す
+fn explore<F>(a: &Regex, b: &Regex, stop: F) -> Result<bool, AnalysisError>
+where
+    F: Fn(bool, bool) -> bool,
+{
+    let (a, b) = (Nfa::new(a)?, Nfa::new(b)?);
+
+    // 改行の直後かを区別するため､改行の文字は常に1文字の区間とする
+    let mut terms = BTreeSet::new();
+    a.terminators(&mut terms);
+    b.terminators(&mut terms);
+
+    let start = (a.start(), b.start());
+    let mut seen = BTreeSet::new();
+    let mut queue = VecDeque::new();
+    seen.insert(start.clone());
+    queue.push_back(start);
+
+    while let Some((sa, sb)) = queue.pop_front() {
+        if stop(a.accepts(&sa), b.accepts(&sb)) {
+            return Ok(true);
+        }
+
+        let mut bounds = terms.clone();
+        a.bounds(&sa, &mut bounds);
+        b.bounds(&sb, &mut bounds);
+        for c in representatives(&bounds) {
+            let next = (a.step(&sa, c), b.step(&sb, c));
+            if next.0.is_empty() && next.1.is_empty() {
+                continue;
+            }
+            if !seen.contains(&next) {
+                if seen.len() >= MAX_STATES {
+                    return Err(AnalysisError::TooManyStates(MAX_STATES));
+                }
+                seen.insert(next.clone());
+                queue.push_back(next);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+/// 区間の境界から､各区間を代表する文字を求める
+///
+/// 境界の間の文字は､どの命令に対しても同じ振る舞いとなる
+/// サロゲートの範囲のように文字を含まない区間は除く
+fn representatives(bounds: &BTreeSet<u32>) -> Vec<char> {
+    let mut bounds = bounds.clone();
+    bounds.extend([0, 0xD800, 0xE000]);
+    bounds.into_iter().filter_map(char::from_u32).collect()
+}
+
+/// 命令の実行位置に課された､それ以降の文字列に対する条件
+///
+/// $のように後ろの文字を調べるアンカーは､次の文字を読む際に確かめる
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rest {
+    Any,             // 条件なし
+    End,             // 文字列の末尾であること
+    NextOrEnd(char), // 末尾か､次の文字が改行であること
+    OnlyOrEnd(char), // 末尾か､残りが改行のみであること
+}
+
+impl Rest {
+    /// 2つの条件を共に満たす条件
+    fn meet(self, other: Rest) -> Rest {
+        match (self, other) {
+            (Rest::Any, r) | (r, Rest::Any) => r,
+            (Rest::NextOrEnd(t1), Rest::NextOrEnd(t2)) if t1 == t2 => Rest::NextOrEnd(t1),
+            (Rest::NextOrEnd(t1) | Rest::OnlyOrEnd(t1), Rest::OnlyOrEnd(t2))
+            | (Rest::OnlyOrEnd(t1), Rest::NextOrEnd(t2))
+                if t1 == t2 =>
+            {
+                Rest::OnlyOrEnd(t1)
+            }
+            _ => Rest::End,
+        }
+    }
+
+    /// 次の文字cを読んだ後の条件
+    ///
+    /// 条件を満たさない場合はNoneを返す
+    fn step(self, c: char) -> Option<Rest> {
+        match self {
+            Rest::Any => Some(Rest::Any),
+            Rest::End => None,
+            Rest::NextOrEnd(t) => (c == t).then_some(Rest::Any),
+            Rest::OnlyOrEnd(t) => (c == t).then_some(Rest::End),
+        }
+    }
+}
+
+/// 命令の位置と条件の組を並べた､NFAの状態の集合
+type States = Vec<(usize, Rest)>;
+
+/// 命令列をNFAとして扱う型
+struct Nfa<'a> {
+    code: &'a [Instruction],
+}
+
+impl<'a> Nfa<'a> {
+    /// 正規表現の命令列からNFAを生成
+    ///
+    /// 扱えない命令を含む場合はErrを返す
+    fn new(re: &'a Regex) -> Result<Nfa<'a>, AnalysisError> {
+        let code = re.instructions();
+        for inst in code {
+            let name = match inst {
+                Instruction::Grapheme => "\\X",
+                Instruction::Backref(_) | Instruction::BackrefIgnoreCase(_) => "backreference",
+                Instruction::LookBehind(..) | Instruction::NegativeLookBehind(..) => "look-behind",
+                Instruction::Cond(..) => "conditional",
+                Instruction::Recurse(_) | Instruction::Return => "recursion",
+                Instruction::MatchEnd(LineTerminator::Crlf)
+                | Instruction::MatchLineBegin(LineTerminator::Crlf)
+                | Instruction::MatchLineEnd(LineTerminator::Crlf) => "CRLF line terminator",
+                _ => continue,
+            };
+            return Err(AnalysisError::Unsupported(name));
+        }
+
+        Ok(Nfa { code })
+    }
+
+    /// 文字列の先頭での状態の集合
+    fn start(&self) -> States {
+        self.closure(vec![(0, Rest::Any)], None)
+    }
+
+    /// 状態の集合のうち､文字を読む命令の状態に対して文字cを読んだ後の状態の集合
+    fn step(&self, states: &States, c: char) -> States {
+        let next = states
+            .iter()
+            .filter_map(|(pc, rest)| {
+                let rest = rest.step(c)?;
+                let is_match = match &self.code[*pc] {
+                    Instruction::Char(x) => *x == c,
+                    Instruction::Class(ranges, negated) => {
+                        ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated
+                    }
+                    Instruction::AnyChar => true,
+                    Instruction::AnyCharExceptNewline => c != '\n',
+                    _ => false,
+                };
+                is_match.then_some((pc + 1, rest))
+            })
+            .collect();
+        self.closure(next, Some(c))
+    }
+
+    /// 状態の集合が､文字列の末尾で受理するかを判定
+    ///
+    /// 条件は全て末尾で満たされるため､match命令の状態を含むかのみを調べる
+    fn accepts(&self, states: &States) -> bool {
+        states
+            .iter()
+            .any(|(pc, _)| matches!(self.code[*pc], Instruction::Match))
+    }
+
+    /// statesから文字を読まずに到達できる､文字を読む命令とmatch命令の状態を全て求める
+    ///
+    /// prevは直前に読んだ文字で､文字列の先頭の場合はNoneとする
+    fn closure(&self, mut stack: States, prev: Option<char>) -> States {
+        let mut visited = BTreeSet::new();
+        let mut states = Vec::new();
+        while let Some((pc, rest)) = stack.pop() {
+            if !visited.insert((pc, rest)) {
+                continue;
+            }
+
+            match &self.code[pc] {
+                Instruction::Jump(addr) => stack.push((*addr, rest)),
+                Instruction::Split(addr1, addr2) => {
+                    stack.push((*addr2, rest));
+                    stack.push((*addr1, rest));
+                }
+                Instruction::Save(_) | Instruction::EmptyCheckStart(_) => {
+                    stack.push((pc + 1, rest))
+                }
+                // 空の繰り返しでループを抜けても､再び繰り返してから抜けても到達できる命令は変わらない
+                Instruction::EmptyCheckEnd(_, addr) => {
+                    stack.push((*addr, rest));
+                    stack.push((pc + 1, rest));
+                }
+                Instruction::MatchBegin | Instruction::MatchStart => {
+                    if prev.is_none() {
+                        stack.push((pc + 1, rest));
+                    }
+                }
+                Instruction::MatchLineBegin(term) => {
+                    if prev.is_none() || prev == terminator(*term) {
+                        stack.push((pc + 1, rest));
+                    }
+                }
+                Instruction::MatchTextEnd => stack.push((pc + 1, rest.meet(Rest::End))),
+                Instruction::MatchEnd(term) => {
+                    let t = terminator(*term).map_or(Rest::End, Rest::OnlyOrEnd);
+                    stack.push((pc + 1, rest.meet(t)));
+                }
+                Instruction::MatchLineEnd(term) => {
+                    let t = terminator(*term).map_or(Rest::End, Rest::NextOrEnd);
+                    stack.push((pc + 1, rest.meet(t)));
+                }
+                _ => states.push((pc, rest)),
+            }
+        }
+
+        states.sort_unstable();
+        states
+    }
+
+    /// 状態の集合の振る舞いが変わる文字の境界を､boundsに加える
+    fn bounds(&self, states: &States, bounds: &mut BTreeSet<u32>) {
+        let mut push = |lo: char, hi: char| {
+            bounds.insert(lo as u32);
+            bounds.insert(hi as u32 + 1);
+        };
+        for (pc, rest) in states {
+            match &self.code[*pc] {
+                Instruction::Char(c) => push(*c, *c),
+                Instruction::Class(ranges, _) => ranges.iter().for_each(|(lo, hi)| push(*lo, *hi)),
+                Instruction::AnyCharExceptNewline => push('\n', '\n'),
+                _ => (),
+            }
+            if let Rest::NextOrEnd(t) | Rest::OnlyOrEnd(t) = rest {
+                push(*t, *t);
+            }
+        }
+    }
+
+    /// ^や$が用いる改行の文字を､termsに加える
+    fn terminators(&self, terms: &mut BTreeSet<u32>) {
+        for inst in self.code {
+            if let Instruction::MatchEnd(term)
+            | Instruction::MatchLineBegin(term)
+            | Instruction::MatchLineEnd(term) = inst
+            {
+                if let Some(t) = terminator(*term) {
+                    terms.extend([t as u32, t as u32 + 1]);
+                }
+            }
+        }
+    }
+}
+
+/// 1文字の改行の場合は､その文字を返す
+fn terminator(term: LineTerminator) -> Option<char> {
+    match term {
+        LineTerminator::Lf => Some('\n'),
+        LineTerminator::Char(c) => Some(c),
+        LineTerminator::Crlf => None,
+    }
+}
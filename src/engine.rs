@@ -1,48 +1,70 @@
 //! 正規表現エンジン
-mod codegen;
+mod class;
+mod dfa;
+mod error;
 mod evaluator;
-mod parser;
+mod grapheme;
+#[cfg(feature = "std")]
+mod grep;
+#[cfg(feature = "unicode-normalization")]
+mod normalize;
+mod onepass;
+mod pattern;
+mod program;
+mod regex;
+#[cfg(feature = "sample")]
+mod sample;
+#[cfg(feature = "std")]
+mod stream;
 
-use crate::helper::DynError;
-use std::{fmt::Display, io};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Display;
+// パーサとコード生成器はregexer-macrosと共有するため､regexer-syntaxクレートに置く
+use regexer_syntax::{codegen, parser, visit};
 
-#[derive(Debug)]
-pub enum Instruction {
-    Char(char),
-    Match,
-    Jump(usize),
-    Split(usize, usize),
-    MatchBegin,
-    MatchEnd,
-}
+pub use class::CharClass;
+pub use codegen::CodeGenError;
+pub use error::RegexError;
+pub use evaluator::{EvalError, MatchMode, Stats};
+#[cfg(feature = "std")]
+pub use grep::{grep, Grep, GrepLine};
+#[cfg(feature = "unicode-normalization")]
+pub use normalize::Normalization;
+pub use parser::{
+    escape, parse, parse_with_flags, Ast, Dialect, Flags, LineTerminator, ParseError,
+};
+pub use pattern::Pattern;
+pub use program::{CompileError, Program};
+pub use regex::{
+    BytesMatch, BytesMatches, Cache, CaptureMatches, Captures, Info, LocatedMatch, LocatedMatches,
+    Match, Matches, NoExpand, OverlappingMatches, Regex, RegexBuilder, Replacer, Split, SplitN,
+};
+pub use regexer_syntax::{Instruction, Limits};
+#[cfg(feature = "std")]
+pub use stream::{IncrementalMatcher, Status, StreamMatch, StreamMatcher};
+pub use visit::Visitor;
 
-impl Display for Instruction {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Instruction::Char(c) => write!(f, "char {}", c),
-            Instruction::Match => write!(f, "match"),
-            Instruction::Jump(addr) => write!(f, "jump {:>04}", addr),
-            Instruction::Split(addr1, addr2) => write!(f, "split {:>04}, {:>04}", addr1, addr2),
-            Instruction::MatchBegin => write!(f, "match begin"),
-            Instruction::MatchEnd => write!(f, "match end"),
-        }
-    }
-}
+/// キャプチャグループごとの(開始位置, 終了位置)を､グループの番号順に並べたもの
+pub type GroupSpans = Vec<Option<(usize, usize)>>;
 
 /// 正規表現と文字列をマッチング
 ///
 /// # 利用例
 ///
 /// ```
-/// use regexer;
-/// regexer::do_matching("abc|(de|cd)+", "decddede", true);
+/// use regexer::{self, MatchMode};
+/// regexer::do_matching("abc|(de|cd)+", "decddede", MatchMode::Dfs);
 /// ```
 ///
 /// # 引数
 ///
 /// exprに正規表現､lineにマッチ対象とする文字列を与える
-/// is_depthがtrueの場合には深さ優先探索を､falseの場合には幅優先探索を利用
+/// modeにはMatchMode::Dfsで深さ優先探索を､MatchMode::Bfsで幅優先探索を指定する
 ///
+/// 以前の版のtrueとfalseによる指定は､MatchMode::from_depth_firstで変換できる
 ///
 /// # 返り値
 ///
@@ -50,21 +72,382 @@ impl Display for Instruction {
 /// エラーがなく実行でき､かつマッチングに**失敗**した場合はOk(false)を返す
 ///
 /// 入力された正規表現にエラーがあったり､内部的な実装エラーが有る場合はErrを返す
-pub fn do_matching(expr: &str, line: &str, is_depth: bool) -> Result<bool, DynError> {
+pub fn do_matching(expr: &str, line: &str, mode: MatchMode) -> Result<bool, RegexError> {
     let ast = parser::parse(expr)?;
     let code = codegen::gen_code(&ast)?;
     let line = line.chars().collect::<Vec<char>>();
 
-    Ok(evaluator::eval(&code, &line, is_depth)?)
+    Ok(evaluator::eval(&code, &line, mode)?)
+}
+
+/// オプションを指定して､正規表現と文字列をマッチング
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, Flags, MatchMode};
+/// let flags = Flags {
+///     unicode: true,
+///     ..Default::default()
+/// };
+/// assert!(regexer::do_matching_with_flags("\\w+", "日本語", flags, MatchMode::Dfs).unwrap());
+/// ```
+///
+/// # 引数
+///
+/// exprに正規表現､lineにマッチ対象とする文字列､flagsにオプションを与える
+/// modeにはMatchMode::Dfsで深さ優先探索を､MatchMode::Bfsで幅優先探索を指定する
+///
+/// flags.case_insensitiveがtrueの場合は､パターン全体で大文字と小文字を区別しない
+/// flags.multi_lineがtrueの場合は､^と$が各行の先頭と末尾にもマッチする
+/// flags.dot_allがtrueの場合は､.が改行にもマッチする
+/// flags.extendedがtrueの場合は､パターン中の空白と#から行末までのコメントを無視する
+/// flags.line_terminatorは^､$､\Zで行末とみなす改行で､LineTerminator::Crlfとすると\r\nも扱える
+/// flags.dialectをDialect::BreやDialect::Ereとすると､grepやegrepの構文でパターンを解釈する
+/// flags.iupacがtrueの場合は､NやRのようなIUPACの記号を[ACGT]や[AG]のような塩基の文字クラスとする
+/// これらはパターンの先頭に(?i)や(?m)を指定した場合と同じで､(?-i)などによって部分的に無効にできる
+///
+/// unicode-normalizationフィーチャが有効な場合､flags.normalizationをNormalization::Nfcなどとすると､
+/// パターン中の文字とlineを正規化してからマッチングを行う
+///
+/// # 返り値
+///
+/// do_matchingと同様
+pub fn do_matching_with_flags(
+    expr: &str,
+    line: &str,
+    flags: Flags,
+    mode: MatchMode,
+) -> Result<bool, RegexError> {
+    let ast = parser::parse_with_flags(expr, flags)?;
+    #[cfg(feature = "unicode-normalization")]
+    let ast = {
+        let mut ast = ast;
+        normalize::normalize_ast(&mut ast, flags.normalization);
+        ast
+    };
+    let code = codegen::gen_code(&ast)?;
+
+    #[cfg(feature = "unicode-normalization")]
+    let line = normalize::normalize_chars(line.chars(), flags.normalization);
+    #[cfg(not(feature = "unicode-normalization"))]
+    let line = line.chars().collect::<Vec<char>>();
+
+    Ok(evaluator::eval(&code, &line, mode)?)
+}
+
+/// 資源の上限を指定して､正規表現と文字列をマッチング
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, Limits, MatchMode};
+/// let limits = Limits {
+///     recursion: 2,
+///     ..Default::default()
+/// };
+/// assert!(regexer::do_matching_with_limits("\\((a|(?R))*\\)", "((a))", limits, MatchMode::Dfs).unwrap());
+/// assert!(regexer::do_matching_with_limits("\\((a|(?R))*\\)", "((((a))))", limits, MatchMode::Dfs).is_err());
+/// ```
+///
+/// # 引数
+///
+/// exprに正規表現､lineにマッチ対象とする文字列､limitsに資源の上限を与える
+/// modeにはMatchMode::Dfsで深さ優先探索を､MatchMode::Bfsで幅優先探索を指定する
+///
+/// limits.recursionは(?R)による再帰呼び出しの深さの上限で､
+/// limits.instsとlimits.repeatは生成する命令数と{n,m}の繰り返し回数の上限､
+/// limits.stepsは深さ優先探索で1回のマッチの試行中に実行する命令数の上限となる
+/// 指定しない場合はLimits::default()の値となる
+///
+/// # 返り値
+///
+/// do_matchingと同様
+/// ただし､上限を超えた場合はErrを返す
+pub fn do_matching_with_limits(
+    expr: &str,
+    line: &str,
+    limits: Limits,
+    mode: MatchMode,
+) -> Result<bool, RegexError> {
+    let ast = parser::parse(expr)?;
+    let code = codegen::gen_code_with_limits(&ast, &limits)?;
+    let line = line.chars().collect::<Vec<char>>();
+
+    Ok(evaluator::eval_with_limits(&code, &line, &limits, mode)?)
+}
+
+/// 正規表現と文字列をマッチングし､マッチした範囲を取得
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, MatchMode};
+/// assert_eq!(regexer::do_match_span("a+b", "aaabc", MatchMode::Dfs).unwrap(), Some((0, 4)));
+/// assert_eq!(regexer::do_match_span("a+b", "xab", MatchMode::Dfs).unwrap(), None);
+/// ```
+///
+/// # 引数
+///
+/// exprに正規表現､lineにマッチ対象とする文字列を与える
+/// modeにはMatchMode::Dfsで深さ優先探索を､MatchMode::Bfsで幅優先探索を指定する
+///
+/// # 返り値
+///
+/// do_matchingと同じくlineの先頭からマッチングを行い､
+/// マッチングに**成功**した場合は､マッチした範囲の(開始位置, 終了位置)をOk(Some(..))で返す
+/// 位置はlineのバイト単位で､\Kを使った場合は開始位置が0以外となる
+///
+/// マッチングに**失敗**した場合はOk(None)を返す
+///
+/// 入力された正規表現にエラーがあったり､内部的な実装エラーが有る場合はErrを返す
+pub fn do_match_span(
+    expr: &str,
+    line: &str,
+    mode: MatchMode,
+) -> Result<Option<(usize, usize)>, RegexError> {
+    let spans = do_captures(expr, line, mode)?;
+    Ok(spans.and_then(|spans| spans.first().copied().flatten()))
+}
+
+/// 正規表現と文字列をマッチングし､キャプチャグループのマッチ位置を取得
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, MatchMode};
+/// let caps = regexer::do_captures("(\\d+)-(\\d+)", "03-1234", MatchMode::Dfs).unwrap().unwrap();
+/// assert_eq!(caps, vec![Some((0, 7)), Some((0, 2)), Some((3, 7))]);
+/// ```
+///
+/// # 引数
+///
+/// exprに正規表現､lineにマッチ対象とする文字列を与える
+/// modeにはMatchMode::Dfsで深さ優先探索を､MatchMode::Bfsで幅優先探索を指定する
+///
+/// # 返り値
+///
+/// マッチングに**成功**した場合は､各グループの(開始位置, 終了位置)を
+/// グループの番号順に並べたものをOk(Some(..))で返す
+/// 位置はlineのバイト単位で､0番目はマッチ全体を表し､
+/// マッチに関与しなかったグループはNoneとなる
+///
+/// マッチングに**失敗**した場合はOk(None)を返す
+///
+/// 入力された正規表現にエラーがあったり､内部的な実装エラーが有る場合はErrを返す
+pub fn do_captures(
+    expr: &str,
+    line: &str,
+    mode: MatchMode,
+) -> Result<Option<GroupSpans>, RegexError> {
+    do_captures_at(expr, line, 0, mode)
+}
+
+/// 文字列の途中の位置から､正規表現と文字列をマッチングし､キャプチャグループのマッチ位置を取得
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, MatchMode};
+/// // \Gは前回のマッチの終了位置から続く場合にのみマッチする
+/// let caps = regexer::do_captures_at("\\G\\d+,?", "12,345,x", 3, MatchMode::Dfs).unwrap().unwrap();
+/// assert_eq!(caps, vec![Some((3, 7))]);
+/// assert_eq!(regexer::do_captures_at("\\G\\d+", "12,345", 2, MatchMode::Dfs).unwrap(), None);
+/// ```
+///
+/// # 引数
+///
+/// exprに正規表現､lineにマッチ対象とする文字列､startにマッチを開始する位置を与える
+/// startはlineのバイト単位の位置で､\Gはこの位置にマッチする
+/// ^や後読みはstartより前の文字も参照する
+/// modeにはMatchMode::Dfsで深さ優先探索を､MatchMode::Bfsで幅優先探索を指定する
+///
+/// # 返り値
+///
+/// do_capturesと同じく､マッチングに**成功**した場合は各グループの(開始位置, 終了位置)を､
+/// **失敗**した場合はOk(None)を返す
+/// 位置はline全体の先頭からのバイト単位となる
+///
+/// startが文字の境界でない場合や､入力された正規表現にエラーがある場合はErrを返す
+pub fn do_captures_at(
+    expr: &str,
+    line: &str,
+    start: usize,
+    mode: MatchMode,
+) -> Result<Option<GroupSpans>, RegexError> {
+    if !line.is_char_boundary(start) {
+        return Err(RegexError::InvalidStart(start));
+    }
+
+    let ast = parser::parse(expr)?;
+    let code = codegen::gen_code(&ast)?;
+    let chars = line.chars().collect::<Vec<char>>();
+    let start = line[..start].chars().count();
+
+    let limits = Limits::default();
+    let slots = if let Some(slots) = evaluator::eval_captures(&code, &chars, start, &limits, mode)?
+    {
+        slots
+    } else {
+        return Ok(None);
+    };
+
+    // 文字単位の位置を､バイト単位の位置に変換
+    let offsets = line
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(Some(line.len()))
+        .collect::<Vec<usize>>();
+    let spans = slots
+        .chunks(2)
+        .map(|pair| match pair {
+            [Some(start), Some(end)] => Some((offsets[*start], offsets[*end])),
+            _ => None,
+        })
+        .collect();
+
+    Ok(Some(spans))
+}
+
+/// 正規表現と文字列を近似的にマッチング
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, MatchMode};
+/// assert!(regexer::do_fuzzy_matching("identifier", "identifeir", 2, MatchMode::Dfs).unwrap());
+/// ```
+///
+/// # 引数
+///
+/// exprに正規表現､lineにマッチ対象とする文字列を与える
+/// kは1回のマッチで許容する編集(置換､挿入､削除)の回数
+/// modeにはMatchMode::Dfsで深さ優先探索を､MatchMode::Bfsで幅優先探索を指定する
+/// 幅優先探索はkが1以上の近似マッチングを扱えないため､Errを返す
+///
+/// # 返り値
+///
+/// 編集距離k以内でマッチングに**成功**した場合はOk(true)を返し､
+/// **失敗**した場合はOk(false)を返す
+///
+/// 入力された正規表現にエラーがあったり､内部的な実装エラーが有る場合はErrを返す
+pub fn do_fuzzy_matching(
+    expr: &str,
+    line: &str,
+    k: usize,
+    mode: MatchMode,
+) -> Result<bool, RegexError> {
+    let ast = parser::parse(expr)?;
+    let code = codegen::gen_code(&ast)?;
+    let line = line.chars().collect::<Vec<char>>();
+
+    Ok(evaluator::eval_fuzzy(&code, &line, k, mode)?)
+}
+
+/// 正規表現のパースとコード生成の結果
+///
+/// Displayでは､パターン､AST､命令列を1行ずつ表示する
+#[derive(Debug)]
+pub struct CompileInfo {
+    expr: String,
+    ast: Ast,
+    code: Vec<Instruction>,
+}
+
+impl CompileInfo {
+    /// 元の正規表現
+    pub fn expr(&self) -> &str {
+        &self.expr
+    }
+
+    /// パースしたAST
+    pub fn ast(&self) -> &Ast {
+        &self.ast
+    }
+
+    /// コード生成した命令列
+    pub fn code(&self) -> &[Instruction] {
+        &self.code
+    }
+}
+
+impl Display for CompileInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "expr: {}", self.expr)?;
+        writeln!(f, "AST: {:?}", self.ast)?;
+        writeln!(f, "code:")?;
+        for (n, inst) in self.code.iter().enumerate() {
+            writeln!(f, "{:>04}: {}", n, inst)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 正規表現をパースとコード生成し､その結果を返す
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, Instruction};
+/// let info = regexer::describe("a|b").unwrap();
+/// assert!(info.code().iter().any(|i| matches!(i, Instruction::Split(..))));
+/// println!("{info}");
+/// ```
+///
+/// # 引数
+///
+/// exprに正規表現を与える
+///
+/// # 返り値
+///
+/// ASTと命令列をCompileInfoとして返す
+/// 入力された正規表現にエラーがある場合はErrを返す
+pub fn describe(expr: &str) -> Result<CompileInfo, RegexError> {
+    let ast = parser::parse(expr)?;
+    let code = codegen::gen_code(&ast)?;
+
+    Ok(CompileInfo {
+        expr: expr.to_string(),
+        ast,
+        code,
+    })
+}
+
+/// 正規表現をコンパイルし､命令列を返す
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{self, Instruction, MatchMode};
+/// let prog = regexer::compile("ab").unwrap();
+/// assert!(matches!(prog[1], Instruction::Char('a')));
+/// assert!(prog.is_match("abc", MatchMode::Dfs).unwrap());
+/// assert!(!prog.is_match("ba", MatchMode::Dfs).unwrap());
+/// ```
+///
+/// # 引数
+///
+/// exprに正規表現を与える
+///
+/// # 返り値
+///
+/// コンパイルに成功した場合はProgramを返す
+/// 入力された正規表現にエラーがある場合はErr(CompileError)を返す
+pub fn compile(expr: &str) -> Result<Program, CompileError> {
+    let ast = parser::parse(expr)?;
+    let code = codegen::gen_code(&ast)?;
+
+    Ok(Program::new(code))
 }
 
-/// 正規表現パターンを表示
+/// 正規表現のASTと命令列を表示
 ///
 /// # 利用例
 ///
 /// ```
 /// use regexer;
-/// regexer::print("a|b");
+/// regexer::print("a|b").unwrap();
 /// ```
 ///
 /// # 引数
@@ -74,8 +457,10 @@ pub fn do_matching(expr: &str, line: &str, is_depth: bool) -> Result<bool, DynEr
 /// # 返り値
 ///
 /// 標準出力に表示されるため､返り値は無し
-pub fn print(expr: &str) -> Result<(), io::Error> {
-    print!("expr: {expr}");
+/// 入力された正規表現にエラーがある場合はErrを返す
+#[cfg(feature = "std")]
+pub fn print(expr: &str) -> Result<(), RegexError> {
+    print!("{}", describe(expr)?);
 
     Ok(())
 }
@@ -0,0 +1,138 @@
+//! 和集合､積集合､補集合を求められる文字クラス
+use super::{
+    parser::{intersect_ranges, negate_ranges, normalize_ranges, Ast},
+    Pattern,
+};
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+/// 文字の集合を表す文字クラス
+///
+/// 範囲は整列し､重複または隣接する範囲を結合した状態で保持する
+/// Patternに変換すると､含まれるいずれかの1文字にマッチする式となる
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{CharClass, Pattern};
+///
+/// // 英数字のうち､母音以外の文字
+/// let alnum = CharClass::range('a', 'z').union(&CharClass::range('0', '9'));
+/// let vowels = CharClass::from_chars("aeiou".chars());
+/// let class = alnum.intersect(&vowels.negate());
+/// assert!(class.contains('b') && class.contains('7'));
+/// assert!(!class.contains('e') && !class.contains('B'));
+/// assert_eq!(class.to_string(), "[0-9b-df-hj-np-tv-z]");
+///
+/// let re = Pattern::from(class).plus().build().unwrap();
+/// assert_eq!(re.find("aeb2x").unwrap().unwrap().as_str(), "b2x");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CharClass {
+    ranges: Vec<(char, char)>,
+}
+
+impl CharClass {
+    /// どの文字も含まない文字クラス
+    pub fn new() -> CharClass {
+        CharClass::default()
+    }
+
+    /// 全ての文字を含む文字クラス
+    pub fn any() -> CharClass {
+        CharClass::range('\0', char::MAX)
+    }
+
+    /// lo以上hi以下の文字からなる文字クラス
+    ///
+    /// loがhiより大きい場合は空となる
+    pub fn range(lo: char, hi: char) -> CharClass {
+        CharClass::from_ranges(&[(lo, hi)])
+    }
+
+    /// いずれかの範囲に含まれる文字からなる文字クラス
+    ///
+    /// 開始が終了より大きい範囲は無視する
+    pub fn from_ranges(ranges: &[(char, char)]) -> CharClass {
+        let ranges = ranges.iter().copied().filter(|(lo, hi)| lo <= hi).collect();
+        CharClass {
+            ranges: normalize_ranges(ranges),
+        }
+    }
+
+    /// 与えられた文字からなる文字クラス
+    pub fn from_chars<I: IntoIterator<Item = char>>(chars: I) -> CharClass {
+        CharClass {
+            ranges: normalize_ranges(chars.into_iter().map(|c| (c, c)).collect()),
+        }
+    }
+
+    /// selfとotherのいずれかに含まれる文字からなる文字クラス
+    pub fn union(&self, other: &CharClass) -> CharClass {
+        let ranges = self.ranges.iter().chain(&other.ranges).copied().collect();
+        CharClass {
+            ranges: normalize_ranges(ranges),
+        }
+    }
+
+    /// selfとotherの両方に含まれる文字からなる文字クラス
+    pub fn intersect(&self, other: &CharClass) -> CharClass {
+        CharClass {
+            ranges: intersect_ranges(&self.ranges, &other.ranges),
+        }
+    }
+
+    /// selfに含まれ､otherに含まれない文字からなる文字クラス
+    pub fn difference(&self, other: &CharClass) -> CharClass {
+        self.intersect(&other.negate())
+    }
+
+    /// selfに含まれない文字からなる文字クラス
+    pub fn negate(&self) -> CharClass {
+        CharClass {
+            ranges: negate_ranges(&self.ranges),
+        }
+    }
+
+    /// 文字cを含むかを判定
+    pub fn contains(&self, c: char) -> bool {
+        // 範囲は整列しているため､cを終了に含みうる最初の範囲のみを調べればよい
+        let i = self.ranges.partition_point(|(_, hi)| *hi < c);
+        self.ranges.get(i).is_some_and(|(lo, _)| *lo <= c)
+    }
+
+    /// どの文字も含まないかを判定
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// 整列し､結合した文字の範囲
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.ranges
+    }
+}
+
+/// [a-z0-9]のような､文字クラスのパターンとして書き出す
+impl Display for CharClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Ast::from(self.clone()))
+    }
+}
+
+impl From<CharClass> for Ast {
+    fn from(class: CharClass) -> Ast {
+        Ast::Class(class.ranges, false)
+    }
+}
+
+impl From<CharClass> for Pattern {
+    fn from(class: CharClass) -> Pattern {
+        Pattern::class(&class.ranges)
+    }
+}
+
+impl FromIterator<char> for CharClass {
+    fn from_iter<I: IntoIterator<Item = char>>(chars: I) -> CharClass {
+        CharClass::from_chars(chars)
+    }
+}
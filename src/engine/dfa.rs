@@ -0,0 +1,505 @@
+//! 後方参照などを含まない命令列から､部分集合構成法でDFAを構築する
+use super::{evaluator, parser, Instruction};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// 命令列から構築したDFA
+///
+/// 各状態は､同じ位置で同時に実行しうる命令の位置の集合に対応する
+/// 文字は遷移が同じになる種類に分けて扱い､1文字につき1回表を引いて遷移する
+/// マッチの有無のみを判定し､マッチの位置やキャプチャは求めない
+///
+/// 全ての状態を事前に構築すると大きさが上限を超える場合は､遅延DFAとして評価する
+/// 遅延DFAは必要になった状態のみをLazyCacheに構築し､上限を超えると最も長く使われていない状態を捨てる
+#[derive(Debug, Clone)]
+pub(crate) struct Dfa {
+    id: usize,               // LazyCacheを構築したDFAを区別するための番号
+    bounds: Vec<char>,       // 文字の種類の境界｡i番目の種類はbounds[i]以上bounds[i + 1]未満の文字
+    ascii: [u32; 128],       // ASCII文字の種類
+    starts: [Vec<usize>; 2], // 文字列の先頭と､それ以外の位置から開始する場合の命令の位置の集合
+    full: Option<Table>,     // 事前に構築した遷移表｡遅延DFAとして評価する場合はNone
+    capacity: usize,         // 遅延DFAで保持する状態数の上限
+}
+
+/// 事前に構築した全ての状態と遷移表
+#[derive(Debug, Clone)]
+struct Table {
+    table: Vec<u32>,    // 状態 * 種類の数 + 種類の位置に､遷移先の状態を格納する
+    states: Vec<State>, // 状態の番号順に並べた状態
+    start: [u32; 2],    // 文字列の先頭と､それ以外の位置から開始する場合の状態
+}
+
+/// DFAの状態
+#[derive(Debug, Clone)]
+struct State {
+    pcs: Vec<usize>, // 文字を読む命令､match命令､文字列の末尾で確定するアンカーの位置
+    is_match: bool,  // match命令を含むか
+    has_end: bool,   // 文字列の末尾で確定するアンカーを含むか
+}
+
+/// 遅延DFAで構築した状態を保持する作業領域
+///
+/// 状態を捨てた場所は新たな状態で使い回し､世代を進めて捨てた状態への遷移を無効にする
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LazyCache {
+    owner: Option<usize>,           // 状態を構築したDFAの番号
+    states: Vec<LazyState>,         // 構築した状態
+    table: Vec<Option<(u32, u32)>>, // 遷移先の状態と､遷移を記録した時点の世代
+    ids: BTreeMap<Vec<usize>, u32>, // 命令の位置の集合に対応する状態
+    clock: u64,                     // 状態を使った順序を表す時刻
+}
+
+/// 遅延DFAで構築した状態
+#[derive(Debug, Clone)]
+struct LazyState {
+    state: State,
+    generation: u32, // 同じ場所に状態を構築し直した回数
+    used: u64,       // 最後に使った時刻
+}
+
+/// Dfaの番号の払い出しに用いる
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+impl Dfa {
+    /// 命令列からDFAを構築
+    ///
+    /// 遷移表の要素数と状態が持つ命令の位置の数の合計がsizeを超える場合は､
+    /// 遷移表の要素数がsizeに収まる状態数を上限とする遅延DFAとなる
+    /// 後方参照､後読み､条件分岐､再帰､\G､行単位のアンカー､\Xを含む場合や､sizeが0の場合はNoneを返す
+    /// 末尾で確定するアンカーの後に文字を読む命令が続く場合も､アンカーを位置によらずに扱えないためNoneを返す
+    pub(crate) fn new(inst: &[Instruction], size: usize) -> Option<Dfa> {
+        if size == 0 || !inst.iter().all(is_supported) || reads_after_end(inst) {
+            return None;
+        }
+
+        let bounds = alphabet(inst);
+        let mut ascii = [0; 128];
+        for (c, class) in ascii.iter_mut().enumerate() {
+            *class = (bounds.partition_point(|b| *b as usize <= c) - 1) as u32;
+        }
+
+        // どの位置からもマッチを開始できるよう､遷移先には先頭以外から開始する場合の集合を常に加える
+        let starts = [
+            closure(inst, &[0], |i| begin_assert(i, true)),
+            closure(inst, &[0], |i| begin_assert(i, false)),
+        ];
+        let full = Table::new(inst, &bounds, &starts, size);
+        Some(Dfa {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            capacity: (size / bounds.len()).max(2),
+            bounds,
+            ascii,
+            starts,
+            full,
+        })
+    }
+
+    /// lineのstart番目以降のいずれかの位置でマッチするかを判定
+    ///
+    /// instはDFAを構築した命令列で､文字列の末尾で確定するアンカーの判定に用いる
+    /// 遅延DFAでは､cacheに状態を構築しながら評価する
+    /// 状態を捨てては構築し直すことが続いた場合は､探索で判定した方が速いため評価を止めてNoneを返す
+    pub(crate) fn is_match(
+        &self,
+        inst: &[Instruction],
+        line: &[char],
+        start: usize,
+        cache: &mut LazyCache,
+    ) -> Option<bool> {
+        self.find_end(inst, line, start, cache)
+            .map(|end| end.is_some())
+    }
+
+    /// lineのstart番目以降から始まるマッチのうち､最も前で終わるものの終了位置を求める
+    ///
+    /// マッチしない場合はSome(None)を返し､評価を止めた場合はis_matchと同じくNoneを返す
+    pub(crate) fn find_end(
+        &self,
+        inst: &[Instruction],
+        line: &[char],
+        start: usize,
+        cache: &mut LazyCache,
+    ) -> Option<Option<usize>> {
+        let Some(full) = &self.full else {
+            return self.find_end_lazy(inst, line, start, cache);
+        };
+
+        let classes = self.bounds.len();
+        let mut id = full.start[usize::from(start != 0)] as usize;
+        for sp in start..=line.len() {
+            if let Some(is_match) = full.states[id].accepts(inst, line, sp) {
+                return Some(is_match.then_some(sp));
+            }
+            let Some(c) = line.get(sp) else {
+                break;
+            };
+            id = full.table[id * classes + self.class_of(*c)] as usize;
+        }
+
+        Some(None)
+    }
+
+    /// 必要になった状態のみをcacheに構築しながら､最も前で終わるマッチの終了位置を求める
+    fn find_end_lazy(
+        &self,
+        inst: &[Instruction],
+        line: &[char],
+        start: usize,
+        cache: &mut LazyCache,
+    ) -> Option<Option<usize>> {
+        cache.prepare(self.id);
+
+        let classes = self.bounds.len();
+        let mut evicted = 0; // この評価中に捨てた状態の数
+        let pcs = self.starts[usize::from(start != 0)].clone();
+        let mut id = cache.insert(inst, pcs, classes, self.capacity, &mut evicted);
+        for sp in start..=line.len() {
+            cache.touch(id);
+            let state = &cache.states[id].state;
+            if let Some(is_match) = state.accepts(inst, line, sp) {
+                return Some(is_match.then_some(sp));
+            }
+            let Some(c) = line.get(sp) else {
+                break;
+            };
+
+            let class = self.class_of(*c);
+            id = match cache.next(id * classes + class) {
+                Some(next) => next,
+                None => {
+                    let mut set = step(inst, &state.pcs, self.bounds[class]);
+                    set.extend(&self.starts[1]);
+                    set.sort_unstable();
+                    set.dedup();
+                    let next = cache.insert(inst, set, classes, self.capacity, &mut evicted);
+
+                    // 全ての状態が入れ替わるほど捨てたにもかかわらず､
+                    // 状態1つあたりに読み進めた文字数が少ない場合は構築の時間が勝るため諦める
+                    if evicted > self.capacity && sp - start < evicted * 8 {
+                        return None;
+                    }
+                    cache.link(id * classes + class, next);
+                    next
+                }
+            };
+        }
+
+        Some(None)
+    }
+
+    /// 文字cの種類
+    fn class_of(&self, c: char) -> usize {
+        match self.ascii.get(c as usize) {
+            Some(class) => *class as usize,
+            None => self.bounds.partition_point(|b| *b <= c) - 1,
+        }
+    }
+}
+
+impl Table {
+    /// 全ての状態と遷移表を構築
+    ///
+    /// 遷移表の要素数と状態が持つ命令の位置の数の合計がsizeを超える場合はNoneを返す
+    fn new(
+        inst: &[Instruction],
+        bounds: &[char],
+        starts: &[Vec<usize>; 2],
+        size: usize,
+    ) -> Option<Table> {
+        let mut full = Table {
+            table: Vec::new(),
+            states: Vec::new(),
+            start: [0; 2],
+        };
+        let mut ids = BTreeMap::new();
+        full.start = [
+            full.intern(inst, &mut ids, starts[0].clone()),
+            full.intern(inst, &mut ids, starts[1].clone()),
+        ];
+
+        // 状態を追加した順に､全ての文字の種類に対する遷移先を求める
+        let classes = bounds.len();
+        let mut id = 0;
+        let mut pcs = 0; // これまでに遷移先を求めた状態が持つ命令の位置の数
+        while id < full.states.len() {
+            pcs += full.states[id].pcs.len();
+            if full.states.len().checked_mul(classes)? + pcs > size {
+                return None;
+            }
+            for c in bounds {
+                let mut set = step(inst, &full.states[id].pcs, *c);
+                set.extend(&starts[1]);
+                set.sort_unstable();
+                set.dedup();
+                let next = full.intern(inst, &mut ids, set);
+                full.table.push(next);
+            }
+            id += 1;
+        }
+
+        Some(full)
+    }
+
+    /// 命令の位置の集合に対応する状態の番号を返す
+    ///
+    /// 初めて現れた集合の場合は､新たな状態として追加する
+    fn intern(
+        &mut self,
+        inst: &[Instruction],
+        ids: &mut BTreeMap<Vec<usize>, u32>,
+        pcs: Vec<usize>,
+    ) -> u32 {
+        if let Some(id) = ids.get(&pcs) {
+            return *id;
+        }
+        let id = self.states.len() as u32;
+        self.states.push(State::new(inst, pcs.clone()));
+        ids.insert(pcs, id);
+        id
+    }
+}
+
+impl LazyCache {
+    /// ownerのDFAの状態を保持するよう準備する
+    ///
+    /// 別のDFAで構築した状態を保持している場合は､全て捨てる
+    fn prepare(&mut self, owner: usize) {
+        if self.owner != Some(owner) {
+            *self = LazyCache {
+                owner: Some(owner),
+                ..Default::default()
+            };
+        }
+    }
+
+    /// 命令の位置の集合に対応する状態の場所を返す
+    ///
+    /// 初めて現れた集合の場合は新たな状態を構築し､状態数がcapacityに達している場合は
+    /// 最も長く使われていない状態を捨てて､その場所を使い回す
+    fn insert(
+        &mut self,
+        inst: &[Instruction],
+        pcs: Vec<usize>,
+        classes: usize,
+        capacity: usize,
+        evicted: &mut usize,
+    ) -> usize {
+        if let Some(id) = self.ids.get(&pcs) {
+            return *id as usize;
+        }
+
+        let state = State::new(inst, pcs.clone());
+        let id = if self.states.len() < capacity {
+            self.states.push(LazyState {
+                state,
+                generation: 0,
+                used: 0,
+            });
+            self.table.resize(self.states.len() * classes, None);
+            self.states.len() - 1
+        } else {
+            // 評価中の状態は直前に使ったため､捨てる対象とはならない
+            let (id, victim) = self
+                .states
+                .iter_mut()
+                .enumerate()
+                .min_by_key(|(_, s)| s.used)
+                .expect("capacity is at least 2");
+            self.ids.remove(&victim.state.pcs);
+            victim.state = state;
+            victim.generation = victim.generation.wrapping_add(1);
+            victim.used = 0;
+            self.table[id * classes..(id + 1) * classes].fill(None);
+            *evicted += 1;
+            id
+        };
+        self.ids.insert(pcs, id as u32);
+        id
+    }
+
+    /// 状態を使った時刻を記録する
+    fn touch(&mut self, id: usize) {
+        self.clock += 1;
+        self.states[id].used = self.clock;
+    }
+
+    /// 遷移表のi番目に記録した遷移先を返す
+    ///
+    /// 遷移先が未構築か､記録した後に捨てられた場合はNoneを返す
+    fn next(&self, i: usize) -> Option<usize> {
+        let (id, generation) = self.table[i]?;
+        (self.states[id as usize].generation == generation).then_some(id as usize)
+    }
+
+    /// 遷移表のi番目に遷移先を記録する
+    fn link(&mut self, i: usize, id: usize) {
+        self.table[i] = Some((id as u32, self.states[id].generation));
+    }
+}
+
+impl State {
+    fn new(inst: &[Instruction], pcs: Vec<usize>) -> State {
+        let is_match = pcs.iter().any(|pc| matches!(inst[*pc], Instruction::Match));
+        let has_end = pcs.iter().any(|pc| {
+            matches!(
+                inst[*pc],
+                Instruction::MatchEnd(_) | Instruction::MatchTextEnd
+            )
+        });
+        State {
+            pcs,
+            is_match,
+            has_end,
+        }
+    }
+
+    /// 位置spでマッチが確定する場合はSome(true)を､マッチしないことが確定する場合はSome(false)を返す
+    ///
+    /// 文字を読み進める必要がある場合はNoneを返す
+    fn accepts(&self, inst: &[Instruction], line: &[char], sp: usize) -> Option<bool> {
+        if self.is_match {
+            return Some(true);
+        }
+        if self.pcs.is_empty() {
+            return Some(false);
+        }
+        // 末尾のアンカーは､末尾か末尾の改行の直前でのみ成り立つ
+        if self.has_end && line.len() - sp <= 2 && accepts(inst, &self.pcs, line, sp) {
+            return Some(true);
+        }
+        None
+    }
+}
+
+/// DFAで扱える命令かを判定
+fn is_supported(inst: &Instruction) -> bool {
+    matches!(
+        inst,
+        Instruction::Char(_)
+            | Instruction::Class(..)
+            | Instruction::AnyChar
+            | Instruction::AnyCharExceptNewline
+            | Instruction::Match
+            | Instruction::Jump(_)
+            | Instruction::Split(..)
+            | Instruction::Save(_)
+            | Instruction::Return
+            | Instruction::EmptyCheckStart(_)
+            | Instruction::EmptyCheckEnd(..)
+            | Instruction::MatchBegin
+            | Instruction::MatchEnd(_)
+            | Instruction::MatchTextEnd
+    )
+}
+
+/// 末尾で確定するアンカーから､文字を読む命令に到達できるかを判定
+///
+/// 状態はアンカーを末尾でのみ判定するため､\Zや$の直後で改行を読むような遷移を表せない
+fn reads_after_end(inst: &[Instruction]) -> bool {
+    inst.iter().enumerate().any(|(pc, i)| {
+        matches!(i, Instruction::MatchEnd(_) | Instruction::MatchTextEnd)
+            && closure(inst, &[pc + 1], |_| Some(true))
+                .iter()
+                .any(|pc| !matches!(inst[*pc], Instruction::Match))
+    })
+}
+
+/// 命令列が区別する文字の種類の境界を､昇順に並べて返す
+///
+/// 隣り合う境界の間の文字は､どの文字を読む命令でも同じ結果となる
+pub(super) fn alphabet(inst: &[Instruction]) -> Vec<char> {
+    let mut bounds = vec!['\0'];
+    let mut add = |lo: char, hi: char| {
+        bounds.push(lo);
+        bounds.extend(parser::char_after(hi));
+    };
+    for i in inst {
+        match i {
+            Instruction::Char(c) => add(*c, *c),
+            Instruction::Class(ranges, _) => ranges.iter().for_each(|(lo, hi)| add(*lo, *hi)),
+            Instruction::AnyCharExceptNewline => add('\n', '\n'),
+            _ => (),
+        }
+    }
+    bounds.sort_unstable();
+    bounds.dedup();
+    bounds
+}
+
+/// 文字列の先頭で成り立つアンカーのみを確定させる
+///
+/// 末尾で確定するアンカーはNoneとして未確定のまま残す
+fn begin_assert(inst: &Instruction, at_begin: bool) -> Option<bool> {
+    match inst {
+        Instruction::MatchBegin => Some(at_begin),
+        _ => None,
+    }
+}
+
+/// pcsから文字を読まずに到達できる､文字を読む命令とmatch命令､未確定のアンカーの位置を昇順に返す
+///
+/// アンカーはassertで判定し､Some(false)の場合はその先を辿らず､Noneの場合は集合に残す
+fn closure<F>(inst: &[Instruction], pcs: &[usize], assert: F) -> Vec<usize>
+where
+    F: Fn(&Instruction) -> Option<bool>,
+{
+    // 集合は命令列全体に比べて小さいことが多いため､辿った命令のみを記録する
+    let mut seen = BTreeSet::new();
+    let mut stack = pcs.to_vec();
+    let mut set = Vec::new();
+    while let Some(pc) = stack.pop() {
+        if !seen.insert(pc) {
+            continue;
+        }
+
+        match &inst[pc] {
+            Instruction::Jump(addr) => stack.push(*addr),
+            Instruction::Split(addr1, addr2) => stack.extend([*addr2, *addr1]),
+            // 再帰を含まないため､returnは常に次の命令へ進む
+            Instruction::Save(_) | Instruction::Return | Instruction::EmptyCheckStart(_) => {
+                stack.push(pc + 1)
+            }
+            // 空の繰り返しでループを抜けても､再び繰り返してから抜けても到達できる命令は変わらない
+            Instruction::EmptyCheckEnd(_, addr) => stack.extend([*addr, pc + 1]),
+            Instruction::MatchBegin | Instruction::MatchEnd(_) | Instruction::MatchTextEnd => {
+                match assert(&inst[pc]) {
+                    Some(true) => stack.push(pc + 1),
+                    Some(false) => (),
+                    None => set.push(pc),
+                }
+            }
+            _ => set.push(pc),
+        }
+    }
+    set.sort_unstable();
+    set
+}
+
+/// pcsの各命令で文字cを読み､読めた命令の次から到達できる命令の位置を返す
+fn step(inst: &[Instruction], pcs: &[usize], c: char) -> Vec<usize> {
+    let next = pcs
+        .iter()
+        .filter(|pc| match &inst[**pc] {
+            Instruction::Char(x) => *x == c,
+            Instruction::Class(ranges, negated) => {
+                ranges.iter().any(|(lo, hi)| *lo <= c && c <= *hi) != *negated
+            }
+            Instruction::AnyChar => true,
+            Instruction::AnyCharExceptNewline => c != '\n',
+            _ => false,
+        })
+        .map(|pc| pc + 1)
+        .collect::<Vec<_>>();
+    closure(inst, &next, |i| begin_assert(i, false))
+}
+
+/// 位置spで未確定のアンカーを判定し､match命令に到達できるかを判定
+fn accepts(inst: &[Instruction], pcs: &[usize], line: &[char], sp: usize) -> bool {
+    closure(inst, pcs, |i| Some(evaluator::is_anchor_match(i, line, sp)))
+        .iter()
+        .any(|pc| matches!(inst[*pc], Instruction::Match))
+}
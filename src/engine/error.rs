@@ -0,0 +1,101 @@
+//! 正規表現のコンパイルとマッチングで起きるエラー
+use super::{codegen::CodeGenError, evaluator::EvalError, parser::ParseError, CompileError};
+use core::{
+    error::Error,
+    fmt::{self, Display},
+    str::Utf8Error,
+};
+
+/// 正規表現の処理中に起きるエラーを表す型
+///
+/// エラーの種類ごとに分かれているため､matchで原因を判別できる
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{ParseError, Regex, RegexError};
+/// match Regex::new("a(b") {
+///     Err(RegexError::Parse(ParseError::NoRightParen(_))) => {}
+///     _ => panic!(),
+/// }
+/// ```
+#[derive(Debug)]
+pub enum RegexError {
+    Parse(ParseError),     // パース時のエラー
+    CodeGen(CodeGenError), // コード生成時のエラー
+    Eval(EvalError),       // 評価時のエラー
+    InvalidStart(usize),   // マッチの開始位置が文字の境界でない
+    Utf8(Utf8Error),       // 入力がUTF-8として不正
+    #[cfg(feature = "std")]
+    Io(std::io::Error), // 入力の読み込み時のエラー
+}
+
+impl Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegexError::Parse(e) => write!(f, "{e}"),
+            RegexError::CodeGen(e) => write!(f, "{e}"),
+            RegexError::Eval(e) => write!(f, "{e}"),
+            RegexError::InvalidStart(pos) => {
+                write!(f, "RegexError: start is not a char boundary: pos = {pos}")
+            }
+            RegexError::Utf8(e) => write!(f, "RegexError: invalid UTF-8: {e}"),
+            #[cfg(feature = "std")]
+            RegexError::Io(e) => write!(f, "RegexError: I/O error: {e}"),
+        }
+    }
+}
+
+impl Error for RegexError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RegexError::Parse(e) => Some(e),
+            RegexError::CodeGen(e) => Some(e),
+            RegexError::Eval(e) => Some(e),
+            RegexError::InvalidStart(_) => None,
+            RegexError::Utf8(e) => Some(e),
+            #[cfg(feature = "std")]
+            RegexError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<ParseError> for RegexError {
+    fn from(e: ParseError) -> Self {
+        RegexError::Parse(e)
+    }
+}
+
+impl From<CodeGenError> for RegexError {
+    fn from(e: CodeGenError) -> Self {
+        RegexError::CodeGen(e)
+    }
+}
+
+impl From<EvalError> for RegexError {
+    fn from(e: EvalError) -> Self {
+        RegexError::Eval(e)
+    }
+}
+
+impl From<CompileError> for RegexError {
+    fn from(e: CompileError) -> Self {
+        match e {
+            CompileError::Parse(e) => RegexError::Parse(e),
+            CompileError::CodeGen(e) => RegexError::CodeGen(e),
+        }
+    }
+}
+
+impl From<Utf8Error> for RegexError {
+    fn from(e: Utf8Error) -> Self {
+        RegexError::Utf8(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for RegexError {
+    fn from(e: std::io::Error) -> Self {
+        RegexError::Io(e)
+    }
+}
@@ -1,20 +1,28 @@
 //! 命令列と入力文字列を受け取り､マッチングを行う
-use super::Instruction;
+use super::{dfa::LazyCache, grapheme, onepass::OnePass, Instruction, Limits, LineTerminator};
 use crate::helper::safe_add;
-use std::{
+use alloc::{rc::Rc, vec, vec::Vec};
+use core::{
+    cell::Cell,
     // collections::VecDeque,
     error::Error,
     fmt::{self, Display},
+    mem::{replace, take},
     // slice::SliceIndex,
 };
 
+/// 評価時のエラーを表す型
 #[derive(Debug)]
 pub enum EvalError {
     PCOverFlow,
     SPOverFlow,
-    POSOvreFlow,
     InvalidPC,
-    // InvalidContext,
+    RecursionLimit,
+    BudgetExceeded,       // 実行した命令数がlimits.stepsを超えた
+    UnsupportedBackref,   // 幅優先探索では後方参照を扱えない
+    UnsupportedConstruct, // 幅優先探索では条件分岐と再帰を扱えない
+    UnsupportedFuzzy,     // 幅優先探索では近似マッチングを扱えない
+                          // InvalidContext,
 }
 
 impl Display for EvalError {
@@ -25,106 +33,1107 @@ impl Display for EvalError {
 
 impl Error for EvalError {}
 
-/// 深さ優先探索で再帰的にマッチングを行う関数
+/// 文字を読む命令かを判定
+fn is_read(inst: &Instruction) -> bool {
+    matches!(
+        inst,
+        Instruction::Char(_)
+            | Instruction::Class(..)
+            | Instruction::AnyChar
+            | Instruction::AnyCharExceptNewline
+            | Instruction::Grapheme
+    )
+}
+
+/// marksに記録した繰り返しの開始位置のいずれかが位置spで､その繰り返しがまだ文字を読んでいないかを判定
+///
+/// 経路上の位置は減らないため､開始位置がspと等しい繰り返しは開始してから文字を読んでいない
+fn is_empty_iteration(marks: &[Option<usize>], sp: usize) -> bool {
+    marks.contains(&Some(sp))
+}
+
+/// 文字cが文字クラスの範囲のいずれかに含まれるかを判定
+fn class_contains(ranges: &[(char, char)], c: char) -> bool {
+    ranges.iter().any(|(lo, hi)| *lo <= c && c <= *hi)
+}
+
+/// マッチングに用いる探索の方式
+///
+/// 今後の探索の方式の追加に備え､網羅的なmatchはできない
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum MatchMode {
+    /// 深さ優先探索｡後方参照､条件分岐､再帰を扱える
+    #[default]
+    Dfs,
+    /// 幅優先探索｡後方参照､条件分岐､再帰を含むパターンや近似マッチングはErrとなる
+    ///
+    /// 1回の試行は命令数と文字数の積に比例する時間で終わり､深さ優先探索と同じキャプチャを求める
+    Bfs,
+}
+
+impl MatchMode {
+    /// 以前のis_depthの引数との互換性のため､trueをDfsに､falseをBfsに変換する
+    #[deprecated(note = "use `MatchMode::Dfs` or `MatchMode::Bfs` instead")]
+    pub const fn from_depth_first(is_depth: bool) -> Self {
+        if is_depth {
+            MatchMode::Dfs
+        } else {
+            MatchMode::Bfs
+        }
+    }
+}
+
+/// マッチングの実行時の統計
+///
+/// パターンが遅い原因を調べるために､評価器が実行した処理の量を数える
+/// 複数の位置からマッチを試みた場合は､全ての試行の合計となる
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Stats {
+    /// 実行した命令数
+    pub steps: usize,
+    /// 深さ優先探索で､失敗した分岐から戻って別の分岐を試した回数
+    pub backtracks: usize,
+    /// 幅優先探索で､同時に保持したスレッドの数の最大値
+    pub max_threads: usize,
+}
+
+/// 評価のたびに確保し直さずに使い回す作業領域
+///
+/// 評価が終わると､slotsにはマッチした場合のキャプチャの位置が残る
+/// statsには､評価のたびに実行時の統計が加算される
+#[derive(Debug, Clone, Default)]
+pub struct Scratch {
+    slots: Vec<Option<usize>>,     // save命令で保存したキャプチャの位置
+    pool: Vec<Vec<Option<usize>>>, // 分岐時にキャプチャの位置を退避するための領域
+    visited: Visited,              // 深さ優先探索で訪れた命令と位置の組
+    start: usize, // 最後にマッチした評価が開始した位置｡\Kで移したslots[0]とは異なりうる
+    stats: Stats, // これまでの評価の統計の合計
+    lazy: LazyCache, // 探索の前にマッチの有無を判定する遅延DFAで構築した状態
+}
+
+impl Scratch {
+    /// 最後にマッチした際のキャプチャの位置
+    pub fn slots(&self) -> &[Option<usize>] {
+        &self.slots
+    }
+
+    /// これまでの評価の統計の合計
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// 最後にマッチした評価が開始した位置
+    ///
+    /// \Kによってslots()の開始位置が後ろに移っても､実際にマッチを始めた位置を返す
+    pub(crate) fn start(&self) -> usize {
+        self.start
+    }
+
+    /// 遅延DFAで構築した状態を保持する作業領域
+    pub(crate) fn lazy(&mut self) -> &mut LazyCache {
+        &mut self.lazy
+    }
+}
+
+/// 深さ優先探索で管理する状態
+#[derive(Debug, Default)]
+struct State {
+    start: usize,                            // 探索を開始した位置｡\Gで利用する
+    slots: Vec<Option<usize>>, // save命令で保存したキャプチャの位置と､繰り返しの開始位置
+    marks: usize,              // slotsのうち､繰り返しの開始位置を保存する先頭の位置
+    calls: Vec<(usize, Vec<Option<usize>>)>, // 再帰呼び出しの戻り先と､呼び出し時のキャプチャ
+    shortest: Option<Rc<Cell<usize>>>, // 最短のマッチを探す場合の､これまでで最も前の終了位置
+    pool: Vec<Vec<Option<usize>>>, // 退避に使い回すslotsの領域
+    steps: usize,              // これまでに実行した命令数
+    backtracks: usize,         // 失敗した分岐から戻って別の分岐を試した回数
+    max_threads: usize,        // 幅優先探索で同時に保持したスレッドの数の最大値
+    visited: Option<Visited>,  // 訪れた命令と位置の組｡記録しない場合はNone
+}
+
+/// 深さ優先探索で訪れた命令と位置の組の集合
+///
+/// 組ごとに1ビットを割り当て､評価の後は立てたビットのみを戻すため､
+/// 評価のたびに全体を初期化せずに使い回せる
+#[derive(Debug, Clone, Default)]
+struct Visited {
+    bits: Vec<u64>,     // pc * width + spのビットが立っている場合は訪問済み
+    marked: Vec<usize>, // ビットを立てたbitsの添字
+    width: usize,       // 位置の数｡入力の文字数 + 1
+}
+
+impl Visited {
+    /// 命令数lenと入力の文字数charsに合わせて､空の集合とする
+    fn reset(&mut self, len: usize, chars: usize) {
+        self.width = chars + 1;
+        let words = (len * self.width).div_ceil(64);
+        if self.bits.len() < words {
+            self.bits.resize(words, 0);
+        }
+    }
+
+    /// (pc, sp)を訪問済みとし､初めて訪れた場合はtrueを返す
+    fn insert(&mut self, pc: usize, sp: usize) -> bool {
+        let i = pc * self.width + sp;
+        let (word, bit) = (i / 64, 1 << (i % 64));
+        if self.bits[word] & bit != 0 {
+            return false;
+        }
+        if self.bits[word] == 0 {
+            self.marked.push(word);
+        }
+        self.bits[word] |= bit;
+        true
+    }
+
+    /// 立てたビットを戻す
+    fn clear(&mut self) {
+        for word in self.marked.drain(..) {
+            self.bits[word] = 0;
+        }
+    }
+}
+
+/// 分岐の前に退避した状態
+struct Snapshot {
+    slots: Vec<Option<usize>>,
+    calls: Vec<(usize, Vec<Option<usize>>)>,
+}
+
+impl State {
+    /// 分岐先で失敗した場合に戻せるよう､キャプチャの位置と再帰呼び出しの状態を退避
+    ///
+    /// slotsの退避にはpoolの領域を使い回すため､通常は新たな確保を行わない
+    fn save(&mut self) -> Snapshot {
+        let mut slots = self.pool.pop().unwrap_or_default();
+        slots.clone_from(&self.slots);
+        Snapshot {
+            slots,
+            calls: self.calls.clone(),
+        }
+    }
+
+    /// 退避した状態に戻す
+    fn restore(&mut self, saved: Snapshot) {
+        let slots = replace(&mut self.slots, saved.slots);
+        self.pool.push(slots);
+        self.calls = saved.calls;
+    }
+
+    /// 退避した状態を捨て､現在の状態を保つ
+    fn discard(&mut self, saved: Snapshot) {
+        self.pool.push(saved.slots);
+    }
+}
+
+/// 深さ優先探索で戻る先の分岐
+struct Branch {
+    pc: usize,
+    sp: usize,
+    budget: usize,
+    saved: Snapshot, // 分岐した時点の状態
+}
+
+/// 深さ優先探索でマッチングを行う関数
+///
+/// 分岐では優先する側を先に試し､もう一方を戻る先としてスタックに積む
+/// 失敗した場合はスタックから最後に積んだ分岐を取り出し､状態を戻して続きを試す
+/// 関数の再帰呼び出しではなくスタックを用いるため､入力が長くても呼び出しの深さは増えない
+///
+/// budgetは残りの編集回数で､0の場合は厳密なマッチングとなる
+/// stateにはsave命令で保存したキャプチャの位置と､再帰呼び出しの戻り先が格納される
+/// 実行した命令数がlimits.stepsを超えた場合はErrを返す
 fn eval_depth(
     inst: &[Instruction],
     line: &[char],
     mut pc: usize,
     mut sp: usize,
+    mut budget: usize,
+    state: &mut State,
+    limits: &Limits,
 ) -> Result<bool, EvalError> {
-    let mut pos: usize = 0;
-    let mut init_position_state = false;
+    let mut branches = Vec::new();
+
     loop {
+        if eval_thread(inst, line, pc, sp, budget, state, limits, &mut branches)? {
+            // 戻る先の分岐は不要となるため､退避した状態を捨てる
+            for branch in branches.drain(..) {
+                state.discard(branch.saved);
+            }
+            return Ok(true);
+        }
+
+        let Some(branch) = branches.pop() else {
+            return Ok(false);
+        };
+        state.restore(branch.saved);
+        state.backtracks += 1;
+        (pc, sp, budget) = (branch.pc, branch.sp, branch.budget);
+    }
+}
+
+/// pcの命令から､失敗するかマッチするまで命令を順に実行する関数
+///
+/// 分岐の一方を選んだ場合は､もう一方をbranchesに積む
+/// match命令に到達した場合はOk(true)を､失敗した場合はOk(false)を返す
+#[allow(clippy::too_many_arguments)]
+fn eval_thread(
+    inst: &[Instruction],
+    line: &[char],
+    mut pc: usize,
+    mut sp: usize,
+    mut budget: usize,
+    state: &mut State,
+    limits: &Limits,
+    branches: &mut Vec<Branch>,
+) -> Result<bool, EvalError> {
+    loop {
+        // 最短のマッチを探す場合は､見つかった終了位置より先に進む経路を打ち切る
+        if state.shortest.as_ref().is_some_and(|end| sp >= end.get()) {
+            return Ok(false);
+        }
+
+        state.steps += 1;
+        if state.steps > limits.steps {
+            return Err(EvalError::BudgetExceeded);
+        }
+
         let next = if let Some(i) = inst.get(pc) {
             i
         } else {
             return Err(EvalError::InvalidPC);
         };
 
-        match next {
-            Instruction::Char(c) => {
-                if let Some(sp_c) = line.get(sp) {
-                    if *c == '\n' {
-                        init_position_state = true;
-                    }
-                    if *c == '.' {
-                        safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
-                        safe_add(&mut sp, &1, || EvalError::SPOverFlow)?;
-                    } else if c == sp_c {
-                        safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
-                        safe_add(&mut sp, &1, || EvalError::SPOverFlow)?;
-                    } else {
-                        return Ok(false);
-                    }
-                } else {
-                    return Ok(false);
-                }
+        // 一度訪れた組からの経路は､既に失敗したか現在の経路と同じ繰り返しのため打ち切る
+        // ただし文字を読まずに繰り返しを始めた位置では､繰り返しを抜けるかが経路によって変わるため､
+        // 文字を読む命令を除いて記録しない
+        let memoize = is_read(next) || !is_empty_iteration(&state.slots[state.marks..], sp);
+        if memoize && state.visited.as_mut().is_some_and(|v| !v.insert(pc, sp)) {
+            return Ok(false);
+        }
 
-                if init_position_state {
-                    pos = 0;
-                    init_position_state = false;
-                } else {
-                    safe_add(&mut pos, &1, || EvalError::POSOvreFlow)?;
-                }
+        // 文字を読む命令の場合は､読めた文字数
+        let len = match next {
+            Instruction::Char(c) => line.get(sp).filter(|sp_c| c == *sp_c).map(|_| 1),
+            Instruction::Class(ranges, negated) => line
+                .get(sp)
+                .filter(|sp_c| class_contains(ranges, **sp_c) != *negated)
+                .map(|_| 1),
+            Instruction::AnyChar | Instruction::AnyCharExceptNewline => {
+                let is_newline_ok = matches!(next, Instruction::AnyChar);
+                line.get(sp)
+                    .filter(|c| **c != '\n' || is_newline_ok)
+                    .map(|_| 1)
             }
+            Instruction::Grapheme => grapheme::cluster_len(line, sp),
             Instruction::Match => {
+                // 最短のマッチを探す場合は､終了位置を記録して他の経路も探す
+                if let Some(end) = &state.shortest {
+                    end.set(sp);
+                    return Ok(false);
+                }
                 return Ok(true);
             }
             Instruction::Jump(addr) => {
                 pc = *addr;
+                continue;
             }
             Instruction::Split(addr1, addr2) => {
-                if eval_depth(inst, line, *addr1, sp)? || eval_depth(inst, line, *addr2, sp)? {
-                    return Ok(true);
+                // 失敗した場合にキャプチャの位置を戻せるよう､状態を退避してから試す
+                branches.push(Branch {
+                    pc: *addr2,
+                    sp,
+                    budget,
+                    saved: state.save(),
+                });
+                pc = *addr1;
+                continue;
+            }
+            Instruction::Save(n) => {
+                if let Some(slot) = state.slots.get_mut(*n) {
+                    *slot = Some(sp);
+                }
+                safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                continue;
+            }
+            Instruction::EmptyCheckStart(n) => {
+                if let Some(slot) = state.slots.get_mut(state.marks + n) {
+                    *slot = Some(sp);
+                }
+                safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                continue;
+            }
+            Instruction::EmptyCheckEnd(n, addr) => {
+                // 文字を読まなかった繰り返しは､再び繰り返さずにループを抜ける
+                if state.slots.get(state.marks + n) == Some(&Some(sp)) {
+                    pc = *addr;
+                } else {
+                    safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                }
+                continue;
+            }
+            Instruction::Backref(n) | Instruction::BackrefIgnoreCase(n) => {
+                // 参照先のグループが未確定の場合はマッチしない
+                let (start, end) = match (state.slots.get(n * 2), state.slots.get(n * 2 + 1)) {
+                    (Some(Some(start)), Some(Some(end))) => (*start, *end),
+                    _ => return Ok(false),
+                };
+
+                let ignore_case = matches!(next, Instruction::BackrefIgnoreCase(_));
+                let len = end - start;
+                let is_match = line.get(sp..sp + len).is_some_and(|rest| {
+                    rest.iter().zip(&line[start..end]).all(|(a, b)| {
+                        a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+                    })
+                });
+                if is_match {
+                    safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                    safe_add(&mut sp, &len, || EvalError::SPOverFlow)?;
+                    continue;
                 } else {
                     return Ok(false);
                 }
             }
-            Instruction::MatchBegin => {
-                if pos == 0 {
+            Instruction::LookBehind(len, addr) | Instruction::NegativeLookBehind(len, addr) => {
+                let negated = matches!(next, Instruction::NegativeLookBehind(..));
+
+                // len文字前から､後読みの式を厳密に評価する
+                // 後読みの式の終了位置はマッチの終了位置ではないため､最短のマッチの探索は止める
+                // 後読みの式の入れ子の深さはパターンで決まるため､ここでは再帰呼び出しで評価する
+                // 後読みの式は同じ位置で再び評価しうるため､訪れた組は記録しない
+                let saved = state.save();
+                let shortest = state.shortest.take();
+                let visited = state.visited.take();
+                let is_match = if let Some(start) = sp.checked_sub(*len) {
+                    let mut sub_pc = pc;
+                    safe_add(&mut sub_pc, &1, || EvalError::PCOverFlow)?;
+                    eval_depth(inst, line, sub_pc, start, 0, state, limits)
+                } else {
+                    Ok(false)
+                };
+                state.shortest = shortest;
+                state.visited = visited;
+
+                if is_match.as_ref().is_ok_and(|m| *m && !negated) {
+                    // 肯定の後読み中のキャプチャは保持する
+                    state.discard(saved);
+                } else {
+                    state.restore(saved);
+                }
+                if is_match? == negated {
+                    return Ok(false);
+                }
+                pc = *addr;
+                continue;
+            }
+            Instruction::Cond(n, addr) => {
+                // n番目のグループがマッチしている場合はyesの式を､それ以外はnoの式を評価する
+                let is_set = matches!(
+                    (state.slots.get(n * 2), state.slots.get(n * 2 + 1)),
+                    (Some(Some(_)), Some(Some(_)))
+                );
+                if is_set {
+                    safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                } else {
+                    pc = *addr;
+                }
+                continue;
+            }
+            Instruction::Recurse(addr) => {
+                // 戻り先と呼び出し時のキャプチャを保存し､パターン全体を評価する
+                if state.calls.len() >= limits.recursion {
+                    return Err(EvalError::RecursionLimit);
+                }
+                let mut ret = pc;
+                safe_add(&mut ret, &1, || EvalError::PCOverFlow)?;
+                state.calls.push((ret, state.slots.clone()));
+                pc = *addr;
+                continue;
+            }
+            Instruction::Return => {
+                // 再帰呼び出し中の場合は､キャプチャを呼び出し時の状態に戻して戻り先へ
+                if let Some((ret, slots)) = state.calls.pop() {
+                    state.slots = slots;
+                    pc = ret;
+                } else {
                     safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                }
+                continue;
+            }
+            Instruction::MatchStart => {
+                if sp == state.start {
+                    safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                    continue;
                 } else {
                     return Ok(false);
                 }
             }
-            Instruction::MatchEnd => {
-                if let Some(c) = line.get(sp) {
-                    if *c == '\n' {
-                        safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
-                    } else {
-                        return Ok(false);
-                    }
-                } else if pos == line.len() {
+            Instruction::MatchBegin
+            | Instruction::MatchEnd(_)
+            | Instruction::MatchLineBegin(_)
+            | Instruction::MatchLineEnd(_)
+            | Instruction::MatchTextEnd => {
+                if is_anchor_match(next, line, sp) {
                     safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                    continue;
                 } else {
                     return Ok(false);
                 }
             }
+        };
+
+        match len {
+            Some(len) => {
+                safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                safe_add(&mut sp, &len, || EvalError::SPOverFlow)?;
+            }
+            None => match eval_edit(line, pc, sp, budget, state, branches)? {
+                Some(resume) => (pc, sp, budget) = resume,
+                None => return Ok(false),
+            },
         }
     }
 }
 
+/// 入力文字列のsp番目の位置で､アンカーの条件が成り立つかを判定
+///
+/// - match begin : 文字列の先頭
+/// - match end : 文字列の末尾､または末尾の改行の直前
+/// - match line begin : 文字列の先頭､または改行の直後
+/// - match line end : 文字列の末尾､または改行の直前
+/// - match text end : 文字列の末尾
+pub(super) fn is_anchor_match(inst: &Instruction, line: &[char], sp: usize) -> bool {
+    match inst {
+        Instruction::MatchBegin => sp == 0,
+        Instruction::MatchEnd(term) => {
+            sp == line.len()
+                || terminator_len(*term, line, sp).is_some_and(|n| sp + n == line.len())
+        }
+        Instruction::MatchLineBegin(term) => sp == 0 || is_after_terminator(*term, line, sp),
+        Instruction::MatchLineEnd(term) => {
+            sp == line.len() || terminator_len(*term, line, sp).is_some()
+        }
+        Instruction::MatchTextEnd => sp == line.len(),
+        _ => false,
+    }
+}
+
+/// sp番目から改行が始まる場合は､その改行の文字数を返す
+///
+/// Crlfの場合､\r\nの\nからは改行が始まらないものとする
+fn terminator_len(term: LineTerminator, line: &[char], sp: usize) -> Option<usize> {
+    let cur = *line.get(sp)?;
+    let prev = sp.checked_sub(1).and_then(|i| line.get(i));
+    match term {
+        LineTerminator::Lf => (cur == '\n').then_some(1),
+        LineTerminator::Char(c) => (cur == c).then_some(1),
+        LineTerminator::Crlf => match cur {
+            '\r' if line.get(sp + 1) == Some(&'\n') => Some(2),
+            '\r' => Some(1),
+            '\n' if prev != Some(&'\r') => Some(1),
+            _ => None,
+        },
+    }
+}
+
+/// sp番目の直前で改行が終わるかを判定
+///
+/// Crlfの場合､\r\nの\rの直後は改行の途中とする
+fn is_after_terminator(term: LineTerminator, line: &[char], sp: usize) -> bool {
+    let Some(prev) = sp.checked_sub(1).and_then(|i| line.get(i)) else {
+        return false;
+    };
+    match term {
+        LineTerminator::Lf => *prev == '\n',
+        LineTerminator::Char(c) => *prev == c,
+        LineTerminator::Crlf => *prev == '\n' || (*prev == '\r' && line.get(sp) != Some(&'\n')),
+    }
+}
+
+/// 文字の不一致時に､編集操作を1回消費してマッチングを継続する関数
+///
+/// 置換､挿入(入力側の余分な文字)､削除(パターン側の文字の欠落)の順に試すため､
+/// 挿入と削除を戻る先の分岐として積み､置換後に再開する(pc, sp, budget)を返す
+/// 編集回数が残っていない場合はOk(None)を返す
+fn eval_edit(
+    line: &[char],
+    pc: usize,
+    sp: usize,
+    budget: usize,
+    state: &mut State,
+    branches: &mut Vec<Branch>,
+) -> Result<Option<(usize, usize, usize)>, EvalError> {
+    if budget == 0 {
+        return Ok(None);
+    }
+    let budget = budget - 1;
+
+    let mut next_pc = pc;
+    safe_add(&mut next_pc, &1, || EvalError::PCOverFlow)?;
+    let mut next_sp = sp;
+    safe_add(&mut next_sp, &1, || EvalError::SPOverFlow)?;
+
+    // 削除
+    if sp >= line.len() {
+        return Ok(Some((next_pc, sp, budget)));
+    }
+    branches.push(Branch {
+        pc: next_pc,
+        sp,
+        budget,
+        saved: state.save(),
+    });
+
+    // 挿入
+    branches.push(Branch {
+        pc,
+        sp: next_sp,
+        budget,
+        saved: state.save(),
+    });
+
+    // 置換
+    Ok(Some((next_pc, next_sp, budget)))
+}
+
+/// 幅優先探索で実行するスレッド
+struct Thread {
+    pc: usize,                 // 次に実行する命令の位置
+    skip: usize,               // \Xで読んだ文字のうち､まだ読み進めていない文字数
+    slots: Vec<Option<usize>>, // save命令で保存したキャプチャの位置
+    start: usize,              // マッチを開始した位置｡\Gで利用する
+}
+
+/// 幅優先探索で見つかったマッチのキャプチャの位置と､そのスレッドが開始した位置
+type Matched = (Vec<Option<usize>>, usize);
+
 /// 幅優先探索でマッチングを行う関数
+///
+/// 文字を読む命令で待つスレッドを優先度の順に持ち､入力を1文字読むごとに全てのスレッドを進める
+/// 同じ位置で同じ命令に到達したスレッドは優先度の高い方のみを残すため､
+/// 評価にかかる時間は命令数と文字数の積に比例する
+/// ただし文字を読まずに繰り返しを始めた位置では､文字を読む命令に到達したスレッドのみをまとめる
+///
+/// threadをspから開始し､マッチが見つかるまではlast番目までの各位置でも同じ命令から新たなスレッドを開始する
+/// 後ろの位置から開始したスレッドほど優先度を低くするため､最も前から始まるマッチを1度の走査で求められる
+///
+/// スレッドごとにキャプチャの位置を持ち､深さ優先探索と同じく優先度の最も高いマッチを求める
+/// マッチした場合は､そのスレッドのキャプチャの位置をOk(Some)で返す
 fn eval_width(
-    _inst: &[Instruction],
-    _line: &[char],
-    mut _pc: usize,
-    mut _sp: usize,
-) -> Result<bool, EvalError> {
-    Ok(false)
+    inst: &[Instruction],
+    line: &[char],
+    thread: Thread,
+    mut sp: usize,
+    last: usize,
+    state: &mut State,
+) -> Result<Option<Matched>, EvalError> {
+    // seen[pc]は､その命令に到達した位置 + 1｡0の場合は未到達
+    let mut seen = vec![0; inst.len()];
+    let mut clist = Vec::new();
+    let mut nlist = Vec::new();
+
+    let (pc, slots) = (thread.pc, thread.slots.clone());
+    let mut matched = add_thread(inst, line, thread, sp, &mut seen, &mut clist, state)?;
+
+    loop {
+        if clist.is_empty() && (matched.is_some() || sp >= last) {
+            break;
+        }
+        state.max_threads = state.max_threads.max(clist.len());
+
+        // 末尾では文字を読めないため､残りのスレッドは全て失敗する
+        let Some(c) = line.get(sp) else {
+            break;
+        };
+        let mut next = sp;
+        safe_add(&mut next, &1, || EvalError::SPOverFlow)?;
+
+        for thread in clist.drain(..) {
+            let Some(thread) = step_thread(inst, line, thread, sp, *c) else {
+                continue;
+            };
+            if thread.skip > 0 {
+                nlist.push(thread);
+            } else if let Some(found) =
+                add_thread(inst, line, thread, next, &mut seen, &mut nlist, state)?
+            {
+                // 以降のスレッドはこのマッチより優先度が低いため､捨てる
+                matched = Some(found);
+                break;
+            }
+        }
+
+        clist.clear();
+        core::mem::swap(&mut clist, &mut nlist);
+        sp = next;
+
+        // マッチが見つかっていなければ､この位置から開始するスレッドを最も低い優先度で加える
+        if matched.is_none() && sp <= last {
+            let thread = Thread {
+                pc,
+                skip: 0,
+                slots: slots.clone(),
+                start: sp,
+            };
+            matched = add_thread(inst, line, thread, sp, &mut seen, &mut clist, state)?;
+        }
+    }
+
+    Ok(matched)
+}
+
+/// start番目からlast番目までの各位置から､幅優先探索でマッチングを行う関数
+///
+/// \Gは開始した位置でのみマッチし､前の位置から開始したスレッドとまとめると結果が変わるため､
+/// \Gを含む命令列では位置ごとに探索し直す
+fn search_width(
+    inst: &[Instruction],
+    line: &[char],
+    start: usize,
+    last: usize,
+    state: &mut State,
+) -> Result<Option<Matched>, EvalError> {
+    let has_start = inst.iter().any(|i| matches!(i, Instruction::MatchStart));
+    let mut sp = start;
+    while sp <= last {
+        let to = if has_start { sp } else { last };
+        let thread = Thread {
+            pc: 0,
+            skip: 0,
+            slots: state.slots.clone(),
+            start: sp,
+        };
+        if let Some(found) = eval_width(inst, line, thread, sp, to, state)? {
+            return Ok(Some(found));
+        }
+        sp = to + 1;
+    }
+
+    Ok(None)
+}
+
+/// 位置spの文字cをスレッドに読ませる
+///
+/// 読めた場合は､次の位置で実行するスレッドを返す
+fn step_thread(
+    inst: &[Instruction],
+    line: &[char],
+    thread: Thread,
+    sp: usize,
+    c: char,
+) -> Option<Thread> {
+    if thread.skip > 0 {
+        return Some(Thread {
+            skip: thread.skip - 1,
+            ..thread
+        });
+    }
+
+    let skip = match &inst[thread.pc] {
+        Instruction::Char(x) => (*x == c).then_some(0),
+        Instruction::Class(ranges, negated) => (class_contains(ranges, c) != *negated).then_some(0),
+        Instruction::AnyChar => Some(0),
+        Instruction::AnyCharExceptNewline => (c != '\n').then_some(0),
+        // 2文字以上の書記素クラスタは､残りの文字を1文字ずつ読み進める
+        Instruction::Grapheme => grapheme::cluster_len(line, sp).map(|len| len - 1),
+        _ => None,
+    }?;
+    Some(Thread {
+        pc: thread.pc + 1,
+        skip,
+        ..thread
+    })
+}
+
+/// 位置spでスレッドの命令から文字を読まずに到達できる命令を辿り､文字を読む命令のスレッドをlistに加える
+///
+/// 分岐は優先する側から順に辿り､既にspで到達した命令は辿らない
+/// match命令に到達した場合は､そのキャプチャの位置をOk(Some)で返し､優先度の低い残りの分岐は辿らない
+fn add_thread(
+    inst: &[Instruction],
+    line: &[char],
+    thread: Thread,
+    sp: usize,
+    seen: &mut [usize],
+    list: &mut Vec<Thread>,
+    state: &mut State,
+) -> Result<Option<Matched>, EvalError> {
+    let start = thread.start;
+    let mut stack = vec![(thread.pc, thread.slots)];
+    while let Some((pc, mut slots)) = stack.pop() {
+        let Some(next) = inst.get(pc) else {
+            return Err(EvalError::InvalidPC);
+        };
+        // 深さ優先探索の記録と同じく､文字を読まずに繰り返しを始めた位置では文字を読む命令のみをまとめる
+        if is_read(next) || !is_empty_iteration(&slots[state.marks..], sp) {
+            if seen[pc] == sp + 1 {
+                continue;
+            }
+            seen[pc] = sp + 1;
+        }
+        state.steps += 1;
+
+        match next {
+            Instruction::Char(_)
+            | Instruction::Class(..)
+            | Instruction::AnyChar
+            | Instruction::AnyCharExceptNewline
+            | Instruction::Grapheme => list.push(Thread {
+                pc,
+                skip: 0,
+                slots,
+                start,
+            }),
+            Instruction::Match => return Ok(Some((slots, start))),
+            Instruction::Jump(addr) => stack.push((*addr, slots)),
+            Instruction::Split(addr1, addr2) => {
+                stack.push((*addr2, slots.clone()));
+                stack.push((*addr1, slots));
+            }
+            Instruction::Save(n) => {
+                if let Some(slot) = slots.get_mut(*n) {
+                    *slot = Some(sp);
+                }
+                stack.push((pc + 1, slots));
+            }
+            Instruction::Return => stack.push((pc + 1, slots)),
+            Instruction::EmptyCheckStart(n) => {
+                if let Some(slot) = slots.get_mut(state.marks + n) {
+                    *slot = Some(sp);
+                }
+                stack.push((pc + 1, slots));
+            }
+            Instruction::EmptyCheckEnd(n, addr) => {
+                if slots.get(state.marks + n) == Some(&Some(sp)) {
+                    stack.push((*addr, slots));
+                } else {
+                    stack.push((pc + 1, slots));
+                }
+            }
+            Instruction::LookBehind(len, addr) | Instruction::NegativeLookBehind(len, addr) => {
+                // 後読みの式は長さが固定のため､len文字前から評価してマッチすればspで終わる
+                let negated = matches!(next, Instruction::NegativeLookBehind(..));
+                let found = match sp.checked_sub(*len) {
+                    Some(from) => {
+                        let thread = Thread {
+                            pc: pc + 1,
+                            skip: 0,
+                            slots: slots.clone(),
+                            start,
+                        };
+                        eval_width(inst, line, thread, from, from, state)?
+                    }
+                    None => None,
+                };
+                match found {
+                    // 肯定の後読み中のキャプチャは保持する
+                    Some((sub, _)) if !negated => stack.push((*addr, sub)),
+                    None if negated => stack.push((*addr, slots)),
+                    _ => (),
+                }
+            }
+            Instruction::MatchStart => {
+                if sp == start {
+                    stack.push((pc + 1, slots));
+                }
+            }
+            Instruction::MatchBegin
+            | Instruction::MatchEnd(_)
+            | Instruction::MatchLineBegin(_)
+            | Instruction::MatchLineEnd(_)
+            | Instruction::MatchTextEnd => {
+                if is_anchor_match(next, line, sp) {
+                    stack.push((pc + 1, slots));
+                }
+            }
+            // 後方参照､条件分岐､再帰は評価前にErrとしている
+            Instruction::Backref(_) | Instruction::BackrefIgnoreCase(_) => {
+                return Err(EvalError::UnsupportedBackref)
+            }
+            Instruction::Cond(..) | Instruction::Recurse(_) => {
+                return Err(EvalError::UnsupportedConstruct)
+            }
+        }
+    }
+
+    Ok(None)
 }
 
 /// 命令列の評価を行う関数
 ///
 /// instが命令列となり､その命令列を用いて入力文字列lineにマッチさせる
-/// is_depthがtrueの場合に深さ優先探索を､falseの場合に幅優先探索を行う
+/// modeで指定した方式で探索を行う
 ///
 /// 実行時にエラーが起きた場合はErrを返す
 /// マッチ成功時はOk(true)を､失敗時はOk(false)を返す
-pub fn eval(inst: &[Instruction], line: &[char], is_depth: bool) -> Result<bool, EvalError> {
-    if is_depth {
-        eval_depth(inst, line, 0, 0)
-    } else {
-        eval_width(inst, line, 0, 0)
+pub fn eval(inst: &[Instruction], line: &[char], mode: MatchMode) -> Result<bool, EvalError> {
+    eval_fuzzy(inst, line, 0, mode)
+}
+
+/// 資源の上限を指定して､命令列の評価を行う関数
+///
+/// 再帰呼び出しがlimits.recursionより深くなった場合や､
+/// 実行した命令数がlimits.stepsを超えた場合はErrを返す
+pub fn eval_with_limits(
+    inst: &[Instruction],
+    line: &[char],
+    limits: &Limits,
+    mode: MatchMode,
+) -> Result<bool, EvalError> {
+    eval_slots(inst, line, 0, 0, 0, limits, mode, &mut Scratch::default())
+}
+
+/// 編集距離k以内での近似マッチングを行う関数
+///
+/// 1回のマッチにつき､置換､挿入､削除を合計k回まで許容する
+/// kが0の場合はevalと同じ厳密なマッチングとなる
+pub fn eval_fuzzy(
+    inst: &[Instruction],
+    line: &[char],
+    k: usize,
+    mode: MatchMode,
+) -> Result<bool, EvalError> {
+    eval_slots(
+        inst,
+        line,
+        0,
+        0,
+        k,
+        &Limits::default(),
+        mode,
+        &mut Scratch::default(),
+    )
+}
+
+/// キャプチャの位置を求めながら､命令列の評価を行う関数
+///
+/// マッチ成功時は､save命令で保存した位置をslot番号順に並べたものを返す
+/// 2n番目と2n + 1番目がn番目のグループの開始位置と終了位置となり､
+/// 0番目のグループはマッチ全体を表す
+/// マッチ失敗時はOk(None)を返す
+///
+/// マッチはlineのstart番目の文字から開始し､\Gはstart番目の位置にマッチする
+/// キャプチャの位置はline全体の先頭からの位置となる
+pub fn eval_captures(
+    inst: &[Instruction],
+    line: &[char],
+    start: usize,
+    limits: &Limits,
+    mode: MatchMode,
+) -> Result<Option<Vec<Option<usize>>>, EvalError> {
+    let mut scratch = Scratch::default();
+    let is_match = eval_slots(inst, line, start, start, 0, limits, mode, &mut scratch)?;
+    Ok(is_match.then_some(scratch.slots))
+}
+
+/// 作業領域を使い回しながら､キャプチャの位置を求める関数
+///
+/// eval_capturesと同じくlineのstart番目の文字からマッチングし､
+/// マッチ成功時はOk(true)を返して､キャプチャの位置をscratch.slots()に残す
+/// 同じscratchで繰り返し評価する場合は､評価のたびの確保を避けられる
+/// kが1以上の場合は､eval_fuzzyと同じく編集距離k以内での近似マッチングを行う
+pub fn eval_with_scratch(
+    inst: &[Instruction],
+    line: &[char],
+    start: usize,
+    k: usize,
+    limits: &Limits,
+    mode: MatchMode,
+    scratch: &mut Scratch,
+) -> Result<bool, EvalError> {
+    eval_slots(inst, line, start, start, k, limits, mode, scratch)
+}
+
+/// lineのstart番目からlast番目までの各位置から開始するマッチのうち､最も前から始まるものを幅優先探索で求める関数
+///
+/// 各位置から開始するスレッドを1度の走査でまとめて進めるため､位置ごとに評価し直す必要がない
+/// マッチ成功時はOk(true)を返し､キャプチャの位置をscratch.slots()に残す
+/// \Gは各スレッドが開始した位置にマッチする
+pub(crate) fn eval_search(
+    inst: &[Instruction],
+    line: &[char],
+    start: usize,
+    last: usize,
+    limits: &Limits,
+    scratch: &mut Scratch,
+) -> Result<bool, EvalError> {
+    eval_slots(inst, line, start, last, 0, limits, MatchMode::Bfs, scratch)
+}
+
+/// 1度の走査で評価できる命令列を､onepassで評価する関数
+///
+/// eval_with_scratchと同じくlineのstart番目の文字からマッチングし､
+/// マッチ成功時はtrueを返して､キャプチャの位置をscratch.slots()に残す
+pub(crate) fn eval_onepass(
+    onepass: &OnePass,
+    inst: &[Instruction],
+    line: &[char],
+    start: usize,
+    scratch: &mut Scratch,
+) -> bool {
+    scratch.slots.clear();
+    scratch.slots.resize(slot_count(inst), None);
+    scratch.start = start;
+    onepass.exec(inst, line, start, &mut scratch.slots)
+}
+
+/// 命令列のsave命令が使うslotの数
+fn slot_count(inst: &[Instruction]) -> usize {
+    inst.iter()
+        .filter_map(|i| match i {
+            Instruction::Save(n) => Some(n + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// 命令列のempty check start命令が開始位置を記録する繰り返しの数
+fn loop_count(inst: &[Instruction]) -> usize {
+    inst.iter()
+        .filter_map(|i| match i {
+            Instruction::EmptyCheckStart(n) => Some(n + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// 最も前で終わるマッチの終了位置を求める関数
+///
+/// lineのstart番目以降の各位置から深さ優先探索で全ての経路を探し､
+/// 見つかったマッチの終了位置のうち最小のものを返す
+/// kが1以上の場合は､編集距離k以内のマッチを探す
+/// マッチ失敗時はOk(None)を返す
+pub fn eval_shortest(
+    inst: &[Instruction],
+    line: &[char],
+    start: usize,
+    k: usize,
+    limits: &Limits,
+) -> Result<Option<usize>, EvalError> {
+    // 終了位置の初期値は､どのマッチよりも後ろの位置とする
+    let end = Rc::new(Cell::new(line.len() + 1));
+    let mut sp = start;
+    let mut state = State {
+        shortest: Some(end.clone()),
+        ..Default::default()
+    };
+
+    // 開始位置より前では終わらないため､見つかった終了位置より後ろの開始位置は探さない
+    state.marks = slot_count(inst);
+    while sp < end.get() {
+        state.start = sp;
+        state.slots.clear();
+        state.slots.resize(state.marks + loop_count(inst), None);
+        state.calls.clear();
+        state.steps = 0;
+        eval_depth(inst, line, 0, sp, k, &mut state, limits)?;
+        safe_add(&mut sp, &1, || EvalError::SPOverFlow)?;
     }
+
+    Ok((end.get() <= line.len()).then_some(end.get()))
+}
+
+/// scratchの領域を使い､指定された方式で評価を行う関数
+///
+/// 幅優先探索ではstart番目からlast番目までの各位置からマッチングし､深さ優先探索ではstart番目からのみマッチングする
+/// マッチ成功時はOk(true)を返し､キャプチャの位置をscratch.slotsに残す
+#[allow(clippy::too_many_arguments)]
+fn eval_slots(
+    inst: &[Instruction],
+    line: &[char],
+    start: usize,
+    last: usize,
+    k: usize,
+    limits: &Limits,
+    mode: MatchMode,
+    scratch: &mut Scratch,
+) -> Result<bool, EvalError> {
+    // 幅優先探索では後方参照､条件分岐､再帰を扱えないため､誤った結果を返さずにエラーとする
+    let has_backref = inst.iter().any(|i| {
+        matches!(
+            i,
+            Instruction::Backref(_)
+                | Instruction::BackrefIgnoreCase(_)
+                | Instruction::Cond(..)
+                | Instruction::Recurse(_)
+        )
+    });
+    if mode == MatchMode::Bfs && has_backref {
+        let is_backref = |i: &Instruction| {
+            matches!(
+                i,
+                Instruction::Backref(_) | Instruction::BackrefIgnoreCase(_)
+            )
+        };
+        return Err(if inst.iter().any(is_backref) {
+            EvalError::UnsupportedBackref
+        } else {
+            EvalError::UnsupportedConstruct
+        });
+    }
+    // 幅優先探索のスレッドは編集の回数を持たないため､近似マッチングもエラーとする
+    if mode == MatchMode::Bfs && k > 0 {
+        return Err(EvalError::UnsupportedFuzzy);
+    }
+
+    // キャプチャの位置の後ろに､繰り返しの開始位置を記録する領域を確保する
+    let marks = slot_count(inst);
+    let mut slots = take(&mut scratch.slots);
+    slots.clear();
+    slots.resize(marks + loop_count(inst), None);
+    // 結果が命令と位置の組のみで決まり､記録する領域が上限に収まる場合のみ訪れた組を記録する
+    let memoize = mode == MatchMode::Dfs
+        && !has_backref
+        && k == 0
+        && inst
+            .len()
+            .checked_mul(line.len() + 1)
+            .is_some_and(|n| 0 < n && n <= limits.visited);
+    let visited = memoize.then(|| {
+        let mut visited = take(&mut scratch.visited);
+        visited.reset(inst.len(), line.len());
+        visited
+    });
+
+    let mut state = State {
+        start,
+        slots,
+        marks,
+        pool: take(&mut scratch.pool),
+        visited,
+        ..Default::default()
+    };
+
+    let mut begin = start; // マッチした場合に､そのスレッドが開始した位置
+    let is_match = match mode {
+        MatchMode::Dfs => eval_depth(inst, line, 0, start, k, &mut state, limits),
+        MatchMode::Bfs => {
+            search_width(inst, line, start, last, &mut state).map(|found| match found {
+                Some((slots, sp)) => {
+                    state.slots = slots;
+                    begin = sp;
+                    true
+                }
+                None => false,
+            })
+        }
+    };
+
+    // 次の評価で使い回せるよう､領域を戻す
+    scratch.slots = state.slots;
+    scratch.slots.truncate(marks);
+    scratch.start = begin;
+    scratch.pool = state.pool;
+    if let Some(mut visited) = state.visited {
+        visited.clear();
+        scratch.visited = visited;
+    }
+    scratch.stats.steps += state.steps;
+    scratch.stats.backtracks += state.backtracks;
+    scratch.stats.max_threads = scratch.stats.max_threads.max(state.max_threads);
+
+    is_match
 }
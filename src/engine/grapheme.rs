@@ -0,0 +1,142 @@
+//! \Xで利用する､拡張書記素クラスタの境界判定
+//!
+//! UAX #29の規則のうち､CRLF､ハングルの音節､地域指示子の組､
+//! 結合文字とZWJによる絵文字の連結を扱う
+
+/// 結合文字などの､直前の文字と同じクラスタとなる文字の範囲
+const EXTEND: &[(char, char)] = &[
+    ('\u{0300}', '\u{036F}'),   // ダイアクリティカルマーク
+    ('\u{0483}', '\u{0489}'),   // キリル文字の結合文字
+    ('\u{0591}', '\u{05BD}'),   // ヘブライ文字の結合文字
+    ('\u{064B}', '\u{065F}'),   // アラビア文字の結合文字
+    ('\u{1AB0}', '\u{1AFF}'),   // ダイアクリティカルマーク拡張
+    ('\u{1DC0}', '\u{1DFF}'),   // ダイアクリティカルマーク補助
+    ('\u{200C}', '\u{200D}'),   // ZWNJ, ZWJ
+    ('\u{20D0}', '\u{20FF}'),   // 記号用ダイアクリティカルマーク
+    ('\u{3099}', '\u{309A}'),   // 結合用の濁点と半濁点
+    ('\u{FE00}', '\u{FE0F}'),   // 異体字セレクタ
+    ('\u{FE20}', '\u{FE2F}'),   // 半記号
+    ('\u{1F3FB}', '\u{1F3FF}'), // 絵文字の肌の色
+    ('\u{E0020}', '\u{E007F}'), // タグ
+    ('\u{E0100}', '\u{E01EF}'), // 異体字セレクタ補助
+];
+
+/// ZWJの後に連結される絵文字の範囲
+const PICTOGRAPHIC: &[(char, char)] = &[
+    ('\u{2600}', '\u{27BF}'),
+    ('\u{2B00}', '\u{2BFF}'),
+    ('\u{1F000}', '\u{1FAFF}'),
+];
+
+/// 地域指示子の範囲
+const REGIONAL_INDICATOR: (char, char) = ('\u{1F1E6}', '\u{1F1FF}');
+
+/// 絵文字を連結するゼロ幅接合子
+const ZWJ: char = '\u{200D}';
+
+/// ハングルの字母と音節の種類
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Hangul {
+    L,   // 初声
+    V,   // 中声
+    T,   // 終声
+    Lv,  // 終声のない音節
+    Lvt, // 終声のある音節
+}
+
+/// 文字cが範囲のいずれかに含まれるかを判定
+fn in_ranges(ranges: &[(char, char)], c: char) -> bool {
+    ranges.iter().any(|(lo, hi)| *lo <= c && c <= *hi)
+}
+
+/// 直前の文字と同じクラスタとなる文字かを判定
+///
+/// unicode-gencatフィーチャが有効な場合は､一般カテゴリがMn, Me, Mcの文字も含める
+fn is_extend(c: char) -> bool {
+    #[cfg(feature = "unicode-gencat")]
+    {
+        use unicode_general_category::{get_general_category, GeneralCategory};
+        if matches!(
+            get_general_category(c),
+            GeneralCategory::NonspacingMark
+                | GeneralCategory::EnclosingMark
+                | GeneralCategory::SpacingMark
+        ) {
+            return true;
+        }
+    }
+
+    in_ranges(EXTEND, c)
+}
+
+/// 国旗を表す地域指示子かを判定
+fn is_regional_indicator(c: char) -> bool {
+    REGIONAL_INDICATOR.0 <= c && c <= REGIONAL_INDICATOR.1
+}
+
+/// ハングルの字母または音節の場合は､その種類を返す
+fn hangul(c: char) -> Option<Hangul> {
+    match c {
+        '\u{1100}'..='\u{115F}' | '\u{A960}'..='\u{A97C}' => Some(Hangul::L),
+        '\u{1160}'..='\u{11A7}' | '\u{D7B0}'..='\u{D7C6}' => Some(Hangul::V),
+        '\u{11A8}'..='\u{11FF}' | '\u{D7CB}'..='\u{D7FB}' => Some(Hangul::T),
+        '\u{AC00}'..='\u{D7A3}' if (c as u32 - 0xAC00).is_multiple_of(28) => Some(Hangul::Lv),
+        '\u{AC00}'..='\u{D7A3}' => Some(Hangul::Lvt),
+        _ => None,
+    }
+}
+
+/// ハングルの字母prevの後にnextが続く場合に､同じ音節となるかを判定
+fn is_hangul_continued(prev: Hangul, next: Hangul) -> bool {
+    use Hangul::*;
+    matches!(
+        (prev, next),
+        (L, L | V | Lv | Lvt) | (Lv | V, V | T) | (Lvt | T, T)
+    )
+}
+
+/// line[sp]から始まる拡張書記素クラスタの文字数を返す
+///
+/// spが文字列の末尾の場合はNoneを返す
+pub fn cluster_len(line: &[char], sp: usize) -> Option<usize> {
+    let first = *line.get(sp)?;
+    let next = |len: usize| line.get(sp + len).copied();
+
+    // CRLFは1つのクラスタとし､それ以外の制御文字は単独でクラスタとなる
+    if first == '\r' && next(1) == Some('\n') {
+        return Some(2);
+    }
+    if first.is_control() {
+        return Some(1);
+    }
+
+    let mut len = 1;
+    if is_regional_indicator(first) {
+        // 地域指示子は2つずつ組にする
+        if next(1).is_some_and(is_regional_indicator) {
+            len = 2;
+        }
+    } else if let Some(mut prev) = hangul(first) {
+        // ハングルの字母は音節としてまとめる
+        while let Some(h) = next(len).and_then(hangul) {
+            if !is_hangul_continued(prev, h) {
+                break;
+            }
+            prev = h;
+            len += 1;
+        }
+    }
+
+    // 結合文字と､ZWJで連結された絵文字を含める
+    while let Some(c) = next(len) {
+        if c == ZWJ && next(len + 1).is_some_and(|c| in_ranges(PICTOGRAPHIC, c)) {
+            len += 2;
+        } else if is_extend(c) {
+            len += 1;
+        } else {
+            break;
+        }
+    }
+
+    Some(len)
+}
@@ -0,0 +1,104 @@
+//! BufReadから読み込んだ行のうち､マッチした行を返す
+use super::Regex;
+use super::RegexError;
+use std::io::{BufRead, Lines};
+
+/// マッチした行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepLine {
+    number: usize,
+    line: String,
+    spans: Vec<(usize, usize)>,
+}
+
+impl GrepLine {
+    /// 行の番号｡1から始まる
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// 改行を除いた行の文字列
+    pub fn as_str(&self) -> &str {
+        &self.line
+    }
+
+    /// 行中の全てのマッチの(開始位置, 終了位置)
+    ///
+    /// 位置は行の先頭からのバイト単位となる
+    pub fn spans(&self) -> &[(usize, usize)] {
+        &self.spans
+    }
+}
+
+/// grepが返す､マッチした行を順に返すイテレータ
+pub struct Grep<R> {
+    regex: Regex,
+    lines: Lines<R>,
+    number: usize, // 最後に読み込んだ行の番号
+}
+
+impl<R: BufRead> Iterator for Grep<R> {
+    type Item = Result<GrepLine, RegexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            self.number += 1;
+
+            let spans = match self
+                .regex
+                .find_iter(&line)
+                .map(|m| m.map(|m| (m.start(), m.end())))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(spans) => spans,
+                Err(e) => return Some(Err(e)),
+            };
+            if !spans.is_empty() {
+                return Some(Ok(GrepLine {
+                    number: self.number,
+                    line,
+                    spans,
+                }));
+            }
+        }
+    }
+}
+
+/// readerから1行ずつ読み込み､正規表現にマッチした行を返す
+///
+/// 行中のいずれかの位置からマッチした場合に､その行がマッチしたものとみなす
+///
+/// # 利用例
+///
+/// ```
+/// use regexer;
+/// let input = "ok\nerror: disk\nok\nerror: net\n".as_bytes();
+/// let lines = regexer::grep("error: (\\w+)", input)
+///     .unwrap()
+///     .map(|l| l.map(|l| (l.number(), l.spans().to_vec())))
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+/// assert_eq!(lines, vec![(2, vec![(0, 11)]), (4, vec![(0, 10)])]);
+/// ```
+///
+/// # 引数
+///
+/// exprに正規表現､readerに入力を与える
+///
+/// # 返り値
+///
+/// マッチした行を順に返すイテレータを返す
+/// イテレータは､読み込みや評価中にエラーが起きた場合にErrを返す
+///
+/// 入力された正規表現にエラーがある場合はErrを返す
+pub fn grep<R: BufRead>(expr: &str, reader: R) -> Result<Grep<R>, RegexError> {
+    Ok(Grep {
+        regex: Regex::new(expr)?,
+        lines: reader.lines(),
+        number: 0,
+    })
+}
@@ -0,0 +1,63 @@
+//! Unicodeの正規化によって､合成済みの文字と分解された文字を同一視する
+pub use super::parser::Normalization;
+
+use super::parser::Ast;
+use alloc::vec::Vec;
+use core::mem::take;
+use unicode_normalization::UnicodeNormalization;
+
+/// 文字列を指定した形式で正規化
+pub fn normalize_chars<I>(chars: I, form: Normalization) -> Vec<char>
+where
+    I: Iterator<Item = char>,
+{
+    match form {
+        Normalization::None => chars.collect(),
+        Normalization::Nfc => chars.nfc().collect(),
+        Normalization::Nfkc => chars.nfkc().collect(),
+    }
+}
+
+/// AST中の連続する通常の文字を､指定した形式で正規化
+///
+/// 例えば､e\u{301}という2つの文字はNFCではéという1つの文字となる
+/// 文字クラスや量指定子を挟んだ文字は正規化の対象としない
+pub fn normalize_ast(ast: &mut Ast, form: Normalization) {
+    match ast {
+        Ast::Seq(v) => {
+            let mut seq = Vec::new();
+            let mut run = Vec::new(); // 正規化前の連続する文字
+            for mut e in take(v) {
+                if let Ast::Char(c) = e {
+                    run.push(c);
+                    continue;
+                }
+                let chars = normalize_chars(take(&mut run).into_iter(), form);
+                seq.extend(chars.into_iter().map(Ast::Char));
+                normalize_ast(&mut e, form);
+                seq.push(e);
+            }
+            let chars = normalize_chars(run.into_iter(), form);
+            seq.extend(chars.into_iter().map(Ast::Char));
+            *v = seq;
+        }
+        Ast::Plus(e)
+        | Ast::Star(e)
+        | Ast::Question(e)
+        | Ast::Repeat(e, _, _)
+        | Ast::Capture(e, _, _)
+        | Ast::LookBehind(e, _)
+        | Ast::Flags(_, e) => normalize_ast(e, form),
+        Ast::Or(e1, e2) => {
+            normalize_ast(e1, form);
+            normalize_ast(e2, form);
+        }
+        Ast::Cond(_, yes, no) => {
+            normalize_ast(yes, form);
+            if let Some(no) = no {
+                normalize_ast(no, form);
+            }
+        }
+        _ => (),
+    }
+}
@@ -0,0 +1,236 @@
+//! 分岐が入力の1文字で一意に決まる命令列を､バックトラックせずに1度の走査で評価する
+use super::{dfa, evaluator, Instruction};
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+
+/// 1度の走査でキャプチャまで求められる命令列の評価器
+///
+/// 文字を読む命令の次の位置ごとに､文字を読まずに到達できる命令を優先度の順に並べておく
+/// 並べた文字を読む命令はどの2つも同じ文字を読まないため､次に読む文字で進む経路は1つに決まる
+/// 進む経路より優先度の低いmatch命令は､その経路が失敗した場合のマッチとして記録する
+#[derive(Debug, Clone)]
+pub(crate) struct OnePass {
+    states: Vec<Vec<Leaf>>, // 位置pcから到達できる命令｡文字を読む命令の次でも先頭でもない位置は空
+}
+
+/// 文字を読まずに到達できる､文字を読む命令かmatch命令
+#[derive(Debug, Clone)]
+struct Leaf {
+    pc: usize,           // 到達した命令の位置
+    saves: Vec<usize>,   // 到達するまでに実行するsave命令のslot番号
+    asserts: Vec<usize>, // 到達するまでに成り立つ必要があるアンカーの位置
+}
+
+impl OnePass {
+    /// 命令列が1度の走査で評価できる場合に､評価器を構築
+    ///
+    /// 同じ文字を読む命令に複数の経路で到達しうる場合や､文字を読まずに同じ命令へ2度到達する場合､
+    /// 後方参照､後読み､条件分岐､再帰､\Xを含む場合や､各位置から到達できる命令の数と
+    /// 経路上のsave命令とアンカーの数の合計がsizeを超える場合や､sizeが0の場合はNoneを返す
+    pub(crate) fn new(inst: &[Instruction], size: usize) -> Option<OnePass> {
+        if size == 0 || !inst.iter().all(is_supported) {
+            return None;
+        }
+
+        // 文字の種類の境界を代表として､同じ文字を読む命令が並ばないかを調べる
+        let bounds = dfa::alphabet(inst);
+        let mut states = vec![Vec::new(); inst.len() + 1];
+        let mut total = 0;
+        let entries = inst
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| is_read(i))
+            .map(|(pc, _)| pc + 1);
+        for entry in core::iter::once(0).chain(entries) {
+            let leaves = closure(inst, entry)?;
+            for c in &bounds {
+                let reads = leaves.iter().filter(|l| reads(&inst[l.pc], *c)).count();
+                if reads > 1 {
+                    return None;
+                }
+            }
+            total += leaves
+                .iter()
+                .map(|l| 1 + l.saves.len() + l.asserts.len())
+                .sum::<usize>();
+            if total > size {
+                return None;
+            }
+            states[entry] = leaves;
+        }
+
+        Some(OnePass { states })
+    }
+
+    /// lineのstart番目の文字からマッチングし､マッチした場合はキャプチャの位置をslotsに残す
+    ///
+    /// slotsは命令列のsave命令が使う数だけNoneで埋めておく
+    /// \Gはstart番目の位置にマッチする
+    pub(crate) fn exec(
+        &self,
+        inst: &[Instruction],
+        line: &[char],
+        start: usize,
+        slots: &mut Vec<Option<usize>>,
+    ) -> bool {
+        // 進んだ経路が失敗した場合に戻る､優先度の低いマッチのキャプチャの位置
+        let mut fallback: Option<Vec<Option<usize>>> = None;
+        let mut pc = 0;
+        for sp in start..=line.len() {
+            let holds = |leaf: &&Leaf| {
+                leaf.asserts
+                    .iter()
+                    .all(|a| assert(&inst[*a], line, start, sp))
+            };
+            let c = line.get(sp);
+            let mut leaves = self.states[pc].iter().filter(holds);
+
+            // 読める文字を読む命令か､match命令のうち最も優先度の高いものに進む
+            let Some(leaf) = leaves.find(|l| match c {
+                Some(c) => reads(&inst[l.pc], *c) || matches!(inst[l.pc], Instruction::Match),
+                None => matches!(inst[l.pc], Instruction::Match),
+            }) else {
+                break;
+            };
+            if matches!(inst[leaf.pc], Instruction::Match) {
+                apply(slots, &leaf.saves, sp);
+                return true;
+            }
+
+            // 同じ文字を読む命令は他にないため､残りはmatch命令に到達した場合のみ戻る先となる
+            if let Some(m) = leaves.find(|l| matches!(inst[l.pc], Instruction::Match)) {
+                let mut matched = fallback.take().unwrap_or_default();
+                matched.clone_from(slots);
+                apply(&mut matched, &m.saves, sp);
+                fallback = Some(matched);
+            }
+            apply(slots, &leaf.saves, sp);
+            pc = leaf.pc + 1;
+        }
+
+        match fallback {
+            Some(matched) => {
+                *slots = matched;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// 1度の走査で扱える命令かを判定
+fn is_supported(inst: &Instruction) -> bool {
+    matches!(
+        inst,
+        Instruction::Char(_)
+            | Instruction::Class(..)
+            | Instruction::AnyChar
+            | Instruction::AnyCharExceptNewline
+            | Instruction::Match
+            | Instruction::Jump(_)
+            | Instruction::Split(..)
+            | Instruction::Save(_)
+            | Instruction::Return
+            | Instruction::MatchBegin
+            | Instruction::MatchStart
+            | Instruction::MatchEnd(_)
+            | Instruction::MatchLineBegin(_)
+            | Instruction::MatchLineEnd(_)
+            | Instruction::MatchTextEnd
+    )
+}
+
+/// 1文字を読む命令かを判定
+fn is_read(inst: &Instruction) -> bool {
+    matches!(
+        inst,
+        Instruction::Char(_)
+            | Instruction::Class(..)
+            | Instruction::AnyChar
+            | Instruction::AnyCharExceptNewline
+    )
+}
+
+/// 命令が文字cを読めるかを判定
+fn reads(inst: &Instruction, c: char) -> bool {
+    match inst {
+        Instruction::Char(x) => *x == c,
+        Instruction::Class(ranges, negated) => {
+            ranges.iter().any(|(lo, hi)| *lo <= c && c <= *hi) != *negated
+        }
+        Instruction::AnyChar => true,
+        Instruction::AnyCharExceptNewline => c != '\n',
+        _ => false,
+    }
+}
+
+/// 位置spでアンカーの条件が成り立つかを判定
+fn assert(inst: &Instruction, line: &[char], start: usize, sp: usize) -> bool {
+    match inst {
+        Instruction::MatchStart => sp == start,
+        _ => evaluator::is_anchor_match(inst, line, sp),
+    }
+}
+
+/// 経路上のsave命令の通りに､slotsに位置spを保存する
+fn apply(slots: &mut [Option<usize>], saves: &[usize], sp: usize) {
+    for n in saves {
+        if let Some(slot) = slots.get_mut(*n) {
+            *slot = Some(sp);
+        }
+    }
+}
+
+/// 位置entryから文字を読まずに到達できる､文字を読む命令とmatch命令を優先度の順に返す
+///
+/// 同じ命令へ2度到達する場合は､どちらの経路で到達したかが一意に決まらないためNoneを返す
+/// 命令列の範囲外へ到達する場合もNoneを返す
+fn closure(inst: &[Instruction], entry: usize) -> Option<Vec<Leaf>> {
+    let mut seen = BTreeSet::new();
+    let mut stack = vec![Leaf {
+        pc: entry,
+        saves: Vec::new(),
+        asserts: Vec::new(),
+    }];
+    let mut leaves = Vec::new();
+    while let Some(mut path) = stack.pop() {
+        if !seen.insert(path.pc) {
+            return None;
+        }
+
+        match inst.get(path.pc)? {
+            Instruction::Jump(addr) => {
+                path.pc = *addr;
+                stack.push(path);
+            }
+            Instruction::Split(addr1, addr2) => {
+                let mut other = path.clone();
+                other.pc = *addr2;
+                path.pc = *addr1;
+                stack.extend([other, path]);
+            }
+            Instruction::Save(n) => {
+                path.saves.push(*n);
+                path.pc += 1;
+                stack.push(path);
+            }
+            // 再帰を含まないため､returnは常に次の命令へ進む
+            Instruction::Return => {
+                path.pc += 1;
+                stack.push(path);
+            }
+            Instruction::MatchBegin
+            | Instruction::MatchStart
+            | Instruction::MatchEnd(_)
+            | Instruction::MatchLineBegin(_)
+            | Instruction::MatchLineEnd(_)
+            | Instruction::MatchTextEnd => {
+                path.asserts.push(path.pc);
+                path.pc += 1;
+                stack.push(path);
+            }
+            _ => leaves.push(path),
+        }
+    }
+
+    Some(leaves)
+}
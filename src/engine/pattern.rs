@@ -0,0 +1,274 @@
+//! 文字列をパースせずに､ASTを直接組み立てて正規表現を生成する
+use super::{
+    evaluator::MatchMode,
+    parser::{Ast, Flags, ParseError},
+    Limits, Regex, RegexError,
+};
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
+use core::{
+    fmt::{self, Display},
+    ops::Range,
+};
+
+/// ASTを組み立てる正規表現の部品
+///
+/// 文字は常にその文字そのものとして扱うため､メタ文字をエスケープする必要がない
+/// キャプチャグループの番号は､buildの際にパターン中の開き括弧の順に振られる
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::Pattern;
+///
+/// let pattern = Pattern::range('a', 'z')
+///     .plus()
+///     .named("key")
+///     .then(Pattern::literal("=1+1"))
+///     .or(Pattern::char('*'));
+/// assert_eq!(pattern.to_string(), "(?<key>[a-z]+)=1\\+1|\\*");
+///
+/// let re = pattern.build().unwrap();
+/// assert_eq!(&re.captures("x: id=1+1").unwrap().unwrap()["key"], "id");
+/// assert!(re.is_match("*").unwrap());
+/// assert!(!re.is_match("id=11").unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern(Ast);
+
+impl Pattern {
+    /// 文字cそのものにマッチする
+    pub fn char(c: char) -> Pattern {
+        Pattern(Ast::Char(c))
+    }
+
+    /// 文字列sそのものにマッチする
+    pub fn literal(s: &str) -> Pattern {
+        Pattern(Ast::Seq(s.chars().map(Ast::Char).collect()))
+    }
+
+    /// 改行以外の任意の1文字にマッチする
+    ///
+    /// dot_allを指定した場合は､改行にもマッチする
+    pub fn any() -> Pattern {
+        Pattern(Ast::Dot)
+    }
+
+    /// lo以上hi以下の1文字にマッチする
+    pub fn range(lo: char, hi: char) -> Pattern {
+        Pattern::class(&[(lo, hi)])
+    }
+
+    /// いずれかの範囲に含まれる1文字にマッチする
+    pub fn class(ranges: &[(char, char)]) -> Pattern {
+        Pattern(Ast::Class(ranges.to_vec(), false))
+    }
+
+    /// どの範囲にも含まれない1文字にマッチする
+    pub fn negated_class(ranges: &[(char, char)]) -> Pattern {
+        Pattern(Ast::Class(ranges.to_vec(), true))
+    }
+
+    /// 文字列の先頭にマッチする(\A)
+    pub fn start() -> Pattern {
+        Pattern(Ast::TextBegin)
+    }
+
+    /// 文字列の末尾にマッチする(\z)
+    pub fn end() -> Pattern {
+        Pattern(Ast::TextEnd)
+    }
+
+    /// 行の先頭にマッチする((?m:^))
+    pub fn line_start() -> Pattern {
+        Pattern::multi_line(Ast::Hat)
+    }
+
+    /// 行の末尾にマッチする((?m:$))
+    pub fn line_end() -> Pattern {
+        Pattern::multi_line(Ast::Doller)
+    }
+
+    fn multi_line(ast: Ast) -> Pattern {
+        let flags = Flags {
+            multi_line: true,
+            ..Default::default()
+        };
+        Pattern(Ast::Flags(flags, Box::new(ast)))
+    }
+
+    /// selfの後にnextが続く
+    pub fn then(self, next: Pattern) -> Pattern {
+        let mut seq = match self.0 {
+            Ast::Seq(v) => v,
+            ast => vec![ast],
+        };
+        match next.0 {
+            Ast::Seq(v) => seq.extend(v),
+            ast => seq.push(ast),
+        }
+        Pattern(Ast::Seq(seq))
+    }
+
+    /// selfかotherのいずれか｡selfを優先する
+    pub fn or(self, other: Pattern) -> Pattern {
+        Pattern(Ast::Or(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /// 0回以上の繰り返し
+    pub fn star(self) -> Pattern {
+        Pattern(Ast::Star(Box::new(self.0)))
+    }
+
+    /// 1回以上の繰り返し
+    pub fn plus(self) -> Pattern {
+        Pattern(Ast::Plus(Box::new(self.0)))
+    }
+
+    /// 0回か1回
+    pub fn optional(self) -> Pattern {
+        Pattern(Ast::Question(Box::new(self.0)))
+    }
+
+    /// min回以上max回以下の繰り返し｡maxがNoneの場合は上限なし
+    pub fn repeat(self, min: usize, max: Option<usize>) -> Pattern {
+        Pattern(Ast::Repeat(Box::new(self.0), min, max))
+    }
+
+    /// 名前のないキャプチャグループとする
+    pub fn group(self) -> Pattern {
+        Pattern(Ast::Capture(Box::new(self.0), 0, None))
+    }
+
+    /// nameという名前のキャプチャグループとする
+    pub fn named(self, name: &str) -> Pattern {
+        Pattern(Ast::Capture(Box::new(self.0), 0, Some(name.to_string())))
+    }
+
+    /// 大文字と小文字を区別しない
+    pub fn case_insensitive(self) -> Pattern {
+        self.with_flags(|flags| flags.case_insensitive = true)
+    }
+
+    /// any()を改行にもマッチさせる
+    pub fn dot_all(self) -> Pattern {
+        self.with_flags(|flags| flags.dot_all = true)
+    }
+
+    /// 内側のフラグを含め､式全体のフラグをsetで変更する
+    fn with_flags<F: Fn(&mut Flags)>(self, set: F) -> Pattern {
+        let ast = self.0.fold(&mut |ast| match ast {
+            Ast::Flags(mut flags, e) => {
+                set(&mut flags);
+                Ast::Flags(flags, e)
+            }
+            ast => ast,
+        });
+        match ast {
+            Ast::Flags(..) => Pattern(ast),
+            ast => {
+                let mut flags = Flags::default();
+                set(&mut flags);
+                Pattern(Ast::Flags(flags, Box::new(ast)))
+            }
+        }
+    }
+
+    /// キャプチャグループに番号を振ったASTに変換
+    pub fn into_ast(self) -> Ast {
+        let mut ast = self.0;
+        number_groups(&mut ast, &mut 1);
+        ast
+    }
+
+    /// 正規表現をコンパイル
+    ///
+    /// 範囲の順序が逆の文字クラスや繰り返し回数､重複したグループの名前はErrとなる
+    /// 英数字と_以外を含むか､数字で始まるグループの名前は､ParseError::InvalidGroupNameとなる
+    /// エラーの範囲は､書き出したパターン全体とする
+    pub fn build(&self) -> Result<Regex, RegexError> {
+        let ast = self.clone().into_ast();
+        let expr = ast.to_string();
+        validate(&ast, &(0..expr.chars().count()), &mut Vec::new())?;
+        Regex::from_ast(expr, &ast, Limits::default(), MatchMode::Dfs, 0)
+    }
+}
+
+/// パターンの文字列として書き出す
+///
+/// 書き出した文字列をパースすると､同じ文字列にマッチする正規表現となる
+impl Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.clone().into_ast())
+    }
+}
+
+impl From<Pattern> for Ast {
+    fn from(pattern: Pattern) -> Ast {
+        pattern.into_ast()
+    }
+}
+
+/// キャプチャグループに､開き括弧の順にnextから番号を振る
+fn number_groups(ast: &mut Ast, next: &mut usize) {
+    match ast {
+        Ast::Capture(e, n, _) => {
+            *n = *next;
+            *next += 1;
+            number_groups(e, next);
+        }
+        Ast::Plus(e)
+        | Ast::Star(e)
+        | Ast::Question(e)
+        | Ast::Repeat(e, _, _)
+        | Ast::LookBehind(e, _)
+        | Ast::Flags(_, e) => number_groups(e, next),
+        Ast::Or(e1, e2) => {
+            number_groups(e1, next);
+            number_groups(e2, next);
+        }
+        Ast::Seq(v) => v.iter_mut().for_each(|e| number_groups(e, next)),
+        Ast::Cond(_, yes, no) => {
+            number_groups(yes, next);
+            if let Some(no) = no {
+                number_groups(no, next);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// パースで検出されるエラーと同じ誤りがないかを確かめる
+///
+/// spanはエラーとする範囲で､namesには､それまでに現れたグループの名前を格納する
+fn validate<'a>(
+    ast: &'a Ast,
+    span: &Range<usize>,
+    names: &mut Vec<&'a str>,
+) -> Result<(), ParseError> {
+    match ast {
+        Ast::Class(ranges, _) => {
+            if let Some((lo, hi)) = ranges.iter().find(|(lo, hi)| lo > hi) {
+                return Err(ParseError::InvalidRange(span.clone(), *lo, *hi));
+            }
+        }
+        Ast::Repeat(_, min, Some(max)) if min > max => {
+            return Err(ParseError::InvalidRepeatRange(span.clone(), *min, *max));
+        }
+        Ast::Capture(_, _, Some(name)) => {
+            let is_valid = name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+            if !is_valid {
+                return Err(ParseError::InvalidGroupName(span.clone()));
+            }
+            if names.contains(&name.as_str()) {
+                return Err(ParseError::DuplicateGroupName(span.clone(), name.clone()));
+            }
+            names.push(name);
+        }
+        _ => (),
+    }
+
+    ast.children()
+        .into_iter()
+        .try_for_each(|e| validate(e, span, names))
+}
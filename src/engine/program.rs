@@ -0,0 +1,312 @@
+//! コンパイル済みの命令列と､コンパイル時のエラー
+use super::{
+    codegen::CodeGenError, evaluator, parser::ParseError, Instruction, MatchMode, RegexError,
+};
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    error::Error,
+    fmt::{self, Display, Write},
+    ops::Index,
+};
+
+/// コンパイル時のエラーを表す型
+#[derive(Debug)]
+pub enum CompileError {
+    Parse(ParseError),     // パース時のエラー
+    CodeGen(CodeGenError), // コード生成時のエラー
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Parse(e) => write!(f, "{e}"),
+            CompileError::CodeGen(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for CompileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CompileError::Parse(e) => Some(e),
+            CompileError::CodeGen(e) => Some(e),
+        }
+    }
+}
+
+impl From<ParseError> for CompileError {
+    fn from(e: ParseError) -> Self {
+        CompileError::Parse(e)
+    }
+}
+
+impl From<CodeGenError> for CompileError {
+    fn from(e: CodeGenError) -> Self {
+        CompileError::CodeGen(e)
+    }
+}
+
+/// コード生成した命令列
+///
+/// 命令の位置はjumpやsplitのアドレスと対応する
+///
+/// serdeフィーチャが有効な場合は､シリアライズして保存し､再コンパイルせずに復元できる
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Program {
+    insts: Vec<Instruction>,
+}
+
+impl Program {
+    pub(super) fn new(insts: Vec<Instruction>) -> Self {
+        Program { insts }
+    }
+
+    /// 命令列全体
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.insts
+    }
+
+    /// pc番目の命令を返す
+    pub fn get(&self, pc: usize) -> Option<&Instruction> {
+        self.insts.get(pc)
+    }
+
+    /// 命令の数
+    pub fn len(&self) -> usize {
+        self.insts.len()
+    }
+
+    /// 命令がないかを判定
+    pub fn is_empty(&self) -> bool {
+        self.insts.is_empty()
+    }
+
+    /// 命令を先頭から順に返すイテレータを生成
+    pub fn iter(&self) -> impl Iterator<Item = &Instruction> + '_ {
+        self.insts.iter()
+    }
+
+    /// ジャンプ先にラベルを付けた､読みやすい命令列の一覧を生成
+    ///
+    /// jumpやsplitなどのジャンプ先の命令の前にL1:､L2:のようなラベルを置き､アドレスの代わりにラベルで参照する
+    /// 各命令の後ろには､その命令に対応するパターンの断片をコメントとして付ける
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer;
+    /// let listing = regexer::compile("a|b").unwrap().disassemble();
+    /// assert!(listing.contains("    split L1, L2\n"));
+    /// assert!(listing.contains("L1:\n    char a"));
+    /// ```
+    pub fn disassemble(&self) -> String {
+        // ジャンプ先のアドレスに､アドレスの順にラベルの番号を付ける
+        let mut labels = BTreeMap::new();
+        for inst in self.insts.iter() {
+            for addr in jump_targets(inst) {
+                labels.insert(addr, 0);
+            }
+        }
+        for (n, label) in labels.values_mut().enumerate() {
+            *label = n + 1;
+        }
+        let label = |addr: &usize| format!("L{}", labels[addr]);
+
+        let mut listing = String::new();
+        for (pc, inst) in self.insts.iter().enumerate() {
+            if let Some(n) = labels.get(&pc) {
+                let _ = writeln!(listing, "L{n}:");
+            }
+
+            let text = match inst {
+                Instruction::Jump(addr) => format!("jump {}", label(addr)),
+                Instruction::Split(addr1, addr2) => {
+                    format!("split {}, {}", label(addr1), label(addr2))
+                }
+                Instruction::LookBehind(len, addr) => {
+                    format!("lookbehind {}, {}", len, label(addr))
+                }
+                Instruction::NegativeLookBehind(len, addr) => {
+                    format!("negative lookbehind {}, {}", len, label(addr))
+                }
+                Instruction::Cond(n, addr) => format!("cond {}, {}", n, label(addr)),
+                Instruction::Recurse(addr) => format!("recurse {}", label(addr)),
+                _ => inst.to_string(),
+            };
+            match fragment(inst) {
+                Some(comment) => {
+                    let _ = writeln!(listing, "    {text:<24}; {comment}");
+                }
+                None => {
+                    let _ = writeln!(listing, "    {text}");
+                }
+            }
+        }
+
+        // 命令列の末尾へのジャンプ
+        if let Some(n) = labels.get(&self.insts.len()) {
+            let _ = writeln!(listing, "L{n}:");
+        }
+
+        listing
+    }
+
+    /// 命令列をGraphvizのDOT形式のグラフに変換
+    ///
+    /// 命令を節点とし､次に実行しうる命令への遷移を辺とする
+    /// splitの辺には優先する順に1と2を､条件分岐と後読みの辺にはyesとnoなどの名前を付ける
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer;
+    /// let dot = regexer::compile("a|b").unwrap().to_dot();
+    /// assert!(dot.starts_with("digraph program {"));
+    /// assert!(dot.contains("1 -> 2 [label=\"1\"];"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph program {\n    rankdir=LR;\n    node [shape=box];\n");
+
+        for (pc, inst) in self.insts.iter().enumerate() {
+            let label = format!("{:>04}: {}", pc, inst)
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            let shape = if matches!(inst, Instruction::Match) {
+                ", peripheries=2"
+            } else {
+                ""
+            };
+            let _ = writeln!(dot, "    {pc} [label=\"{label}\"{shape}];");
+        }
+
+        for (pc, inst) in self.insts.iter().enumerate() {
+            let next = pc + 1;
+            let edges: Vec<(usize, Option<&str>)> = match inst {
+                Instruction::Match | Instruction::Return => vec![],
+                Instruction::Jump(addr) => vec![(*addr, None)],
+                Instruction::Split(addr1, addr2) => vec![(*addr1, Some("1")), (*addr2, Some("2"))],
+                Instruction::LookBehind(_, addr) | Instruction::NegativeLookBehind(_, addr) => {
+                    vec![(next, Some("look")), (*addr, Some("next"))]
+                }
+                Instruction::Cond(_, addr) => vec![(next, Some("yes")), (*addr, Some("no"))],
+                Instruction::Recurse(addr) => vec![(*addr, Some("call")), (next, Some("return"))],
+                Instruction::EmptyCheckEnd(_, addr) => {
+                    vec![(next, Some("moved")), (*addr, Some("empty"))]
+                }
+                _ => vec![(next, None)],
+            };
+
+            for (to, label) in edges {
+                match label {
+                    Some(label) => {
+                        let _ = writeln!(dot, "    {pc} -> {to} [label=\"{label}\"];");
+                    }
+                    None => {
+                        let _ = writeln!(dot, "    {pc} -> {to};");
+                    }
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// 命令列を評価し､lineとマッチングする
+    ///
+    /// do_matchingと同じくlineの先頭からマッチングを行い､
+    /// modeにはMatchMode::Dfsで深さ優先探索を､MatchMode::Bfsで幅優先探索を指定する
+    ///
+    /// # 返り値
+    ///
+    /// マッチングに成功した場合はOk(true)を､失敗した場合はOk(false)を返す
+    /// 評価中にエラーが起きた場合はErrを返す
+    pub fn is_match(&self, line: &str, mode: MatchMode) -> Result<bool, RegexError> {
+        let line = line.chars().collect::<Vec<char>>();
+        Ok(evaluator::eval(&self.insts, &line, mode)?)
+    }
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, inst) in self.insts.iter().enumerate() {
+            writeln!(f, "{:>04}: {}", n, inst)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 命令のジャンプ先のアドレス
+fn jump_targets(inst: &Instruction) -> Vec<usize> {
+    match inst {
+        Instruction::Jump(addr)
+        | Instruction::LookBehind(_, addr)
+        | Instruction::NegativeLookBehind(_, addr)
+        | Instruction::Cond(_, addr)
+        | Instruction::Recurse(addr)
+        | Instruction::EmptyCheckEnd(_, addr) => vec![*addr],
+        Instruction::Split(addr1, addr2) => vec![*addr1, *addr2],
+        _ => vec![],
+    }
+}
+
+/// 命令に対応するパターンの断片
+///
+/// 分岐のように､特定の断片に対応しない命令の場合はNoneを返す
+fn fragment(inst: &Instruction) -> Option<String> {
+    let s = match inst {
+        Instruction::Char(c) => {
+            if "\\.^$|?*+()[]{}".contains(*c) {
+                format!("\\{c}")
+            } else {
+                c.escape_debug().to_string()
+            }
+        }
+        Instruction::Class(..) => inst.to_string().trim_start_matches("class ").to_string(),
+        Instruction::AnyChar => "(?s).".to_string(),
+        Instruction::AnyCharExceptNewline => ".".to_string(),
+        Instruction::Grapheme => "\\X".to_string(),
+        Instruction::Save(0) => "start of match".to_string(),
+        Instruction::Save(1) => "end of match".to_string(),
+        Instruction::Save(n) if n % 2 == 0 => format!("( group {}", n / 2),
+        Instruction::Save(n) => format!(") group {}", n / 2),
+        Instruction::Backref(n) => format!("\\{n}"),
+        Instruction::BackrefIgnoreCase(n) => format!("(?i)\\{n}"),
+        Instruction::LookBehind(..) => "(?<=".to_string(),
+        Instruction::NegativeLookBehind(..) => "(?<!".to_string(),
+        Instruction::Cond(n, _) => format!("(?({n})"),
+        Instruction::Recurse(_) => "(?R)".to_string(),
+        Instruction::MatchBegin => "\\A".to_string(),
+        Instruction::MatchStart => "\\G".to_string(),
+        Instruction::MatchEnd(_) => "$".to_string(),
+        Instruction::MatchLineBegin(_) => "(?m)^".to_string(),
+        Instruction::MatchLineEnd(_) => "(?m)$".to_string(),
+        Instruction::MatchTextEnd => "\\z".to_string(),
+        Instruction::Match
+        | Instruction::Jump(_)
+        | Instruction::Split(..)
+        | Instruction::Return
+        | Instruction::EmptyCheckStart(_)
+        | Instruction::EmptyCheckEnd(..) => return None,
+    };
+    Some(s)
+}
+
+/// program[pc]のように､pc番目の命令を取得する
+///
+/// pcが命令の数以上の場合はパニックする
+impl Index<usize> for Program {
+    type Output = Instruction;
+
+    fn index(&self, pc: usize) -> &Instruction {
+        &self.insts[pc]
+    }
+}
@@ -0,0 +1,2229 @@
+//! コンパイル済みの正規表現と､マッチ結果を表す型
+use super::RegexError;
+use super::{
+    codegen,
+    dfa::{Dfa, LazyCache},
+    evaluator,
+    onepass::OnePass,
+    parser,
+    parser::{Ast, ParseError},
+    CodeGenError, Dialect, Flags, Instruction, Limits, LineTerminator, MatchMode, Stats,
+};
+use alloc::{
+    borrow::Cow,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    fmt::Display,
+    ops::{Index, Range},
+};
+
+/// コンパイル済みの正規表現
+///
+/// パースとコード生成は生成時に1度だけ行い､同じパターンで何度もマッチングする場合に利用する
+/// マッチングは深さ優先探索で行い､探索の方式や資源の上限はRegexBuilderで変更できる
+/// 後方参照などを含まないパターンでは､is_matchなどのマッチの有無の判定にDFAを用いる
+/// 分岐が入力の1文字で一意に決まるパターンでは､マッチの位置とキャプチャも探索せずに1度の走査で求める
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::Regex;
+/// let re = Regex::new("\\d+").unwrap();
+/// let m = re.find("tel: 03-1234").unwrap().unwrap();
+/// assert_eq!((m.start(), m.end(), m.as_str()), (5, 7, "03"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Regex {
+    expr: String,
+    code: Vec<Instruction>,
+    names: Arc<[Option<String>]>, // グループの番号順に並べた､キャプチャグループの名前
+    limits: Limits,
+    mode: MatchMode,
+    edits: usize, // 近似マッチングで1回のマッチに許容する編集の回数
+    info: Info,
+    dfa: Option<Arc<Dfa>>, // マッチの有無の判定に用いるDFA｡構築できない場合はNone
+    onepass: Option<Arc<OnePass>>, // 1度の走査でマッチングする評価器｡構築できない場合はNone
+    reverse: Option<Arc<Reverse>>, // rfindでマッチの開始位置を求めるDFA｡構築できない場合はNone
+}
+
+/// 逆順にした文字列を読み､マッチが始まる最も後ろの位置を求めるDFA
+#[derive(Debug)]
+struct Reverse {
+    code: Vec<Instruction>, // パターンを逆順にした式の命令列
+    dfa: Dfa,
+}
+
+impl Reverse {
+    /// charsのいずれかの位置から始まるマッチのうち､最も後ろの開始位置を求める
+    ///
+    /// マッチしない場合はSome(None)を返し､遅延DFAの評価を止めた場合はNoneを返す
+    fn last_start(&self, chars: &[char]) -> Option<Option<usize>> {
+        // 末尾から読み､最も前で終わる逆順のマッチが最も後ろから始まるマッチとなる
+        let rev = chars.iter().rev().copied().collect::<Vec<char>>();
+        let end = self
+            .dfa
+            .find_end(&self.code, &rev, 0, &mut LazyCache::default())?;
+        Some(end.map(|end| chars.len() - end))
+    }
+}
+
+/// パターンのASTから求めた､マッチする文字列の性質
+///
+/// regexer!マクロが展開するコードから生成するため公開しているが､直接利用することは想定しない
+#[doc(hidden)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Info {
+    pub min_len: usize,         // マッチする文字列の最小の文字数
+    pub max_len: Option<usize>, // マッチする文字列の最大の文字数｡Noneの場合は上限なし
+    pub prefix: String,         // 全てのマッチが持つリテラルの接頭辞
+    pub suffix: String,         // 全てのマッチが持つリテラルの接尾辞
+    pub anchored_start: bool,   // 全てのマッチが文字列の先頭から始まるか
+    pub anchored_end: bool,     // 全てのマッチが文字列の末尾か､末尾の改行の直前で終わるか
+}
+
+impl Info {
+    fn new(ast: &Ast) -> Info {
+        Info {
+            min_len: ast.min_len(),
+            max_len: ast.max_len(),
+            prefix: ast.literal_prefix(),
+            suffix: ast.literal_suffix(),
+            anchored_start: ast.is_anchored_start(),
+            anchored_end: ast.is_anchored_end(),
+        }
+    }
+
+    /// selfかotherのいずれかにマッチする式の性質
+    fn or(&self, other: &Info) -> Info {
+        let prefix = common_prefix(self.prefix.chars(), other.prefix.chars());
+        let mut suffix = common_prefix(self.suffix.chars().rev(), other.suffix.chars().rev());
+        suffix.reverse();
+
+        Info {
+            min_len: self.min_len.min(other.min_len),
+            max_len: self.max_len.zip(other.max_len).map(|(a, b)| a.max(b)),
+            prefix: prefix.into_iter().collect(),
+            suffix: suffix.into_iter().collect(),
+            anchored_start: self.anchored_start && other.anchored_start,
+            anchored_end: self.anchored_end && other.anchored_end,
+        }
+    }
+
+    /// selfの後にotherが続く式の性質
+    ///
+    /// resetはotherが\Kを含むかで､含む場合はマッチの開始位置がotherの中となる
+    fn concat(&self, other: &Info, reset: bool) -> Info {
+        // マッチする文字列が接頭辞(接尾辞)そのものである場合は､続く式のリテラルとつなげられる
+        let is_literal = |info: &Info, s: &str| info.max_len == Some(s.chars().count());
+        let prefix = if reset {
+            String::new()
+        } else if is_literal(self, &self.prefix) {
+            self.prefix.clone() + &other.prefix
+        } else {
+            self.prefix.clone()
+        };
+        let suffix = if !reset && is_literal(other, &other.suffix) {
+            self.suffix.clone() + &other.suffix
+        } else {
+            other.suffix.clone()
+        };
+
+        Info {
+            min_len: self.min_len.saturating_add(other.min_len),
+            max_len: self
+                .max_len
+                .zip(other.max_len)
+                .and_then(|(a, b)| a.checked_add(b)),
+            prefix,
+            suffix,
+            anchored_start: self.anchored_start,
+            anchored_end: other.anchored_end,
+        }
+    }
+}
+
+/// 2つの文字の並びに共通する先頭の文字
+fn common_prefix<I: Iterator<Item = char>>(a: I, b: I) -> Vec<char> {
+    a.zip(b)
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a)
+        .collect()
+}
+
+impl Regex {
+    /// 正規表現をコンパイル
+    ///
+    /// パターンにエラーがある場合はErrを返す
+    pub fn new(expr: &str) -> Result<Regex, RegexError> {
+        Regex::with_flags(expr, Flags::default())
+    }
+
+    /// オプションを指定して､正規表現をコンパイル
+    ///
+    /// flagsの意味はdo_matching_with_flagsと同じだが､
+    /// マッチ位置が変わらないよう､flags.normalizationによる正規化は行わない
+    pub fn with_flags(expr: &str, flags: Flags) -> Result<Regex, RegexError> {
+        RegexBuilder::new(expr).flags(flags).build()
+    }
+
+    /// selfかotherのいずれかにマッチする正規表現を生成
+    ///
+    /// パターンを文字列として組み立て直さず､コンパイル済みの命令列をつなげる
+    /// コンパイル時のフラグはそれぞれの式に対して保たれ､(?R)はそれぞれの式自身を再帰する
+    /// otherのキャプチャグループの番号は､selfのグループの数だけ後ろにずらす
+    /// 探索の方式や資源の上限､近似マッチングの編集の回数はselfのものを引き継ぐ
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::{Regex, RegexBuilder};
+    /// let date = Regex::new("(?<y>\\d{4})-(\\d\\d)").unwrap();
+    /// let word = RegexBuilder::new("(?<w>[a-z]+)").case_insensitive(true).build().unwrap();
+    /// let re = date.or(&word).unwrap();
+    /// assert_eq!(re.as_str(), "(?:(?<y>\\d{4})-(\\d\\d))|(?:(?<w>[a-z]+))");
+    /// assert_eq!(re.capture_index("w"), Some(3));
+    ///
+    /// let caps = re.captures("-- Hello").unwrap().unwrap();
+    /// assert_eq!(&caps["w"], "Hello");
+    /// assert!(caps.name("y").is_none());
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// 2つの式に同じ名前のグループがある場合や､命令数が上限を超えた場合はErrを返す
+    pub fn or(&self, other: &Regex) -> Result<Regex, RegexError> {
+        self.combine(other, true)
+    }
+
+    /// selfの後にotherが続く式にマッチする正規表現を生成
+    ///
+    /// 命令列のつなぎ方やキャプチャグループの番号の扱いはorと同じ
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let key = Regex::new("(\\w+)=").unwrap();
+    /// let value = Regex::new("(\\d+)(?:,\\1)*").unwrap();
+    /// let re = key.concat(&value).unwrap();
+    ///
+    /// // otherの\1は､ずらした後のグループ2を参照する
+    /// let caps = re.captures("x=3,3,4").unwrap().unwrap();
+    /// assert_eq!((&caps[0], &caps[1], &caps[2]), ("x=3,3", "x", "3"));
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// 2つの式に同じ名前のグループがある場合や､命令数が上限を超えた場合はErrを返す
+    pub fn concat(&self, other: &Regex) -> Result<Regex, RegexError> {
+        self.combine(other, false)
+    }
+
+    /// selfとotherの命令列をつなげる
+    ///
+    /// orがtrueの場合は以下の､falseの場合はe1とe2を並べたコードを生成
+    /// ```text
+    ///     save 0
+    ///     split L1, L2
+    /// L1: e1のコード
+    ///     jmp L3
+    /// L2: e2のコード
+    /// L3: save 1
+    ///     match
+    /// ```
+    fn combine(&self, other: &Regex, or: bool) -> Result<Regex, RegexError> {
+        let expr = if or {
+            format!("(?:{})|(?:{})", self.expr, other.expr)
+        } else {
+            format!("(?:{})(?:{})", self.expr, other.expr)
+        };
+
+        let mut names = self.names.to_vec();
+        for name in other.names.iter().skip(1) {
+            if let Some(name) = name.as_deref() {
+                if self.capture_index(name).is_some() {
+                    // 結合したパターン全体をエラーの範囲とする
+                    let span = 0..expr.chars().count();
+                    return Err(ParseError::DuplicateGroupName(span, name.to_string()).into());
+                }
+            }
+            names.push(name.clone());
+        }
+
+        let (body1, body2) = (body(&self.code), body(&other.code));
+        let mut code = Vec::with_capacity(body1.len() + body2.len() + 5);
+        code.push(Instruction::Save(0));
+        if or {
+            let l2 = body1.len() + 3;
+            code.push(Instruction::Split(2, l2));
+            relocate(&mut code, body1, 0);
+            code.push(Instruction::Jump(l2 + body2.len()));
+        } else {
+            relocate(&mut code, body1, 0);
+        }
+        relocate(&mut code, body2, self.names.len() - 1);
+        code.push(Instruction::Save(1));
+        code.push(Instruction::Match);
+        if code.len() > self.limits.insts {
+            return Err(CodeGenError::ProgramTooLarge(self.limits.insts).into());
+        }
+
+        let info = if or {
+            self.info.or(&other.info)
+        } else {
+            let reset = body2.iter().any(|i| matches!(i, Instruction::Save(0)));
+            self.info.concat(&other.info, reset)
+        };
+
+        Ok(Regex {
+            dfa: build_dfa(&code, &self.limits, self.edits),
+            onepass: build_onepass(&code, &self.limits, self.edits),
+            reverse: None,
+            expr,
+            code,
+            names: names.into(),
+            limits: self.limits,
+            mode: self.mode,
+            edits: self.edits,
+            info,
+        })
+    }
+
+    /// コード生成済みの命令列から正規表現を生成
+    ///
+    /// regexer-macrosのregexer!マクロが展開するコードから呼ばれる
+    /// code以降の引数はRegex::new(expr)で生成した場合と一致している必要がある
+    #[doc(hidden)]
+    pub fn __from_compiled(
+        expr: &str,
+        code: Vec<Instruction>,
+        names: &[Option<&str>],
+        info: Info,
+    ) -> Regex {
+        Regex {
+            expr: expr.to_string(),
+            dfa: build_dfa(&code, &Limits::default(), 0),
+            onepass: build_onepass(&code, &Limits::default(), 0),
+            reverse: None,
+            code,
+            names: names.iter().map(|n| n.map(String::from)).collect(),
+            limits: Limits::default(),
+            mode: MatchMode::Dfs,
+            edits: 0,
+            info,
+        }
+    }
+
+    /// ASTのコード生成を行い､exprをパターンの文字列とする正規表現を生成
+    ///
+    /// editsは近似マッチングで1回のマッチに許容する編集の回数で､0の場合は厳密なマッチングとなる
+    pub(crate) fn from_ast(
+        expr: String,
+        ast: &Ast,
+        limits: Limits,
+        mode: MatchMode,
+        edits: usize,
+    ) -> Result<Regex, RegexError> {
+        let code = codegen::gen_code_with_limits(ast, &limits)?;
+
+        Ok(Regex {
+            expr,
+            dfa: build_dfa(&code, &limits, edits),
+            onepass: build_onepass(&code, &limits, edits),
+            reverse: build_reverse(ast, &limits, edits),
+            code,
+            names: ast.capture_names().into(),
+            limits,
+            mode,
+            edits,
+            info: Info::new(ast),
+        })
+    }
+
+    /// コンパイルした命令列
+    ///
+    /// analysisモジュールが､命令列からNFAを構築するために利用する
+    pub(crate) fn instructions(&self) -> &[Instruction] {
+        &self.code
+    }
+
+    /// コンパイル元のパターンを返す
+    pub fn as_str(&self) -> &str {
+        &self.expr
+    }
+
+    /// マッチ全体を含めた､キャプチャグループの数
+    ///
+    /// 例えば､(a)(?<x>b)は3となる
+    pub fn captures_len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// キャプチャグループの名前を､グループの番号順に返すイテレータを生成
+    ///
+    /// 0番目はマッチ全体を表し､名前のないグループと同じくNoneとなる
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("(?<year>\\d{4})-(\\d{2})-(?<day>\\d{2})").unwrap();
+    /// let names = re.capture_names().collect::<Vec<_>>();
+    /// assert_eq!(names, vec![None, Some("year"), None, Some("day")]);
+    /// assert_eq!(re.captures_len(), 4);
+    /// assert_eq!(re.capture_index("day"), Some(3));
+    /// ```
+    pub fn capture_names(&self) -> impl Iterator<Item = Option<&str>> + '_ {
+        self.names.iter().map(|name| name.as_deref())
+    }
+
+    /// nameという名前のグループの番号を返す
+    ///
+    /// その名前のグループが存在しない場合はNoneを返す
+    pub fn capture_index(&self, name: &str) -> Option<usize> {
+        self.capture_names().position(|n| n == Some(name))
+    }
+
+    /// マッチする文字列の最小の長さを､文字単位で返す
+    ///
+    /// これより文字数の少ない行はマッチしないため､探索前に読み飛ばせる
+    /// 後方参照と(?R)は空文字列にもマッチしうるものとして数える
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("\\d{3}-\\d{4}").unwrap();
+    /// assert_eq!((re.min_len(), re.max_len()), (8, Some(8)));
+    ///
+    /// let re = Regex::new("ab+|c?").unwrap();
+    /// assert_eq!((re.min_len(), re.max_len()), (0, None));
+    /// ```
+    pub fn min_len(&self) -> usize {
+        self.info.min_len
+    }
+
+    /// マッチする文字列の最大の長さを､文字単位で返す
+    ///
+    /// a*や\X､後方参照､(?R)のように上限がない場合はNoneを返す
+    pub fn max_len(&self) -> Option<usize> {
+        self.info.max_len
+    }
+
+    /// 全てのマッチが文字列の先頭から始まるかを判定
+    ///
+    /// \Aや､マルチラインモードでない^で始まるパターンがtrueとなる
+    /// trueの場合は､文字列の先頭のみを調べればよい
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("^GET (\\S+)").unwrap();
+    /// assert!(re.is_anchored_start());
+    /// assert!(!re.is_anchored_end());
+    /// assert_eq!(re.literal_prefix(), "GET ");
+    ///
+    /// let re = Regex::new("(?m)^\\d+(?:px|em)$").unwrap();
+    /// assert!(!re.is_anchored_start());
+    /// assert_eq!((re.literal_prefix(), re.literal_suffix()), ("", ""));
+    /// ```
+    pub fn is_anchored_start(&self) -> bool {
+        self.info.anchored_start
+    }
+
+    /// 全てのマッチが文字列の末尾か､末尾の改行の直前で終わるかを判定
+    ///
+    /// \zや\Z､マルチラインモードでない$で終わるパターンがtrueとなる
+    pub fn is_anchored_end(&self) -> bool {
+        self.info.anchored_end
+    }
+
+    /// 全てのマッチが共通して持つ､リテラルの接頭辞を返す
+    ///
+    /// 持たない場合は空文字列を返す
+    /// パターン全体がリテラルの場合は､パターンがマッチする文字列そのものとなる
+    pub fn literal_prefix(&self) -> &str {
+        &self.info.prefix
+    }
+
+    /// 全てのマッチが共通して持つ､リテラルの接尾辞を返す
+    ///
+    /// 持たない場合は空文字列を返す
+    pub fn literal_suffix(&self) -> &str {
+        &self.info.suffix
+    }
+
+    /// パターン全体にマッチする文字列を､rngを用いて無作為に生成
+    ///
+    /// 分岐や繰り返しの回数を無作為に選び､.や否定の文字クラスには主に表示可能なASCII文字を生成する
+    /// 後読みなどの条件を満たす文字列を生成できなかった場合はNoneを返す
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use rand_core::SeedableRng;
+    /// use regexer::Regex;
+    ///
+    /// let re = Regex::new("(?<y>\\d{4})-(0[1-9]|1[0-2])").unwrap();
+    /// let mut rng = rand_pcg::Pcg32::seed_from_u64(1);
+    /// for _ in 0..10 {
+    ///     let s = re.sample(&mut rng).unwrap();
+    ///     let caps = re.captures(&s).unwrap().unwrap();
+    ///     assert_eq!(caps.get(0).unwrap().as_str(), s);
+    /// }
+    /// ```
+    #[cfg(feature = "sample")]
+    pub fn sample<R: rand_core::RngCore + ?Sized>(&self, rng: &mut R) -> Option<String> {
+        super::sample::sample(&self.code, &self.limits, rng)
+    }
+
+    /// haystack中のいずれかの位置でマッチするかを判定
+    ///
+    /// 呼び出しごとに作業領域を確保するため､繰り返し判定する場合はis_match_withを利用する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("\\d+").unwrap();
+    /// assert!(re.is_match("tel: 03").unwrap());
+    /// assert!(!re.is_match("tel: none").unwrap());
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// マッチした場合はOk(true)を､マッチしなかった場合はOk(false)を返す
+    /// 評価中にエラーが起きた場合はErrを返す
+    pub fn is_match(&self, haystack: &str) -> Result<bool, RegexError> {
+        self.is_match_with(&mut Cache::new(), haystack)
+    }
+
+    /// cacheの作業領域を使い回しながら､haystack中のいずれかの位置でマッチするかを判定
+    ///
+    /// Regexは評価時の状態を持たずにSendかつSyncであるため､スレッド間で共有し､
+    /// 可変な状態はスレッドごとのCacheに持たせることで､判定のたびの確保を避けられる
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::{Cache, Regex};
+    /// let re = Regex::new("err(or)?").unwrap();
+    /// let mut cache = Cache::new();
+    /// let lines = ["ok", "error: disk", "ok", "err: net"];
+    /// let n = lines.iter().filter(|l| re.is_match_with(&mut cache, l).unwrap()).count();
+    /// assert_eq!(n, 2);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// is_matchと同様
+    pub fn is_match_with(&self, cache: &mut Cache, haystack: &str) -> Result<bool, RegexError> {
+        cache.chars.clear();
+        cache.chars.extend(haystack.chars());
+
+        let dfa = self.dfa.as_ref();
+        if let Some(is_match) =
+            dfa.and_then(|dfa| dfa.is_match(&self.code, &cache.chars, 0, cache.scratch.lazy()))
+        {
+            return Ok(is_match);
+        }
+        self.search_chars(&cache.chars, 0, &mut cache.scratch)
+    }
+
+    /// 複数のhaystackのそれぞれについて､いずれかの位置でマッチするかを判定
+    ///
+    /// 作業領域を全てのhaystackで使い回すため､is_matchを繰り返し呼ぶよりも確保が少ない
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("^\\d{3}-\\d{4}$").unwrap();
+    /// let rows = ["123-4567", "1234567", "", "000-0000"];
+    /// assert_eq!(re.matches_many(&rows).unwrap(), vec![true, false, false, true]);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// haystacksと同じ順に判定結果を並べてOkで返す
+    /// いずれかの評価中にエラーが起きた場合はErrを返す
+    pub fn matches_many(&self, haystacks: &[&str]) -> Result<Vec<bool>, RegexError> {
+        let mut cache = Cache::new();
+        haystacks
+            .iter()
+            .map(|haystack| self.is_match_with(&mut cache, haystack))
+            .collect()
+    }
+
+    /// haystack中の重ならないマッチの数を数える
+    ///
+    /// find_iterと同じマッチを数えるが､マッチごとのslotsの複製やバイト単位の位置への変換を行わない
+    /// DFAを構築できる場合は､以降にマッチがないと分かった時点で探索を終える
+    /// 後方参照などでグループの位置を読まないパターンは､マッチ全体の位置のみを記録して評価する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("ERROR|WARN").unwrap();
+    /// let log = "INFO a\nWARN b\nERROR c\nERROR d\n";
+    /// assert_eq!(re.count(log).unwrap(), 3);
+    /// assert_eq!(re.count(log).unwrap(), re.find_iter(log).count());
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// マッチの数をOkで返す
+    /// 評価中にエラーが起きた場合はErrを返す
+    pub fn count(&self, haystack: &str) -> Result<usize, RegexError> {
+        let chars = haystack.chars().collect::<Vec<char>>();
+        let mut scratch = evaluator::Scratch::default();
+        let whole = self.whole_match_only();
+        let re = whole.as_ref().unwrap_or(self);
+
+        let mut count = 0;
+        let mut pos = 0;
+        while pos <= chars.len() {
+            // 探索はDFAでposより後ろにマッチがないと分かった時点で終わる
+            if !re.search_chars(&chars, pos, &mut scratch)? {
+                break;
+            }
+            count += 1;
+
+            // find_iterと同じく､空文字列にマッチした場合は1文字進める
+            let (_, end) = slot_span(scratch.slots());
+            pos = if scratch.start() == end { end + 1 } else { end };
+        }
+
+        Ok(count)
+    }
+
+    /// グループの位置を記録するsave命令を読み飛ばす命令列に置き換えたRegexを生成
+    ///
+    /// 命令の位置を変えないよう､save命令はjump命令に置き換える
+    /// 後方参照や条件分岐でグループの位置を読む場合や､1度の走査で評価できる場合はNoneを返す
+    fn whole_match_only(&self) -> Option<Regex> {
+        let reads_groups = self.code.iter().any(|i| {
+            matches!(
+                i,
+                Instruction::Backref(_) | Instruction::BackrefIgnoreCase(_) | Instruction::Cond(..)
+            )
+        });
+        if reads_groups || self.onepass.is_some() || self.names.len() < 2 {
+            return None;
+        }
+
+        let code = self
+            .code
+            .iter()
+            .enumerate()
+            .map(|(pc, i)| match i {
+                Instruction::Save(n) if *n >= 2 => Instruction::Jump(pc + 1),
+                _ => i.clone(),
+            })
+            .collect();
+        Some(Regex {
+            code,
+            ..self.clone()
+        })
+    }
+
+    /// haystack中で最も左にあるマッチを探す
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("b+").unwrap();
+    /// assert_eq!(re.find("abbbc").unwrap().unwrap().range(), 1..4);
+    /// assert!(re.find("ac").unwrap().is_none());
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// マッチした場合はOk(Some(..))を､マッチしなかった場合はOk(None)を返す
+    /// 再帰呼び出しの上限を超えたなど､評価中にエラーが起きた場合はErrを返す
+    pub fn find<'h>(&self, haystack: &'h str) -> Result<Option<Match<'h>>, RegexError> {
+        let input = Input::new(haystack);
+        Ok(self
+            .search(&input, 0, &mut evaluator::Scratch::default())?
+            .map(|slots| input.to_match(&slots)))
+    }
+
+    /// 複数のhaystackのそれぞれについて､最も左にあるマッチを探す
+    ///
+    /// matches_manyと同じく､作業領域を全てのhaystackで使い回す
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("[A-Z]+-\\d+").unwrap();
+    /// let titles = ["fix: JIRA-12 crash", "docs", "ABC-1 and XY-2"];
+    /// let ids = re
+    ///     .find_many(&titles)
+    ///     .unwrap()
+    ///     .into_iter()
+    ///     .map(|m| m.map(|m| m.as_str()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(ids, vec![Some("JIRA-12"), None, Some("ABC-1")]);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// haystacksと同じ順にfindの結果を並べてOkで返す
+    /// いずれかの評価中にエラーが起きた場合はErrを返す
+    pub fn find_many<'h>(
+        &self,
+        haystacks: &[&'h str],
+    ) -> Result<Vec<Option<Match<'h>>>, RegexError> {
+        let mut input = Input::new("");
+        let mut scratch = evaluator::Scratch::default();
+        haystacks
+            .iter()
+            .map(|haystack| {
+                input.reset(haystack);
+                let is_match = self.search_chars(&input.chars, 0, &mut scratch)?;
+                Ok(is_match.then(|| input.to_match(scratch.slots())))
+            })
+            .collect()
+    }
+
+    /// findと同じマッチを探し､評価器の実行時の統計と共に返す
+    ///
+    /// 統計はマッチを試みた全ての開始位置での合計となる
+    /// 1度の走査で評価できるパターンやDFAでマッチがないと分かる場合も､統計を求めるために指定された方式で探索する
+    /// 同じ文字列に対するパターンの書き方による処理量の違いを比べるために利用する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::{Limits, Regex, RegexBuilder};
+    /// let haystack = "a".repeat(12);
+    /// let limits = Limits {
+    ///     visited: 0,
+    ///     ..Default::default()
+    /// };
+    /// let re = RegexBuilder::new("(a|aa)+b").limits(limits).build().unwrap();
+    /// let (m, nested) = re.find_with_stats(&haystack).unwrap();
+    /// assert!(m.is_none());
+    /// let (_, flat) = Regex::new("a+b").unwrap().find_with_stats(&haystack).unwrap();
+    /// assert!(nested.steps > flat.steps * 10);
+    /// assert!(nested.backtracks > flat.backtracks);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// findの結果と統計の組をOkで返す
+    /// 評価中にエラーが起きた場合はErrを返す
+    pub fn find_with_stats<'h>(
+        &self,
+        haystack: &'h str,
+    ) -> Result<(Option<Match<'h>>, Stats), RegexError> {
+        let input = Input::new(haystack);
+        let mut scratch = evaluator::Scratch::default();
+        let is_match = self.search_with(&input.chars, 0, false, &mut scratch)?;
+        let m = is_match.then(|| input.to_match(scratch.slots()));
+
+        Ok((m, scratch.stats()))
+    }
+
+    /// haystack中の重ならないマッチのうち､最後のものを探す
+    ///
+    /// find_iterの最後のマッチと同じマッチを返す
+    /// 末尾から逆順に読むDFAでマッチが始まる最も後ろの位置を求め､マッチがない場合は探索せずに終える
+    /// マッチがある場合は先頭から順にマッチを探し､その位置より後ろからはマッチしないと分かった時点で終える
+    /// 後方参照や後読みなどを含み逆順のDFAを構築できないパターンでは､全てのマッチを探す
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("v\\d+").unwrap();
+    /// assert_eq!(re.rfind("v1 v22 v333").unwrap().unwrap().as_str(), "v333");
+    ///
+    /// // 重ならないマッチの最後のため､aaaaaでは最も後ろから始まる3..5ではなく2..4となる
+    /// let re = Regex::new("aa").unwrap();
+    /// assert_eq!(re.rfind("aaaaa").unwrap().unwrap().range(), 2..4);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// findと同様
+    pub fn rfind<'h>(&self, haystack: &'h str) -> Result<Option<Match<'h>>, RegexError> {
+        let input = Input::new(haystack);
+        let last = match self
+            .reverse
+            .as_deref()
+            .and_then(|r| r.last_start(&input.chars))
+        {
+            Some(Some(start)) => start,
+            Some(None) => return Ok(None),
+            None => input.chars.len(),
+        };
+
+        // 次に探し始める位置がlastを超えた場合は､それ以降から始まるマッチはない
+        let mut searcher = Searcher::new(self, input);
+        let mut span = None;
+        while searcher.pos <= last {
+            let Some(found) = searcher.advance() else {
+                break;
+            };
+            found?;
+            span = Some(slot_span(searcher.scratch.slots()));
+        }
+
+        Ok(span.map(|(start, end)| searcher.input.to_match(&[Some(start), Some(end)])))
+    }
+
+    /// haystackのstartバイト目以降で､最も左にあるマッチを探す
+    ///
+    /// haystackをstartで切り出して探す場合とは異なり､^や後読みはstartより前の文字も考慮する
+    /// 字句解析器のように､前回のマッチの終了位置から続けて探す場合に利用する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// // 単語の先頭のxから始まる2文字
+    /// let re = Regex::new("(?<!\\w)x\\w").unwrap();
+    /// assert_eq!(re.find_at("xa bxb xc", 4).unwrap().unwrap().range(), 7..9);
+    /// // 切り出した場合は､bxbのxが単語の先頭となる
+    /// assert_eq!(re.find(&"xa bxb xc"[4..]).unwrap().unwrap().range(), 0..2);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// findと同様で､位置はhaystack全体の先頭からのバイト単位となる
+    /// startが文字の境界でないか､haystackの長さを超える場合はErrを返す
+    pub fn find_at<'h>(
+        &self,
+        haystack: &'h str,
+        start: usize,
+    ) -> Result<Option<Match<'h>>, RegexError> {
+        let input = Input::new(haystack);
+        let start = input.char_pos(start)?;
+        Ok(self
+            .search(&input, start, &mut evaluator::Scratch::default())?
+            .map(|slots| input.to_match(&slots)))
+    }
+
+    /// haystackのstartバイト目以降のいずれかの位置でマッチするかを判定
+    ///
+    /// startより前の文字の扱いと返り値はfind_atと同様
+    pub fn is_match_at(&self, haystack: &str, start: usize) -> Result<bool, RegexError> {
+        let input = Input::new(haystack);
+        let start = input.char_pos(start)?;
+        let dfa = self.dfa.as_ref();
+        if let Some(is_match) = dfa.and_then(|dfa| {
+            dfa.is_match(&self.code, &input.chars, start, &mut LazyCache::default())
+        }) {
+            return Ok(is_match);
+        }
+        self.search_chars(&input.chars, start, &mut evaluator::Scratch::default())
+    }
+
+    /// haystackのstartバイト目から始まるマッチを探す
+    ///
+    /// find_atとは異なり､startより後ろの位置から始まるマッチは探さない
+    /// パターン中の\Gはstartの位置にマッチする
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// // 字句解析器のように､前のトークンの直後から次のトークンを読み込む
+    /// let re = Regex::new("\\d+|[+*]").unwrap();
+    /// let input = "12+3*45";
+    /// let mut pos = 0;
+    /// let mut tokens = Vec::new();
+    /// while let Some(m) = re.find_anchored_at(input, pos).unwrap() {
+    ///     tokens.push(m.as_str());
+    ///     pos = m.end();
+    /// }
+    /// assert_eq!(tokens, vec!["12", "+", "3", "*", "45"]);
+    /// assert!(re.find_anchored_at("a1", 0).unwrap().is_none());
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// find_atと同様
+    pub fn find_anchored_at<'h>(
+        &self,
+        haystack: &'h str,
+        start: usize,
+    ) -> Result<Option<Match<'h>>, RegexError> {
+        let input = Input::new(haystack);
+        let start = input.char_pos(start)?;
+        let mut scratch = evaluator::Scratch::default();
+        let is_match = self.eval_at(&input.chars, start, true, &mut scratch)?;
+        Ok(is_match.then(|| input.to_match(scratch.slots())))
+    }
+
+    /// バイト列中で最も左にあるマッチを探す
+    ///
+    /// UTF-8として不正なバイトは､1バイトずつU+FFFDの文字として扱う
+    /// そのため､不正なバイトは.や[^a]､\x{FFFD}にマッチし､マッチの位置が不正なバイトの途中となることはない
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("id=\\d+").unwrap();
+    /// let m = re.find_bytes(b"\xff\xfeid=42\x00").unwrap().unwrap();
+    /// assert_eq!((m.range(), m.as_bytes()), (2..7, &b"id=42"[..]));
+    /// assert!(Regex::new("a.b").unwrap().find_bytes(b"a\xffb").unwrap().is_some());
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// findと同様
+    pub fn find_bytes<'h>(&self, haystack: &'h [u8]) -> Result<Option<BytesMatch<'h>>, RegexError> {
+        let input = Input::from_bytes(haystack);
+        Ok(self
+            .search(&input, 0, &mut evaluator::Scratch::default())?
+            .map(|slots| input.to_bytes_match(&slots)))
+    }
+
+    /// バイト列中の重ならない全てのマッチを､左から順に返すイテレータを生成
+    ///
+    /// マッチの探し方はfind_iterと､不正なバイトの扱いはfind_bytesと同じ
+    pub fn find_iter_bytes<'r, 'h>(&'r self, haystack: &'h [u8]) -> BytesMatches<'r, 'h> {
+        BytesMatches(Searcher::new(self, Input::from_bytes(haystack)))
+    }
+
+    /// 最も前で終わるマッチの終了位置を返す
+    ///
+    /// 最も左にあるマッチを最長一致で探すfindとは異なり､
+    /// どこかで始まるマッチのうち､最も早く終わるものの終了位置を返す
+    /// マッチするかの判定や､どこまで読めばマッチが確定するかを知りたい場合に利用する
+    ///
+    /// 探索の方式の指定によらず､深さ優先探索で全ての経路を探す
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("a+").unwrap();
+    /// assert_eq!(re.shortest_match("baaa").unwrap(), Some(2));
+    /// assert_eq!(re.find("baaa").unwrap().unwrap().end(), 4);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// マッチした場合は､バイト単位の終了位置をOk(Some(..))で返し､マッチしなかった場合はOk(None)を返す
+    /// 評価中にエラーが起きた場合はErrを返す
+    pub fn shortest_match(&self, haystack: &str) -> Result<Option<usize>, RegexError> {
+        let input = Input::new(haystack);
+        let end = evaluator::eval_shortest(&self.code, &input.chars, 0, self.edits, &self.limits)?;
+        Ok(end.map(|end| input.offsets[end]))
+    }
+
+    /// haystack中で最も左にあるマッチを探し､キャプチャグループごとのマッチを返す
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("(\\d+)-(\\d+)").unwrap();
+    /// let caps = re.captures("tel: 03-1234").unwrap().unwrap();
+    /// assert_eq!(&caps[0], "03-1234");
+    /// assert_eq!(&caps[2], "1234");
+    /// assert_eq!(caps.get(1).unwrap().range(), 5..7);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// マッチした場合はOk(Some(..))を､マッチしなかった場合はOk(None)を返す
+    /// 評価中にエラーが起きた場合はErrを返す
+    pub fn captures<'h>(&self, haystack: &'h str) -> Result<Option<Captures<'h>>, RegexError> {
+        let input = Input::new(haystack);
+        Ok(self
+            .search(&input, 0, &mut evaluator::Scratch::default())?
+            .map(|slots| input.to_captures(&slots, &self.names)))
+    }
+
+    /// haystack中の重ならない全てのマッチを､左から順に返すイテレータを生成
+    ///
+    /// マッチの終了位置から次のマッチを探し､空文字列にマッチした場合は1文字進めてから探す
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("\\d+").unwrap();
+    /// let nums = re
+    ///     .find_iter("1, 22, 333")
+    ///     .map(|m| m.map(|m| m.as_str()))
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert_eq!(nums, vec!["1", "22", "333"]);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// マッチごとにOk(Match)を返すイテレータ
+    /// 評価中にエラーが起きた場合はErrを返し､以降は何も返さない
+    pub fn find_iter<'r, 'h>(&'r self, haystack: &'h str) -> Matches<'r, 'h> {
+        Matches(Searcher::new(self, Input::new(haystack)))
+    }
+
+    /// 複数行のtext中の重ならない全てのマッチを､開始位置の行と列と共に左から順に返すイテレータを生成
+    ///
+    /// マッチはfind_iterと同じで､行ごとに分割せずにtext全体から探すため､
+    /// 複数行にまたがるマッチも返す
+    /// エディタやレポートで､マッチの位置を行と列で示す場合に利用する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("TODO|FIXME").unwrap();
+    /// let text = "fn main() {\n    // TODO: 引数\n    // FIXME\n}\n// TODO\n";
+    /// let found = re
+    ///     .find_iter_located(text)
+    ///     .map(|m| m.map(|m| (m.line(), m.column(), m.as_match().as_str())))
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert_eq!(found, vec![(2, 8, "TODO"), (3, 8, "FIXME"), (5, 4, "TODO")]);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// マッチごとにOk(LocatedMatch)を返すイテレータ
+    /// 評価中にエラーが起きた場合はErrを返し､以降は何も返さない
+    pub fn find_iter_located<'r, 'h>(&'r self, text: &'h str) -> LocatedMatches<'r, 'h> {
+        LocatedMatches {
+            searcher: Searcher::new(self, Input::new(text)),
+            pos: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// 重なるものも含め､haystack中の各位置から始まるマッチを左から順に返すイテレータを生成
+    ///
+    /// find_iterとは異なり､マッチの終了位置ではなく開始位置の1文字後ろから次のマッチを探す
+    /// 各位置から始まるマッチはfindと同じく1つのみで､例えばa+はaaaに対してaaa､aa､aの3つとなる
+    /// DNA配列中のk-merのように､重なりうる全ての出現位置を数える場合に利用する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("aa").unwrap();
+    /// let starts = re
+    ///     .find_overlapping_iter("aaaa")
+    ///     .map(|m| m.map(|m| m.start()))
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert_eq!(starts, vec![0, 1, 2]);
+    /// assert_eq!(re.find_iter("aaaa").count(), 2);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// find_iterと同様
+    pub fn find_overlapping_iter<'r, 'h>(
+        &'r self,
+        haystack: &'h str,
+    ) -> OverlappingMatches<'r, 'h> {
+        OverlappingMatches(Searcher::new(self, Input::new(haystack)))
+    }
+
+    /// haystack中の重ならない全てのマッチについて､キャプチャグループごとのマッチを返すイテレータを生成
+    ///
+    /// マッチの探し方はfind_iterと同じ
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("(\\w+)=(\\w+)").unwrap();
+    /// let mut pairs = Vec::new();
+    /// for caps in re.captures_iter("level=warn code=42") {
+    ///     let caps = caps.unwrap();
+    ///     pairs.push((caps[1].to_string(), caps[2].to_string()));
+    /// }
+    /// assert_eq!(pairs, vec![("level".into(), "warn".into()), ("code".into(), "42".into())]);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// マッチごとにOk(Captures)を返すイテレータ
+    /// 評価中にエラーが起きた場合はErrを返し､以降は何も返さない
+    pub fn captures_iter<'r, 'h>(&'r self, haystack: &'h str) -> CaptureMatches<'r, 'h> {
+        CaptureMatches(Searcher::new(self, Input::new(haystack)))
+    }
+
+    /// マッチした部分を区切りとして､haystackを分割した部分文字列を順に返すイテレータを生成
+    ///
+    /// マッチの探し方はfind_iterと同じで､先頭や末尾でマッチした場合は空文字列も返す
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("\\s*,\\s*").unwrap();
+    /// let fields = re.split("a, b ,c,").collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(fields, vec!["a", "b", "c", ""]);
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// 部分文字列ごとにOk(&str)を返すイテレータ
+    /// 評価中にエラーが起きた場合はErrを返し､以降は何も返さない
+    pub fn split<'r, 'h>(&'r self, haystack: &'h str) -> Split<'r, 'h> {
+        Split {
+            matches: self.find_iter(haystack),
+            haystack,
+            last: Some(0),
+        }
+    }
+
+    /// splitと同様に分割し､最大limit個の部分文字列を返すイテレータを生成
+    ///
+    /// limit個目の部分文字列は､残りの文字列全体となる
+    /// limitが0の場合は何も返さない
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new(":").unwrap();
+    /// let fields = re.splitn("key:a:b", 2).collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(fields, vec!["key", "a:b"]);
+    /// ```
+    pub fn splitn<'r, 'h>(&'r self, haystack: &'h str, limit: usize) -> SplitN<'r, 'h> {
+        SplitN {
+            split: self.split(haystack),
+            limit,
+        }
+    }
+
+    /// 最も左にあるマッチを､repが返す文字列に置き換える
+    ///
+    /// repに文字列を与えた場合はテンプレートとして展開する
+    /// テンプレート中の$1や${1}は番号､$nameや${name}は名前で指定したグループにマッチした文字列となる
+    /// $0はマッチ全体､$$は$自体となり､存在しないグループやマッチに関与しなかったグループは空文字列となる
+    ///
+    /// repにクロージャを与えた場合は､マッチごとにCapturesを渡して呼び出し､返した文字列に置き換える
+    /// 展開せずにそのまま置き換える場合はNoExpandを与える
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("(?<y>\\d{4})-(\\d{2})").unwrap();
+    /// assert_eq!(re.replace("2024-05, 2025-06", "$2/${y}").unwrap(), "05/2024, 2025-06");
+    ///
+    /// // マッチした数に1を加える
+    /// let re = Regex::new("\\d+").unwrap();
+    /// let s = re.replace("v9.1", |caps: &regexer::Captures| {
+    ///     (caps[0].parse::<u32>().unwrap() + 1).to_string()
+    /// });
+    /// assert_eq!(s.unwrap(), "v10.1");
+    /// ```
+    ///
+    /// # 返り値
+    ///
+    /// マッチしなかった場合は､haystackを借用したOk(Cow::Borrowed(..))を返す
+    /// 評価中にエラーが起きた場合はErrを返す
+    pub fn replace<'h, R: Replacer>(
+        &self,
+        haystack: &'h str,
+        rep: R,
+    ) -> Result<Cow<'h, str>, RegexError> {
+        self.replacen(haystack, 1, rep)
+    }
+
+    /// 重ならない全てのマッチを､repが返す文字列に置き換える
+    ///
+    /// repの扱いと返り値はreplaceと同じ
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("(\\w+)@(\\w+)").unwrap();
+    /// assert_eq!(re.replace_all("a@b, c@d", "$2@$1").unwrap(), "b@a, d@c");
+    /// ```
+    pub fn replace_all<'h, R: Replacer>(
+        &self,
+        haystack: &'h str,
+        rep: R,
+    ) -> Result<Cow<'h, str>, RegexError> {
+        self.replacen(haystack, 0, rep)
+    }
+
+    /// 左から最大limit個のマッチを､repが返す文字列に置き換える
+    ///
+    /// limitが0の場合はreplace_allと同じく全てのマッチを置き換える
+    /// repの扱いと返り値はreplaceと同じ
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("o").unwrap();
+    /// assert_eq!(re.replacen("foo boo", 2, "0").unwrap(), "f00 boo");
+    /// ```
+    pub fn replacen<'h, R: Replacer>(
+        &self,
+        haystack: &'h str,
+        limit: usize,
+        mut rep: R,
+    ) -> Result<Cow<'h, str>, RegexError> {
+        let limit = if limit == 0 { usize::MAX } else { limit };
+
+        let mut result = String::new();
+        let mut last = 0; // 置き換え済みの位置
+        let mut count = 0;
+        for caps in self.captures_iter(haystack).take(limit) {
+            let caps = caps?;
+            // マッチした場合､マッチ全体のグループは常に存在する
+            let Some(m) = caps.get(0) else { continue };
+
+            // \Kによって開始位置が前回のマッチより前になる場合は､重なる部分を再び置き換えない
+            result.push_str(&haystack[last..m.start().max(last)]);
+            rep.replace_append(&caps, &mut result);
+            last = m.end().max(last);
+            count += 1;
+        }
+
+        if count == 0 {
+            return Ok(Cow::Borrowed(haystack));
+        }
+        result.push_str(&haystack[last..]);
+
+        Ok(Cow::Owned(result))
+    }
+
+    /// startから文字列の末尾まで1文字ずつずらしながらマッチングし､最初にマッチした位置のslotsを返す
+    ///
+    /// startは文字単位の位置で､slotsの位置も文字単位となる
+    /// 評価時の作業領域にはscratchを使い回す
+    fn search<H: ?Sized>(
+        &self,
+        input: &Input<H>,
+        start: usize,
+        scratch: &mut evaluator::Scratch,
+    ) -> Result<Option<Vec<Option<usize>>>, RegexError> {
+        let is_match = self.search_chars(&input.chars, start, scratch)?;
+        Ok(is_match.then(|| scratch.slots().to_vec()))
+    }
+
+    /// 文字単位に分解した入力に対して､startから1文字ずつずらしながらマッチングする
+    ///
+    /// マッチした場合はOk(true)を返し､最初にマッチした位置のslotsをscratchに残す
+    fn search_chars(
+        &self,
+        chars: &[char],
+        start: usize,
+        scratch: &mut evaluator::Scratch,
+    ) -> Result<bool, RegexError> {
+        self.search_with(chars, start, true, scratch)
+    }
+
+    /// search_charsと同様にマッチングする
+    ///
+    /// onepassがfalseの場合は､1度の走査で評価できるパターンも指定された方式の探索で評価し､
+    /// DFAによる開始位置の絞り込みも行わない
+    fn search_with(
+        &self,
+        chars: &[char],
+        start: usize,
+        onepass: bool,
+        scratch: &mut evaluator::Scratch,
+    ) -> Result<bool, RegexError> {
+        // 残りの文字数がmin_lenに満たない位置からはマッチしないため､探索しない
+        // 近似マッチングでは､削除した文字数だけ短い文字列にもマッチする
+        let min_len = self.info.min_len.saturating_sub(self.edits);
+        let Some(last) = chars.len().checked_sub(min_len) else {
+            return Ok(false);
+        };
+        // 先頭に固定されたパターンは､文字列の先頭以外からはマッチしない
+        let last = if self.info.anchored_start { 0 } else { last };
+        // DFAで最も前で終わるマッチの終了位置を求め､マッチがない場合は位置ごとに評価せずに終える
+        // 最も左のマッチはその終了位置より後ろからは始まらないため､それ以降の位置も評価しない
+        let last = match self.dfa.as_deref().filter(|_| onepass && start <= last) {
+            Some(dfa) => match dfa.find_end(&self.code, chars, start, scratch.lazy()) {
+                Some(Some(end)) => last.min(end),
+                Some(None) => return Ok(false),
+                None => last,
+            },
+            None => last,
+        };
+        // 幅優先探索は各位置から開始するスレッドを1度の走査でまとめて進める
+        // 近似マッチングは扱えないため､位置ごとの評価でErrとする
+        if self.mode == MatchMode::Bfs && self.edits == 0 && !(onepass && self.onepass.is_some()) {
+            if start > last {
+                return Ok(false);
+            }
+            let is_match =
+                evaluator::eval_search(&self.code, chars, start, last, &self.limits, scratch)?;
+            return Ok(is_match);
+        }
+        for sp in start..=last {
+            if self.eval_at(chars, sp, onepass, scratch)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// charsのsp番目の文字からマッチングし､マッチした場合はOk(true)を返してslotsをscratchに残す
+    ///
+    /// onepassがtrueで､1度の走査で評価できるパターンの場合は､探索の方式によらずその評価器を用いる
+    fn eval_at(
+        &self,
+        chars: &[char],
+        sp: usize,
+        onepass: bool,
+        scratch: &mut evaluator::Scratch,
+    ) -> Result<bool, RegexError> {
+        if let Some(onepass) = self.onepass.as_deref().filter(|_| onepass) {
+            return Ok(evaluator::eval_onepass(
+                onepass, &self.code, chars, sp, scratch,
+            ));
+        }
+        let is_match = evaluator::eval_with_scratch(
+            &self.code,
+            chars,
+            sp,
+            self.edits,
+            &self.limits,
+            self.mode,
+            scratch,
+        )?;
+        Ok(is_match)
+    }
+}
+
+/// マッチングのたびに確保し直さずに使い回す､評価時の可変な作業領域
+///
+/// 入力を文字に分解した領域や､キャプチャの位置を保存する領域を保持する
+/// どのRegexとも組み合わせて使えるが､同時に使えるのは1つのスレッドのみのため､スレッドごとに用意する
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{Cache, Regex};
+/// let re = Regex::new("a+b").unwrap();
+/// let mut cache = Cache::new();
+/// assert!(re.is_match_with(&mut cache, "xaab").unwrap());
+/// assert!(!re.is_match_with(&mut cache, "xaa").unwrap());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Cache {
+    chars: Vec<char>,            // 文字単位に分解した入力
+    scratch: evaluator::Scratch, // 評価器と遅延DFAの作業領域
+}
+
+impl Cache {
+    /// 空の作業領域を生成
+    pub fn new() -> Self {
+        Cache::default()
+    }
+}
+
+/// オプションを指定して､Regexを生成するためのビルダ
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::RegexBuilder;
+/// let re = RegexBuilder::new("^error:.*$")
+///     .case_insensitive(true)
+///     .multi_line(true)
+///     .build()
+///     .unwrap();
+/// let m = re.find("ok\nERROR: disk full\n").unwrap().unwrap();
+/// assert_eq!(m.as_str(), "ERROR: disk full");
+/// ```
+#[derive(Debug, Clone)]
+pub struct RegexBuilder {
+    expr: String,
+    flags: Flags,
+    limits: Limits,
+    mode: MatchMode,
+    edits: usize,
+}
+
+impl RegexBuilder {
+    /// パターンを指定して､既定のオプションのビルダを生成
+    pub fn new(expr: &str) -> Self {
+        RegexBuilder {
+            expr: expr.to_string(),
+            flags: Flags::default(),
+            limits: Limits::default(),
+            mode: MatchMode::Dfs,
+            edits: 0,
+        }
+    }
+
+    /// オプションをまとめて指定
+    ///
+    /// それまでに個別に指定したオプションは上書きされる
+    pub fn flags(&mut self, flags: Flags) -> &mut Self {
+        self.flags = flags;
+        self
+    }
+
+    /// \wや\dなどの略記をUnicodeの文字に対応させるかを指定
+    pub fn unicode(&mut self, yes: bool) -> &mut Self {
+        self.flags.unicode = yes;
+        self
+    }
+
+    /// パターン全体で大文字と小文字を区別しないかを指定
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut Self {
+        self.flags.case_insensitive = yes;
+        self
+    }
+
+    /// ^と$が各行の先頭と末尾にもマッチするかを指定
+    pub fn multi_line(&mut self, yes: bool) -> &mut Self {
+        self.flags.multi_line = yes;
+        self
+    }
+
+    /// .が改行にもマッチするかを指定
+    pub fn dot_all(&mut self, yes: bool) -> &mut Self {
+        self.flags.dot_all = yes;
+        self
+    }
+
+    /// パターン中の空白とコメントを無視するかを指定
+    pub fn extended(&mut self, yes: bool) -> &mut Self {
+        self.flags.extended = yes;
+        self
+    }
+
+    /// ^､$､\Zで行末とみなす改行を指定
+    pub fn line_terminator(&mut self, term: LineTerminator) -> &mut Self {
+        self.flags.line_terminator = term;
+        self
+    }
+
+    /// パターンの構文を指定
+    pub fn dialect(&mut self, dialect: Dialect) -> &mut Self {
+        self.flags.dialect = dialect;
+        self
+    }
+
+    /// NやRのようなIUPACの記号を､塩基の文字クラスとするかを指定
+    pub fn iupac(&mut self, yes: bool) -> &mut Self {
+        self.flags.iupac = yes;
+        self
+    }
+
+    /// コード生成時と評価時の資源の上限を指定
+    ///
+    /// 命令数や繰り返し回数が上限を超えるパターンは､buildがErrを返す
+    pub fn limits(&mut self, limits: Limits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+
+    /// マッチングに用いる探索の方式を指定
+    ///
+    /// MatchMode::Bfsでは後方参照などを含むパターンを評価できず､マッチング時にErrを返す
+    pub fn match_mode(&mut self, mode: MatchMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// trueの場合は深さ優先探索を､falseの場合は幅優先探索を利用
+    #[deprecated(note = "use `match_mode` instead")]
+    pub fn depth_first(&mut self, yes: bool) -> &mut Self {
+        #[allow(deprecated)]
+        self.match_mode(MatchMode::from_depth_first(yes))
+    }
+
+    /// 近似マッチングで､1回のマッチに許容する編集(置換､挿入､削除)の回数を指定
+    ///
+    /// 既定値の0では厳密なマッチングとなる
+    /// 1以上の場合はfindやcapturesなどのマッチングが編集距離k以内のマッチを探し､DFAや1度の走査での評価は用いない
+    /// MatchMode::Bfsでは近似マッチングを扱えず､マッチング時にErrを返す
+    /// min_lenなどが返すパターンの性質は､編集を考慮しない
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::RegexBuilder;
+    /// let re = RegexBuilder::new("colou?r").max_edits(1).build().unwrap();
+    /// assert_eq!(re.find("a colr chart").unwrap().unwrap().as_str(), "colr");
+    /// assert!(re.find("a clr chart").unwrap().is_none());
+    /// ```
+    pub fn max_edits(&mut self, k: usize) -> &mut Self {
+        self.edits = k;
+        self
+    }
+
+    /// 指定したオプションで正規表現をコンパイル
+    ///
+    /// パターンにエラーがある場合はErrを返す
+    pub fn build(&self) -> Result<Regex, RegexError> {
+        let ast = parser::parse_with_flags(&self.expr, self.flags)?;
+        Regex::from_ast(self.expr.clone(), &ast, self.limits, self.mode, self.edits)
+    }
+}
+
+impl Display for Regex {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+/// マッチした部分文字列
+///
+/// 位置はhaystackの先頭からのバイト単位となる
+/// haystackを借用して位置のみを持つため､マッチごとに文字列を確保しない
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'h> {
+    haystack: &'h str,
+    start: usize,
+    end: usize,
+}
+
+impl<'h> Match<'h> {
+    /// マッチの開始位置
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// マッチの終了位置
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// マッチの範囲
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// マッチした部分文字列
+    pub fn as_str(&self) -> &'h str {
+        &self.haystack[self.range()]
+    }
+
+    /// マッチが空文字列かを判定
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// マッチした文字列の長さ
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+/// let s: &str = m.into()のように､マッチした部分文字列を取り出す
+///
+/// 返す文字列はMatchではなくhaystackの寿命を持つ
+impl<'h> From<Match<'h>> for &'h str {
+    fn from(m: Match<'h>) -> Self {
+        m.as_str()
+    }
+}
+
+/// マッチの範囲を取り出す
+impl From<Match<'_>> for Range<usize> {
+    fn from(m: Match<'_>) -> Self {
+        m.range()
+    }
+}
+
+/// 複数行のテキスト中のマッチと､その開始位置の行と列
+///
+/// 行と列は1から始まり､列は行の先頭からの文字単位で数える
+/// 行は\nで区切るため､\r\nの\rは行末の文字として数える
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocatedMatch<'h> {
+    line: usize,
+    column: usize,
+    m: Match<'h>,
+}
+
+impl<'h> LocatedMatch<'h> {
+    /// マッチの開始位置の行
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// マッチの開始位置の列
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// テキストの先頭からのバイト単位の位置を持つマッチ
+    pub fn as_match(&self) -> Match<'h> {
+        self.m
+    }
+}
+
+/// バイト列中のマッチした部分
+///
+/// 位置はhaystackの先頭からのバイト単位となる
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BytesMatch<'h> {
+    haystack: &'h [u8],
+    start: usize,
+    end: usize,
+}
+
+impl<'h> BytesMatch<'h> {
+    /// マッチの開始位置
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// マッチの終了位置
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// マッチの範囲
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// マッチした部分のバイト列
+    pub fn as_bytes(&self) -> &'h [u8] {
+        &self.haystack[self.range()]
+    }
+
+    /// マッチが空かを判定
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// マッチしたバイト列の長さ
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+/// キャプチャグループごとのマッチ
+///
+/// 0番目のグループはマッチ全体を表し､1番目以降は開き括弧の順となる
+/// Matchと同じく､各グループの文字列はhaystackを借用する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures<'h> {
+    haystack: &'h str,
+    spans: Vec<Option<(usize, usize)>>, // グループごとのバイト単位の(開始位置, 終了位置)
+    names: Arc<[Option<String>]>,       // グループの番号順に並べた名前
+}
+
+impl<'h> Captures<'h> {
+    /// i番目のグループのマッチを返す
+    ///
+    /// グループが存在しないか､マッチに関与しなかった場合はNoneを返す
+    pub fn get(&self, i: usize) -> Option<Match<'h>> {
+        let (start, end) = (*self.spans.get(i)?)?;
+        Some(Match {
+            haystack: self.haystack,
+            start,
+            end,
+        })
+    }
+
+    /// nameという名前のグループのマッチを返す
+    ///
+    /// グループが存在しないか､マッチに関与しなかった場合はNoneを返す
+    pub fn name(&self, name: &str) -> Option<Match<'h>> {
+        let i = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+        self.get(i)
+    }
+
+    /// マッチ全体を含めたグループの数
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// グループの数が0かを判定
+    ///
+    /// マッチ全体を表す0番目のグループが常にあるため､falseとなる
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// グループごとのマッチを､番号順に返すイテレータを生成
+    pub fn iter(&self) -> impl Iterator<Item = Option<Match<'h>>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    /// テンプレート中の$1や${name}などを､グループにマッチした文字列に展開してdstに追加
+    ///
+    /// $1や${1}は番号､$nameや${name}は名前で指定したグループ､$0はマッチ全体､$$は$自体となる
+    /// 存在しないグループやマッチに関与しなかったグループは空文字列とし､
+    /// $の後がグループの番号や名前でない場合は､$をそのまま追加する
+    /// replace系のメソッドで文字列を与えた場合も､この方法で展開する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regexer::Regex;
+    /// let re = Regex::new("(?<key>\\w+)=(\\w+)").unwrap();
+    /// let caps = re.captures("lang=rust").unwrap().unwrap();
+    /// let mut dst = String::from("> ");
+    /// caps.expand("${key}: $2 ($$)", &mut dst);
+    /// assert_eq!(dst, "> lang: rust ($)");
+    /// ```
+    pub fn expand(&self, template: &str, dst: &mut String) {
+        let mut rest = template;
+        while let Some(i) = rest.find('$') {
+            dst.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+
+            if let Some(r) = rest.strip_prefix('$') {
+                dst.push('$');
+                rest = r;
+                continue;
+            }
+
+            // ${...}は閉じ括弧まで､それ以外は英数字と_が続く部分をグループの参照とする
+            let (name, r) = match rest.strip_prefix('{').and_then(|r| r.split_once('}')) {
+                Some(pair) => pair,
+                None => {
+                    let len = rest
+                        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .unwrap_or(rest.len());
+                    rest.split_at(len)
+                }
+            };
+            if name.is_empty() {
+                dst.push('$');
+                continue;
+            }
+
+            let m = match name.parse::<usize>() {
+                Ok(n) => self.get(n),
+                Err(_) => self.name(name),
+            };
+            if let Some(m) = m {
+                dst.push_str(m.as_str());
+            }
+            rest = r;
+        }
+        dst.push_str(rest);
+    }
+}
+
+/// &caps[i]のように､i番目のグループにマッチした文字列を取得する
+///
+/// グループが存在しないか､マッチに関与しなかった場合はパニックする
+impl Index<usize> for Captures<'_> {
+    type Output = str;
+
+    fn index(&self, i: usize) -> &str {
+        self.get(i)
+            .map(|m| m.as_str())
+            .unwrap_or_else(|| panic!("no group at index {i}"))
+    }
+}
+
+/// &caps["name"]のように､名前で指定したグループにマッチした文字列を取得する
+///
+/// グループが存在しないか､マッチに関与しなかった場合はパニックする
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::Regex;
+/// let re = Regex::new("(?<date>\\d{4}-\\d{2}-\\d{2}) (?<level>\\w+)").unwrap();
+/// let caps = re.captures("2024-05-01 WARN disk").unwrap().unwrap();
+/// assert_eq!(&caps["date"], "2024-05-01");
+/// assert_eq!(&caps["level"], "WARN");
+/// ```
+impl<'n> Index<&'n str> for Captures<'_> {
+    type Output = str;
+
+    fn index(&self, name: &'n str) -> &str {
+        self.name(name)
+            .map(|m| m.as_str())
+            .unwrap_or_else(|| panic!("no group named '{name}'"))
+    }
+}
+
+/// 重ならないマッチを左から順に探し､slotsを返すための状態
+struct Searcher<'r, 'h, H: ?Sized = str> {
+    regex: &'r Regex,
+    input: Input<'h, H>,
+    pos: usize, // 次にマッチを探し始める文字単位の位置｡文字列の長さを超えた場合は終了
+    scratch: evaluator::Scratch, // マッチごとに使い回す作業領域
+}
+
+impl<'r, 'h, H: ?Sized> Searcher<'r, 'h, H> {
+    fn new(regex: &'r Regex, input: Input<'h, H>) -> Self {
+        Searcher {
+            regex,
+            input,
+            pos: 0,
+            scratch: evaluator::Scratch::default(),
+        }
+    }
+
+    /// 次のマッチを探し､そのslotsをscratchに残す
+    ///
+    /// マッチがない場合はNoneを返し､エラーが起きた場合はErrを返して以降はNoneを返す
+    /// slotsを複製せずにscratchに残すため､マッチごとの確保は行わない
+    fn advance(&mut self) -> Option<Result<(), RegexError>> {
+        if self.pos > self.input.chars.len() {
+            return None;
+        }
+
+        match self
+            .regex
+            .search_chars(&self.input.chars, self.pos, &mut self.scratch)
+        {
+            Ok(true) => (),
+            Ok(false) => {
+                self.pos = usize::MAX;
+                return None;
+            }
+            Err(e) => {
+                self.pos = usize::MAX;
+                return Some(Err(e));
+            }
+        }
+
+        // 空文字列にマッチした場合は､同じ位置で再びマッチしないよう1文字進める
+        // \Kで報告する開始位置が移っても､実際にマッチを始めた位置から空文字列かを判定する
+        let (_, end) = slot_span(self.scratch.slots());
+        self.pos = if self.scratch.start() == end {
+            end + 1
+        } else {
+            end
+        };
+
+        Some(Ok(()))
+    }
+
+    /// 次の開始位置から始まるマッチを探し､そのslotsをscratchに残す
+    ///
+    /// advanceとは異なり､マッチした開始位置の1文字後ろから次のマッチを探すため､
+    /// 前のマッチと重なるマッチも見つける
+    fn advance_overlapping(&mut self) -> Option<Result<(), RegexError>> {
+        while self.pos <= self.input.chars.len() {
+            let sp = self.pos;
+            self.pos += 1;
+
+            match self
+                .regex
+                .eval_at(&self.input.chars, sp, true, &mut self.scratch)
+            {
+                Ok(true) => return Some(Ok(())),
+                Ok(false) => (),
+                Err(e) => {
+                    self.pos = usize::MAX;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Regex::find_iterが返す､全てのマッチを順に返すイテレータ
+pub struct Matches<'r, 'h>(Searcher<'r, 'h>);
+
+impl<'h> Iterator for Matches<'_, 'h> {
+    type Item = Result<Match<'h>, RegexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = self.0.advance()?;
+        Some(found.map(|_| self.0.input.to_match(self.0.scratch.slots())))
+    }
+}
+
+/// Regex::find_iter_locatedが返す､全てのマッチを行と列の位置と共に順に返すイテレータ
+pub struct LocatedMatches<'r, 'h> {
+    searcher: Searcher<'r, 'h>,
+    pos: usize,    // 行と列を求め済みの文字単位の位置
+    line: usize,   // posの行
+    column: usize, // posの列
+}
+
+impl<'h> Iterator for LocatedMatches<'_, 'h> {
+    type Item = Result<LocatedMatch<'h>, RegexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.searcher.advance()? {
+            return Some(Err(e));
+        }
+        let slots = self.searcher.scratch.slots();
+        let (start, _) = slot_span(slots);
+
+        // \Kによって前回のマッチより前から始まる場合は､テキストの先頭から数え直す
+        if start < self.pos {
+            (self.pos, self.line, self.column) = (0, 1, 1);
+        }
+        for c in &self.searcher.input.chars[self.pos..start] {
+            if *c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.pos = start;
+
+        Some(Ok(LocatedMatch {
+            line: self.line,
+            column: self.column,
+            m: self.searcher.input.to_match(slots),
+        }))
+    }
+}
+
+/// Regex::captures_iterが返す､全てのマッチのキャプチャグループを順に返すイテレータ
+pub struct CaptureMatches<'r, 'h>(Searcher<'r, 'h>);
+
+impl<'h> Iterator for CaptureMatches<'_, 'h> {
+    type Item = Result<Captures<'h>, RegexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = self.0.advance()?;
+        let names = &self.0.regex.names;
+        Some(found.map(|_| self.0.input.to_captures(self.0.scratch.slots(), names)))
+    }
+}
+
+/// Regex::find_overlapping_iterが返す､重なるものも含めた全てのマッチを順に返すイテレータ
+pub struct OverlappingMatches<'r, 'h>(Searcher<'r, 'h>);
+
+impl<'h> Iterator for OverlappingMatches<'_, 'h> {
+    type Item = Result<Match<'h>, RegexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = self.0.advance_overlapping()?;
+        Some(found.map(|_| self.0.input.to_match(self.0.scratch.slots())))
+    }
+}
+
+/// Regex::find_iter_bytesが返す､バイト列中の全てのマッチを順に返すイテレータ
+pub struct BytesMatches<'r, 'h>(Searcher<'r, 'h, [u8]>);
+
+impl<'h> Iterator for BytesMatches<'_, 'h> {
+    type Item = Result<BytesMatch<'h>, RegexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = self.0.advance()?;
+        Some(found.map(|_| self.0.input.to_bytes_match(self.0.scratch.slots())))
+    }
+}
+
+/// Regex::splitが返す､マッチの間の部分文字列を順に返すイテレータ
+pub struct Split<'r, 'h> {
+    matches: Matches<'r, 'h>,
+    haystack: &'h str,
+    last: Option<usize>, // 次の部分文字列の開始位置｡全て返した場合はNone
+}
+
+impl<'h> Iterator for Split<'_, 'h> {
+    type Item = Result<&'h str, RegexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let last = self.last?;
+        match self.matches.next() {
+            Some(Ok(m)) => {
+                // \Kによって開始位置が前回のマッチより前になる場合は､空文字列とする
+                let piece = &self.haystack[last..m.start().max(last)];
+                self.last = Some(m.end().max(last));
+                Some(Ok(piece))
+            }
+            Some(Err(e)) => {
+                self.last = None;
+                Some(Err(e))
+            }
+            None => {
+                self.last = None;
+                Some(Ok(&self.haystack[last..]))
+            }
+        }
+    }
+}
+
+/// Regex::splitnが返す､最大limit個の部分文字列を順に返すイテレータ
+pub struct SplitN<'r, 'h> {
+    split: Split<'r, 'h>,
+    limit: usize, // 残りの返せる部分文字列の数
+}
+
+impl<'h> Iterator for SplitN<'_, 'h> {
+    type Item = Result<&'h str, RegexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.limit {
+            0 => None,
+            1 => {
+                // 最後の部分文字列は､残りの文字列全体とする
+                self.limit = 0;
+                let last = self.split.last.take()?;
+                Some(Ok(&self.split.haystack[last..]))
+            }
+            _ => {
+                self.limit -= 1;
+                self.split.next()
+            }
+        }
+    }
+}
+
+/// replace系のメソッドで､マッチを置き換える文字列を返す
+///
+/// 文字列はテンプレートとして展開し､NoExpandは展開せずにそのまま用いる
+/// &Capturesを受け取り文字列を返すクロージャも利用できる
+pub trait Replacer {
+    /// capsのマッチを置き換える文字列を､dstの末尾に追加する
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String);
+}
+
+impl Replacer for &str {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+}
+
+impl Replacer for &String {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+}
+
+impl Replacer for String {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+}
+
+impl<F, T> Replacer for F
+where
+    F: FnMut(&Captures<'_>) -> T,
+    T: AsRef<str>,
+{
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        dst.push_str(self(caps).as_ref());
+    }
+}
+
+/// $を展開せず､そのままの文字列で置き換えるReplacer
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{NoExpand, Regex};
+/// let re = Regex::new("\\d+").unwrap();
+/// assert_eq!(re.replace_all("1 or 2", NoExpand("$1")).unwrap(), "$1 or $1");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoExpand<'t>(pub &'t str);
+
+impl Replacer for NoExpand<'_> {
+    fn replace_append(&mut self, _caps: &Captures<'_>, dst: &mut String) {
+        dst.push_str(self.0);
+    }
+}
+
+/// 命令列から､先頭のsave 0と末尾のsave 1､match命令を除いた式本体のコードを取り出す
+///
+/// (?R)を含む場合のreturn命令は本体に含める
+fn body(code: &[Instruction]) -> &[Instruction] {
+    &code[1..code.len() - 2]
+}
+
+/// 式本体のコードをcodeの末尾に追加する
+///
+/// 本体の先頭が追加する位置となるようアドレスをずらし､
+/// キャプチャグループの番号をgroupsだけ後ろにずらす
+fn relocate(code: &mut Vec<Instruction>, body: &[Instruction], groups: usize) {
+    // 繰り返しの番号は､既に追加した命令列の繰り返しと重ならないようずらす
+    let loops = code
+        .iter()
+        .filter_map(|i| match i {
+            Instruction::EmptyCheckStart(n) => Some(n + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    let addr = |a: &usize| a - 1 + code.len();
+    let group = |n: &usize| if *n == 0 { 0 } else { n + groups };
+    let insts = body
+        .iter()
+        .map(|inst| match inst {
+            Instruction::Jump(a) => Instruction::Jump(addr(a)),
+            Instruction::Split(a1, a2) => Instruction::Split(addr(a1), addr(a2)),
+            // slot 0と1は､\Kによるマッチ全体の位置のためそのままとする
+            Instruction::Save(n) if *n >= 2 => Instruction::Save(n + groups * 2),
+            Instruction::Backref(n) => Instruction::Backref(group(n)),
+            Instruction::BackrefIgnoreCase(n) => Instruction::BackrefIgnoreCase(group(n)),
+            Instruction::LookBehind(len, a) => Instruction::LookBehind(*len, addr(a)),
+            Instruction::NegativeLookBehind(len, a) => {
+                Instruction::NegativeLookBehind(*len, addr(a))
+            }
+            Instruction::Cond(n, a) => Instruction::Cond(group(n), addr(a)),
+            Instruction::Recurse(a) => Instruction::Recurse(addr(a)),
+            Instruction::EmptyCheckStart(n) => Instruction::EmptyCheckStart(n + loops),
+            Instruction::EmptyCheckEnd(n, a) => Instruction::EmptyCheckEnd(n + loops, addr(a)),
+            inst => inst.clone(),
+        })
+        .collect::<Vec<_>>();
+    code.extend(insts);
+}
+
+/// マッチの有無の判定に用いるDFAを構築する
+///
+/// DFAの大きさがlimits.dfaを超える場合は遅延DFAとなり､後方参照などを含む場合はNoneを返す
+/// DFAは厳密なマッチングのみを判定するため､editsが1以上の場合もNoneを返す
+fn build_dfa(code: &[Instruction], limits: &Limits, edits: usize) -> Option<Arc<Dfa>> {
+    if edits > 0 {
+        return None;
+    }
+    Dfa::new(code, limits.dfa).map(Arc::new)
+}
+
+/// rfindでマッチの開始位置を求める､逆順のDFAを構築する
+///
+/// パターンを逆順にできない場合や､DFAを構築できない場合､editsが1以上の場合はNoneを返す
+fn build_reverse(ast: &Ast, limits: &Limits, edits: usize) -> Option<Arc<Reverse>> {
+    if edits > 0 {
+        return None;
+    }
+    let code = codegen::gen_code_with_limits(&ast.reversed()?, limits).ok()?;
+    let dfa = Dfa::new(&code, limits.dfa)?;
+    Some(Arc::new(Reverse { code, dfa }))
+}
+
+/// 1度の走査でマッチングする評価器を構築する
+///
+/// 分岐が入力の1文字で一意に決まらない場合や､大きさがlimits.onepassを超える場合はNoneを返す
+/// 編集によって進む経路が一意に決まらなくなるため､editsが1以上の場合もNoneを返す
+fn build_onepass(code: &[Instruction], limits: &Limits, edits: usize) -> Option<Arc<OnePass>> {
+    if edits > 0 {
+        return None;
+    }
+    OnePass::new(code, limits.onepass).map(Arc::new)
+}
+
+/// slotsから､マッチ全体の文字単位の(開始位置, 終了位置)を取り出す
+fn slot_span(slots: &[Option<usize>]) -> (usize, usize) {
+    match slots {
+        [Some(start), Some(end), ..] => (*start, *end),
+        _ => (0, 0),
+    }
+}
+
+/// マッチ対象の文字列を､文字単位で評価するための情報
+///
+/// Hはマッチ対象の型で､strか[u8]となる
+struct Input<'h, H: ?Sized = str> {
+    text: &'h H,
+    chars: Vec<char>,
+    offsets: Vec<usize>, // 文字単位の位置から､バイト単位の位置への対応
+}
+
+impl<'h> Input<'h, [u8]> {
+    /// バイト列を文字に変換
+    ///
+    /// UTF-8として不正なバイトは､1バイトずつU+FFFDとする
+    fn from_bytes(text: &'h [u8]) -> Self {
+        let mut chars = Vec::new();
+        let mut offsets = Vec::new();
+        let mut pos = 0;
+        for chunk in text.utf8_chunks() {
+            for (i, c) in chunk.valid().char_indices() {
+                chars.push(c);
+                offsets.push(pos + i);
+            }
+            pos += chunk.valid().len();
+            for _ in chunk.invalid() {
+                chars.push(char::REPLACEMENT_CHARACTER);
+                offsets.push(pos);
+                pos += 1;
+            }
+        }
+        offsets.push(text.len());
+
+        Input {
+            text,
+            chars,
+            offsets,
+        }
+    }
+
+    /// slotsのマッチ全体の位置を､バイト単位のBytesMatchに変換
+    fn to_bytes_match(&self, slots: &[Option<usize>]) -> BytesMatch<'h> {
+        let (start, end) = slot_span(slots);
+        BytesMatch {
+            haystack: self.text,
+            start: self.offsets[start],
+            end: self.offsets[end],
+        }
+    }
+}
+
+impl<'h> Input<'h> {
+    fn new(text: &'h str) -> Self {
+        let mut input = Input {
+            text,
+            chars: Vec::new(),
+            offsets: Vec::new(),
+        };
+        input.reset(text);
+        input
+    }
+
+    /// 確保済みの領域を使い回し､マッチ対象をtextに入れ替える
+    fn reset(&mut self, text: &'h str) {
+        self.text = text;
+        self.chars.clear();
+        self.chars.extend(text.chars());
+        self.offsets.clear();
+        self.offsets
+            .extend(text.char_indices().map(|(i, _)| i).chain(Some(text.len())));
+    }
+
+    /// バイト単位の位置を文字単位の位置に変換
+    ///
+    /// 文字の境界でないか､文字列の長さを超える場合はErrを返す
+    fn char_pos(&self, byte: usize) -> Result<usize, RegexError> {
+        self.offsets
+            .binary_search(&byte)
+            .map_err(|_| RegexError::InvalidStart(byte))
+    }
+
+    /// slotsの全てのグループの位置を､バイト単位のCapturesに変換
+    fn to_captures(&self, slots: &[Option<usize>], names: &Arc<[Option<String>]>) -> Captures<'h> {
+        let spans = slots
+            .chunks(2)
+            .map(|pair| match pair {
+                [Some(start), Some(end)] => Some((self.offsets[*start], self.offsets[*end])),
+                _ => None,
+            })
+            .collect();
+        Captures {
+            haystack: self.text,
+            spans,
+            names: names.clone(),
+        }
+    }
+
+    /// slotsのマッチ全体の位置を､バイト単位のMatchに変換
+    fn to_match(&self, slots: &[Option<usize>]) -> Match<'h> {
+        let (start, end) = slot_span(slots);
+        Match {
+            haystack: self.text,
+            start: self.offsets[start],
+            end: self.offsets[end],
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! 命令列を無作為に辿り､パターンにマッチする文字列を生成する
+use super::{evaluator, Instruction, Limits, MatchMode};
+use alloc::{string::String, vec, vec::Vec};
+use rand_core::RngCore;
+
+/// 生成を試みる回数の上限
+const MAX_TRIES: usize = 100;
+
+/// 1回の生成で実行する命令数の上限
+const MAX_STEPS: usize = 10_000;
+
+/// .や\Xなどの任意の文字に対して生成する文字｡表示可能なASCII文字とする
+const PRINTABLE: (u32, u32) = (0x20, 0x7E);
+
+/// 命令列codeのパターン全体にマッチする文字列を､rngを用いて無作為に生成
+///
+/// 分岐を無作為に選んで命令列を辿り､生成した文字列全体にマッチするかを評価器で確かめる
+/// 後読みやアンカーを満たさない場合は生成し直し､MAX_TRIES回失敗した場合はNoneを返す
+pub fn sample<R: RngCore + ?Sized>(
+    code: &[Instruction],
+    limits: &Limits,
+    rng: &mut R,
+) -> Option<String> {
+    // 末尾のmatch命令の前に\zを挿入し､文字列全体へのマッチのみを成功とする
+    let mut full = code.to_vec();
+    let last = full.pop()?;
+    full.push(Instruction::MatchTextEnd);
+    full.push(last);
+
+    for _ in 0..MAX_TRIES {
+        let Some(chars) = walk(code, limits, rng) else {
+            continue;
+        };
+        if matches!(
+            evaluator::eval_with_limits(&full, &chars, limits, MatchMode::Dfs),
+            Ok(true)
+        ) {
+            return Some(chars.into_iter().collect());
+        }
+    }
+
+    None
+}
+
+/// 命令列を先頭から辿り､文字を生成する
+///
+/// split命令ではどちらかの分岐を等しい確率で選ぶ
+/// 後読みと､先頭以外のアンカーの条件はここでは確かめない
+/// 未設定のグループへの後方参照や､命令数の上限に達した場合はNoneを返す
+fn walk<R: RngCore + ?Sized>(
+    code: &[Instruction],
+    limits: &Limits,
+    rng: &mut R,
+) -> Option<Vec<char>> {
+    let mut out = Vec::new();
+    let mut slots = vec![None; slot_count(code)];
+    let mut calls = Vec::new(); // 再帰呼び出しの戻り先と､呼び出し時のキャプチャ
+    let mut pc = 0;
+
+    for _ in 0..MAX_STEPS {
+        match code.get(pc)? {
+            Instruction::Char(c) => out.push(*c),
+            Instruction::Class(ranges, negated) => out.push(class_char(ranges, *negated, rng)?),
+            Instruction::AnyChar => out.push(any_char(true, rng)),
+            Instruction::AnyCharExceptNewline | Instruction::Grapheme => {
+                out.push(any_char(false, rng))
+            }
+            Instruction::Match => return Some(out),
+            Instruction::Jump(addr) => {
+                pc = *addr;
+                continue;
+            }
+            Instruction::Split(addr1, addr2) => {
+                pc = if rng.next_u32() & 1 == 0 {
+                    *addr1
+                } else {
+                    *addr2
+                };
+                continue;
+            }
+            Instruction::Save(n) => *slots.get_mut(*n)? = Some(out.len()),
+            Instruction::Backref(n) | Instruction::BackrefIgnoreCase(n) => {
+                let start = (*slots.get(n * 2)?)?;
+                let end = (*slots.get(n * 2 + 1)?)?;
+                out.extend_from_within(start..end);
+            }
+            // 後読みの式は読み飛ばし､生成後の評価で確かめる
+            Instruction::LookBehind(_, addr) | Instruction::NegativeLookBehind(_, addr) => {
+                pc = *addr;
+                continue;
+            }
+            Instruction::Cond(n, addr) => {
+                let is_set = matches!(
+                    (slots.get(n * 2), slots.get(n * 2 + 1)),
+                    (Some(Some(_)), Some(Some(_)))
+                );
+                if !is_set {
+                    pc = *addr;
+                    continue;
+                }
+            }
+            Instruction::Recurse(addr) => {
+                if calls.len() >= limits.recursion {
+                    return None;
+                }
+                calls.push((pc + 1, slots.clone()));
+                pc = *addr;
+                continue;
+            }
+            Instruction::Return => {
+                if let Some((ret, saved)) = calls.pop() {
+                    slots = saved;
+                    pc = ret;
+                    continue;
+                }
+            }
+            Instruction::MatchBegin | Instruction::MatchStart => {
+                if !out.is_empty() {
+                    return None;
+                }
+            }
+            // 空の繰り返しを続けても生成する文字列は変わらないため､常に繰り返しを続ける
+            Instruction::EmptyCheckStart(_)
+            | Instruction::EmptyCheckEnd(..)
+            | Instruction::MatchEnd(_)
+            | Instruction::MatchLineBegin(_)
+            | Instruction::MatchLineEnd(_)
+            | Instruction::MatchTextEnd => (),
+        }
+        pc += 1;
+    }
+
+    None
+}
+
+/// 命令列のsave命令が使うslotの数
+fn slot_count(code: &[Instruction]) -> usize {
+    code.iter()
+        .filter_map(|i| match i {
+            Instruction::Save(n) => Some(n + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// 文字クラスにマッチする文字を無作為に選ぶ
+///
+/// 否定の文字クラスの場合は､表示可能なASCII文字から優先して選ぶ
+/// 選べなかった場合はNoneを返す
+fn class_char<R: RngCore + ?Sized>(
+    ranges: &[(char, char)],
+    negated: bool,
+    rng: &mut R,
+) -> Option<char> {
+    let contains = |c: char| ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c));
+
+    if negated {
+        for i in 0..MAX_TRIES {
+            // 表示可能なASCII文字で見つからない場合は､全ての文字から選ぶ
+            let c = if i < MAX_TRIES / 2 {
+                Some(any_char(false, rng))
+            } else {
+                char::from_u32(uniform(rng, 0x11_0000) as u32)
+            };
+            if let Some(c) = c.filter(|c| !contains(*c)) {
+                return Some(c);
+            }
+        }
+        return None;
+    }
+
+    let total = ranges
+        .iter()
+        .map(|(lo, hi)| u64::from(*hi as u32 - *lo as u32) + 1)
+        .sum::<u64>();
+    if total == 0 {
+        return None;
+    }
+    for _ in 0..MAX_TRIES {
+        let mut k = uniform(rng, total);
+        for (lo, hi) in ranges {
+            let len = u64::from(*hi as u32 - *lo as u32) + 1;
+            if k < len {
+                // サロゲートの範囲を選んだ場合は選び直す
+                match char::from_u32(*lo as u32 + k as u32) {
+                    Some(c) => return Some(c),
+                    None => break,
+                }
+            }
+            k -= len;
+        }
+    }
+
+    None
+}
+
+/// 表示可能なASCII文字を無作為に選ぶ
+///
+/// newlineがtrueの場合は､改行も選ぶ
+fn any_char<R: RngCore + ?Sized>(newline: bool, rng: &mut R) -> char {
+    let (lo, hi) = PRINTABLE;
+    let n = u64::from(hi - lo) + 1 + u64::from(newline);
+    let k = uniform(rng, n) as u32;
+    char::from_u32(lo + k).filter(|c| *c <= '~').unwrap_or('\n')
+}
+
+/// 0以上n未満の整数を無作為に選ぶ
+///
+/// nが0の場合は0を返す
+fn uniform<R: RngCore + ?Sized>(rng: &mut R, n: u64) -> u64 {
+    rng.next_u64().checked_rem(n).unwrap_or(0)
+}
@@ -0,0 +1,293 @@
+//! 入力を少しずつ読み込みながら､行ごとにマッチングする
+use super::Regex;
+use super::RegexError;
+use std::{
+    collections::VecDeque,
+    io::{ErrorKind, Read},
+    ops::Range,
+};
+
+/// 1回の読み込みで読み込むバイト数
+const CHUNK_SIZE: usize = 8192;
+
+/// io::Readから少しずつ読み込みながら､全てのマッチを順に返すイテレータ
+///
+/// 入力を改行ごとの行に分けてマッチングするため､保持するのは読み込み途中の1行分のみとなる
+/// そのため､改行をまたぐマッチは見つからない
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{Regex, StreamMatcher};
+/// let re = Regex::new("\\d+").unwrap();
+/// let input = "a1\nb22\n".as_bytes();
+/// let matches = StreamMatcher::new(&re, input)
+///     .map(|m| m.map(|m| (m.line(), m.range(), m.as_str().to_string())))
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+/// assert_eq!(matches, vec![(1, 1..2, "1".to_string()), (2, 4..6, "22".to_string())]);
+/// ```
+pub struct StreamMatcher<'r, R> {
+    matcher: IncrementalMatcher<'r>,
+    reader: R,
+    pending: VecDeque<StreamMatch>, // 見つかったが､まだ返していないマッチ
+    is_eof: bool,                   // 入力を最後まで読み込んだか
+}
+
+/// 入力を少しずつ与えながら､マッチングを行う
+///
+/// feedで与えた入力のうち､改行で終わった行ごとにマッチングし､見つかったマッチを返す
+/// 入力の終わりでfinishを呼ぶと､改行で終わらない最後の行もマッチングする
+/// StreamMatcherと同じく､改行をまたぐマッチは見つからない
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::{IncrementalMatcher, Regex, Status};
+/// let re = Regex::new("\\d+").unwrap();
+/// let mut matcher = IncrementalMatcher::new(&re);
+/// assert_eq!(matcher.feed(b"id=1").unwrap(), Status::Pending);
+/// let Status::Matches(ms) = matcher.feed(b"2\nid=34").unwrap() else { panic!() };
+/// assert_eq!(ms[0].as_str(), "12");
+/// let Status::Matches(ms) = matcher.finish().unwrap() else { panic!() };
+/// assert_eq!(ms[0].range(), 9..11);
+/// ```
+pub struct IncrementalMatcher<'r> {
+    regex: &'r Regex,
+    buf: Vec<u8>,    // 読み込んだ入力｡先頭のconsumedバイトは行として処理済み
+    consumed: usize, // bufのうち､行として処理済みのバイト数｡feedの終わりに取り除く
+    searched: usize, // bufのうち､改行がないことを確認済みの位置
+    offset: usize,   // 処理していない最初の行の､入力全体の先頭からのバイト単位の位置
+    line: usize,     // 処理していない最初の行の番号
+}
+
+/// IncrementalMatcherに入力を与えた結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Pending,                   // 新たなマッチはなく､続きの入力を待っている
+    Matches(Vec<StreamMatch>), // 行の終わりまでに見つかったマッチ
+}
+
+/// StreamMatcherが返すマッチ
+///
+/// 位置は入力全体の先頭からのバイト単位となる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamMatch {
+    line: usize,
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+impl StreamMatch {
+    /// マッチした行の番号｡1から始まる
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// マッチの開始位置
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// マッチの終了位置
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// マッチの範囲
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// マッチした部分文字列
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+}
+
+impl<'r> IncrementalMatcher<'r> {
+    /// regexでマッチングを行う､入力が空の状態を生成
+    pub fn new(regex: &'r Regex) -> Self {
+        IncrementalMatcher {
+            regex,
+            buf: Vec::new(),
+            consumed: 0,
+            searched: 0,
+            offset: 0,
+            line: 1,
+        }
+    }
+
+    /// 入力の続きを与え､改行で終わった行をマッチングする
+    ///
+    /// # 返り値
+    ///
+    /// マッチがあった場合はOk(Status::Matches(..))を､なかった場合はOk(Status::Pending)を返す
+    /// 行がUTF-8として不正な場合や､評価中にエラーが起きた場合はErrを返す
+    pub fn feed(&mut self, data: &[u8]) -> Result<Status, RegexError> {
+        self.buf.extend_from_slice(data);
+
+        let mut matches = Vec::new();
+        let result = self.scan_lines(&mut matches);
+        self.compact();
+        result?;
+
+        Ok(Status::from(matches))
+    }
+
+    /// 入力の終わりを知らせ､改行で終わらない最後の行をマッチングする
+    ///
+    /// 以降にfeedで与えた入力は､新しい行の続きとして扱う
+    ///
+    /// # 返り値
+    ///
+    /// feedと同様
+    /// 一部の行を残して返した場合は､再びfinishを呼ぶと残りの行をマッチングする
+    pub fn finish(&mut self) -> Result<Status, RegexError> {
+        let mut matches = Vec::new();
+        let result = self.scan_lines(&mut matches).and_then(|scanned| {
+            if scanned && self.consumed < self.buf.len() {
+                self.scan_line(self.buf.len() - self.consumed, 0, &mut matches)?;
+            }
+            Ok(())
+        });
+        self.compact();
+        result?;
+
+        Ok(Status::from(matches))
+    }
+
+    /// 行として処理済みの入力をbufから取り除く
+    ///
+    /// 行ごとに取り除くと残りの入力を毎回詰め直すことになるため､feedやfinishの終わりにまとめて取り除く
+    fn compact(&mut self) {
+        self.buf.drain(..self.consumed);
+        self.searched -= self.consumed;
+        self.consumed = 0;
+    }
+
+    /// bufの改行で終わった行を全てマッチングし､マッチをmatchesに追加
+    ///
+    /// エラーのある行の前に既にマッチが見つかっている場合は､その行以降を残してOk(false)を返す
+    fn scan_lines(&mut self, matches: &mut Vec<StreamMatch>) -> Result<bool, RegexError> {
+        while let Some(len) = self.buf[self.searched..].iter().position(|b| *b == b'\n') {
+            let len = self.searched + len - self.consumed;
+            if !self.scan_line(len, 1, matches)? {
+                return Ok(false);
+            }
+        }
+        self.searched = self.buf.len();
+
+        Ok(true)
+    }
+
+    /// 処理していない最初のlenバイトを1行としてマッチングし､マッチをmatchesに追加して行を処理済みとする
+    ///
+    /// 行がUTF-8として不正な場合などのエラーでは､matchesが空でなければ行を残してOk(false)を返し､
+    /// 空であれば行を取り除いてErrを返す
+    /// そのため､見つかったマッチを返した後の次の呼び出しでエラーを返す
+    fn scan_line(
+        &mut self,
+        len: usize,
+        terminator_len: usize,
+        matches: &mut Vec<StreamMatch>,
+    ) -> Result<bool, RegexError> {
+        let result = self.find_in_line(len);
+        if result.is_err() && !matches.is_empty() {
+            self.searched = self.consumed;
+            return Ok(false);
+        }
+
+        self.consumed += len + terminator_len;
+        self.searched = self.consumed;
+        self.offset += len + terminator_len;
+        self.line += 1;
+
+        matches.extend(result?);
+        Ok(true)
+    }
+
+    /// 処理していない最初のlenバイトの行から､全てのマッチを探す
+    fn find_in_line(&self, len: usize) -> Result<Vec<StreamMatch>, RegexError> {
+        let mut matches = Vec::new();
+        let line = std::str::from_utf8(&self.buf[self.consumed..self.consumed + len])?;
+        for m in self.regex.find_iter(line) {
+            let m = m?;
+            matches.push(StreamMatch {
+                line: self.line,
+                start: self.offset + m.start(),
+                end: self.offset + m.end(),
+                text: m.as_str().to_string(),
+            });
+        }
+
+        Ok(matches)
+    }
+}
+
+impl From<Vec<StreamMatch>> for Status {
+    fn from(matches: Vec<StreamMatch>) -> Self {
+        if matches.is_empty() {
+            Status::Pending
+        } else {
+            Status::Matches(matches)
+        }
+    }
+}
+
+impl<'r, R: Read> StreamMatcher<'r, R> {
+    /// regexでreaderの入力をマッチングするイテレータを生成
+    pub fn new(regex: &'r Regex, reader: R) -> Self {
+        StreamMatcher {
+            matcher: IncrementalMatcher::new(regex),
+            reader,
+            pending: VecDeque::new(),
+            is_eof: false,
+        }
+    }
+
+    /// マッチが見つかるか､入力の末尾に達するまで読み込む
+    fn fill(&mut self) -> Result<(), RegexError> {
+        let mut chunk = [0; CHUNK_SIZE];
+        while self.pending.is_empty() && !self.is_eof {
+            let n = match self.reader.read(&mut chunk) {
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            let status = if n == 0 {
+                self.matcher.finish()?
+            } else {
+                self.matcher.feed(&chunk[..n])?
+            };
+            match status {
+                Status::Matches(matches) => self.pending.extend(matches),
+                // 入力の末尾で､残りの行がなくなった
+                Status::Pending if n == 0 => self.is_eof = true,
+                Status::Pending => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for StreamMatcher<'_, R> {
+    type Item = Result<StreamMatch, RegexError>;
+
+    /// 次のマッチを返す
+    ///
+    /// 読み込みやマッチングでエラーが起きた場合はErrを返し､以降は何も返さない
+    /// UTF-8として不正な行がある場合もErrを返す
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.fill() {
+            self.is_eof = true;
+            self.pending.clear();
+            return Some(Err(e));
+        }
+
+        self.pending.pop_front().map(Ok)
+    }
+}
@@ -0,0 +1,106 @@
+//! シェルのglobパターンを正規表現に変換
+use crate::{escape, Regex, RegexError};
+use alloc::string::String;
+use core::{iter::Peekable, str::Chars};
+
+/// globパターンを､同じ文字列全体にマッチする正規表現にコンパイル
+///
+/// - `*` : /以外の0文字以上
+/// - `**` : /を含む0文字以上｡`**/`の場合は0個以上のディレクトリ
+/// - `?` : /以外の1文字
+/// - `[abc]`, `[a-z]`, `[!abc]` : 文字クラス｡`[^abc]`も否定とし､否定した文字クラスは/にマッチしない
+/// - `\c` : 文字cそのもの
+///
+/// 閉じ括弧のない`[`は通常の文字とする
+///
+/// # 利用例
+///
+/// ```
+/// use regexer::glob;
+///
+/// let re = glob::to_regex("*.log").unwrap();
+/// assert!(re.is_match("app.log").unwrap());
+/// assert!(!re.is_match("logs/app.log").unwrap());
+///
+/// let re = glob::to_regex("src/**/*.rs").unwrap();
+/// assert!(re.is_match("src/main.rs").unwrap());
+/// assert!(re.is_match("src/engine/parser.rs").unwrap());
+/// ```
+///
+/// # 引数
+///
+/// globに変換するglobパターンを与える
+///
+/// # 返り値
+///
+/// 文字列全体にマッチするRegexを返す
+/// 変換した正規表現のコンパイルに失敗した場合はErrを返す
+pub fn to_regex(glob: &str) -> Result<Regex, RegexError> {
+    let mut expr = String::from("\\A");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.next_if_eq(&'*').is_some() => {
+                // **/は0個以上のディレクトリとし､それ以外の**は/を含む任意の文字列とする
+                if chars.next_if_eq(&'/').is_some() {
+                    expr.push_str("(?:.*/)?");
+                } else {
+                    expr.push_str(".*");
+                }
+            }
+            '*' => expr.push_str("[^/]*"),
+            '?' => expr.push_str("[^/]"),
+            '[' => match parse_class(&mut chars) {
+                Some(class) => expr.push_str(&class),
+                None => push_literal(&mut expr, c),
+            },
+            '\\' => push_literal(&mut expr, chars.next().unwrap_or('\\')),
+            _ => push_literal(&mut expr, c),
+        }
+    }
+    expr.push_str("\\z");
+    Regex::new(&expr)
+}
+
+/// 文字cを､その文字自体にマッチする正規表現としてexprに追加
+///
+/// escapeは文字クラスの中に埋め込んでも通常の文字となるようエスケープするため､
+/// 文字クラスの中の文字にも用いる
+fn push_literal(expr: &mut String, c: char) {
+    expr.push_str(&escape(c.encode_utf8(&mut [0; 4])));
+}
+
+/// globの文字クラス[...]を､正規表現の文字クラスに変換
+///
+/// 開き括弧の直後から読み込み､閉じ括弧がない場合は何も読み込まずにNoneを返す
+/// 先頭の!と^は否定とし､先頭の]は通常の文字として扱う
+/// 否定した文字クラスは､*や?と同じく/にもマッチしない
+fn parse_class(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut ahead = chars.clone();
+    let mut class = String::from("[");
+    let negated = ahead.next_if(|c| matches!(c, '!' | '^')).is_some();
+    if negated {
+        class.push('^');
+    }
+
+    let mut is_first = true;
+    while let Some(c) = ahead.next() {
+        match c {
+            ']' if !is_first => {
+                if negated {
+                    class.push('/');
+                }
+                class.push(']');
+                *chars = ahead;
+                return Some(class);
+            }
+            '\\' => push_literal(&mut class, ahead.next()?),
+            // 範囲指定の-はそのまま残す
+            '-' if !is_first && ahead.peek().is_some_and(|c| *c != ']') => class.push('-'),
+            _ => push_literal(&mut class, c),
+        }
+        is_first = false;
+    }
+
+    None
+}
@@ -3,13 +3,3328 @@
 //! ## 利用例
 //!
 //! ```
-//! use regexer;
+//! use regexer::{self, MatchMode};
 //! let expr = "a(bc)+|c(def)"; // 正規表現
 //! let line = "cdefdefdef"; // マッチ対象文字列
-//! regexer::do_matching(expr, line, true); // 深さ優先探索でマッチング
-//! regexer::print(expr); // 正規表現のASTと命令列を表示
+//! regexer::do_matching(expr, line, MatchMode::Dfs); // 深さ優先探索でマッチング
+//! regexer::print(expr).unwrap(); // 正規表現のASTと命令列を表示
 //! ```
+//!
+//! ## フィーチャ
+//!
+//! - `std` (デフォルトで有効) : 標準ライブラリを利用する｡無効にすると`alloc`のみで動作し､パーサ､コード生成器､評価器を組み込み環境でも利用できる｡
+//!   `grep`や`StreamMatcher`のような入出力を伴う機能と､`print`は利用できなくなる
+//! - `sample` : `Regex::sample`で､パターンにマッチする文字列を無作為に生成できるようにする｡乱数生成器には`rand_core::RngCore`を実装した型を用いる
+//! - `serde` : `compile`で生成した`Program`を､serdeでシリアライズとデシリアライズできるようにする
+//! - `macros` : `regexer-macros`の`regexer!`を再公開する｡`unicode-gencat`と`unicode-script`を有効にした場合は､マクロのパターンでもそれらの文字クラスを利用できる
+//! - `unicode-gencat` : `\p{L}`や`\P{Nd}`のような､Unicodeの一般カテゴリによる文字クラスを有効にする
+//! - `unicode-normalization` : パターンとマッチ対象の文字列を､NFCやNFKCで正規化してからマッチングするオプションを有効にする
+//! - `unicode-script` : `\p{Hiragana}`や`\p{InHiragana}`のような､Unicodeの用字とブロックによる文字クラスを有効にする
+//!
+//! ## 関連クレート
+//!
+//! - `regexer-macros` : `regexer!("...")`でパターンをコンパイル時に検査し､命令列を埋め込んだ`Regex`を生成する｡
+//!   誤ったパターンはビルドエラーとなり､実行時のパースとコード生成を省ける｡`macros`フィーチャで`regexer::regexer`として利用できる
+//! - `regexer-syntax` : パーサとコード生成器｡`regexer`と`regexer-macros`が共有し､`regexer`が必要な項目を再公開する
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod analysis;
 mod engine;
+pub mod glob;
 mod helper;
 
-pub use engine::{do_matching, print};
+#[cfg(feature = "unicode-normalization")]
+pub use engine::Normalization;
+pub use engine::{
+    compile, describe, do_captures, do_captures_at, do_fuzzy_matching, do_match_span, do_matching,
+    do_matching_with_flags, do_matching_with_limits, escape, parse, parse_with_flags, Ast,
+    BytesMatch, BytesMatches, Cache, CaptureMatches, Captures, CharClass, CodeGenError,
+    CompileError, CompileInfo, Dialect, EvalError, Flags, GroupSpans, Instruction, Limits,
+    LineTerminator, LocatedMatch, LocatedMatches, Match, MatchMode, Matches, NoExpand,
+    OverlappingMatches, ParseError, Pattern, Program, Regex, RegexBuilder, RegexError, Replacer,
+    Split, SplitN, Stats, Visitor,
+};
+#[cfg(feature = "std")]
+pub use engine::{
+    grep, print, Grep, GrepLine, IncrementalMatcher, Status, StreamMatch, StreamMatcher,
+};
+pub use helper::DynError;
+#[cfg(feature = "macros")]
+pub use regexer_macros::regexer;
+
+/// regexer-macrosが展開するコードから利用する項目
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::engine::Info;
+    pub use crate::helper::OnceLock;
+    pub use alloc::vec;
+}
+
+// 単体テスト
+#[cfg(test)]
+mod tests {
+    use crate::{
+        engine::{
+            compile, describe, do_captures, do_captures_at, do_fuzzy_matching, do_match_span,
+            do_matching, do_matching_with_flags, do_matching_with_limits, escape, parse, Ast,
+            Cache, CompileError, Dialect, Flags, Instruction, Limits, LineTerminator, MatchMode,
+            ParseError, Pattern, Regex, RegexBuilder,
+        },
+        glob,
+    };
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_word_class() {
+        let unicode = Flags {
+            unicode: true,
+            ..Default::default()
+        };
+
+        // パース成功､マッチ成功
+        assert!(do_matching("\\w+", "snake_case", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\W", "-", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\w-]+x", "kebab-x", MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("\\w\\w\\w", "日本語", unicode, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("\\W", " ", unicode, MatchMode::Dfs).unwrap());
+
+        // パース成功､マッチ失敗
+        assert!(!do_matching("\\w", "-", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\W", "_", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\w", "日", MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("\\W", "日", unicode, MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_space_class() {
+        let unicode = Flags {
+            unicode: true,
+            ..Default::default()
+        };
+
+        // パース成功､マッチ成功
+        assert!(do_matching("a\\sb", "a b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a\\s+b", "a \t\r\n\x0cb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\S+", "token", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\s,]+", ", ", MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("a\\sb", "a\u{3000}b", unicode, MatchMode::Dfs).unwrap());
+
+        // パース成功､マッチ失敗
+        assert!(!do_matching("\\s", "a", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\S", " ", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a\\sb", "a\u{3000}b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("\\S", "\u{3000}", unicode, MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_repeat() {
+        // パースエラー
+        assert!(do_matching("{2}", "aa", MatchMode::Dfs).is_err());
+        assert!(do_matching("a{", "aa", MatchMode::Dfs).is_err());
+        assert!(do_matching("a{}", "aa", MatchMode::Dfs).is_err());
+        assert!(do_matching("a{x}", "aa", MatchMode::Dfs).is_err());
+
+        // パース成功､マッチ成功
+        assert!(do_matching("a{3}", "aaa", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(ab){2}c", "ababc", MatchMode::Dfs).unwrap());
+        assert!(do_matching("((ab){2}c){2}", "ababcababc", MatchMode::Dfs).unwrap());
+        assert!(do_matching("x[0-9]{2}y", "x42y", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a{0}b", "b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a\\{2\\}", "a{2}", MatchMode::Dfs).unwrap());
+
+        // パース成功､マッチ失敗
+        assert!(!do_matching("a{3}", "aa", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(ab){2}c", "abc", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("x[0-9]{2}y", "x4y", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_repeat_range() {
+        // パースエラー
+        assert!(do_matching("a{,}", "aa", MatchMode::Dfs).is_err());
+        assert!(do_matching("a{,5}", "aa", MatchMode::Dfs).is_err());
+        assert!(do_matching("a{5,2}", "aa", MatchMode::Dfs).is_err());
+        assert!(do_matching("a{2,3", "aa", MatchMode::Dfs).is_err());
+        assert!(do_matching("a{2,,3}", "aa", MatchMode::Dfs).is_err());
+
+        // パース成功､マッチ成功
+        assert!(do_matching("^a{2,3}b", "aab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^a{2,3}b", "aaab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^a{2,}b", "aaaaab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^(ab){1,2}c", "ababc", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^(ab){0,}c", "c", MatchMode::Dfs).unwrap());
+
+        // パース成功､マッチ失敗
+        assert!(!do_matching("^a{2,3}b", "ab", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^a{2,3}b", "aaaab", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^a{2,}b", "ab", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^(ab){1,2}c", "abababc", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_captures() {
+        // マッチ失敗
+        assert_eq!(None, do_captures("(a)(b)", "ac", MatchMode::Dfs).unwrap());
+
+        // グループの位置
+        assert_eq!(
+            Some(vec![Some((0, 3)), Some((0, 1)), Some((1, 3))]),
+            do_captures("(a)(bc)", "abcd", MatchMode::Dfs).unwrap()
+        );
+
+        // 入れ子のグループは開き括弧の順に番号が付く
+        assert_eq!(
+            Some(vec![Some((0, 4)), Some((0, 4)), Some((2, 4))]),
+            do_captures("(ab(cd))", "abcd", MatchMode::Dfs).unwrap()
+        );
+
+        // マッチに関与しなかったグループ
+        assert_eq!(
+            Some(vec![Some((0, 1)), Some((0, 1)), None]),
+            do_captures("(a)|(b)", "a", MatchMode::Dfs).unwrap()
+        );
+
+        // 繰り返しの場合は最後にマッチした位置
+        assert_eq!(
+            Some(vec![Some((0, 3)), Some((2, 3))]),
+            do_captures("([a-z])+", "abc", MatchMode::Dfs).unwrap()
+        );
+
+        // バイト単位の位置
+        assert_eq!(
+            Some(vec![Some((0, 6)), Some((3, 6))]),
+            do_captures("あ(い)", "あい", MatchMode::Dfs).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_backref() {
+        // パースエラー
+        assert!(do_matching("(a)\\2", "aa", MatchMode::Dfs).is_err());
+        assert!(do_matching("a\\1", "aa", MatchMode::Dfs).is_err());
+
+        // パース成功､マッチ成功
+        assert!(do_matching("(a+)b\\1", "aabaa", MatchMode::Dfs).unwrap());
+        assert!(do_matching("([a-z])\\1", "xx", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(a)(b)\\2\\1", "abba", MatchMode::Dfs).unwrap());
+
+        // パース成功､マッチ失敗
+        assert!(!do_matching("(a+)b\\1", "aab", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^(a+)b\\1", "aaba", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("([a-z])\\1", "xy", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(a)|b\\1", "b", MatchMode::Dfs).unwrap());
+
+        // 幅優先探索ではエラー
+        assert!(do_matching("(a)\\1", "aa", MatchMode::Bfs).is_err());
+    }
+
+    #[test]
+    fn test_look_behind() {
+        // パースエラー
+        assert!(do_matching("(?<=a+)b", "ab", MatchMode::Dfs).is_err());
+        assert!(do_matching("(?<=a|bc)d", "ad", MatchMode::Dfs).is_err());
+        assert!(do_matching("(?<a)b", "ab", MatchMode::Dfs).is_err());
+
+        // パース成功､マッチ成功
+        assert!(do_matching("a(?<=a)b", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[a-z]+(?<=ing)!", "going!", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\d(?<!0)x", "5x", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?<!a)b", "b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("ab(?<=(a|c)b)c", "abc", MatchMode::Dfs).unwrap());
+
+        // パース成功､マッチ失敗
+        assert!(!do_matching("(?<=a)b", "b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[a-z]+(?<=ing)!", "gone!", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\d(?<!0)x", "0x", MatchMode::Dfs).unwrap());
+
+        // 肯定の後読み中のキャプチャ
+        assert_eq!(
+            Some(vec![Some((0, 3)), Some((1, 2))]),
+            do_captures("abc(?<=(b)c)", "abc", MatchMode::Dfs).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_inline_flags() {
+        // パースエラー
+        assert!(do_matching("(?z)a", "a", MatchMode::Dfs).is_err());
+        assert!(do_matching("(?i", "a", MatchMode::Dfs).is_err());
+        assert!(do_matching("(?i:a", "a", MatchMode::Dfs).is_err());
+
+        // パターン全体
+        assert!(do_matching("(?i)error", "ERROR", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?i)[a-c]+", "AbC", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?i)[^a]", "b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(?i)[^a]", "A", MatchMode::Dfs).unwrap());
+
+        // 途中から適用
+        assert!(do_matching("a(?i)b", "aB", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a(?i)b", "AB", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a(?i)b|c", "C", MatchMode::Dfs).unwrap());
+
+        // グループの終わりまで適用
+        assert!(do_matching("((?i)a)b", "Ab", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("((?i)a)b", "AB", MatchMode::Dfs).unwrap());
+
+        // 括弧内のみに適用
+        assert!(do_matching("(?i:a)b", "Ab", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(?i:a)b", "AB", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?i)a(?-i:b)c", "AbC", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(?i)a(?-i:b)c", "ABC", MatchMode::Dfs).unwrap());
+
+        // 非キャプチャグループ
+        assert!(do_matching("(?:ab)+c", "ababc", MatchMode::Dfs).unwrap());
+        assert_eq!(
+            Some(vec![Some((0, 3)), Some((2, 3))]),
+            do_captures("(?:a)(?i:b)(c)", "aBc", MatchMode::Dfs).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let flags = Flags {
+            case_insensitive: true,
+            ..Default::default()
+        };
+
+        // パース成功､マッチ成功
+        assert!(do_matching_with_flags("err", "ERR", flags, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("[a-z]+", "Regex", flags, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("(a)\\1", "aA", flags, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("a(?-i)b", "Ab", flags, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("ä", "Ä", flags, MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?i)(ab)\\1", "abAB", MatchMode::Dfs).unwrap());
+
+        // パース成功､マッチ失敗
+        assert!(!do_matching_with_flags("err", "ERX", flags, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("a(?-i)b", "AB", flags, MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(a)\\1", "aA", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_multi_line() {
+        let flags = Flags {
+            multi_line: true,
+            ..Default::default()
+        };
+
+        // 量化子の後のアンカー
+        assert!(do_matching("^a+$", "aaa", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(ab)*$", "abab", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(ab)*$", "ababa", MatchMode::Dfs).unwrap());
+
+        // 通常は文字列の先頭と末尾のみにマッチ
+        assert!(!do_matching("a$\nb", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a\n^b", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a$", "a\n\n", MatchMode::Dfs).unwrap());
+
+        // マルチラインモードでは行の先頭と末尾にもマッチ
+        assert!(do_matching("(?m)a$\nb", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?m)a\n^b", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("a$\n^b$", "a\nb", flags, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("a$", "a\n\n", flags, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("a$", "ab\n", flags, MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(?m)a(?-m:$)\nb", "a\nb", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_dot_all() {
+        let flags = Flags {
+            dot_all: true,
+            ..Default::default()
+        };
+
+        // 通常は改行にマッチしない
+        assert!(do_matching("a.b", "a b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a.b", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^.*$", "a\nb", MatchMode::Dfs).unwrap());
+
+        // dotallモードでは改行にもマッチする
+        assert!(do_matching("(?s)a.b", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?s)^.*$", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("a.b", "a\nb", flags, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("a(?-s:.)b", "a\nb", flags, MatchMode::Dfs).unwrap());
+        assert!(do_matching("a(?s:.)b", "a\nb", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_extended() {
+        let flags = Flags {
+            extended: true,
+            ..Default::default()
+        };
+
+        // 空白とコメントを無視
+        assert!(do_matching("(?x) a b c ", "abc", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?x)a+ (b | c) # コメント", "aac", MatchMode::Dfs).unwrap());
+        assert!(do_matching(
+            "(?x)^ \\d{3} # 3桁の数字\n - \\d{4} $",
+            "123-4567",
+            MatchMode::Dfs
+        )
+        .unwrap());
+        assert!(!do_matching("(?x)a b", "a b", MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("a b", "ab", flags, MatchMode::Dfs).unwrap());
+
+        // エスケープされた空白と#､文字クラス内の空白はそのまま扱う
+        assert!(do_matching("(?x)a\\ b\\#", "a b#", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?x)a[ ]b", "a b", MatchMode::Dfs).unwrap());
+
+        // 拡張モードを指定しない場合や､無効にした場合は空白も文字として扱う
+        assert!(do_matching("a b", "a b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?x)a(?-x: )b", "a b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a b", "ab", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_comment() {
+        // 成功ケース
+        assert!(do_matching("a(?#コメント)b", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?#先頭)abc", "abc", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?#年)\\d{4}-(?#月)\\d{2}", "2024-01", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(a|b(?#[(*+|))c", "bc", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a(?#コメント)+", "aaa", MatchMode::Dfs).unwrap());
+
+        // 失敗ケース
+        assert!(!do_matching("a(?#b)c", "abc", MatchMode::Dfs).unwrap());
+
+        // パニックしないかの検査
+        assert!(do_matching("a(?#コメント", "a", MatchMode::Dfs).is_err());
+        assert!(do_matching("(?#コメント)", "", MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_text_anchor() {
+        // \Aと\zは文字列全体の先頭と末尾
+        assert!(do_matching("\\Aabc\\z", "abc", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\Aabc\\z", "abc\n", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a\\Ab", "ab", MatchMode::Dfs).unwrap());
+
+        // \Zは末尾の改行の直前にもマッチ
+        assert!(do_matching("abc\\Z", "abc", MatchMode::Dfs).unwrap());
+        assert!(do_matching("abc\\Z", "abc\n", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("abc\\Z", "abc\n\n", MatchMode::Dfs).unwrap());
+
+        // マルチラインモードでも行の境界にはマッチしない
+        assert!(do_matching("(?m)a\n^b$", "a\nb\nc", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(?m)\\Ab", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(?m)a\\z", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(?m)a\\Z", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?m)\\A(a$\n)+b\\z", "a\na\nb", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_posix_class() {
+        // 成功ケース
+        assert!(do_matching("[[:alpha:]]+", "abcXYZ", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^[[:digit:]]{3}$", "123", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[[:alnum:]_]+$", "a_1", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[[:space:]]", "\t", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[[:punct:]]", "!", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[[:xdigit:]]+$", "09afAF", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[^[:digit:]]", "a", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[[:^digit:]]", "a", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?i)[[:upper:]]", "a", MatchMode::Dfs).unwrap());
+
+        // 失敗ケース
+        assert!(!do_matching("[[:alpha:]]", "1", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[[:upper:]]", "a", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[^[:digit:]]", "1", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[[:^digit:]]", "1", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[[:xdigit:]]+$", "0g", MatchMode::Dfs).unwrap());
+
+        // [:名前:]の形でない場合は入れ子の文字クラスとして扱う
+        assert!(do_matching("[[:]]+$", ":", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\[:]+$", "[:", MatchMode::Dfs).unwrap());
+
+        // パニックしないかの検査
+        assert!(do_matching("[[:foo:]]", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("[[:alpha:]", "", MatchMode::Dfs).is_err());
+    }
+
+    #[cfg(feature = "unicode-gencat")]
+    #[test]
+    fn test_unicode_general_category() {
+        // 成功ケース
+        assert!(do_matching("\\p{L}+$", "abcあいう漢字", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\pL\\pN", "a1", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\p{Lu}\\p{Ll}", "Ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\p{Nd}+$", "123１２３", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\P{L}", "1", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\p{N}_]+$", "1_2", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[^\\p{L}]", "1", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\p{Letter}", "a", MatchMode::Dfs).unwrap());
+
+        // 失敗ケース
+        assert!(!do_matching("\\p{L}", "1", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\p{Lu}", "a", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\P{L}", "あ", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[\\P{N}]", "1", MatchMode::Dfs).unwrap());
+
+        // パニックしないかの検査
+        assert!(do_matching("\\p{Foo}", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\p{L", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\p", "", MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_hex_escape() {
+        // 成功ケース
+        assert!(do_matching("\\x41\\x62", "Ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\x{41}+$", "AAA", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\u3042\\u{3044}", "あい", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\u{1F600}", "😀", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\x2a\\x2B", "*+", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\x41-\\x43]+$", "ABC", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\u3041-\\u{3096}]+$", "ひらがな", MatchMode::Dfs).unwrap());
+
+        // 失敗ケース
+        assert!(!do_matching("\\x41", "B", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[\\x41-\\x43]", "D", MatchMode::Dfs).unwrap());
+
+        // パニックしないかの検査
+        assert!(do_matching("\\x4", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\xGG", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\x{}", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\x{41", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\u{1234567}", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\u{110000}", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\u{D800}", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\u+041", "", MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_control_escape() {
+        // 成功ケース
+        assert!(do_matching("a\\tb", "a\tb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a\\r\\nb", "a\r\nb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?m)a$\\n^b", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\f\\0", "\x0c\0", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\t\\n]+$", "\t\n\t", MatchMode::Dfs).unwrap());
+
+        // 失敗ケース
+        assert!(!do_matching("a\\tb", "a b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a\\nb", "anb", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[\\t]", "t", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_escaped_dot() {
+        // エスケープした.は文字そのものにマッチ
+        assert!(do_matching("a\\.b", "a.b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a\\.b", "axb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[.]", ".", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[.]", "a", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\.]+$", "..", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\d+\\.\\d+$", "3.14", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\d+\\.\\d+$", "3x14", MatchMode::Dfs).unwrap());
+
+        // エスケープしない.は任意の文字にマッチ
+        assert!(do_matching("a.b", "a.b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a.b", "axb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?i)a.B", "Axb", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_quote() {
+        // 成功ケース
+        assert!(do_matching("\\Qa.b*c\\E", "a.b*c", MatchMode::Dfs).unwrap());
+        assert!(do_matching("x\\Q(+)|[\\E+y", "x(+)|[[[y", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\Q$^\\E$", "$^", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\Q\\d\\E", "\\d", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\Qa.b", "a.b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?i)\\QA.B\\E", "a.b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a\\Q\\Eb", "ab", MatchMode::Dfs).unwrap());
+
+        // 失敗ケース
+        assert!(!do_matching("\\Qa.b\\E", "axb", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\Qa*\\E", "aa", MatchMode::Dfs).unwrap());
+
+        // パニックしないかの検査
+        assert!(do_matching("\\Q\\E", "", MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_cond() {
+        // グループの有無による分岐
+        assert!(do_matching("^(<)?a(?(1)>|b)$", "<a>", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^(<)?a(?(1)>|b)$", "ab", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^(<)?a(?(1)>|b)$", "<ab", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^(<)?a(?(1)>|b)$", "a>", MatchMode::Dfs).unwrap());
+
+        // noの選択肢がない場合は空文字列にマッチ
+        assert!(do_matching("^(\\()?\\d+(?(1)\\))$", "(12)", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^(\\()?\\d+(?(1)\\))$", "12", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^(\\()?\\d+(?(1)\\))$", "(12", MatchMode::Dfs).unwrap());
+
+        // 選択肢の中の式
+        assert!(do_matching("^(a)?(?(1)b+|c*d)$", "abbb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^(a)?(?(1)b+|c*d)$", "ccd", MatchMode::Dfs).unwrap());
+
+        // 後読みでの利用
+        assert!(do_matching("(a)?b(?<=(?(1)b|b))", "ab", MatchMode::Dfs).unwrap());
+
+        // パニックしないかの検査
+        assert!(do_matching("(a)(?(2)b)", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("(a)(?(1)b|c|d)", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("(a)(?(0)b)", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("(a)(?(x)b)", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("(a)(?(1", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("(a)(?(1)b", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("(a)(?(1)b)", "a", MatchMode::Bfs).is_err());
+    }
+
+    #[test]
+    fn test_recurse() {
+        // 対応の取れた括弧
+        // 再帰するとパターン全体を評価するため､^と$などのアンカーは含めない
+        let paren = "\\((\\w|(?R))*\\)";
+        assert!(do_matching(paren, "(a(b)(c(d)))", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\((\\w|(?0))*\\)", "()", MatchMode::Dfs).unwrap());
+        assert!(!do_matching(paren, "(a(b)", MatchMode::Dfs).unwrap());
+        let caps = do_captures(paren, "(a))", MatchMode::Dfs).unwrap().unwrap();
+        assert_eq!(caps[0], Some((0, 3)));
+
+        // 再帰中のキャプチャは呼び出し元に影響しない
+        assert!(do_matching("a(?R)?b", "aaabbb", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a(?R)?b", "aaabb", MatchMode::Dfs).unwrap());
+        let caps = do_captures("(a|b)(?R)?c", "abcc", MatchMode::Dfs)
+            .unwrap()
+            .unwrap();
+        assert_eq!(caps, vec![Some((0, 4)), Some((0, 1))]);
+
+        // 再帰の深さの上限
+        let limits = Limits {
+            recursion: 2,
+            ..Default::default()
+        };
+        assert!(do_matching_with_limits("a(?R)?b", "aabb", limits, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_limits("a(?R)?b", "aaaabbbb", limits, MatchMode::Dfs).is_err());
+        let deep = format!("{}{}", "(".repeat(40), ")".repeat(40));
+        assert!(do_matching(paren, &deep, MatchMode::Dfs).is_err());
+
+        // パニックしないかの検査
+        assert!(do_matching("(?<=(?R))a", "a", MatchMode::Dfs).is_err());
+        assert!(do_matching("a(?R)", "a", MatchMode::Bfs).is_err());
+        assert!(do_matching("a(?R", "a", MatchMode::Dfs).is_err());
+        assert!(do_matching("a(?Rb)", "a", MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_class_set_operation() {
+        // 積集合
+        assert!(do_matching("[a-z&&[^aeiou]]+$", "xyz", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[a-z&&[^aeiou]]", "a", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[a-z&&[^aeiou]]", "B", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\w&&\\d]", "1", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[\\w&&\\d]", "a", MatchMode::Dfs).unwrap());
+
+        // 差集合
+        assert!(do_matching("[\\w--\\d]+$", "ab_", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[\\w--\\d]", "1", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[a-z--[aeiou]]", "b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[a-z--[aeiou]]", "e", MatchMode::Dfs).unwrap());
+
+        // 左から順に適用し､全体を否定できる
+        assert!(do_matching("[a-z--a--b]", "c", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[a-z--a--b]", "b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[a-z&&a-c--b]", "a", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[a-z&&a-c--b]", "b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[^a-z&&[^aeiou]]", "a", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[^a-z&&[^aeiou]]", "b", MatchMode::Dfs).unwrap());
+
+        // 演算子でない&と-は通常の文字
+        assert!(do_matching("[a&b-]+$", "&-", MatchMode::Dfs).unwrap());
+
+        // パニックしないかの検査
+        assert!(do_matching("[a-z&&[^aeiou]", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("[a-z&&]", "a", MatchMode::Dfs).is_ok());
+    }
+
+    #[test]
+    fn test_nested_class() {
+        // 成功ケース
+        assert!(do_matching("[[a-c][x-z]]+$", "abxz", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\d\\s]+$", "1 2", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[[a-c]\\d_]+$", "a1_", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[[^a-z]a]+$", "A1a", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[[a[b[c]]]]+$", "abc", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[^[a-c][x-z]]", "d", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\[\\]]+$", "[]", MatchMode::Dfs).unwrap());
+
+        // 失敗ケース
+        assert!(!do_matching("[[a-c][x-z]]", "d", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[[^a-z]a]", "b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[^[a-c][x-z]]", "y", MatchMode::Dfs).unwrap());
+
+        // パニックしないかの検査
+        assert!(do_matching("[[a-c]", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("[[]", "", MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_grapheme() {
+        // 結合文字
+        assert!(do_matching("^\\X$", "e\u{301}", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^\\X$", "か\u{3099}", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^.$", "e\u{301}", MatchMode::Dfs).unwrap());
+
+        // 絵文字の連結と肌の色､国旗
+        assert!(do_matching("^\\X$", "👨\u{200D}👩\u{200D}👧", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^\\X$", "👍\u{1F3FD}", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^\\X$", "🇯🇵", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^\\X\\X$", "🇯🇵🇺🇸", MatchMode::Dfs).unwrap());
+
+        // ハングルの字母とCRLF
+        assert!(do_matching("^\\X$", "\u{1100}\u{1161}\u{11A8}", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^\\X$", "\r\n", MatchMode::Dfs).unwrap());
+
+        // 複数のクラスタ
+        assert!(do_matching("^\\X{3}$", "ae\u{301}👍\u{1F3FD}", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^\\X$", "ab", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\X", "", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_linebreak() {
+        // 成功ケース
+        assert!(do_matching("a\\Rb", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a\\Rb", "a\r\nb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a\\Rb", "a\rb", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a\\Rb", "a\u{2028}b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^(\\w+\\R)+$", "a\r\nb\nc\r", MatchMode::Dfs).unwrap());
+
+        // 失敗ケース
+        assert!(!do_matching("a\\Rb", "a b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a\\Rb", "a\n\nb", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a\\R", "a", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_horizontal_vertical_space() {
+        // 成功ケース
+        assert!(do_matching("a\\h+b", "a \t\u{3000}b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a\\v+b", "a\r\n\u{2028}b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\H\\V", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\h\\d]+$", " 1\t2", MatchMode::Dfs).unwrap());
+
+        // 失敗ケース
+        assert!(!do_matching("a\\hb", "a\nb", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a\\vb", "a b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\H", " ", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\V", "\n", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_empty_alternation() {
+        // 空の選択肢は空文字列にマッチ
+        assert!(do_matching("(a|)b", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(a|)b", "b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(|a)b", "b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("x(a||b)y", "xy", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^(a|)$", "", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^a|$", "", MatchMode::Dfs).unwrap());
+        assert!(do_matching("^(x)?(?(1)a|)b$", "b", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(a|)b", "cb", MatchMode::Dfs).unwrap());
+
+        // 空の選択肢のキャプチャは空文字列となる
+        let caps = do_captures("x(a|)y", "xy", MatchMode::Dfs)
+            .unwrap()
+            .unwrap();
+        assert_eq!(caps, vec![Some((0, 2)), Some((1, 1))]);
+
+        // 後読みでは選択肢の長さが揃っている必要がある
+        assert!(do_matching("(?<=a|)b", "b", MatchMode::Dfs).is_err());
+        assert!(do_matching("a(?<=a(|))b", "ab", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_octal_escape() {
+        // 8進数のエスケープ
+        assert!(do_matching("\\101\\o{102}", "AB", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\0\\012", "\0\n", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\0101", "\u{8}1", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\101-\\103]+$", "ABC", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\o{30102}", "\u{3042}", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\101", "B", MatchMode::Dfs).unwrap());
+
+        // \1から\9は常に後方参照
+        assert!(do_matching("(a)\\1", "aa", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\1a", "a", MatchMode::Dfs).is_err());
+
+        // グループの総数より大きい場合は8進数
+        assert!(do_matching("(a)\\12", "a\n", MatchMode::Dfs).unwrap());
+        assert!(do_matching(
+            "(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)(k)(l)\\12",
+            "abcdefghijkll",
+            MatchMode::Dfs
+        )
+        .unwrap());
+        assert!(do_matching(
+            "(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)\\10",
+            "abcdefghijj",
+            MatchMode::Dfs
+        )
+        .unwrap());
+        assert!(do_matching(
+            "(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)\\11",
+            "abcdefghij\t",
+            MatchMode::Dfs
+        )
+        .unwrap());
+
+        // エスケープや文字クラス､コメント中の括弧はグループとして数えない
+        assert!(do_matching("\\((a)\\12", "(a\n", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[(](a)\\12", "(a\n", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(?#(b(c)(a)\\12", "a\n", MatchMode::Dfs).unwrap());
+
+        // パニックしないかの検査
+        assert!(do_matching("(a)\\81", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\o{}", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\o{8}", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\o{101", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\o101", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\o{7777777777}", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\99999999999999999999999", "", MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_line_terminator() {
+        let crlf = Flags {
+            line_terminator: LineTerminator::Crlf,
+            ..Default::default()
+        };
+        let crlf_multi = Flags {
+            multi_line: true,
+            ..crlf
+        };
+
+        // 通常は\nのみを改行とする
+        assert!(!do_matching("foo$", "foo\r\n", MatchMode::Dfs).unwrap());
+
+        // \r\n､\r､\nを改行とする
+        assert!(do_matching_with_flags("foo$", "foo\r\n", crlf, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("foo$", "foo\r", crlf, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("foo$", "foo\n", crlf, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("foo\\Z", "foo\r\n", crlf, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("foo$", "foo\r\n\r\n", crlf, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("foo\\z", "foo\r\n", crlf, MatchMode::Dfs).unwrap());
+
+        // マルチラインモードでは\rと\nの間にはマッチしない
+        assert!(do_matching_with_flags("a$\r\n^b$", "a\r\nb", crlf_multi, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("a$\r^b", "a\rb", crlf_multi, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("a\r$", "a\r\n", crlf_multi, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("a\r^\nb", "a\r\nb", crlf_multi, MatchMode::Dfs).unwrap());
+
+        // 任意の1文字を改行とする
+        let nul = Flags {
+            multi_line: true,
+            line_terminator: LineTerminator::Char('\0'),
+            ..Default::default()
+        };
+        assert!(do_matching_with_flags("a$\0^b$", "a\0b", nul, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("a$", "a\nb", nul, MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_dialect() {
+        let bre = Flags {
+            dialect: Dialect::Bre,
+            ..Default::default()
+        };
+        let ere = Flags {
+            dialect: Dialect::Ere,
+            ..Default::default()
+        };
+
+        // BREでは\(\)､\{n,m\}､\|がメタ文字となる
+        assert!(do_matching_with_flags("\\(ab\\)\\{2\\}$", "abab", bre, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("a\\{1,\\}b", "aaab", bre, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("\\(a\\)b\\1", "aba", bre, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("x\\|y", "y", bre, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("a\\+b\\?$", "aa", bre, MatchMode::Dfs).unwrap());
+
+        // BREでは( ) { } | + ?は通常の文字となる
+        assert!(do_matching_with_flags("(a|b){2}+?", "(a|b){2}+?", bre, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("a+", "aa", bre, MatchMode::Dfs).unwrap());
+
+        // BREでは先頭の*と､先頭以外の^､末尾以外の$は通常の文字となる
+        assert!(do_matching_with_flags("*a", "*a", bre, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("^*a", "*a", bre, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("a^b$c", "a^b$c", bre, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("\\(^a$\\)", "a", bre, MatchMode::Dfs).unwrap());
+
+        // EREでは( ) { } |がメタ文字となり､(?...)の拡張構文はない
+        assert!(do_matching_with_flags("(ab){2}|c$", "abab", ere, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("(a)\\1", "aa", ere, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("(?i)a", "A", ere, MatchMode::Dfs).is_err());
+
+        // POSIXの構文では､文字クラス内の\と[は通常の文字となる
+        assert!(do_matching_with_flags("[\\d]", "\\", ere, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("[\\d]", "d", bre, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("[[]", "[", ere, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("[[:digit:]-]", "-", ere, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("[\\d]", "1", ere, MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_glob() {
+        let is_match = |pattern: &str, path: &str| glob::to_regex(pattern).unwrap().is_match(path);
+
+        // *と?は/以外の文字にマッチ
+        assert!(is_match("*.log", "app.log").unwrap());
+        assert!(is_match("*.log", ".log").unwrap());
+        assert!(!is_match("*.log", "app.log.gz").unwrap());
+        assert!(!is_match("*.log", "var/app.log").unwrap());
+        assert!(is_match("file?.txt", "file1.txt").unwrap());
+        assert!(!is_match("file?.txt", "file10.txt").unwrap());
+
+        // **は/を含む文字列にマッチ
+        assert!(is_match("**/*.rs", "main.rs").unwrap());
+        assert!(is_match("**/*.rs", "src/engine/parser.rs").unwrap());
+        assert!(is_match("src/**", "src/engine/parser.rs").unwrap());
+
+        // 文字クラス
+        assert!(is_match("[a-c]?", "b1").unwrap());
+        assert!(!is_match("[!a-c]?", "b1").unwrap());
+        assert!(is_match("[]]", "]").unwrap());
+        assert!(is_match("[^&-]", "x").unwrap());
+        assert!(is_match("[", "[").unwrap());
+
+        // 否定した文字クラスは/にマッチしない
+        assert!(!is_match("a[!b]c", "a/c").unwrap());
+        assert!(!is_match("a[^]]c", "a/c").unwrap());
+        assert!(is_match("a[!/]c", "a-c").unwrap());
+
+        // 正規表現のメタ文字は通常の文字とする
+        assert!(is_match("a+(b)|{c}^$.", "a+(b)|{c}^$.").unwrap());
+        assert!(!is_match("a.b", "axb").unwrap());
+        assert!(is_match("\\*", "*").unwrap());
+        assert!(is_match("[$^]-[~&]", "^-&").unwrap());
+    }
+
+    #[test]
+    fn test_reset_start() {
+        // \Kより前はマッチに必要だが､マッチ全体の範囲には含めない
+        assert_eq!(
+            Some(vec![Some((3, 6))]),
+            do_captures("foo\\Kbar", "foobar", MatchMode::Dfs).unwrap()
+        );
+        assert_eq!(
+            None,
+            do_captures("foo\\Kbar", "fobar", MatchMode::Dfs).unwrap()
+        );
+        assert_eq!(
+            Some(vec![Some((4, 7)), Some((0, 3))]),
+            do_captures("(\\w+)=\\K\\d+", "key=123", MatchMode::Dfs).unwrap()
+        );
+
+        // 失敗した分岐中の\Kは適用しない
+        assert_eq!(
+            Some(vec![Some((0, 3))]),
+            do_captures("ab\\Kx|abc", "abc", MatchMode::Dfs).unwrap()
+        );
+        assert_eq!(
+            Some(vec![Some((2, 2))]),
+            do_captures("a+\\K", "aa", MatchMode::Dfs).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_search_start() {
+        // \Gは探索を開始した位置にのみマッチする
+        assert!(do_matching("\\Ga", "a", MatchMode::Dfs).unwrap());
+        assert_eq!(
+            Some(vec![Some((2, 4))]),
+            do_captures_at("\\G\\w+", "a bc", 2, MatchMode::Dfs).unwrap()
+        );
+        assert_eq!(
+            None,
+            do_captures_at("\\G\\w+", "a bc", 1, MatchMode::Dfs).unwrap()
+        );
+
+        // 前回のマッチの終了位置から続けることで､隙間なく字句を切り出す
+        let line = "ab12cd";
+        let mut start = 0;
+        let mut tokens = Vec::new();
+        while let Some(caps) =
+            do_captures_at("\\G(?:[a-z]+|\\d+)", line, start, MatchMode::Dfs).unwrap()
+        {
+            let (s, e) = caps[0].unwrap();
+            tokens.push(&line[s..e]);
+            start = e;
+        }
+        assert_eq!(vec!["ab", "12", "cd"], tokens);
+
+        // ^と後読みは開始位置より前の文字も参照する
+        assert_eq!(None, do_captures_at("^b", "ab", 1, MatchMode::Dfs).unwrap());
+        assert!(do_captures_at("(?<=a)b", "ab", 1, MatchMode::Dfs)
+            .unwrap()
+            .is_some());
+
+        // 文字の境界でない位置はエラー
+        assert!(do_captures_at("a", "あa", 1, MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_empty_group() {
+        // 空のグループは空文字列にマッチし､繰り返しの対象となる
+        assert!(do_matching("a()b", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a()?$", "a", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("a()?$", "aa", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a()+b", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a()*b", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a(){3}b", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("a(?:)*b", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("()", "", MatchMode::Dfs).unwrap());
+
+        // 空のグループもキャプチャグループとして数える
+        assert_eq!(
+            Some(vec![Some((0, 2)), Some((1, 1)), Some((1, 2))]),
+            do_captures("a()(b)", "ab", MatchMode::Dfs).unwrap()
+        );
+        assert_eq!(
+            Some(vec![Some((0, 1)), Some((1, 1))]),
+            do_captures("a()+", "a", MatchMode::Dfs).unwrap()
+        );
+        assert!(do_matching("()a\\1", "a", MatchMode::Dfs).unwrap());
+    }
+
+    #[cfg(feature = "unicode-script")]
+    #[test]
+    fn test_unicode_script() {
+        // 用字
+        assert!(do_matching("\\p{Hiragana}+\\p{Han}+$", "ひらがな漢字", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\p{Katakana}+$", "カタカナ", MatchMode::Dfs).unwrap());
+        assert!(do_matching(
+            "\\p{Hira}\\p{sc=Kana}\\p{Script=Latin}",
+            "あアa",
+            MatchMode::Dfs
+        )
+        .unwrap());
+        assert!(do_matching("\\p{old_italic}", "\u{10300}", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\p{Hiragana}\\p{Katakana}]+$", "かなカナ", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\p{Hiragana}", "カ", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\P{Han}", "漢", MatchMode::Dfs).unwrap());
+
+        // ブロック
+        assert!(do_matching("\\p{InHiragana}", "ゟ", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\p{Block=CJK Unified Ideographs}", "字", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\p{blk=cjk_unified_ideographs}", "字", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\p{InHiragana}", "カ", MatchMode::Dfs).unwrap());
+
+        // 未知の名前はエラー
+        assert!(do_matching("\\p{Klingon}", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\p{InKlingon}", "", MatchMode::Dfs).is_err());
+        assert!(do_matching("\\p{Foo=Latin}", "", MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_iupac() {
+        let iupac = Flags {
+            iupac: true,
+            ..Default::default()
+        };
+
+        // 成功ケース
+        assert!(do_matching_with_flags("GAATTC", "GAATTC", iupac, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("GRNNYC", "GAACTC", iupac, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("GRNNYC", "GGTGCC", iupac, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("TATAWAW", "TATAAAT", iupac, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("[RT]+$", "AGTGA", iupac, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("ryn", "gct", iupac, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("(?i)ryn", "gcA", iupac, MatchMode::Dfs).unwrap());
+
+        // 失敗ケース
+        assert!(!do_matching_with_flags("GRNNYC", "GCAATC", iupac, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("N", "X", iupac, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("[^N]", "A", iupac, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("n", "A", iupac, MatchMode::Dfs).unwrap());
+
+        // 通常はIUPACの記号もそのままの文字とする
+        assert!(!do_matching("GRNNYC", "GAACTC", MatchMode::Dfs).unwrap());
+        assert!(do_matching("N", "N", MatchMode::Dfs).unwrap());
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_normalization() {
+        use crate::Normalization;
+
+        let nfc = Flags {
+            normalization: Normalization::Nfc,
+            ..Default::default()
+        };
+        let nfkc = Flags {
+            normalization: Normalization::Nfkc,
+            ..Default::default()
+        };
+
+        // 合成済みの文字と分解された文字を同一視する
+        assert!(do_matching_with_flags("caf\u{e9}$", "cafe\u{301}", nfc, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("cafe\u{301}$", "caf\u{e9}", nfc, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags(
+            "(\u{304b}\u{3099})+$",
+            "\u{304c}\u{304c}",
+            nfc,
+            MatchMode::Dfs
+        )
+        .unwrap());
+        assert!(!do_matching("caf\u{e9}$", "cafe\u{301}", MatchMode::Dfs).unwrap());
+
+        // NFKCでは互換文字も同一視する
+        assert!(do_matching_with_flags("ｶﾞ", "\u{30ac}", nfkc, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_flags("\\d+$", "１２３", nfkc, MatchMode::Dfs).unwrap());
+        assert!(!do_matching_with_flags("\\d+$", "１２３", nfc, MatchMode::Dfs).unwrap());
+
+        // メタ文字の全角文字は通常の文字とする
+        assert!(do_matching_with_flags("a\\*", "a＊", nfkc, MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_find() {
+        // 最も左にあるマッチを探す
+        let re = Regex::new("\\d+").unwrap();
+        let m = re.find("tel: 03-1234").unwrap().unwrap();
+        assert_eq!((5, 7, "03"), (m.start(), m.end(), m.as_str()));
+        assert!(re.find("tel: none").unwrap().is_none());
+
+        // 位置はバイト単位
+        let re = Regex::new("い+").unwrap();
+        let m = re.find("あいいう").unwrap().unwrap();
+        assert_eq!((3, 9, "いい"), (m.start(), m.end(), m.as_str()));
+
+        // 空文字列へのマッチ
+        let re = Regex::new("x*").unwrap();
+        let m = re.find("abc").unwrap().unwrap();
+        assert_eq!(0..0, m.range());
+        assert!(m.is_empty());
+        let re = Regex::new("$").unwrap();
+        assert_eq!(3..3, re.find("abc").unwrap().unwrap().range());
+
+        // アンカーと\Kは文字列全体に対して評価する
+        let re = Regex::new("^b").unwrap();
+        assert!(re.find("ab").unwrap().is_none());
+        let re = Regex::new("(?<=a)b").unwrap();
+        assert_eq!(1..2, re.find("ab").unwrap().unwrap().range());
+        let re = Regex::new("key=\\K\\w+").unwrap();
+        assert_eq!("val", re.find("a key=val").unwrap().unwrap().as_str());
+
+        // フラグの指定とパターンの取得
+        let flags = Flags {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let re = Regex::with_flags("abc", flags).unwrap();
+        assert_eq!("ABC", re.find("xABC").unwrap().unwrap().as_str());
+        assert_eq!("abc", re.as_str());
+        assert!(Regex::new("a(").is_err());
+    }
+
+    #[test]
+    fn test_find_iter() {
+        let find_all = |expr: &str, haystack: &str| {
+            Regex::new(expr)
+                .unwrap()
+                .find_iter(haystack)
+                .map(|m| m.map(|m| m.range()))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        // 重ならない全てのマッチ
+        assert_eq!(vec![0..1, 3..5, 7..10], find_all("\\d+", "1, 22, 333"));
+        assert_eq!(vec![0..2, 2..4], find_all("aa", "aaaaa"));
+        assert_eq!(Vec::<std::ops::Range<usize>>::new(), find_all("x", "abc"));
+
+        // 空文字列へのマッチは1文字ずつ進める
+        assert_eq!(vec![0..0, 1..1, 2..2], find_all("x*", "ab"));
+        assert_eq!(vec![0..0, 1..3, 3..3], find_all("a*", "baa"));
+        assert_eq!(vec![0..0, 3..3, 6..6], find_all("x?", "あい"));
+
+        // \Kで開始位置を移しても､空文字列かは実際にマッチを始めた位置から判定する
+        assert_eq!(vec![1..1, 2..2, 3..3], find_all("a\\K", "aaa"));
+        assert_eq!(vec![1..2, 3..4], find_all("a\\Kb", "abab"));
+        let re = RegexBuilder::new("a\\K")
+            .match_mode(MatchMode::Bfs)
+            .build()
+            .unwrap();
+        assert_eq!(3, re.find_iter("aaa").count());
+
+        // アンカーは文字列全体に対して評価する
+        assert_eq!(vec![0..1], find_all("^a", "aaa"));
+        assert_eq!(vec![0..1, 2..3], find_all("(?m)^\\w", "a\nb"));
+
+        // エラーが起きた場合はErrを返して終了
+        let re = Regex::new("(?R)").unwrap();
+        let mut iter = re.find_iter("a");
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_regex_captures() {
+        let re = Regex::new("(\\w+)@(\\w+)(\\.com)?").unwrap();
+
+        // 番号によるアクセス
+        let caps = re.captures("mail: user@example").unwrap().unwrap();
+        assert_eq!(4, caps.len());
+        assert_eq!("user@example", &caps[0]);
+        assert_eq!("user", &caps[1]);
+        assert_eq!(6..10, caps.get(1).unwrap().range());
+        assert_eq!("example", caps.get(2).unwrap().as_str());
+
+        // マッチに関与しなかったグループと存在しないグループはNone
+        assert!(caps.get(3).is_none());
+        assert!(caps.get(4).is_none());
+        assert_eq!(
+            vec![Some("user@example"), Some("user"), Some("example"), None],
+            caps.iter()
+                .map(|m| m.map(|m| m.as_str()))
+                .collect::<Vec<_>>()
+        );
+
+        // マッチしない場合はNone
+        assert!(re.captures("no mail").unwrap().is_none());
+
+        // 位置はバイト単位
+        let re = Regex::new("(い)(う)").unwrap();
+        let caps = re.captures("あいう").unwrap().unwrap();
+        assert_eq!(Some(6..9), caps.get(2).map(|m| m.range()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_regex_captures_index_panic() {
+        let re = Regex::new("a(b)?").unwrap();
+        let caps = re.captures("a").unwrap().unwrap();
+        let _ = &caps[1];
+    }
+
+    #[test]
+    fn test_captures_iter() {
+        let re = Regex::new("(\\d+):(\\w+)?").unwrap();
+        let all = re
+            .captures_iter("1:a 22: 333:ccc")
+            .map(|caps| {
+                let caps = caps.unwrap();
+                (
+                    caps[1].to_string(),
+                    caps.get(2).map(|m| m.as_str().to_string()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("1".to_string(), Some("a".to_string())),
+                ("22".to_string(), None),
+                ("333".to_string(), Some("ccc".to_string())),
+            ],
+            all
+        );
+
+        // 空文字列へのマッチはfind_iterと同じく1文字ずつ進める
+        let re = Regex::new("(a?)").unwrap();
+        let spans = re
+            .captures_iter("ba")
+            .map(|caps| caps.unwrap().get(1).unwrap().range())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![0..0, 1..2, 2..2], spans);
+
+        // 空の文字列には1度だけマッチし､マッチしない場合は何も返さない
+        assert_eq!(1, re.captures_iter("").count());
+        assert_eq!(0, Regex::new("x").unwrap().captures_iter("abc").count());
+    }
+
+    #[test]
+    fn test_replace() {
+        let re = Regex::new("(\\d+)-(\\d+)").unwrap();
+        assert_eq!("2-1, 3-4", re.replace("1-2, 3-4", "$2-$1").unwrap());
+        assert_eq!("2-1, 4-3", re.replace_all("1-2, 3-4", "${2}-${1}").unwrap());
+        assert_eq!("[1-2]", re.replace("1-2", "[$0]").unwrap());
+
+        // $$は$自体､後に名前が続かない$はそのまま､存在しないグループは空文字列
+        assert_eq!("$1 $ x", re.replace("1-2", "$$1 $ x$3").unwrap());
+        assert_eq!("2x", re.replace("1-2", "${2}x").unwrap());
+        assert_eq!("", re.replace("1-2", "$2x").unwrap());
+
+        // 名前付きグループ
+        let re = Regex::new("(?<y>\\d{4})/(?P<m>\\d\\d)").unwrap();
+        assert_eq!("05.2024", re.replace("2024/05", "$m.$y").unwrap());
+        assert_eq!("05_2024", re.replace("2024/05", "${m}_${y}").unwrap());
+        let caps = re.captures("2024/05").unwrap().unwrap();
+        assert_eq!(Some("05"), caps.name("m").map(|m| m.as_str()));
+        assert!(caps.name("d").is_none());
+
+        // マッチしない場合は借用したまま返す
+        let re = Regex::new("z+").unwrap();
+        assert!(matches!(
+            re.replace_all("abc", "x").unwrap(),
+            Cow::Borrowed("abc")
+        ));
+
+        // 空文字列へのマッチ
+        let re = Regex::new("x*").unwrap();
+        assert_eq!("-a-b-", re.replace_all("ab", "-").unwrap());
+
+        // 名前の誤り
+        assert!(Regex::new("(?<a>x)(?<a>y)").is_err());
+        assert!(Regex::new("(?<1a>x)").is_err());
+        assert!(Regex::new("(?<a").is_err());
+    }
+
+    #[test]
+    fn test_replacen() {
+        let re = Regex::new("(\\w)\\d").unwrap();
+        assert_eq!("a b c3 d4", re.replacen("a1 b2 c3 d4", 2, "$1").unwrap());
+        assert_eq!("a b c d", re.replacen("a1 b2 c3 d4", 0, "$1").unwrap());
+        assert_eq!("a b c d", re.replacen("a1 b2 c3 d4", 10, "$1").unwrap());
+        assert_eq!("a1", re.replacen("a1", 1, "$0").unwrap());
+        assert!(matches!(
+            re.replacen("abc", 1, "x").unwrap(),
+            Cow::Borrowed("abc")
+        ));
+    }
+
+    #[test]
+    fn test_split() {
+        fn split<'h>(expr: &str, text: &'h str) -> Vec<&'h str> {
+            Regex::new(expr)
+                .unwrap()
+                .split(text)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        }
+        assert_eq!(vec!["a", "b", "c"], split(",", "a,b,c"));
+        assert_eq!(vec!["", "a", "", "b", ""], split(",", ",a,,b,"));
+        assert_eq!(vec!["a", "b"], split("\\s+", "a \t b"));
+        assert_eq!(vec!["abc"], split("x", "abc"));
+        assert_eq!(vec![""], split("x", ""));
+
+        // 空文字列へのマッチは1文字ずつ区切る
+        assert_eq!(vec!["", "a", "b", ""], split("x*", "ab"));
+
+        fn splitn(text: &str, limit: usize) -> Vec<&str> {
+            Regex::new(",")
+                .unwrap()
+                .splitn(text, limit)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        }
+        assert_eq!(Vec::<&str>::new(), splitn("a,b,c", 0));
+        assert_eq!(vec!["a,b,c"], splitn("a,b,c", 1));
+        assert_eq!(vec!["a", "b,c"], splitn("a,b,c", 2));
+        assert_eq!(vec!["a", "b", "c"], splitn("a,b,c", 5));
+        assert_eq!(vec!["a", ""], splitn("a,", 2));
+    }
+
+    #[test]
+    fn test_regex_builder() {
+        let re = RegexBuilder::new("^b.c$")
+            .case_insensitive(true)
+            .multi_line(true)
+            .dot_all(true)
+            .build()
+            .unwrap();
+        assert_eq!(Some(2..5), re.find("a\nB\nC").unwrap().map(|m| m.range()));
+        assert!(RegexBuilder::new("^b.c$")
+            .build()
+            .unwrap()
+            .find("a\nB\nC")
+            .unwrap()
+            .is_none());
+
+        // オプションをまとめて指定した後に､個別に上書きできる
+        let flags = Flags {
+            extended: true,
+            ..Default::default()
+        };
+        let re = RegexBuilder::new("a b # comment")
+            .flags(flags)
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        assert!(re.find("AB").unwrap().is_some());
+
+        // 構文と改行の指定
+        let re = RegexBuilder::new("a\\{2\\}$")
+            .dialect(Dialect::Bre)
+            .line_terminator(LineTerminator::Crlf)
+            .build()
+            .unwrap();
+        assert!(re.find("aa\r\n").unwrap().is_some());
+
+        // 資源の上限と探索方式
+        let re = RegexBuilder::new("\\((a|(?R))*\\)")
+            .limits(Limits {
+                recursion: 2,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        assert!(re.find("((a))").unwrap().is_some());
+        assert!(re.find("((((a))))").is_err());
+        let re = RegexBuilder::new("(a)\\1")
+            .match_mode(MatchMode::Bfs)
+            .build()
+            .unwrap();
+        assert!(re.find("aa").is_err());
+
+        // パターンのエラーはbuild時に返す
+        assert!(RegexBuilder::new("a(").build().is_err());
+    }
+
+    #[test]
+    fn test_shortest_match() {
+        let shortest =
+            |expr: &str, text: &str| Regex::new(expr).unwrap().shortest_match(text).unwrap();
+        assert_eq!(Some(2), shortest("a+", "baaa"));
+        assert_eq!(Some(1), shortest("a+", "aaa"));
+        assert_eq!(Some(0), shortest("a*", "aaa"));
+        assert_eq!(None, shortest("x", "aaa"));
+
+        // 後ろで始まるマッチの方が先に終わる場合
+        assert_eq!(Some(3), shortest("abcd|c", "abcd"));
+
+        // アンカー､後読み､後方参照
+        assert_eq!(Some(3), shortest("a+$", "aaa"));
+        assert_eq!(Some(2), shortest("(?<=a)a+", "aaa"));
+        assert_eq!(Some(3), shortest("(?<=aa)a", "aaa"));
+        assert_eq!(Some(4), shortest("(a)b\\1", "xaba"));
+
+        // 位置はバイト単位
+        assert_eq!(Some(6), shortest("い", "あいう"));
+    }
+
+    #[test]
+    fn test_match_span() {
+        // do_matchingと同じく先頭からマッチングする
+        assert_eq!(
+            Some((0, 4)),
+            do_match_span("a+b", "aaabc", MatchMode::Dfs).unwrap()
+        );
+        assert_eq!(None, do_match_span("a+b", "xab", MatchMode::Dfs).unwrap());
+        assert_eq!(
+            Some((0, 0)),
+            do_match_span("x*", "abc", MatchMode::Dfs).unwrap()
+        );
+
+        // \Kで開始位置を変えられる
+        assert_eq!(
+            Some((3, 5)),
+            do_match_span("foo\\Kba", "foobar", MatchMode::Dfs).unwrap()
+        );
+
+        // 位置はバイト単位
+        assert_eq!(
+            Some((0, 6)),
+            do_match_span("あい", "あいう", MatchMode::Dfs).unwrap()
+        );
+        assert!(do_match_span("(", "abc", MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_describe() {
+        let info = describe("a|b").unwrap();
+        assert_eq!("a|b", info.expr());
+        assert!(matches!(info.ast(), Ast::Or(..)));
+        assert_eq!(
+            "expr: a|b\n\
+             AST: Or(Seq([Char('a')]), Seq([Char('b')]))\n\
+             code:\n\
+             0000: save 0\n\
+             0001: split 0002, 0004\n\
+             0002: char a\n\
+             0003: jump 0005\n\
+             0004: char b\n\
+             0005: save 1\n\
+             0006: match\n",
+            info.to_string()
+        );
+
+        assert!(describe("a(").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Ast::Seq(vec![
+                Ast::Char('a'),
+                Ast::Plus(Box::new(Ast::Class(vec![('0', '9')], false))),
+            ]),
+            parse("a[0-9]+").unwrap()
+        );
+        assert_eq!(
+            Ast::Seq(vec![Ast::Capture(
+                Box::new(Ast::Seq(vec![Ast::Char('x')])),
+                1,
+                Some("n".to_string())
+            )]),
+            parse("(?<n>x)").unwrap()
+        );
+        assert_eq!(Err(ParseError::NoRightParen(1..2)), parse("a("));
+        assert_eq!(Err(ParseError::Empty(0..0)), parse(""));
+        assert_eq!(Err(ParseError::Empty(0..4)), parse("(?i)"));
+    }
+
+    #[test]
+    fn test_compile() {
+        let prog = compile("a+").unwrap();
+        assert_eq!(5, prog.len());
+        assert!(matches!(prog[1], Instruction::Char('a')));
+        assert!(matches!(prog.get(2), Some(Instruction::Split(1, 3))));
+        assert!(prog.get(5).is_none());
+        assert_eq!(
+            "0000: save 0\n0001: char a\n0002: split 0001, 0003\n0003: save 1\n0004: match\n",
+            prog.to_string()
+        );
+
+        // 1度コンパイルした命令列で何度もマッチングできる
+        assert!(prog.is_match("aaa", MatchMode::Dfs).unwrap());
+        assert!(!prog.is_match("baa", MatchMode::Dfs).unwrap());
+
+        assert!(matches!(
+            compile("a("),
+            Err(CompileError::Parse(ParseError::NoRightParen(_)))
+        ));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        assert_eq!(
+            "digraph program {\n    rankdir=LR;\n    node [shape=box];\n\
+             \x20   0 [label=\"0000: save 0\"];\n\
+             \x20   1 [label=\"0001: split 0002, 0004\"];\n\
+             \x20   2 [label=\"0002: char a\"];\n\
+             \x20   3 [label=\"0003: jump 0005\"];\n\
+             \x20   4 [label=\"0004: char \\\"\"];\n\
+             \x20   5 [label=\"0005: save 1\"];\n\
+             \x20   6 [label=\"0006: match\", peripheries=2];\n\
+             \x20   0 -> 1;\n\
+             \x20   1 -> 2 [label=\"1\"];\n\
+             \x20   1 -> 4 [label=\"2\"];\n\
+             \x20   2 -> 3;\n\
+             \x20   3 -> 5;\n\
+             \x20   4 -> 5;\n\
+             \x20   5 -> 6;\n\
+             }\n",
+            compile("a|\"").unwrap().to_dot()
+        );
+
+        // 後読みと条件分岐の辺
+        let dot = compile("(a)?(?(1)b|c)(?<=c)").unwrap().to_dot();
+        assert!(dot.contains("[label=\"yes\"]"));
+        assert!(dot.contains("[label=\"no\"]"));
+        assert!(dot.contains("[label=\"look\"]"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_program() {
+        let prog = compile("(?m)^a(b|c)+\\1$").unwrap();
+        let json = serde_json::to_string(&prog).unwrap();
+        let prog2: crate::Program = serde_json::from_str(&json).unwrap();
+
+        // 復元した命令列は元の命令列と同じく評価できる
+        assert_eq!(prog.to_string(), prog2.to_string());
+        assert!(prog2.is_match("abcc", MatchMode::Dfs).unwrap());
+        assert!(!prog2.is_match("abcb", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(
+            "    save 0                  ; start of match\n\
+             L1:\n\
+             \x20   save 2                  ; ( group 1\n\
+             \x20   split L2, L3\n\
+             L2:\n\
+             \x20   char a                  ; a\n\
+             \x20   jump L4\n\
+             L3:\n\
+             \x20   char .                  ; \\.\n\
+             L4:\n\
+             \x20   save 3                  ; ) group 1\n\
+             \x20   split L1, L5\n\
+             L5:\n\
+             \x20   class [0-9]             ; [0-9]\n\
+             \x20   save 1                  ; end of match\n\
+             \x20   match\n",
+            compile("(a|\\.)+[0-9]").unwrap().disassemble()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_stream_matcher() {
+        use crate::StreamMatcher;
+        use std::io::Read;
+
+        // 1バイトずつ読み込むreader
+        struct OneByte<'a>(&'a [u8]);
+        impl Read for OneByte<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let Some((b, rest)) = self.0.split_first() else {
+                    return Ok(0);
+                };
+                buf[0] = *b;
+                self.0 = rest;
+                Ok(1)
+            }
+        }
+
+        let re = Regex::new("^\\w+|あ+").unwrap();
+        let input = "foo bar\nあああ\n\nbaz";
+        let matches = StreamMatcher::new(&re, OneByte(input.as_bytes()))
+            .map(|m| {
+                let m = m.unwrap();
+                assert_eq!(&input[m.range()], m.as_str());
+                (m.line(), m.as_str().to_string())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (1, "foo".to_string()),
+                (2, "あああ".to_string()),
+                (4, "baz".to_string())
+            ],
+            matches
+        );
+
+        // 大きな入力
+        let input = "x\n".repeat(10000) + "y";
+        let re = Regex::new("y").unwrap();
+        let matches = StreamMatcher::new(&re, input.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(1, matches.len());
+        assert_eq!(
+            (10001, 20000..20001),
+            (matches[0].line(), matches[0].range())
+        );
+
+        // UTF-8として不正な行はエラー
+        let mut iter = StreamMatcher::new(&re, &b"y\n\xff\ny"[..]);
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_incremental_matcher() {
+        use crate::{IncrementalMatcher, Status};
+
+        let re = Regex::new("\\w+@\\w+").unwrap();
+        let mut matcher = IncrementalMatcher::new(&re);
+        let ranges = |status: Status| match status {
+            Status::Pending => vec![],
+            Status::Matches(ms) => ms.iter().map(|m| (m.line(), m.range())).collect(),
+        };
+
+        // 改行で行が終わるまでは保留する
+        assert_eq!(Status::Pending, matcher.feed(b"to: a").unwrap());
+        assert_eq!(Status::Pending, matcher.feed(b"@b").unwrap());
+        assert_eq!(
+            vec![(1, 4..7), (2, 12..15), (2, 17..20)],
+            ranges(matcher.feed(b"\ncc: c@d, e@f\nx").unwrap())
+        );
+        assert_eq!(Status::Pending, matcher.feed(b"@y").unwrap());
+
+        // 最後の行はfinishでマッチングする
+        assert_eq!(vec![(3, 21..24)], ranges(matcher.finish().unwrap()));
+        assert_eq!(Status::Pending, matcher.finish().unwrap());
+
+        // 不正な行はエラーとし､次の行から続けられる
+        let mut matcher = IncrementalMatcher::new(&re);
+        assert!(matcher.feed(b"\xff a@b\n").is_err());
+        assert_eq!(vec![(2, 6..9)], ranges(matcher.feed(b"c@d\n").unwrap()));
+
+        // 1度に与えた複数の行は順に処理し､不正な行の前のマッチを返してからエラーとする
+        let mut matcher = IncrementalMatcher::new(&re);
+        let input = (0..1000).map(|i| format!("{i}@x\n")).collect::<String>();
+        let found = ranges(matcher.feed(input.as_bytes()).unwrap());
+        assert_eq!(1000, found.len());
+        assert_eq!((1000, 5884..5889), found[999]);
+        assert_eq!(
+            vec![(1001, 5890..5893)],
+            ranges(matcher.feed(b"a@b\n\xff\nc@").unwrap())
+        );
+        assert!(matcher.feed(b"d").is_err());
+        assert_eq!(vec![(1003, 5896..5899)], ranges(matcher.finish().unwrap()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_grep() {
+        let input = "foo 1\nbar\n\nbaz 22 333\n";
+        let lines = crate::grep("\\d+", input.as_bytes())
+            .unwrap()
+            .map(|l| {
+                let l = l.unwrap();
+                (l.number(), l.as_str().to_string(), l.spans().to_vec())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (1, "foo 1".to_string(), vec![(4, 5)]),
+                (4, "baz 22 333".to_string(), vec![(4, 6), (7, 10)]),
+            ],
+            lines
+        );
+
+        // 空文字列にマッチする場合は空行もマッチする
+        assert_eq!(3, crate::grep("x*", "a\n\nb".as_bytes()).unwrap().count());
+        assert!(crate::grep("(", "a".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_find_bytes() {
+        let re = Regex::new("\\w+").unwrap();
+        let ranges = |hay: &[u8]| {
+            re.find_iter_bytes(hay)
+                .map(|m| m.map(|m| m.range()))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+        assert_eq!(vec![0..2, 3..5], ranges(b"ab cd"));
+
+        // 不正なバイトは1バイトずつU+FFFDとなる
+        assert_eq!(vec![0..1, 3..4], ranges(b"a\xff\xfeb"));
+        let re = Regex::new("\\x{FFFD}+").unwrap();
+        assert_eq!(
+            Some(1..3),
+            re.find_bytes(b"a\xff\xfeb").unwrap().map(|m| m.range())
+        );
+        let re = Regex::new("a.b").unwrap();
+        assert!(re.find_bytes(b"xa\x80b").unwrap().is_some());
+        assert!(re.find_bytes(b"xa\xc3\xa9b").unwrap().is_some());
+        assert!(re.find_bytes(b"xa\xc3b").unwrap().is_some());
+
+        // 正しいUTF-8の文字は1文字として扱い､位置はバイト単位
+        let re = Regex::new("い").unwrap();
+        let m = re.find_bytes("あいう".as_bytes()).unwrap().unwrap();
+        assert_eq!((3..6, "い".as_bytes()), (m.range(), m.as_bytes()));
+    }
+
+    #[test]
+    fn test_cache() {
+        // Regexはスレッド間で共有でき､Cacheはスレッドごとに移動できる
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Regex>();
+        assert_send_sync::<Cache>();
+
+        // 同じCacheを別のRegexと使い回しても､結果は変わらない
+        let mut cache = Cache::new();
+        let re1 = Regex::new("(a|ab)(c|bcd)(d*)").unwrap();
+        let re2 = Regex::new("(?<=x)y+").unwrap();
+        for (re, hay) in [
+            (&re1, "abcd"),
+            (&re2, "xyy"),
+            (&re1, "zzz"),
+            (&re2, "yy"),
+            (&re1, "xabcdd"),
+        ] {
+            assert_eq!(
+                re.is_match(hay).unwrap(),
+                re.is_match_with(&mut cache, hay).unwrap()
+            );
+            assert_eq!(
+                re.find(hay).unwrap().is_some(),
+                re.is_match_with(&mut cache, hay).unwrap()
+            );
+        }
+
+        // キャプチャの位置は分岐ごとに正しく戻される
+        let caps = re1.captures("abcd").unwrap().unwrap();
+        assert_eq!(("a", "bcd", ""), (&caps[1], &caps[2], &caps[3]));
+
+        // 再帰の上限を超えた場合はエラー
+        let re = RegexBuilder::new("a(?R)?b")
+            .limits(Limits {
+                recursion: 2,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        assert!(re.is_match_with(&mut cache, "aaaabbbb").is_err());
+        assert!(re.is_match_with(&mut cache, "aabb").unwrap());
+    }
+
+    #[test]
+    fn test_regex_error() {
+        use crate::{EvalError, RegexError};
+        use std::error::Error;
+
+        // エラーの種類ごとに判別できる
+        assert!(matches!(
+            Regex::new("a)"),
+            Err(RegexError::Parse(ParseError::InvalidRightParen(span))) if span == (1..2)
+        ));
+        let re = RegexBuilder::new("a(?R)?b")
+            .limits(Limits {
+                recursion: 1,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        assert!(matches!(
+            re.find("aaabbb"),
+            Err(RegexError::Eval(EvalError::RecursionLimit))
+        ));
+        assert!(matches!(
+            do_captures_at("a", "あa", 1, MatchMode::Dfs),
+            Err(RegexError::InvalidStart(1))
+        ));
+
+        // 元のエラーをsourceで辿れる
+        let e = Regex::new("a)").unwrap_err();
+        assert!(e.source().is_some());
+        assert_eq!(
+            e.to_string(),
+            ParseError::InvalidRightParen(1..2).to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_error_span() {
+        let span = |expr: &str| parse(expr).unwrap_err().span();
+
+        // 範囲は文字単位
+        assert_eq!(1..2, span("a)"));
+        assert_eq!(1..3, span("あ\\q"));
+        assert_eq!(1..4, span("a{x}"));
+        assert_eq!(0..7, span("(?<=a+)b"));
+        assert_eq!(9..12, span("(?<n>a)(?<n>b)"));
+
+        // 同じ文字列が前にあっても､エラーとなった箇所の範囲となる
+        assert_eq!(5..8, span("z-a|[z-a]"));
+        assert_eq!(5..7, span("(a)\\1\\2"));
+        assert_eq!(3..10, span("(a)(?(2)b)"));
+
+        // エスケープは\から読み込んだ末尾まで
+        assert_eq!(1..6, span("a\\x{41"));
+        assert_eq!(0..7, span("\\p{Foo}a"));
+        assert_eq!(0..6, span("\\u30ZZ"));
+        assert_eq!(1..8, span("[[:foo:]]"));
+        assert_eq!(0..1, span("+a"));
+        assert_eq!(2..3, span("(?z)"));
+
+        // 閉じ括弧がない場合は､閉じられていない開き括弧の位置
+        assert_eq!(0..1, span("(a(b)"));
+        assert_eq!(0..1, span("[]a\\]"));
+        assert_eq!(3..4, span("[a](b[)]"));
+        assert_eq!(0..0, span(""));
+
+        // 下線はエラーの範囲を含む行に引く
+        let expr = "(?x)a\n  b{3,1}";
+        assert_eq!(
+            parse(expr).unwrap_err().render(expr),
+            "ParseError: invalid repetition range: {3,1}\n    b{3,1}\n     ^^^^^ the minimum count must not be greater than the maximum\n"
+        );
+    }
+
+    #[test]
+    fn test_visitor() {
+        use crate::Visitor;
+
+        // enterとleaveの順に式の種類を記録する
+        #[derive(Default)]
+        struct Trace(Vec<String>);
+        impl Visitor for Trace {
+            fn enter(&mut self, ast: &Ast) -> bool {
+                let name = format!("{ast:?}");
+                let name = name.split('(').next().unwrap();
+                self.0.push(format!("+{name}"));
+                // 後読みの中は辿らない
+                !matches!(ast, Ast::LookBehind(..))
+            }
+            fn leave(&mut self, _ast: &Ast) {
+                self.0.push("-".to_string());
+            }
+        }
+
+        let mut trace = Trace::default();
+        parse("a|(?<=b)c*").unwrap().walk(&mut trace);
+        assert_eq!(
+            trace.0.join(" "),
+            "+Or +Seq +Char - - +Seq +LookBehind - +Star +Char - - - -"
+        );
+
+        // 条件分岐のnoの式も子として辿る
+        let ast = parse("(a)(?(1)b|c)").unwrap();
+        let cond = &ast.children()[1];
+        assert_eq!(cond.children().len(), 2);
+
+        // foldは子から順に変換する
+        let mut order = Vec::new();
+        let ast = parse("(ab)+").unwrap().fold(&mut |ast| {
+            order.push(format!("{ast:?}").chars().take(4).collect::<String>());
+            match ast {
+                Ast::Plus(e) => Ast::Star(e),
+                ast => ast,
+            }
+        });
+        assert_eq!(ast, parse("(ab)*").unwrap());
+        assert_eq!(order, vec!["Char", "Char", "Seq(", "Capt", "Plus", "Seq("]);
+    }
+
+    #[test]
+    fn test_ast_display() {
+        // 最小限のエスケープでパターンに戻す
+        let pattern = |expr: &str| parse(expr).unwrap().to_string();
+        assert_eq!("a|b|c", pattern("a|b|c"));
+        assert_eq!("(?:a|b)c", pattern("(?:a|b)c"));
+        assert_eq!("a{2,}b{1,3}c{3}", pattern("a{2,}b{1,3}c{3}"));
+        assert_eq!("[\\]a\\-z\\^]", pattern("[\\]a\\-z^]"));
+        assert_eq!("[\\&\\&x]", pattern("[\\&\\&x]"));
+        assert_eq!("a\\.b\\$", pattern("\\Qa.b$\\E"));
+        assert_eq!("(a)\\1\\x{30}", pattern("(a)\\1\\x{30}"));
+        assert_eq!("(?i-ms:ab(?-ims:c))", pattern("(?i)ab(?-i:c)"));
+        assert_eq!("(?<n>a)(?(1)x|(?:y|z))", pattern("(?<n>a)(?(1)x|(?:y|z))"));
+
+        // 空の(?:)は､パターン全体やその一部が空とならないよう残す
+        for expr in [
+            "(?:)",
+            "a(?:)b",
+            "(?:)|(?:)",
+            "(?:)*",
+            "a|",
+            "()",
+            "(?i-ms:)",
+        ] {
+            assert_eq!(expr, pattern(expr));
+            assert_eq!(parse(expr).unwrap(), parse(&pattern(expr)).unwrap());
+        }
+
+        // 生成したパターンは同じ文字列にマッチし､再び生成しても変わらない
+        let exprs = [
+            "(?<y>\\d{4})-(a|b)+\\.",
+            "(?:ab)*|a**",
+            "(?<=ab)c(?<!d)\\K\\w+",
+            "(?s).(?m)^x$",
+            "\\R\\t[^\\n]",
+            "(a)(?(1)b|c)\\1",
+            "x(?R)?y",
+        ];
+        let hays = [
+            "2024-abab.",
+            "ababa",
+            "abcdef",
+            "\n\nx\ny",
+            "\r\n\ta",
+            "abba",
+            "xxyy",
+        ];
+        for expr in exprs {
+            let generated = pattern(expr);
+            assert_eq!(generated, pattern(&generated));
+            let (re1, re2) = (Regex::new(expr).unwrap(), Regex::new(&generated).unwrap());
+            for hay in hays {
+                let find = |re: &Regex| re.find(hay).unwrap().map(|m| m.range());
+                assert_eq!(find(&re1), find(&re2), "{expr} => {generated}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_capture_names() {
+        let re = Regex::new("(a)(?<x>b(?<y>c))?(?:d)(e)").unwrap();
+        assert_eq!(5, re.captures_len());
+        assert_eq!(
+            vec![None, None, Some("x"), Some("y"), None],
+            re.capture_names().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(2), re.capture_index("x"));
+        assert_eq!(Some(3), re.capture_index("y"));
+        assert_eq!(None, re.capture_index("z"));
+
+        // Capturesのグループの数と一致する
+        let caps = re.captures("ade").unwrap().unwrap();
+        assert_eq!(re.captures_len(), caps.len());
+
+        // グループがない場合はマッチ全体のみ
+        let re = Regex::new("abc").unwrap();
+        assert_eq!(1, re.captures_len());
+        assert_eq!(vec![None], re.capture_names().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_replacer() {
+        use crate::{Captures, NoExpand};
+
+        // クロージャが返した文字列で置き換える
+        let re = Regex::new("\\d+").unwrap();
+        let inc = |caps: &Captures| (caps[0].parse::<u32>().unwrap() + 1).to_string();
+        assert_eq!("a2 b10 c100", re.replace_all("a1 b9 c99", inc).unwrap());
+        assert_eq!("a2 b9 c99", re.replace("a1 b9 c99", inc).unwrap());
+
+        // 状態を持つクロージャも利用できる
+        let re = Regex::new("x").unwrap();
+        let mut n = 0;
+        let numbered = re
+            .replace_all("x x x", |_: &Captures| {
+                n += 1;
+                n.to_string()
+            })
+            .unwrap();
+        assert_eq!("1 2 3", numbered);
+
+        // NoExpandは$を展開しない
+        let re = Regex::new("(\\w+)").unwrap();
+        assert_eq!("$1", re.replace("ab", NoExpand("$1")).unwrap());
+        assert_eq!("ab", re.replace("ab", "$1".to_string()).unwrap());
+        assert_eq!("ab", re.replace("ab", &"$1".to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_captures_expand() {
+        let re = Regex::new("(?<y>\\d{4})-(\\d{2})(x)?").unwrap();
+        let caps = re.captures("at 2024-05").unwrap().unwrap();
+
+        let mut dst = String::new();
+        caps.expand("$0|$1|${2}|$y|${y}|$3|$9|$$|$|${}|$-", &mut dst);
+        assert_eq!("2024-05|2024|05|2024|2024|||$|$|${}|$-", dst);
+
+        // 既存の内容の後に追加する
+        caps.expand("!", &mut dst);
+        assert!(dst.ends_with("$-!"));
+
+        // replaceで与えたテンプレートと同じ結果になる
+        let mut dst = String::new();
+        caps.expand("$2/$1", &mut dst);
+        assert_eq!(re.replace("2024-05", "$2/$1").unwrap(), dst);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            r"\\\(\)\|\+\*\?\[\]\{\}\.\^\$\#\ ",
+            escape(r"\()|+*?[]{}.^$# ")
+        );
+        assert_eq!(
+            r"a\nb\t\x{B}\x{3000}\-\&\~=日本",
+            escape("a\nb\t\x0b\u{3000}-&~=日本")
+        );
+        assert_eq!("", escape(""));
+
+        // エスケープした文字列そのものにのみマッチする
+        for text in ["a.b", "(x|y)*", "^$", "$1.00 #tag", "[a-z]{2}", "\\d\r\n"] {
+            let expr = format!("^{}$", escape(text));
+            assert!(do_matching(&expr, text, MatchMode::Dfs).unwrap(), "{expr}");
+            let flags = Flags {
+                extended: true,
+                case_insensitive: true,
+                ..Default::default()
+            };
+            assert!(
+                do_matching_with_flags(&expr, text, flags, MatchMode::Dfs).unwrap(),
+                "{expr}"
+            );
+            assert!(
+                !do_matching(&expr, &format!("{text}_"), MatchMode::Dfs).unwrap(),
+                "{expr}"
+            );
+        }
+        assert!(!do_matching(&escape("a.b"), "axb", MatchMode::Dfs).unwrap());
+
+        // 文字クラスに埋め込んだ場合も､範囲や集合演算とならず各文字そのものにのみマッチする
+        for text in [
+            "a-z",
+            "x&&y",
+            "a--b",
+            "~~",
+            "^]",
+            "[\\w]",
+            "(|)+*?{}.$# ",
+            "\t\n",
+        ] {
+            let expr = format!("^[{}]+$", escape(text));
+            assert!(do_matching(&expr, text, MatchMode::Dfs).unwrap(), "{expr}");
+            assert!(!do_matching(&expr, "m", MatchMode::Dfs).unwrap(), "{expr}");
+        }
+
+        // \-､\&､\~は文字クラスの外でもリテラルとなる
+        assert!(do_matching("^\\-\\&\\~$", "-&~", MatchMode::Dfs).unwrap());
+
+        // \^と\$はリテラルとなる
+        assert_eq!(Ast::Seq(vec![Ast::Char('^')]), parse("\\^").unwrap());
+        assert_eq!(Ast::Seq(vec![Ast::Char('$')]), parse("\\$").unwrap());
+    }
+
+    #[test]
+    fn test_codegen_limits() {
+        use crate::{CodeGenError, RegexError};
+
+        // 展開すると巨大になるパターンは､既定の上限でエラーとなる
+        let err = Regex::new("(a|b){1000}{1000}").unwrap_err();
+        assert!(matches!(
+            err,
+            RegexError::CodeGen(CodeGenError::ProgramTooLarge(n)) if n == Limits::default().insts
+        ));
+        assert!(err.to_string().contains("program too large"));
+
+        let err = Regex::new("a{20000}").unwrap_err();
+        assert!(matches!(
+            err,
+            RegexError::CodeGen(CodeGenError::RepeatTooLarge(20000))
+        ));
+        assert!(Regex::new("a{2,20000}").is_err());
+        assert!(Regex::new("a{20000,}").is_err());
+        assert!(Regex::new("a{100}").is_ok());
+
+        // 上限を変更できる
+        let limits = Limits {
+            insts: 16,
+            repeat: 3,
+            ..Default::default()
+        };
+        assert!(RegexBuilder::new("a{3}").limits(limits).build().is_ok());
+        assert!(RegexBuilder::new("a{4}").limits(limits).build().is_err());
+        assert!(RegexBuilder::new("(abc){3}")
+            .limits(limits)
+            .build()
+            .is_err());
+        assert!(do_matching_with_limits("a{1,3}", "aa", limits, MatchMode::Dfs).unwrap());
+        assert!(do_matching_with_limits("a{4}", "aaaa", limits, MatchMode::Dfs).is_err());
+    }
+
+    #[test]
+    fn test_step_budget() {
+        use crate::{EvalError, RegexError};
+
+        // 訪れた組の記録とDFAを用いない場合､バックトラックが指数的に増えるパターンは上限で打ち切る
+        let limits = Limits {
+            steps: 100_000,
+            visited: 0,
+            dfa: 0,
+            ..Default::default()
+        };
+        let re = RegexBuilder::new("^(a|a)*b$")
+            .limits(limits)
+            .build()
+            .unwrap();
+        let input = "a".repeat(30);
+        assert!(matches!(
+            re.is_match(&input),
+            Err(RegexError::Eval(EvalError::BudgetExceeded))
+        ));
+        assert!(matches!(
+            do_matching_with_limits("(a|aa)+c", &input, limits, MatchMode::Dfs),
+            Err(RegexError::Eval(EvalError::BudgetExceeded))
+        ));
+
+        // 上限内で終わる場合は通常通りマッチする
+        assert!(re.is_match("aaab").unwrap());
+        assert!(!re.is_match("aaa").unwrap());
+
+        // 上限は1回の試行ごとのため､入力が長くても線形なパターンは打ち切られない
+        let limits = Limits {
+            steps: 10,
+            ..Default::default()
+        };
+        let re = RegexBuilder::new("ab").limits(limits).build().unwrap();
+        assert_eq!(100, re.find_iter(&"ab".repeat(100)).count());
+        assert!(re.find(&"a".repeat(100)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_count() {
+        // find_iterと同じマッチを数える
+        for (expr, haystack, expected) in [
+            ("\\d+", "1, 22, 333", 3),
+            ("a*", "baaab", 4),
+            ("x?", "日本語", 4),
+            ("x", "abc", 0),
+            ("aa", "aaaaa", 2),
+            ("(?m)^\\w", "ab\ncd\n", 2),
+            ("(a)(b)?|(c)", "abacx c", 4),
+            ("(a|ab)(c|bcd)(d*)", "abcd acd abc", 3),
+            ("(\\w)\\1", "aabbcd", 2),
+            ("(?<y>\\d{4})-(\\d\\d)", "2024-05 1999-12 x", 2),
+            ("a\\K", "aaa", 3),
+            ("(a)\\K(b)?", "abaa", 3),
+            ("a$\\n", "a\na\n", 1),
+            ("\\Z\\n|b", "bb\n", 3),
+            ("(?s)a$.", "a\n", 1),
+            ("^a|a$", "aaa", 2),
+            ("\\Aa\\K|\\Kb\\z", "aab", 2),
+        ] {
+            // DFAと1度の走査での評価の有無によらず､同じ数となる
+            for (dfa, onepass) in [(10_000, 10_000), (1, 10_000), (0, 0)] {
+                let re = RegexBuilder::new(expr)
+                    .limits(Limits {
+                        dfa,
+                        onepass,
+                        ..Default::default()
+                    })
+                    .build()
+                    .unwrap();
+                assert_eq!(expected, re.count(haystack).unwrap(), "{expr}");
+                assert_eq!(re.find_iter(haystack).count(), re.count(haystack).unwrap());
+            }
+        }
+
+        // 評価中のエラーを返す
+        let re = RegexBuilder::new("\\((a|(?R))*\\)")
+            .limits(Limits {
+                recursion: 1,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        assert!(re.count("((a)) (((a)))").is_err());
+    }
+
+    #[test]
+    fn test_match_mode() {
+        use crate::{EvalError, RegexError};
+
+        assert_eq!(MatchMode::Dfs, MatchMode::default());
+        assert!(do_matching("abc|(de|cd)+", "decddede", MatchMode::Dfs).unwrap());
+        assert!(matches!(
+            do_matching("(a)\\1", "aa", MatchMode::Bfs),
+            Err(RegexError::Eval(EvalError::UnsupportedBackref))
+        ));
+        assert!(matches!(
+            do_matching("(a)?(?(1)b|c)", "ab", MatchMode::Bfs),
+            Err(RegexError::Eval(EvalError::UnsupportedConstruct))
+        ));
+        assert!(matches!(
+            do_matching("\\((?R)?\\)", "(())", MatchMode::Bfs),
+            Err(RegexError::Eval(EvalError::UnsupportedConstruct))
+        ));
+
+        // 互換性のため､非推奨のfrom_depth_firstでboolから変換できる
+        #[allow(deprecated)]
+        let (dfs, bfs) = (
+            MatchMode::from_depth_first(true),
+            MatchMode::from_depth_first(false),
+        );
+        assert_eq!((MatchMode::Dfs, MatchMode::Bfs), (dfs, bfs));
+
+        // RegexBuilderでも指定できる
+        let re = RegexBuilder::new("(a)\\1")
+            .match_mode(MatchMode::Bfs)
+            .build()
+            .unwrap();
+        assert!(re.is_match("aa").is_err());
+        #[allow(deprecated)]
+        let re = RegexBuilder::new("(a)\\1")
+            .depth_first(true)
+            .build()
+            .unwrap();
+        assert!(re.is_match("aa").unwrap());
+
+        // 幅優先探索では近似マッチングを扱えない
+        assert!(matches!(
+            do_fuzzy_matching("abc", "abd", 1, MatchMode::Bfs),
+            Err(RegexError::Eval(EvalError::UnsupportedFuzzy))
+        ));
+        assert!(do_fuzzy_matching("abc", "abc", 0, MatchMode::Bfs).unwrap());
+
+        // 幅優先探索でも各位置から開始したマッチのうち､最も前から始まるものを求める
+        let limits = Limits {
+            onepass: 0,
+            dfa: 0,
+            ..Default::default()
+        };
+        for (expr, line, expected) in [
+            ("a+b|b", "xaabb", vec![1..4, 4..5]),
+            ("b|ab*", "abbab", vec![0..3, 3..5]),
+            ("(?<=a)b|c", "cabb", vec![0..1, 2..3]),
+            ("\\Gb|a?\\Gb", "bab", vec![0..1, 2..3]),
+            ("a|ab", "xabab", vec![1..2, 3..4]),
+        ] {
+            let bfs = RegexBuilder::new(expr)
+                .match_mode(MatchMode::Bfs)
+                .limits(limits)
+                .build()
+                .unwrap();
+            let dfs = RegexBuilder::new(expr).limits(limits).build().unwrap();
+            let spans = |re: &Regex| {
+                re.find_iter(line)
+                    .map(|m| m.unwrap().range())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(expected, spans(&bfs), "{expr}");
+            assert_eq!(expected, spans(&dfs), "{expr}");
+        }
+    }
+
+    #[test]
+    fn test_find_at() {
+        use crate::RegexError;
+
+        let re = Regex::new("\\d+").unwrap();
+        let range = |m: Option<crate::Match>| m.map(|m| m.range());
+        assert_eq!(Some(0..2), range(re.find_at("12 345", 0).unwrap()));
+        assert_eq!(Some(1..2), range(re.find_at("12 345", 1).unwrap()));
+        assert_eq!(Some(3..6), range(re.find_at("12 345", 2).unwrap()));
+        assert_eq!(None, range(re.find_at("12 345", 6).unwrap()));
+        assert!(re.is_match_at("12 345", 5).unwrap());
+        assert!(!re.is_match_at("12 abc", 2).unwrap());
+
+        // startより前の文字も考慮する
+        let re = Regex::new("^a|(?<=b)c").unwrap();
+        assert_eq!(None, range(re.find_at("aac", 1).unwrap()));
+        assert_eq!(Some(2..3), range(re.find_at("abc", 2).unwrap()));
+
+        // 開始位置を固定する
+        let re = Regex::new("\\Gb+|c").unwrap();
+        assert_eq!(Some(1..3), range(re.find_anchored_at("abbc", 1).unwrap()));
+        assert_eq!(Some(3..4), range(re.find_anchored_at("abbc", 3).unwrap()));
+        assert_eq!(None, range(re.find_anchored_at("abbc", 0).unwrap()));
+
+        // 文字の境界でない位置や､範囲外の位置はエラー
+        let re = Regex::new("a").unwrap();
+        assert!(matches!(
+            re.find_at("あa", 1),
+            Err(RegexError::InvalidStart(1))
+        ));
+        assert!(re.is_match_at("あa", 5).is_err());
+        assert!(re.find_anchored_at("a", 2).is_err());
+        assert_eq!(Some(3..4), range(re.find_at("あa", 3).unwrap()));
+    }
+
+    #[test]
+    fn test_find_overlapping_iter() {
+        let spans = |expr: &str, haystack: &str| {
+            Regex::new(expr)
+                .unwrap()
+                .find_overlapping_iter(haystack)
+                .map(|m| m.map(|m| m.range()))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        assert_eq!(vec![0..2, 1..3, 2..4], spans("aa", "aaaa"));
+        assert_eq!(vec![0..3, 1..3, 2..3], spans("a+", "aaa"));
+        assert_eq!(vec![1..4, 4..7], spans("A[CG]T", "GACTAGTA"));
+        assert_eq!(Vec::<std::ops::Range<usize>>::new(), spans("x", "abc"));
+
+        // 空文字列のマッチは各位置で1つずつ返す
+        assert_eq!(vec![0..0, 3..3, 6..6], spans("x?", "日本"));
+
+        // k-merの出現位置
+        let dna = "ATATAT";
+        assert_eq!(vec![0..3, 2..5], spans("ATA", dna));
+        assert_eq!(vec![0..2, 2..4, 4..6], spans("AT", dna));
+
+        // 評価中のエラーを返し､以降は何も返さない
+        let re = RegexBuilder::new("\\((a|(?R))*\\)")
+            .limits(Limits {
+                recursion: 1,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let mut iter = re.find_overlapping_iter("(a) ((a))");
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_rfind() {
+        let rfind = |expr: &str, haystack: &str| {
+            Regex::new(expr)
+                .unwrap()
+                .rfind(haystack)
+                .unwrap()
+                .map(|m| m.range())
+        };
+
+        assert_eq!(Some(6..9), rfind("\\d+", "v1.22.333"));
+        assert_eq!(Some(5..9), rfind("[a-z.]\\d+", "v1.22.333"));
+        assert_eq!(Some(6..11), rfind("\\d+", "order 12345"));
+        assert_eq!(Some(6..11), rfind("\\w+", "hello world"));
+        assert_eq!(Some(0..2), rfind("aa", "aaa"));
+        assert_eq!(Some(2..4), rfind("aa", "aaaa"));
+        assert_eq!(Some(3..5), rfind("ab|b", "ab ab"));
+        assert_eq!(Some(6..6), rfind("x?", "日本"));
+        assert_eq!(None, rfind("x", "abc"));
+
+        // 後読みやアンカーは文字列全体を考慮する
+        assert_eq!(Some(0..1), rfind("^a", "aaa"));
+        assert_eq!(Some(4..5), rfind("(?<=b)a", "baaba"));
+        assert_eq!(Some(2..3), rfind("a\\z", "aba"));
+        assert_eq!(None, rfind("\\Ab", "aba"));
+
+        // find_iterの最後のマッチと一致する
+        for (expr, haystack) in [
+            ("a+", "aab aaa"),
+            ("(ab)*c?", "abcab"),
+            ("a\\Kb", "abab"),
+            ("(?i)AB|c", "xabcAb"),
+            ("(a)\\1", "aaaa"),
+            ("(?<=a)b+|x", "abbxabb"),
+            ("\\Gb|a", "abab"),
+            ("(?m)^\\d", "1\n2\nx"),
+            ("a|ab", "ab ab"),
+            ("\\d*", "12 34"),
+        ] {
+            let re = Regex::new(expr).unwrap();
+            let expected = re.find_iter(haystack).last().map(|m| m.unwrap().range());
+            assert_eq!(expected, rfind(expr, haystack), "{expr}");
+        }
+    }
+
+    #[test]
+    fn test_captures_index_by_name() {
+        let re = Regex::new("(?<key>\\w+)=(?<value>\\d*)(?<unit>ms)?").unwrap();
+        let caps = re.captures("timeout=30ms").unwrap().unwrap();
+        assert_eq!("timeout", &caps["key"]);
+        assert_eq!("30", &caps["value"]);
+        assert_eq!("ms", &caps["unit"]);
+        assert_eq!(&caps[1], &caps["key"]);
+
+        let caps = re.captures("retry=").unwrap().unwrap();
+        assert_eq!("", &caps["value"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_captures_index_by_name_panic() {
+        let re = Regex::new("(?<key>a)(?<opt>b)?").unwrap();
+        let caps = re.captures("a").unwrap().unwrap();
+        let _ = &caps["opt"];
+    }
+
+    #[test]
+    fn test_match_borrows_haystack() {
+        let haystack = String::from("id=1 id=22 id=333");
+
+        // Regexやイテレータを破棄した後も､haystackの寿命でマッチを利用できる
+        let (words, ranges) = {
+            let re = Regex::new("id=(\\d+)").unwrap();
+            let words = re
+                .find_iter(&haystack)
+                .map(|m| m.map(<&str>::from))
+                .collect::<Result<Vec<&str>, _>>()
+                .unwrap();
+            let ranges = re
+                .captures_iter(&haystack)
+                .map(|caps| caps.map(|caps| std::ops::Range::from(caps.get(1).unwrap())))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            (words, ranges)
+        };
+        assert_eq!(vec!["id=1", "id=22", "id=333"], words);
+        assert_eq!(vec![3..4, 8..10, 14..17], ranges);
+
+        // 部分文字列はhaystackの一部を指す
+        let m = Regex::new("22").unwrap().find(&haystack).unwrap().unwrap();
+        assert!(std::ptr::eq(m.as_str().as_ptr(), haystack[8..].as_ptr()));
+    }
+
+    #[test]
+    fn test_min_max_len() {
+        let lens = |expr: &str| {
+            let re = Regex::new(expr).unwrap();
+            (re.min_len(), re.max_len())
+        };
+        assert_eq!((1, Some(1)), lens("a"));
+        assert_eq!((3, Some(3)), lens("ab[cd]"));
+        assert_eq!((2, None), lens("ab+|cde?"));
+        assert_eq!((1, Some(4)), lens("ab?|c{2,4}"));
+        assert_eq!((0, Some(0)), lens("^(?:)*$"));
+        assert_eq!((0, Some(0)), lens("a{0}"));
+        assert_eq!((0, Some(2)), lens("(a)?(?(1)b)"));
+        assert_eq!((1, Some(1)), lens("(?<=x)\\w\\K"));
+        assert_eq!((1, None), lens("\\X"));
+        assert_eq!((1, None), lens("(a)\\1"));
+        assert_eq!((2, None), lens("\\((?R)*\\)"));
+
+        // 残りの文字数がmin_lenに満たない位置は探索しない
+        let re = Regex::new("\\d{3}").unwrap();
+        assert!(!re.is_match("12").unwrap());
+        assert_eq!(Some(2..5), re.find("ab123").unwrap().map(|m| m.range()));
+        assert_eq!(None, re.find_at("123", 1).unwrap());
+    }
+
+    #[test]
+    fn test_literal_affix_and_anchors() {
+        let affix = |expr: &str| {
+            let re = Regex::new(expr).unwrap();
+            (
+                re.literal_prefix().to_string(),
+                re.literal_suffix().to_string(),
+            )
+        };
+        let s = |p: &str, q: &str| (p.to_string(), q.to_string());
+        assert_eq!(s("abc", "abc"), affix("abc"));
+        assert_eq!(s("abc", "c"), affix("abc+"));
+        assert_eq!(s("a", "abc"), affix("a+bc"));
+        assert_eq!(s("", "bc"), affix("a*bc"));
+        assert_eq!(s("a", "d"), affix("(?:ab|ac)d"));
+        assert_eq!(s("xabab", "xabab"), affix("x(ab){2}"));
+        assert_eq!(s("ab", "z"), affix("^(a)b\\w*z$"));
+        assert_eq!(s("b", "b"), affix("(?<=a)b"));
+        assert_eq!(s("", "cd"), affix("ab\\Kcd"));
+        assert_eq!(s("1", "1"), affix("(?i)1a1"));
+        assert_eq!(s("", ""), affix("a|b"));
+        assert_eq!(s("", "b"), affix("a?b"));
+
+        let anchors = |expr: &str| {
+            let re = Regex::new(expr).unwrap();
+            (re.is_anchored_start(), re.is_anchored_end())
+        };
+        assert_eq!((true, true), anchors("^ab$"));
+        assert_eq!((true, true), anchors("\\A(?:a|b)+\\z"));
+        assert_eq!((true, true), anchors("(^a$|^b\\Z)"));
+        assert_eq!((false, false), anchors("(?m)^ab$"));
+        assert_eq!((false, false), anchors("^a|b$"));
+        assert_eq!((false, false), anchors("\\Gab"));
+        assert_eq!((true, false), anchors("(?:^a){1,2}"));
+
+        // 先頭に固定されたパターンは､先頭以外からマッチしない
+        let re = Regex::new("^a").unwrap();
+        assert!(re.is_match("ab").unwrap());
+        assert_eq!(1, re.find_iter("aaa").count());
+        assert_eq!(None, re.find_at("ba", 1).unwrap());
+    }
+
+    #[cfg(feature = "sample")]
+    #[test]
+    fn test_sample() {
+        use rand_core::SeedableRng;
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(0);
+        let exprs = [
+            "a(bc)+|c(def)",
+            "^[a-z]{2,5}@[^@\\s]+\\.(?:com|jp)$",
+            "(?i)straße",
+            "(\\w)\\1",
+            "(a)?(?(1)b|c)",
+            "\\((a|(?R))*\\)",
+            "(?<=a)b|c",
+            "(?m)^x$\\n^y",
+            ".\\X[^a-z]",
+        ];
+        for expr in exprs {
+            let re = Regex::new(expr).unwrap();
+            for _ in 0..20 {
+                let s = re.sample(&mut rng).unwrap();
+                let m = re.find(&s).unwrap().map(|m| m.range());
+                assert_eq!(Some(0..s.len()), m, "{expr} generated {s:?}");
+            }
+        }
+
+        // マッチする文字列が存在しない場合はNone
+        let re = Regex::new("a\\Ab").unwrap();
+        assert_eq!(None, re.sample(&mut rng));
+    }
+
+    #[test]
+    fn test_analysis() {
+        use crate::analysis::{is_disjoint, is_equivalent, AnalysisError};
+
+        let re = |expr: &str| Regex::new(expr).unwrap();
+        let eq = |a: &str, b: &str| is_equivalent(&re(a), &re(b)).unwrap();
+        let disjoint = |a: &str, b: &str| is_disjoint(&re(a), &re(b)).unwrap();
+
+        assert!(eq("a+", "aa*"));
+        assert!(eq("[a-c]", "a|b|c"));
+        assert!(eq("(a|b)*", "(a*b*)*"));
+        assert!(eq("(?i)ab", "[aA][bB]"));
+        assert!(eq("[^a]", "[^a-a]|[b]"));
+        assert!(eq("^a$", "\\Aa\\z"));
+        assert!(eq("x{2,3}", "xxx?"));
+        assert!(!eq("a*", "a+"));
+        assert!(!eq(".", "(?s)."));
+        assert!(!eq("[^a]", "[b-z]"));
+        // $は末尾の改行の直前にもマッチする
+        assert!(!eq("a$\\n?", "a"));
+        assert!(eq("a$\\n?", "a\\n?"));
+        assert!(eq("(?m)a$\\nb", "a\\nb"));
+        assert!(eq("(?m)a\\n^b", "a\\nb"));
+        assert!(disjoint("(?m)a$b", "ab"));
+
+        assert!(disjoint("\\d+px", "[0-9]+(?:em|rem)"));
+        assert!(disjoint("a*", "b+"));
+        assert!(!disjoint("1\\w*", "\\d+px"));
+        assert!(!disjoint("[^\\n]*", "(?s).*"));
+        assert!(disjoint("a^b", "a.*"));
+
+        assert_eq!(
+            Err(AnalysisError::Unsupported("backreference")),
+            is_equivalent(&re("(a)\\1"), &re("aa"))
+        );
+        assert_eq!(
+            Err(AnalysisError::Unsupported("look-behind")),
+            is_disjoint(&re("a"), &re("(?<=a)b"))
+        );
+    }
+
+    #[test]
+    fn test_pattern_builder() {
+        use crate::RegexError;
+
+        // メタ文字もそのままの文字として扱う
+        let p = Pattern::literal("a.b(c)").or(Pattern::literal("[x]"));
+        let re = p.build().unwrap();
+        assert!(re.is_match("a.b(c)").unwrap());
+        assert!(!re.is_match("axb(c)").unwrap());
+        assert!(re.is_match("[x]").unwrap());
+        assert_eq!(re.as_str(), p.to_string());
+        assert_eq!(Regex::new(&p.to_string()).unwrap().as_str(), re.as_str());
+
+        // グループには開き括弧の順に番号が振られる
+        let p = Pattern::range('0', '9')
+            .plus()
+            .named("n")
+            .then(Pattern::char('-'))
+            .then(
+                Pattern::any()
+                    .group()
+                    .then(Pattern::char('x').group())
+                    .group(),
+            );
+        assert_eq!(p.to_string(), "(?<n>[0-9]+)-((.)(x))");
+        let caps = p.build().unwrap().captures("id 42-yx").unwrap().unwrap();
+        assert_eq!(&caps["n"], "42");
+        assert_eq!(caps.get(2).unwrap().as_str(), "yx");
+        assert_eq!(caps.get(3).unwrap().as_str(), "y");
+        assert_eq!(caps.get(4).unwrap().as_str(), "x");
+
+        // フラグは内側のフラグにも適用する
+        let p = Pattern::line_start()
+            .then(Pattern::char('a'))
+            .then(Pattern::any().star())
+            .case_insensitive()
+            .dot_all();
+        let re = p.build().unwrap();
+        assert!(re.is_match("x\nA\nb").unwrap());
+        assert_eq!(
+            Some("A\nb"),
+            re.find("x\nA\nb").unwrap().map(|m| m.as_str())
+        );
+        let re = Pattern::start()
+            .then(Pattern::negated_class(&[('a', 'z')]).repeat(2, Some(3)))
+            .then(Pattern::end())
+            .build()
+            .unwrap();
+        assert!(re.is_match("AB1").unwrap());
+        assert!(!re.is_match("ABCD").unwrap());
+
+        assert!(matches!(
+            Pattern::range('z', 'a').build(),
+            Err(RegexError::Parse(ParseError::InvalidRange(_, 'z', 'a')))
+        ));
+        assert!(matches!(
+            Pattern::any().repeat(3, Some(2)).build(),
+            Err(RegexError::Parse(ParseError::InvalidRepeatRange(_, 3, 2)))
+        ));
+        assert!(matches!(
+            Pattern::any()
+                .named("x")
+                .then(Pattern::any().named("x"))
+                .build(),
+            Err(RegexError::Parse(ParseError::DuplicateGroupName(..)))
+        ));
+        assert!(matches!(
+            Pattern::any()
+                .group()
+                .then(Pattern::any().named("1x"))
+                .build(),
+            Err(RegexError::Parse(ParseError::InvalidGroupName(_)))
+        ));
+    }
+
+    #[test]
+    fn test_char_class() {
+        use crate::CharClass;
+
+        let digits = CharClass::range('0', '9');
+        let hex = digits.union(&CharClass::from_ranges(&[('a', 'f'), ('A', 'F')]));
+        assert_eq!(hex.ranges(), &[('0', '9'), ('A', 'F'), ('a', 'f')]);
+        assert!(hex.contains('c') && hex.contains('0') && hex.contains('F'));
+        assert!(!hex.contains('g') && !hex.contains(':'));
+
+        // 隣接する範囲は結合する
+        let class = CharClass::from_chars("cbad".chars()).union(&CharClass::range('e', 'g'));
+        assert_eq!(class.ranges(), &[('a', 'g')]);
+
+        assert_eq!(
+            hex.intersect(&CharClass::range('8', 'B')).to_string(),
+            "[8-9A-B]"
+        );
+        assert_eq!(hex.difference(&digits).ranges(), &[('A', 'F'), ('a', 'f')]);
+        assert_eq!(hex.negate().negate(), hex);
+        assert!(hex.negate().contains('g') && !hex.negate().contains('5'));
+        assert_eq!(CharClass::new().negate(), CharClass::any());
+        assert!(CharClass::any().negate().is_empty());
+        assert!(CharClass::range('z', 'a').is_empty());
+        assert_eq!(hex, "0123456789abcdefABCDEF".chars().collect::<CharClass>());
+
+        // 文字クラスの表示をパースすると同じ文字クラスとなる
+        let class = CharClass::from_chars(['-', ']', '^', '\\', 'あ']);
+        let re = Regex::new(&format!("^{class}+$")).unwrap();
+        assert!(re.is_match("-]^\\あ").unwrap());
+        assert!(!re.is_match("-a").unwrap());
+
+        let re = Pattern::from(hex.negate()).plus().build().unwrap();
+        assert_eq!(Some("xyz"), re.find("0xyz1").unwrap().map(|m| m.as_str()));
+    }
+
+    #[test]
+    fn test_combine_regex() {
+        use crate::RegexError;
+
+        // 再帰と後方参照は､それぞれの式の中で閉じる
+        let paren = Regex::new("\\((?:[^()]|(?R))*\\)").unwrap();
+        let quote = Regex::new("(['\"])[a-z]*\\1").unwrap();
+        let re = paren.or(&quote).unwrap();
+        assert_eq!(re.captures_len(), 2);
+        let found = re
+            .find_iter("f((a)b) 'x\" \"y\"")
+            .map(|m| m.unwrap().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec!["((a)b)", "\"y\""]);
+
+        let re = quote.concat(&paren).unwrap().concat(&quote).unwrap();
+        assert_eq!(re.captures_len(), 3);
+        let caps = re.captures("'ab'(()())\"\"").unwrap().unwrap();
+        assert_eq!((&caps[1], &caps[2]), ("'", "\""));
+        assert!(!re.is_match("'ab'(()\"\"").unwrap());
+
+        // フラグはコンパイル時のものを保つ
+        let upper = RegexBuilder::new("ab")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        let re = upper.concat(&Regex::new("c$").unwrap()).unwrap();
+        assert!(re.is_match("xABc").unwrap());
+        assert!(!re.is_match("ABC").unwrap());
+
+        // 組み合わせた式の性質
+        let re = Regex::new("^abc+")
+            .unwrap()
+            .or(&Regex::new("^abd").unwrap())
+            .unwrap();
+        assert_eq!((re.min_len(), re.max_len()), (3, None));
+        assert_eq!((re.literal_prefix(), re.is_anchored_start()), ("ab", true));
+        let re = Regex::new("abc+")
+            .unwrap()
+            .or(&Regex::new("abd").unwrap())
+            .unwrap();
+        let re = Regex::new("x").unwrap().concat(&re).unwrap();
+        assert_eq!((re.min_len(), re.literal_prefix()), (4, "xab"));
+        let re = Regex::new("x")
+            .unwrap()
+            .concat(&Regex::new("y\\Kz").unwrap())
+            .unwrap();
+        assert_eq!((re.literal_prefix(), re.literal_suffix()), ("", "z"));
+        assert_eq!(re.find("xyz").unwrap().unwrap().as_str(), "z");
+
+        let named = Regex::new("(?<n>a)").unwrap();
+        assert!(matches!(
+            named.or(&named),
+            Err(RegexError::Parse(ParseError::DuplicateGroupName(..)))
+        ));
+    }
+
+    #[test]
+    fn test_match_stats() {
+        use crate::Stats;
+
+        let re = Regex::new("ab").unwrap();
+        let (m, stats) = re.find_with_stats("ab").unwrap();
+        assert_eq!(m.unwrap().as_str(), "ab");
+        // save 0, char a, char b, save 1, match
+        assert_eq!(
+            stats,
+            Stats {
+                steps: 5,
+                backtracks: 0,
+                max_threads: 0
+            }
+        );
+
+        // 最初の分岐が失敗するたびに数える
+        let (m, stats) = Regex::new("a|b").unwrap().find_with_stats("b").unwrap();
+        assert!(m.is_some());
+        assert_eq!(stats.backtracks, 1);
+
+        // 全ての開始位置での合計となる
+        let (m, stats) = re.find_with_stats("xxab").unwrap();
+        assert_eq!(m.unwrap().start(), 2);
+        assert_eq!(stats.steps, 2 + 2 + 5);
+    }
+
+    #[test]
+    fn test_analysis_check() {
+        use crate::analysis::{check, Warning};
+
+        let nested = |expr: &str| vec![Warning::NestedQuantifier(expr.to_string())];
+        let overlap = |expr: &str| vec![Warning::OverlappingAlternation(expr.to_string())];
+
+        assert_eq!(check("(a+)+$").unwrap(), nested("(a+)+"));
+        assert_eq!(check("x(?:a*b*)*y").unwrap(), nested("(?:a*b*)*"));
+        assert_eq!(
+            check("(?:(?:ab)+|c){2,}").unwrap(),
+            nested("(?:(?:ab)+|c){2,}")
+        );
+        assert_eq!(check("(a|a)*b").unwrap(), overlap("a|a"));
+        assert_eq!(
+            check("(?:x(?:\\d|[0-5]|y))+").unwrap(),
+            overlap("[0-9]|[0-5]|y")
+        );
+        // 大文字と小文字を区別しない場合は､aとAが重なる
+        assert_eq!(check("(?i)(?:a|A)+").unwrap(), overlap("a|A"));
+        assert_eq!(check("(?:a|A)+").unwrap(), vec![]);
+
+        // 繰り返しの本体の他の要素が空文字列にマッチしない場合や､繰り返しの外の選択は検出しない
+        for expr in [
+            "(a+b)+",
+            "(?:a|ab)*",
+            "a|a",
+            "\\d+-\\d+",
+            "(?:a{0})*",
+            "(a?)*",
+        ] {
+            assert_eq!(check(expr).unwrap(), vec![], "{expr}");
+        }
+        // 比較できない分岐の組は報告しない
+        assert_eq!(check("(?:(x)\\1|xx)*").unwrap(), vec![]);
+
+        assert_eq!(
+            check("((a|a)+)*").unwrap(),
+            vec![
+                Warning::NestedQuantifier("((a|a)+)*".to_string()),
+                Warning::OverlappingAlternation("a|a".to_string()),
+            ]
+        );
+        assert!(matches!(check("(a"), Err(ParseError::NoRightParen(_))));
+        assert_eq!(
+            Warning::NestedQuantifier("(a+)+".to_string()).to_string(),
+            "Warning: nested quantifier: (a+)+"
+        );
+    }
+
+    #[test]
+    fn test_matches_many() {
+        let re = Regex::new("(\\w+)@(\\w+)\\.com").unwrap();
+        let haystacks = ["to: a@b.com", "", "none", "x@y.com, p@q.com", "é: x@y.com"];
+        assert_eq!(
+            re.matches_many(&haystacks).unwrap(),
+            haystacks
+                .iter()
+                .map(|h| re.is_match(h).unwrap())
+                .collect::<Vec<_>>()
+        );
+
+        let found = re.find_many(&haystacks).unwrap();
+        assert_eq!(found.len(), haystacks.len());
+        for (m, h) in found.iter().zip(haystacks) {
+            assert_eq!(*m, re.find(h).unwrap());
+        }
+        assert_eq!(found[4].unwrap().range(), 4..11);
+
+        assert!(re.matches_many(&[]).unwrap().is_empty());
+        let re = RegexBuilder::new("(a|aa)+b")
+            .limits(Limits {
+                steps: 100,
+                dfa: 0,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        assert!(re.matches_many(&["ab", &"a".repeat(30)]).is_err());
+    }
+
+    #[test]
+    fn test_find_iter_located() {
+        let located = |re: &Regex, text: &str| {
+            re.find_iter_located(text)
+                .map(|m| m.map(|m| (m.line(), m.column(), m.as_match().range())))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        let re = Regex::new("\\d+").unwrap();
+        assert_eq!(
+            located(&re, "a1\n\nあい22 3\r\n4"),
+            vec![(1, 2, 1..2), (3, 3, 10..12), (3, 6, 13..14), (4, 1, 16..17)]
+        );
+        assert_eq!(located(&re, ""), vec![]);
+
+        // 複数行にまたがるマッチは開始位置の行と列となる
+        let re = Regex::new("b\\nc+").unwrap();
+        assert_eq!(
+            located(&re, "ab\ncc\nb\nc"),
+            vec![(1, 2, 1..5), (3, 1, 6..9)]
+        );
+
+        // 空文字列へのマッチ
+        let re = RegexBuilder::new("$").multi_line(true).build().unwrap();
+        assert_eq!(located(&re, "ab\nc"), vec![(1, 3, 2..2), (2, 2, 4..4)]);
+
+        // \Kによって前回のマッチより前から始まる場合
+        let re = Regex::new("(?s)(?<=\\K....)c|ab").unwrap();
+        assert_eq!(located(&re, "z\nabc"), vec![(2, 1, 2..4), (1, 1, 0..5)]);
+    }
+
+    #[test]
+    fn test_bfs() {
+        // 幅優先探索と深さ優先探索のマッチの有無は一致する
+        let cases = [
+            ("abc|(de|cd)+", "decddede"),
+            ("a*b+c?$", "aabbbd"),
+            ("(?:a|ab)(?:c|bcd)$", "abcd"),
+            ("(a+)*b", "aaab"),
+            ("[^a-c]\\d{2,3}", "x12"),
+            ("(?m)^b$", "a\nb\nc"),
+            ("(?s)a.b", "a\nb"),
+            ("a.b", "a\nb"),
+            ("(?<=ab)c(?<!xc)", "abc"),
+            ("(?<!a)b", "ab"),
+            ("\\X\\X$", "e\u{301}🇯🇵"),
+            ("\\Aa\\z", "a"),
+            ("x\\Ky", "xy"),
+        ];
+        for (expr, line) in cases {
+            for (start, _) in line.char_indices().chain(Some((line.len(), ' '))) {
+                let dfs = RegexBuilder::new(expr).build().unwrap();
+                let bfs = RegexBuilder::new(expr)
+                    .match_mode(MatchMode::Bfs)
+                    .build()
+                    .unwrap();
+                assert_eq!(
+                    dfs.is_match_at(line, start).unwrap(),
+                    bfs.is_match_at(line, start).unwrap(),
+                    "{expr} {line} {start}"
+                );
+            }
+            assert_eq!(
+                do_matching(expr, line, MatchMode::Dfs).unwrap(),
+                do_matching(expr, line, MatchMode::Bfs).unwrap(),
+                "{expr} {line}"
+            );
+        }
+
+        // 深さ優先探索では指数的な時間がかかるパターンも､命令数と文字数の積に比例する時間で評価する
+        let line = "a".repeat(64);
+        let re = RegexBuilder::new("(a|a)*b")
+            .match_mode(MatchMode::Bfs)
+            .build()
+            .unwrap();
+        let (m, stats) = re.find_with_stats(&line).unwrap();
+        assert!(m.is_none());
+        assert!(stats.steps < 64 * 64 * 10);
+        assert_eq!(stats.backtracks, 0);
+        assert!(stats.max_threads >= 2);
+        assert!(RegexBuilder::new("(a|a)*b")
+            .limits(Limits {
+                steps: 100_000,
+                visited: 0,
+                dfa: 0,
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+            .is_match(&line[..24])
+            .is_err());
+    }
+
+    #[test]
+    fn test_bfs_captures() {
+        // 幅優先探索でも深さ優先探索と同じマッチとキャプチャを求める
+        let cases = [
+            ("(a+)(b*)", "xaabbc"),
+            ("(a|ab)(c|bcd)(d*)", "abcd"),
+            ("(?<y>\\d{4})-(?<m>\\d\\d)?", "on 2024-1"),
+            ("(a*?)(a*)", "aaa"),
+            ("(?:(a)|b)+", "abab"),
+            ("(x)?y", "y"),
+            ("(?<=(a))b", "ab"),
+            ("(?<!(a))b", "cb"),
+            ("\\w+\\K(\\d)", "ab12"),
+            ("(\\X)(.)", "e\u{301}x"),
+            ("(?m)^(\\w+)$", "a b\ncd\n"),
+            ("()|a", "a"),
+        ];
+        for (expr, haystack) in cases {
+            let dfs = Regex::new(expr).unwrap();
+            let bfs = RegexBuilder::new(expr)
+                .match_mode(MatchMode::Bfs)
+                .build()
+                .unwrap();
+            let spans = |re: &Regex| {
+                re.captures_iter(haystack)
+                    .map(|caps| {
+                        caps.unwrap()
+                            .iter()
+                            .map(|m| m.map(|m| m.range()))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(spans(&dfs), spans(&bfs), "{expr}");
+        }
+
+        let re = RegexBuilder::new("(?<key>\\w+)=(?<value>\\w*)")
+            .match_mode(MatchMode::Bfs)
+            .build()
+            .unwrap();
+        let caps = re.captures("a=1 b= c=3").unwrap().unwrap();
+        assert_eq!((&caps["key"], &caps["value"]), ("a", "1"));
+        assert_eq!(re.find_iter("a=1 b= c=3").count(), 3);
+    }
+
+    #[test]
+    fn test_dfs_deep_input() {
+        // 分岐を重ねる長い入力でも､呼び出しの深さが増えずに評価できる
+        let line = "ab".repeat(50_000) + "c";
+        let re = Regex::new("(?:a|b)*c").unwrap();
+        assert_eq!(re.find(&line).unwrap().unwrap().range(), 0..line.len());
+        let re = Regex::new("((?:a|b)+?)c").unwrap();
+        assert_eq!(re.captures(&line).unwrap().unwrap()[1].len(), 100_000);
+        assert!(!Regex::new("\\A(?:a|b)*d").unwrap().is_match(&line).unwrap());
+
+        // 分岐の優先順位は再帰で評価していた場合と変わらない
+        let re = Regex::new("(a|ab)(c|bcd)(d*)").unwrap();
+        let caps = re.captures("abcd").unwrap().unwrap();
+        assert_eq!((&caps[1], &caps[2], &caps[3]), ("a", "bcd", ""));
+
+        // 指数的な時間がかかる場合や空の繰り返しも､スタックを溢れさせずに命令数の上限でErrとなる
+        let line = "a".repeat(64);
+        let limits = Limits {
+            steps: 100_000,
+            visited: 0,
+            dfa: 0,
+            ..Default::default()
+        };
+        for expr in ["(a|a)*b", "(a*)*b"] {
+            let re = RegexBuilder::new(expr).limits(limits).build().unwrap();
+            assert!(re.is_match(&line).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn test_dfs_memoize() {
+        use crate::{EvalError, RegexError};
+
+        // 訪れた組を記録するため､指数的なバックトラックが起きるパターンも少ない命令数で終わる
+        let line = "a".repeat(64);
+        let limits = Limits {
+            steps: 100_000,
+            ..Default::default()
+        };
+        for expr in ["(a|a)*b", "^(a|aa)+c$", "(a*)*b", "(?:a+)+(?<=b)"] {
+            let re = RegexBuilder::new(expr).limits(limits).build().unwrap();
+            let (m, stats) = re.find_with_stats(&line).unwrap();
+            assert!(m.is_none(), "{expr}");
+            assert!(stats.steps < 64 * 64 * 10, "{expr}");
+        }
+
+        // 記録しても､マッチとキャプチャは変わらない
+        let cases = [
+            ("(a|ab)(c|bcd)(d*)", "abcd"),
+            ("((?:a|a)*)(a)b", "xaaab"),
+            ("(a*?)(a*)", "aaa"),
+            ("(?<=(a))b|(\\w)c", "xcab"),
+            ("(x)?y", "y"),
+        ];
+        for (expr, line) in cases {
+            let memo = Regex::new(expr).unwrap();
+            let plain = RegexBuilder::new(expr)
+                .limits(Limits {
+                    visited: 0,
+                    ..Default::default()
+                })
+                .build()
+                .unwrap();
+            let spans = |re: &Regex| {
+                let caps = re.captures(line).unwrap().unwrap();
+                (0..caps.len())
+                    .map(|i| caps.get(i).map(|m| m.range()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(spans(&memo), spans(&plain), "{expr}");
+        }
+
+        // 後方参照を含むパターンでは記録しないため､上限でErrとなる
+        let re = RegexBuilder::new("(a|a)*b\\1")
+            .limits(limits)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            re.is_match(&line),
+            Err(RegexError::Eval(EvalError::BudgetExceeded))
+        ));
+    }
+
+    #[test]
+    fn test_empty_loop() {
+        use crate::{EvalError, RegexError};
+
+        let no_memo = Limits {
+            visited: 0,
+            ..Default::default()
+        };
+
+        // 文字を読まなかった繰り返しでループを抜けるため､記録しなくても上限に達せずに終わる
+        // 抜けた時点のキャプチャは空文字列となる
+        let cases = [
+            ("(a|)+b\\1", "ab", vec![Some(0..2), Some(1..1)]),
+            ("(a*)*\\1b", "ab", vec![Some(0..2), Some(1..1)]),
+            ("(a|)*\\1", "aa", vec![Some(0..2), Some(2..2)]),
+            ("(?:|b)*", "b", vec![Some(0..0)]),
+            ("(a?)*b", "aab", vec![Some(0..3), Some(2..2)]),
+            ("(a|b|)*c", "abac", vec![Some(0..4), Some(3..3)]),
+            ("(?:a*|b)*", "ab", vec![Some(0..1)]),
+            (
+                "(?:(a?)(b?))+c",
+                "abac",
+                vec![Some(0..4), Some(3..3), Some(3..3)],
+            ),
+        ];
+        for (expr, line, expected) in cases {
+            let memo = Regex::new(expr).unwrap();
+            let plain = RegexBuilder::new(expr).limits(no_memo).build().unwrap();
+            let bfs = RegexBuilder::new(expr)
+                .match_mode(MatchMode::Bfs)
+                .build()
+                .unwrap();
+            let spans = |re: &Regex| {
+                let caps = re.captures(line)?.unwrap();
+                Ok(caps
+                    .iter()
+                    .map(|m| m.map(|m| m.range()))
+                    .collect::<Vec<_>>())
+            };
+            assert_eq!(spans(&memo).unwrap(), expected, "{expr}");
+            assert_eq!(spans(&plain).unwrap(), expected, "{expr}");
+            // 幅優先探索では後方参照を扱えない
+            match spans(&bfs) {
+                Err(RegexError::Eval(EvalError::UnsupportedBackref)) => (),
+                found => assert_eq!(found.unwrap(), expected, "{expr}"),
+            }
+        }
+
+        // 記録しない場合も､入力の長さに比例する命令数で終わる
+        let line = "a".repeat(100_000) + "b";
+        let re = RegexBuilder::new("(a*)*b").limits(no_memo).build().unwrap();
+        assert_eq!(re.find(&line).unwrap().unwrap().range(), 0..line.len());
+    }
+
+    #[test]
+    fn test_dfa() {
+        // DFAでの判定と探索での判定は一致する
+        let no_dfa = Limits {
+            dfa: 0,
+            ..Default::default()
+        };
+        let cases = [
+            ("abc|(de|cd)+", ["decddede", "abd", ""]),
+            ("^ab+c?$", ["abbb", "abbbc\n", "xabc"]),
+            ("\\Aa\\z|b$", ["a", "a\n", "cb\n"]),
+            ("[^a-c]+x", ["abx", "ddx", "x"]),
+            ("(?s)a.b|x.y", ["a\nb", "x\ny", "xzy"]),
+            ("(?i)straße|k", ["STRASSE", "Straße", "\u{212a}"]),
+            ("[0-9]{2,3}-é", ["1-é", "123-é", "日本12-é"]),
+            ("(a|ab)(c|bcd)$", ["abcd", "abc\r\n", "abcde"]),
+            ("x\\Ky|^z", ["xy", "az", "z"]),
+            ("a$\\n|b", ["a\n", "a", "ba\n\n"]),
+            ("\\Z\\n", ["\n", "a\n", "\n\n"]),
+            ("a$[^x]", ["a\n", "a\r\n", "ax"]),
+            ("(?s)a$.", ["a\n", "a", "a\n\n"]),
+        ];
+        for (expr, lines) in cases {
+            let dfa = Regex::new(expr).unwrap();
+            let plain = RegexBuilder::new(expr).limits(no_dfa).build().unwrap();
+            for line in lines {
+                assert_eq!(
+                    dfa.is_match(line).unwrap(),
+                    plain.is_match(line).unwrap(),
+                    "{expr} {line:?}"
+                );
+                // 判定とマッチの探索､数え上げの結果も一致する
+                assert_eq!(
+                    dfa.is_match(line).unwrap(),
+                    dfa.find(line).unwrap().is_some(),
+                    "{expr} {line:?}"
+                );
+                assert_eq!(
+                    dfa.count(line).unwrap(),
+                    dfa.find_iter(line).count(),
+                    "{expr} {line:?}"
+                );
+                for (start, _) in line.char_indices().skip(1) {
+                    assert_eq!(
+                        dfa.is_match_at(line, start).unwrap(),
+                        plain.is_match_at(line, start).unwrap(),
+                        "{expr} {line:?} {start}"
+                    );
+                }
+            }
+        }
+
+        // DFAでは命令数の上限に達せず､マッチがないと分かる場合は探索も行わない
+        let limits = Limits {
+            steps: 1_000,
+            visited: 0,
+            ..Default::default()
+        };
+        let re = RegexBuilder::new("(a|a)*b").limits(limits).build().unwrap();
+        let line = "a".repeat(64);
+        assert!(!re.is_match(&line).unwrap());
+        assert_eq!(None, re.find(&line).unwrap());
+        assert_eq!(0, re.find_iter(&line).count());
+
+        // 後方参照を含むパターンは探索で判定する
+        let re = RegexBuilder::new("(a|a)*b\\1")
+            .limits(limits)
+            .build()
+            .unwrap();
+        assert!(re.is_match(&line).is_err());
+    }
+
+    #[test]
+    fn test_lazy_dfa() {
+        use crate::Cache;
+
+        // 事前に構築すると上限を超えるDFAは､必要な状態のみを構築しながら判定する
+        let lazy = Limits {
+            dfa: 40,
+            ..Default::default()
+        };
+        let no_dfa = Limits {
+            dfa: 0,
+            ..Default::default()
+        };
+        let lines = ["abbbabaa", "babbbbbb", "aaaa", "bbbbbbbbbbbbbbbabbb", ""];
+        for expr in ["(a|b)*a(a|b){3}$", "(?:a|b)*a(?:a|b){2}b", "\\Ab*a(a|b)b"] {
+            let re = RegexBuilder::new(expr).limits(lazy).build().unwrap();
+            let plain = RegexBuilder::new(expr).limits(no_dfa).build().unwrap();
+            for line in lines {
+                assert_eq!(
+                    re.is_match(line).unwrap(),
+                    plain.is_match(line).unwrap(),
+                    "{expr} {line:?}"
+                );
+                // 判定とマッチの探索､数え上げの結果も一致する
+                assert_eq!(
+                    re.is_match(line).unwrap(),
+                    re.find(line).unwrap().is_some(),
+                    "{expr} {line:?}"
+                );
+                assert_eq!(
+                    re.count(line).unwrap(),
+                    re.find_iter(line).count(),
+                    "{expr} {line:?}"
+                );
+            }
+        }
+
+        // 状態を捨てては構築し直すことが続く場合は､探索に切り替えても同じ結果となる
+        let line = "ab".repeat(300) + "aabbaabbbaba" + &"ba".repeat(300);
+        let re = RegexBuilder::new("(a|b)*a(a|b){4}c|bbb")
+            .limits(lazy)
+            .build()
+            .unwrap();
+        assert!(re.is_match(&line).unwrap());
+        assert!(!re.is_match(&line.replace("bbb", "bab")).unwrap());
+
+        // 異なる正規表現で同じCacheを使い回せる
+        let mut cache = Cache::new();
+        let re1 = RegexBuilder::new("(a|b)*a(a|b){3}$")
+            .limits(lazy)
+            .build()
+            .unwrap();
+        let re2 = RegexBuilder::new("(a|b)*b(a|b){3}$")
+            .limits(lazy)
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            assert!(re1.is_match_with(&mut cache, "babbb").unwrap());
+            assert!(!re2.is_match_with(&mut cache, "babbb").unwrap());
+            assert!(re2.is_match_with(&mut cache, "abaaa").unwrap());
+        }
+
+        // 状態数が上限に収まる場合は､探索の命令数の上限に達しない
+        let limits = Limits {
+            steps: 1_000,
+            visited: 0,
+            dfa: 12,
+            ..Default::default()
+        };
+        let re = RegexBuilder::new("(a|a)*b").limits(limits).build().unwrap();
+        let line = "a".repeat(64);
+        assert!(!re.is_match(&line).unwrap());
+        assert_eq!(None, re.find(&line).unwrap());
+    }
+
+    #[test]
+    fn test_onepass() {
+        let no_onepass = Limits {
+            onepass: 0,
+            ..Default::default()
+        };
+        let lines = [
+            "",
+            "a",
+            "ab",
+            "user@example.com",
+            "x=1, y=22\nz=333",
+            "aaab-ab_cd",
+            "abc\nabd\n",
+            "a1b2c3",
+        ];
+        let exprs = [
+            "(\\w+)@(\\w+)\\.com",
+            "(\\w+)=(\\d+)",
+            "^(\\w+)=(\\d+)$",
+            "(?m)^(\\w+)=(\\d+)$",
+            "(a*)b",
+            "(a*?)b",
+            "a(b)?",
+            "(a|b)*c?",
+            "([a-c])([0-9])",
+            "(x)?(a+)(b|c)",
+            "\\Aab(c|d)\\n",
+            "a(?:b\\z|c)",
+            "(a+)$",
+            "(ab)*",
+        ];
+        for expr in exprs {
+            let re = Regex::new(expr).unwrap();
+            let plain = RegexBuilder::new(expr).limits(no_onepass).build().unwrap();
+            for line in lines {
+                let caps = |re: &Regex| {
+                    re.captures_iter(line)
+                        .map(|caps| {
+                            caps.unwrap()
+                                .iter()
+                                .map(|m| m.map(|m| m.range()))
+                                .collect::<Vec<_>>()
+                        })
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(caps(&re), caps(&plain), "{expr} {line:?}");
+            }
+        }
+
+        // 1度の走査で評価する場合は､探索の命令数の上限に達しない
+        let limits = Limits {
+            steps: 3,
+            ..Default::default()
+        };
+        let re = RegexBuilder::new("(\\w+)@(\\w+)")
+            .limits(limits)
+            .build()
+            .unwrap();
+        let caps = re.captures("to: user@example").unwrap().unwrap();
+        assert_eq!((&caps[1], &caps[2]), ("user", "example"));
+
+        // 同じ文字で複数の分岐に進みうる場合は､探索でマッチングする
+        for expr in ["(a|ab)(c|bcd)", "(\\w+)(\\d)", "(a*)*b"] {
+            let re = RegexBuilder::new(expr).limits(limits).build().unwrap();
+            assert!(re.captures("xabcd123").is_err(), "{expr}");
+        }
+    }
+}
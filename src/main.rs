@@ -1,40 +1,42 @@
-mod engine;
-mod helper;
-
 use std::{
     env,
     fs::File,
     io::{BufRead, BufReader},
 };
 
-use helper::DynError;
+use regexer::{DynError, RegexBuilder};
 
 /// ファイルをオープンし､行ごとにマッチングを行う
 ///
-/// マッチングはそれぞれの行頭から1文字ずつずらして行い､
-/// いずれかにマッチした場合に､その行がマッチしたものとみなす
-///
-/// 例えば､abcdという文字列があった場合､以下の順にマッチが行われ､
-/// このいずれかにマッチした場合､与えられた正規表現にマッチする行と判定する
+/// 行中のいずれかの位置からマッチした場合に､その行がマッチしたものとみなす
 ///
-/// - abcd
-/// - bcd
-/// - cd
-/// - d
-fn match_file(expr: &str, file_path: &str) -> Result<(), DynError> {
+/// kが1以上の場合は､編集距離k以内の近似マッチングを行う
+fn match_file(expr: &str, file_path: &str, k: usize) -> Result<(), DynError> {
     let f = File::open(file_path)?;
     let reader = BufReader::new(f);
 
-    engine::print(expr)?;
+    // パターンにエラーがある場合は､エラーの位置を示す
+    if let Err(e) = regexer::parse(expr) {
+        eprint!("{}", e.render(expr));
+        return Err(e.into());
+    }
+
+    regexer::print(expr)?;
     println!();
 
+    if k == 0 {
+        for line in regexer::grep(expr, reader)? {
+            println!("{}", line?.as_str());
+        }
+        return Ok(());
+    }
+
+    // パターンは1度だけコンパイルし､各行の任意の位置からのマッチを探す
+    let re = RegexBuilder::new(expr).max_edits(k).build()?;
     for line in reader.lines() {
         let line = line?;
-        for (i, _) in line.char_indices() {
-            if engine::do_matching(expr, &line[i..], true)? {
-                println!("{line}");
-                break;
-            }
+        if re.is_match(&line)? {
+            println!("{line}");
         }
     }
 
@@ -42,12 +44,39 @@ fn match_file(expr: &str, file_path: &str) -> Result<(), DynError> {
 }
 
 fn main() -> Result<(), DynError> {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let usage = format!("usage: {} [-i] [--fuzzy k] regex file", args[0]);
+
+    // --fuzzy kが指定された場合は近似マッチングを行い､
+    // -iが指定された場合は大文字と小文字を区別しない
+    let mut k = 0;
+    let mut ignore_case = false;
+    while let Some(opt) = args.get(1).filter(|a| a.starts_with('-')) {
+        match opt.as_str() {
+            "--fuzzy" => {
+                if let Some(n) = args.get(2) {
+                    k = n.parse()?;
+                    args.drain(1..3);
+                } else {
+                    eprintln!("{usage}");
+                    return Err("invalid arguments".into());
+                }
+            }
+            "-i" | "--ignore-case" => {
+                ignore_case = true;
+                args.remove(1);
+            }
+            _ => break,
+        }
+    }
+
     if args.len() <= 2 {
-        eprintln!("usage: {} regex file", args[0]);
+        eprintln!("{usage}");
         return Err("invalid arguments".into());
+    } else if ignore_case {
+        match_file(&format!("(?i){}", args[1]), &args[2], k)?;
     } else {
-        match_file(&args[1], &args[2])?;
+        match_file(&args[1], &args[2], k)?;
     }
 
     Ok(())
@@ -56,10 +85,8 @@ fn main() -> Result<(), DynError> {
 // 単体テスト
 #[cfg(test)]
 mod tests {
-    use crate::{
-        engine::do_matching,
-        helper::{safe_add, SafeAdd},
-    };
+    use regexer::{do_fuzzy_matching, do_matching, Match, MatchMode, Regex, RegexBuilder};
+    use regexer_syntax::helper::{safe_add, SafeAdd};
 
     #[test]
     fn test_safe_add() {
@@ -79,60 +106,166 @@ mod tests {
     #[test]
     fn test_matching() {
         // パースエラー
-        assert!(do_matching("+b", "bbb", true).is_err());
-        assert!(do_matching("*b", "bbb", true).is_err());
-        assert!(do_matching("|b", "bbb", true).is_err());
-        assert!(do_matching("?b", "bbb", true).is_err());
+        assert!(do_matching("+b", "bbb", MatchMode::Dfs).is_err());
+        assert!(do_matching("*b", "bbb", MatchMode::Dfs).is_err());
+        assert!(do_matching("?b", "bbb", MatchMode::Dfs).is_err());
 
         // パース成功､マッチ成功
-        assert!(do_matching("abc|def", "def", true).unwrap());
-        assert!(do_matching("(abc)*", "abcabc", true).unwrap());
-        assert!(do_matching("(ab|cd)+", "abcdcd", true).unwrap());
-        assert!(do_matching("abc?", "ab", true).unwrap());
+        assert!(do_matching("abc|def", "def", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(abc)*", "abcabc", MatchMode::Dfs).unwrap());
+        assert!(do_matching("(ab|cd)+", "abcdcd", MatchMode::Dfs).unwrap());
+        assert!(do_matching("abc?", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching("|b", "bbb", MatchMode::Dfs).unwrap());
 
         // パース成功､マッチ失敗
-        assert!(!do_matching("abc|def", "efa", true).unwrap());
-        assert!(!do_matching("(ab|cd)+", "", true).unwrap());
-        assert!(!do_matching("abc?", "acb", true).unwrap());
+        assert!(!do_matching("abc|def", "efa", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("(ab|cd)+", "", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("abc?", "acb", MatchMode::Dfs).unwrap());
     }
 
     #[test]
     fn test_dot_pattern() {
         // パース成功､マッチ成功
-        assert!(do_matching(".", "a", true).unwrap());
-        assert!(do_matching(".", "b", true).unwrap());
+        assert!(do_matching(".", "a", MatchMode::Dfs).unwrap());
+        assert!(do_matching(".", "b", MatchMode::Dfs).unwrap());
 
         // パース成功､マッチ失敗
-        assert!(!do_matching(".", "", true).unwrap());
+        assert!(!do_matching(".", "", MatchMode::Dfs).unwrap());
     }
 
     #[test]
     fn test_dot_star_pattern() {
         // パース成功､マッチ成功
-        assert!(do_matching(".*", "a", true).unwrap());
-        assert!(do_matching(".*", "b", true).unwrap());
-        assert!(do_matching(".*", "ab", true).unwrap());
-        assert!(do_matching(".*", "bcde", true).unwrap());
+        assert!(do_matching(".*", "a", MatchMode::Dfs).unwrap());
+        assert!(do_matching(".*", "b", MatchMode::Dfs).unwrap());
+        assert!(do_matching(".*", "ab", MatchMode::Dfs).unwrap());
+        assert!(do_matching(".*", "bcde", MatchMode::Dfs).unwrap());
     }
 
     #[test]
     fn test_match_begin() {
-        assert!(do_matching("^foo", "foo", true).unwrap());
-        assert!(!do_matching("^foo", "barfoo", true).unwrap());
+        assert!(do_matching("^foo", "foo", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^foo", "barfoo", MatchMode::Dfs).unwrap());
     }
 
     #[test]
     fn test_match_end() {
-        assert!(do_matching("foo$", "foo", true).unwrap());
-        assert!(!do_matching("foo$", "foobar", true).unwrap());
-        assert!(do_matching("foo$", "foo\n", true).unwrap());
+        assert!(do_matching("foo$", "foo", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("foo$", "foobar", MatchMode::Dfs).unwrap());
+        assert!(do_matching("foo$", "foo\n", MatchMode::Dfs).unwrap());
     }
 
     #[test]
     fn test_match_begin_end() {
-        assert!(do_matching("^foo$", "foo", true).unwrap());
-        assert!(!do_matching("^foo$", "foobar", true).unwrap());
-        assert!(!do_matching("^foo$", "barfoo", true).unwrap());
-        assert!(!do_matching("^foo$", "barfoobar", true).unwrap());
+        assert!(do_matching("^foo$", "foo", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^foo$", "foobar", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^foo$", "barfoo", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("^foo$", "barfoobar", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_fuzzy_matching() {
+        // 編集なしの場合は厳密なマッチング
+        assert!(do_fuzzy_matching("abc", "abc", 0, MatchMode::Dfs).unwrap());
+        assert!(!do_fuzzy_matching("abc", "abd", 0, MatchMode::Dfs).unwrap());
+
+        // 置換､挿入､削除
+        assert!(do_fuzzy_matching("abc", "abd", 1, MatchMode::Dfs).unwrap());
+        assert!(do_fuzzy_matching("abc", "abxc", 1, MatchMode::Dfs).unwrap());
+        assert!(do_fuzzy_matching("abc", "ac", 1, MatchMode::Dfs).unwrap());
+        assert!(do_fuzzy_matching("abc", "ab", 1, MatchMode::Dfs).unwrap());
+
+        // 予算を超える編集
+        assert!(!do_fuzzy_matching("abc", "axx", 1, MatchMode::Dfs).unwrap());
+        assert!(do_fuzzy_matching("abc", "axx", 2, MatchMode::Dfs).unwrap());
+
+        // 量化子との組み合わせ
+        assert!(do_fuzzy_matching("(ab)+c", "ababxc", 1, MatchMode::Dfs).unwrap());
+        assert!(!do_fuzzy_matching("(ab)+c", "axbxc", 1, MatchMode::Dfs).unwrap());
+
+        // Regexの検索でも近似マッチングを行う
+        let re = RegexBuilder::new("(colou?r)s")
+            .max_edits(1)
+            .build()
+            .unwrap();
+        let range = |m: Option<Match>| m.map(|m| m.range());
+        assert_eq!(Some(1..7), range(re.find("xcolorz").unwrap()));
+        assert_eq!(Some(0..5), range(re.find("colrs").unwrap()));
+        let caps = re.captures("colrs and colurs").unwrap().unwrap();
+        assert_eq!(Some(0..4), range(caps.get(1)));
+        assert_eq!(2, re.count("colrs and colurs").unwrap());
+        assert_eq!(Some(5), re.shortest_match("colrs").unwrap());
+        assert!(re.is_match("colours").unwrap());
+        assert!(!re.is_match("a colr chart").unwrap());
+
+        // パターンの最小の長さより短い文字列にもマッチする
+        let re = RegexBuilder::new("abc").max_edits(1).build().unwrap();
+        assert_eq!(Some(0..2), range(re.find("ab").unwrap()));
+        assert!(!Regex::new("abc").unwrap().is_match("ab").unwrap());
+
+        // 組み合わせた正規表現は編集の回数を引き継ぐ
+        let re = re.or(&Regex::new("xyz").unwrap()).unwrap();
+        assert!(re.is_match("xbc").unwrap());
+
+        // 幅優先探索では近似マッチングを扱えない
+        let re = RegexBuilder::new("abc")
+            .max_edits(1)
+            .match_mode(MatchMode::Bfs)
+            .build()
+            .unwrap();
+        assert!(re.find("abd").is_err());
+    }
+
+    #[test]
+    fn test_class() {
+        // パースエラー
+        assert!(do_matching("[abc", "a", MatchMode::Dfs).is_err());
+
+        // パース成功､マッチ成功
+        assert!(do_matching("[abc]", "b", MatchMode::Dfs).unwrap());
+        assert!(do_matching("x[abc]+y", "xcaby", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[^abc]", "d", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[]a]", "]", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\]]", "]", MatchMode::Dfs).unwrap());
+
+        // パース成功､マッチ失敗
+        assert!(!do_matching("[abc]", "d", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[^abc]", "a", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[abc]", "", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_class_range() {
+        // パースエラー
+        assert!(do_matching("[z-a]", "a", MatchMode::Dfs).is_err());
+        assert!(do_matching("[a-", "a", MatchMode::Dfs).is_err());
+
+        // パース成功､マッチ成功
+        assert!(do_matching("[a-z]+", "regex", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[a-z0-9_]+", "x_1", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[^0-9]", "a", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[a-]", "-", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[-a]", "-", MatchMode::Dfs).unwrap());
+
+        // パース成功､マッチ失敗
+        assert!(!do_matching("[a-z]", "A", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[^0-9]", "5", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[a-c]", "-", MatchMode::Dfs).unwrap());
+    }
+
+    #[test]
+    fn test_digit_class() {
+        // パース成功､マッチ成功
+        assert!(do_matching("\\d+", "2023", MatchMode::Dfs).unwrap());
+        assert!(do_matching("\\D", "a", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\d_]+", "1_2", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[\\Dx]", "y", MatchMode::Dfs).unwrap());
+        assert!(do_matching("[^\\d]", "a", MatchMode::Dfs).unwrap());
+
+        // パース成功､マッチ失敗
+        assert!(!do_matching("\\d", "a", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("\\D", "5", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[\\Dx]", "5", MatchMode::Dfs).unwrap());
+        assert!(!do_matching("[^\\d]", "5", MatchMode::Dfs).unwrap());
     }
 }